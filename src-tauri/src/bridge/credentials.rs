@@ -1,13 +1,13 @@
 //! Credential and backend-state Tauri commands.
 //!
 //! This module owns credential status, redacted views, package import/export,
-//! mutation commands, and legacy vault shims. It must not perform object,
+//! mutation and storage-profile commands, and legacy vault shims. It must not perform object,
 //! transfer, platform filesystem, thumbnail, sharing, or usage operations.
 
 use crate::sp_backend::{
     BackendPackage, BackendState as BackendStatus, CredentialBundle, SpBackend,
 };
-use crate::types::{ErrorKind, SpError, SpResult};
+use crate::types::{ErrorKind, R2Config, SpError, SpResult};
 use base64::Engine;
 
 #[tauri::command]
@@ -122,7 +122,13 @@ fn redact_key(value: &str) -> String {
 #[tauri::command]
 pub async fn backend_set_credentials(bundle: CredentialBundle) -> SpResult<()> {
     crate::logger::info("bridge", "backend_set_credentials called");
-    let result = SpBackend::set_with_plaintext(bundle);
+    // Clients that only know the single-config shape edit the active profile
+    // and must not wipe the others.
+    let result = if bundle.profiles.is_empty() {
+        SpBackend::set_active_r2_config(bundle.r2)
+    } else {
+        SpBackend::set_with_plaintext(with_existing_encryption(bundle))
+    };
     match &result {
         Ok(_) => crate::logger::info("bridge", "backend_set_credentials ok"),
         Err(error) => crate::logger::info(
@@ -136,6 +142,17 @@ pub async fn backend_set_credentials(bundle: CredentialBundle) -> SpResult<()> {
     result
 }
 
+/// Profile editors send bundles without encryption settings; keep the ones
+/// already in the vault rather than silently turning encryption off.
+fn with_existing_encryption(mut bundle: CredentialBundle) -> CredentialBundle {
+    if bundle.encryption.is_empty() {
+        if let Ok(existing) = SpBackend::get_decrypted_bundle_if_unlocked() {
+            bundle.encryption = existing.encryption;
+        }
+    }
+    bundle
+}
+
 #[tauri::command]
 pub async fn backend_patch_credentials(patch: crate::sp_backend::R2ConfigPatch) -> SpResult<()> {
     crate::logger::info("bridge", "backend_patch_credentials called");
@@ -153,6 +170,68 @@ pub async fn backend_patch_credentials(patch: crate::sp_backend::R2ConfigPatch)
     result
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StorageProfileView {
+    pub name: String,
+    pub is_active: bool,
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: Option<String>,
+    pub is_encrypted: bool,
+}
+
+#[tauri::command]
+pub async fn backend_list_profiles() -> SpResult<Vec<StorageProfileView>> {
+    crate::logger::debug("bridge", "backend_list_profiles");
+    Ok(SpBackend::list_profiles()?
+        .into_iter()
+        .map(|profile| StorageProfileView {
+            name: profile.name,
+            is_active: profile.is_active,
+            endpoint: redact_endpoint(&profile.r2.endpoint),
+            bucket: redact_key(&profile.r2.bucket),
+            region: profile.r2.region,
+            is_encrypted: profile.is_encrypted,
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn backend_add_profile(name: String, r2: R2Config) -> SpResult<()> {
+    crate::logger::info("bridge", "backend_add_profile called");
+    SpBackend::add_profile(&name, r2)
+}
+
+#[tauri::command]
+pub async fn backend_switch_profile(name: String) -> SpResult<()> {
+    crate::logger::info("bridge", &format!("backend_switch_profile name={name}"));
+    SpBackend::switch_profile(&name)
+}
+
+#[tauri::command]
+pub async fn backend_delete_profile(name: String) -> SpResult<()> {
+    crate::logger::info("bridge", &format!("backend_delete_profile name={name}"));
+    SpBackend::delete_profile(&name)?;
+    crate::storage::evict_cached_operator(&name).await;
+    let _ = crate::object_index::forget_profile(&name);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn backend_set_profile_encryption(
+    name: String,
+    passphrase: Option<String>,
+) -> SpResult<()> {
+    crate::logger::info(
+        "bridge",
+        &format!(
+            "backend_set_profile_encryption name={name} enabled={}",
+            passphrase.is_some()
+        ),
+    );
+    SpBackend::set_profile_encryption(&name, passphrase.as_deref())
+}
+
 #[tauri::command]
 pub async fn vault_status() -> SpResult<BackendStatus> {
     backend_status().await
@@ -80,18 +80,12 @@ pub async fn download_sandbox_dir() -> SpResult<String> {
 #[tauri::command]
 pub async fn download_now(key: String, dest_path: String) -> SpResult<()> {
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = crate::storage::build_operator(&bundle.r2).await?;
+    let operator = crate::storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     let bytes = operator
         .read(&key)
         .await
         .map(|bytes| bytes.to_vec())
-        .map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("GetObject: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        })?;
+        .map_err(|error| crate::storage::opendal_error("GetObject", &key, &error))?;
     let path = {
         let raw = dest_path.trim();
         let raw = raw.strip_prefix("file://").unwrap_or(raw);
@@ -0,0 +1,36 @@
+//! Bucket lifecycle Tauri commands.
+//!
+//! This module owns resolving the active backend for lifecycle requests and
+//! logging their outcome. Rule validation and the S3 document format belong
+//! to the application-level `lifecycle` module.
+
+use crate::lifecycle;
+use crate::sp_backend::SpBackend;
+use crate::types::{LifecycleRule, SpResult};
+
+#[tauri::command]
+pub async fn lifecycle_get() -> SpResult<Vec<LifecycleRule>> {
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    lifecycle::get_rules(&bundle.r2).await
+}
+
+/// Checks a rule set without contacting the bucket.
+#[tauri::command]
+pub async fn lifecycle_validate(rules: Vec<LifecycleRule>) -> SpResult<()> {
+    lifecycle::validate_rules(&rules)
+}
+
+/// Replaces the bucket's lifecycle rules; an empty list removes them all.
+#[tauri::command]
+pub async fn lifecycle_set(rules: Vec<LifecycleRule>) -> SpResult<()> {
+    crate::logger::info("bridge", &format!("lifecycle_set rules={}", rules.len()));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let result = lifecycle::put_rules(&bundle.r2, &rules).await;
+    if let Err(error) = &result {
+        crate::logger::error(
+            "bridge",
+            &format!("lifecycle_set error: err={}", error.message),
+        );
+    }
+    result
+}
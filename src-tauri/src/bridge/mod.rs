@@ -9,7 +9,9 @@ mod android_uploads;
 mod background;
 mod credentials;
 mod downloads;
+mod lifecycle;
 mod objects;
+mod search;
 mod sharing;
 mod thumbnails;
 mod transfers;
@@ -22,7 +24,9 @@ pub use android_uploads::*;
 pub use background::*;
 pub use credentials::*;
 pub use downloads::*;
+pub use lifecycle::*;
 pub use objects::*;
+pub use search::*;
 pub use sharing::*;
 pub use thumbnails::*;
 pub use transfers::*;
@@ -6,13 +6,17 @@
 //! application-level `objects` module.
 
 use crate::sp_backend::SpBackend;
-use crate::types::{FileEntry, ListPage, SpResult};
-use crate::{objects, storage};
+use crate::types::{
+    DiagnosticsReport, FileEntry, ListPage, ObjectMetadata, ObjectMetadataUpdate,
+    ObjectVerification, PrefixOpProgress, R2Config, SpResult, TrashEntry,
+};
+use crate::{object_index, objects, storage};
+use tauri::Emitter;
 
 #[tauri::command]
 pub async fn r2_sanity_check() -> SpResult<()> {
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = storage::build_operator(&bundle.r2).await?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     let result = storage::sanity_check(&operator).await;
     if let Err(error) = &result {
         crate::logger::error(
@@ -24,6 +28,17 @@ pub async fn r2_sanity_check() -> SpResult<()> {
     result
 }
 
+/// Diagnoses `r2` when given, so credentials can be checked before they are
+/// saved, or the active profile otherwise.
+#[tauri::command]
+pub async fn r2_diagnostics(r2: Option<R2Config>) -> SpResult<DiagnosticsReport> {
+    let cfg = match r2 {
+        Some(cfg) => cfg,
+        None => SpBackend::get_decrypted_bundle_if_unlocked()?.r2,
+    };
+    storage::run_diagnostics(&cfg).await
+}
+
 #[tauri::command]
 pub async fn list_objects(
     prefix: Option<String>,
@@ -38,24 +53,9 @@ pub async fn list_objects(
         ),
     );
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = storage::build_operator(&bundle.r2).await?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     let prefix = prefix.unwrap_or_default();
-    let mut result =
-        objects::list_objects(&operator, &prefix, token.clone(), max_keys.unwrap_or(1000)).await;
-    if let Err(error) = &result {
-        let message = error.message.to_lowercase();
-        if message.contains("unknownissuer") || message.contains("invalid peer certificate") {
-            crate::logger::warn(
-                "bridge",
-                "list_objects TLS error; invalidating cached storage operator and retrying once",
-            );
-            storage::invalidate_cached_operator().await;
-            let retry_operator = storage::build_operator(&bundle.r2).await?;
-            result =
-                objects::list_objects(&retry_operator, &prefix, token, max_keys.unwrap_or(1000))
-                    .await;
-        }
-    }
+    let result = objects::list_objects(&operator, &prefix, token, max_keys.unwrap_or(1000)).await;
     if let Err(error) = &result {
         crate::logger::error(
             "bridge",
@@ -75,20 +75,8 @@ pub async fn list_all_objects(max_total: Option<i32>) -> SpResult<Vec<FileEntry>
         &format!("list_all_objects max_total={max_total:?}"),
     );
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = storage::build_operator(&bundle.r2).await?;
-    let mut result = objects::list_all_objects(&operator, max_total.unwrap_or(10_000)).await;
-    if let Err(error) = &result {
-        let message = error.message.to_lowercase();
-        if message.contains("unknownissuer") || message.contains("invalid peer certificate") {
-            crate::logger::warn(
-                "bridge",
-                "list_all_objects TLS error; invalidating cached storage operator and retrying once",
-            );
-            storage::invalidate_cached_operator().await;
-            let retry_operator = storage::build_operator(&bundle.r2).await?;
-            result = objects::list_all_objects(&retry_operator, max_total.unwrap_or(10_000)).await;
-        }
-    }
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = objects::list_all_objects(&operator, max_total.unwrap_or(10_000)).await;
     if let Err(error) = &result {
         crate::logger::error(
             "bridge",
@@ -98,18 +86,419 @@ pub async fn list_all_objects(max_total: Option<i32>) -> SpResult<Vec<FileEntry>
     result
 }
 
+/// Deletes an object, or moves it to the recycle bin when that setting is
+/// on; a folder key takes everything under it along in either mode. Returns
+/// the key that was removed.
 #[tauri::command]
 pub async fn delete_object(key: String) -> SpResult<String> {
     objects::validate_delete_key(&key)?;
     crate::logger::info("bridge", &format!("delete_object key={key}"));
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = storage::build_operator(&bundle.r2).await?;
-    let result = objects::delete_object(&operator, &key).await;
-    if let Err(error) = &result {
-        crate::logger::error(
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    if key.ends_with('/') {
+        let op_id = uuid::Uuid::new_v4().to_string();
+        let cancel = std::sync::atomic::AtomicBool::new(false);
+        if crate::settings::get().recycle_bin {
+            return trash_folder(
+                &operator,
+                &bundle.active_profile,
+                &op_id,
+                &key,
+                &cancel,
+                |_| {},
+            )
+            .await
+            .map(|_| key);
+        }
+        let result = objects::delete_prefix(
+            &operator,
+            &bundle.active_profile,
+            &op_id,
+            &key,
+            &cancel,
+            |_| {},
+        )
+        .await;
+        return match result {
+            Ok(progress) => {
+                let _ = object_index::forget_prefix(&bundle.active_profile, &progress.prefix);
+                Ok(key)
+            }
+            Err(error) => {
+                crate::logger::error(
+                    "bridge",
+                    &format!("delete_object error: key={key} err={}", error.message),
+                );
+                resync_index(&operator, &bundle.active_profile, &[&key]).await;
+                Err(error)
+            }
+        };
+    }
+    if crate::settings::get().recycle_bin {
+        let result = objects::trash_object(&operator, &key).await;
+        return match result {
+            Ok(entry) => {
+                let _ = object_index::relocate_object(
+                    &bundle.active_profile,
+                    &key,
+                    &entry.trash_key,
+                    false,
+                );
+                Ok(key)
+            }
+            Err(error) => {
+                crate::logger::error(
+                    "bridge",
+                    &format!("delete_object trash error: key={key} err={}", error.message),
+                );
+                Err(error)
+            }
+        };
+    }
+    let result = objects::delete_object(&operator, &bundle.active_profile, &key).await;
+    match &result {
+        Ok(_) => {
+            let _ = object_index::forget_object(&bundle.active_profile, &key);
+        }
+        Err(error) => crate::logger::error(
             "bridge",
             &format!("delete_object error: key={key} err={}", error.message),
+        ),
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn stat_object(key: String) -> SpResult<ObjectMetadata> {
+    crate::logger::debug("bridge", &format!("stat_object key={key}"));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    objects::stat_object(&operator, &key).await
+}
+
+/// Downloads the object without storing it and compares its SHA-256 with
+/// the one recorded at upload.
+#[tauri::command]
+pub async fn verify_object(key: String) -> SpResult<ObjectVerification> {
+    crate::logger::info("bridge", &format!("verify_object key={key}"));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = objects::verify_object(&operator, &key).await;
+    match &result {
+        Ok(verification) if verification.matches == Some(false) => {
+            crate::logger::warn("bridge", &format!("verify_object mismatch: key={key}"))
+        }
+        Err(error) => crate::logger::error(
+            "bridge",
+            &format!("verify_object error: key={key} err={}", error.message),
+        ),
+        _ => {}
+    }
+    result
+}
+
+/// Rewrites content headers and custom metadata in place and returns the
+/// object's metadata as stored afterwards.
+#[tauri::command]
+pub async fn update_object_metadata(
+    key: String,
+    mut update: ObjectMetadataUpdate,
+) -> SpResult<ObjectMetadata> {
+    objects::validate_metadata_update(&key, &update)?;
+    crate::logger::info("bridge", &format!("update_object_metadata key={key}"));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    // The rewrite copies the object as stated here, so it must not change
+    // in between.
+    let current = objects::stat_object(&operator, &key).await?;
+    update.expected_etag = update.expected_etag.or(current.etag);
+    if let Err(error) =
+        storage::replace_object_metadata(&bundle.r2, &key, current.size, &update).await
+    {
+        crate::logger::error(
+            "bridge",
+            &format!(
+                "update_object_metadata error: key={key} err={}",
+                error.message
+            ),
         );
+        return Err(error);
+    }
+    objects::stat_object(&operator, &key).await
+}
+
+#[tauri::command]
+pub async fn create_folder(prefix: String) -> SpResult<String> {
+    crate::logger::info("bridge", &format!("create_folder prefix={prefix}"));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = objects::create_folder(&operator, &prefix).await;
+    match &result {
+        Ok(marker) => {
+            let now = chrono::Utc::now().timestamp_millis();
+            let _ =
+                object_index::record_object(&bundle.active_profile, marker, None, Some(now), None);
+        }
+        Err(error) => crate::logger::error(
+            "bridge",
+            &format!("create_folder error: prefix={prefix} err={}", error.message),
+        ),
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn copy_object(from_key: String, to_key: String) -> SpResult<String> {
+    crate::logger::info(
+        "bridge",
+        &format!("copy_object from={from_key} to={to_key}"),
+    );
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = objects::copy_object(&operator, &bundle.active_profile, &from_key, &to_key).await;
+    match &result {
+        Ok(_) => {
+            let _ = object_index::relocate_object(&bundle.active_profile, &from_key, &to_key, true);
+        }
+        Err(error) => crate::logger::error(
+            "bridge",
+            &format!("copy_object error: from={from_key} err={}", error.message),
+        ),
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn move_object(from_key: String, to_key: String) -> SpResult<String> {
+    crate::logger::info(
+        "bridge",
+        &format!("move_object from={from_key} to={to_key}"),
+    );
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = objects::move_object(&operator, &from_key, &to_key).await;
+    match &result {
+        Ok(_) => {
+            let _ =
+                object_index::relocate_object(&bundle.active_profile, &from_key, &to_key, false);
+        }
+        Err(error) => crate::logger::error(
+            "bridge",
+            &format!("move_object error: from={from_key} err={}", error.message),
+        ),
+    }
+    result
+}
+
+/// Moves a whole folder. Progress is emitted as `sp://prefix_op_event`
+/// carrying the caller-chosen `op_id`, which `cancel_prefix_op` accepts.
+#[tauri::command]
+pub async fn move_prefix(
+    app: tauri::AppHandle,
+    op_id: String,
+    from_prefix: String,
+    to_prefix: String,
+) -> SpResult<PrefixOpProgress> {
+    crate::logger::info(
+        "bridge",
+        &format!("move_prefix op={op_id} from={from_prefix} to={to_prefix}"),
+    );
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let guard = objects::begin_prefix_op(&op_id)?;
+    let result = objects::move_prefix(
+        &operator,
+        &op_id,
+        &from_prefix,
+        &to_prefix,
+        guard.cancel_flag(),
+        |progress| {
+            let _ = app.emit("sp://prefix_op_event", progress);
+        },
+    )
+    .await;
+    match &result {
+        Ok(progress) if !progress.cancelled => {
+            let _ = object_index::relocate_prefix(&bundle.active_profile, &from_prefix, &to_prefix);
+        }
+        Ok(_) => {
+            resync_index(
+                &operator,
+                &bundle.active_profile,
+                &[&from_prefix, &to_prefix],
+            )
+            .await
+        }
+        Err(error) => {
+            crate::logger::error(
+                "bridge",
+                &format!(
+                    "move_prefix error: from={from_prefix} err={}",
+                    error.message
+                ),
+            );
+            resync_index(
+                &operator,
+                &bundle.active_profile,
+                &[&from_prefix, &to_prefix],
+            )
+            .await
+        }
+    }
+    result
+}
+
+/// Deletes a whole folder in DeleteObjects batches, or moves its files to
+/// the recycle bin when that setting is on, reporting progress the same way
+/// as `move_prefix`.
+#[tauri::command]
+pub async fn delete_prefix(
+    app: tauri::AppHandle,
+    op_id: String,
+    prefix: String,
+) -> SpResult<PrefixOpProgress> {
+    crate::logger::info(
+        "bridge",
+        &format!("delete_prefix op={op_id} prefix={prefix}"),
+    );
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let guard = objects::begin_prefix_op(&op_id)?;
+    if crate::settings::get().recycle_bin {
+        return trash_folder(
+            &operator,
+            &bundle.active_profile,
+            &op_id,
+            &prefix,
+            guard.cancel_flag(),
+            |progress| {
+                let _ = app.emit("sp://prefix_op_event", progress);
+            },
+        )
+        .await;
+    }
+    let result = objects::delete_prefix(
+        &operator,
+        &bundle.active_profile,
+        &op_id,
+        &prefix,
+        guard.cancel_flag(),
+        |progress| {
+            let _ = app.emit("sp://prefix_op_event", progress);
+        },
+    )
+    .await;
+    match &result {
+        Ok(progress) if !progress.cancelled => {
+            let _ = object_index::forget_prefix(&bundle.active_profile, &progress.prefix);
+        }
+        Ok(_) => resync_index(&operator, &bundle.active_profile, &[&prefix]).await,
+        Err(error) => {
+            crate::logger::error(
+                "bridge",
+                &format!("delete_prefix error: prefix={prefix} err={}", error.message),
+            );
+            resync_index(&operator, &bundle.active_profile, &[&prefix]).await
+        }
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn trash_list() -> SpResult<Vec<TrashEntry>> {
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    objects::list_trash(&operator).await
+}
+
+#[tauri::command]
+pub async fn trash_restore(trash_key: String) -> SpResult<String> {
+    crate::logger::info("bridge", &format!("trash_restore key={trash_key}"));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = objects::restore_trashed(&operator, &trash_key).await;
+    match &result {
+        Ok(original_key) => {
+            let _ = object_index::relocate_object(
+                &bundle.active_profile,
+                &trash_key,
+                original_key,
+                false,
+            );
+        }
+        Err(error) => crate::logger::error(
+            "bridge",
+            &format!("trash_restore error: key={trash_key} err={}", error.message),
+        ),
     }
     result
 }
+
+/// Permanently deletes trashed files older than `older_than_days`, which
+/// defaults to the configured retention; `0` empties the recycle bin.
+#[tauri::command]
+pub async fn trash_purge(older_than_days: Option<u32>) -> SpResult<Vec<TrashEntry>> {
+    let days = older_than_days.unwrap_or_else(|| crate::settings::get().trash_retention_days);
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    objects::purge_trash_older_than(&operator, &bundle.active_profile, days).await
+}
+
+#[tauri::command]
+pub fn cancel_prefix_op(op_id: String) -> SpResult<()> {
+    crate::logger::info("bridge", &format!("cancel_prefix_op op={op_id}"));
+    objects::cancel_prefix_op(&op_id)
+}
+
+/// Moves a folder's files to the recycle bin and re-keys them in the index.
+async fn trash_folder(
+    operator: &opendal::Operator,
+    profile: &str,
+    op_id: &str,
+    prefix: &str,
+    cancel: &std::sync::atomic::AtomicBool,
+    on_progress: impl FnMut(&PrefixOpProgress),
+) -> SpResult<PrefixOpProgress> {
+    match objects::trash_prefix(operator, op_id, prefix, cancel, on_progress).await {
+        Ok((progress, entries)) => {
+            for entry in &entries {
+                let _ = object_index::relocate_object(
+                    profile,
+                    &entry.original_key,
+                    &entry.trash_key,
+                    false,
+                );
+            }
+            if progress.cancelled {
+                resync_index(operator, profile, &[prefix]).await;
+            } else {
+                let _ = object_index::forget_prefix(profile, &progress.prefix);
+            }
+            Ok(progress)
+        }
+        Err(error) => {
+            crate::logger::error(
+                "bridge",
+                &format!("trash folder error: prefix={prefix} err={}", error.message),
+            );
+            resync_index(operator, profile, &[prefix, objects::TRASH_PREFIX]).await;
+            Err(error)
+        }
+    }
+}
+
+/// A stopped folder operation leaves an unknown subset of keys moved or
+/// deleted, so the affected prefixes are re-listed into the index.
+async fn resync_index(operator: &opendal::Operator, profile: &str, prefixes: &[&str]) {
+    for prefix in prefixes {
+        if let Err(error) = object_index::reconcile(operator, profile, prefix).await {
+            crate::logger::warn(
+                "bridge",
+                &format!(
+                    "object index resync failed: prefix={prefix} err={}",
+                    error.message
+                ),
+            );
+        }
+    }
+}
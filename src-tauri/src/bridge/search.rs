@@ -0,0 +1,43 @@
+//! Object-index Tauri commands.
+//!
+//! This module owns resolving the active profile for index queries and
+//! on-demand reconciles. Indexing, sweeping and query building belong to the
+//! application-level `object_index` module.
+
+use crate::sp_backend::SpBackend;
+use crate::types::{
+    ObjectIndexPage, ObjectIndexQuery, ObjectIndexStatus, ObjectIndexSync, SpResult,
+};
+use crate::{object_index, storage};
+
+#[tauri::command]
+pub async fn object_index_search(query: ObjectIndexQuery) -> SpResult<ObjectIndexPage> {
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    object_index::search(&bundle.active_profile, &query)
+}
+
+/// Re-lists `prefix` (the whole bucket when omitted) and reconciles the index.
+#[tauri::command]
+pub async fn object_index_refresh(prefix: Option<String>) -> SpResult<ObjectIndexSync> {
+    let prefix = prefix.unwrap_or_default();
+    crate::logger::info("bridge", &format!("object_index_refresh prefix={prefix}"));
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
+    let result = object_index::reconcile(&operator, &bundle.active_profile, &prefix).await;
+    if let Err(error) = &result {
+        crate::logger::error(
+            "bridge",
+            &format!(
+                "object_index_refresh error: prefix={prefix} err={}",
+                error.message
+            ),
+        );
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn object_index_status() -> SpResult<ObjectIndexStatus> {
+    let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+    object_index::status(&bundle.active_profile)
+}
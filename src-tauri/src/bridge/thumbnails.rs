@@ -18,7 +18,11 @@ pub async fn generate_thumbnail_and_upload(
         return Ok(None);
     }
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = crate::storage::build_operator(&bundle.r2).await?;
+    // A thumbnail would be a plaintext copy of an encrypted image.
+    if bundle.encryption_for(&bundle.active_profile).is_some() {
+        return Ok(None);
+    }
+    let operator = crate::storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     crate::thumbnail::generate_and_store(&operator, &key, &source_path, 128, 16 * 1024).await
 }
 
@@ -34,7 +38,7 @@ pub async fn thumbnail_get_cached_data(
     }
 
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = crate::storage::build_operator(&bundle.r2).await?;
+    let operator = crate::storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     let Some(bytes) = crate::thumbnail::read_stored(&operator, &object_key).await? else {
         let _ = crate::transfer_db::delete_thumbnail_cache(&object_key);
         return Ok(None);
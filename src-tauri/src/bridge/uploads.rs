@@ -1,11 +1,14 @@
 //! Upload Tauri commands.
 //!
-//! This module owns bridge validation, logging, and dispatch for file and
-//! push-stream uploads. It must not implement upload I/O, runtime state,
+//! This module owns bridge validation, logging, and dispatch for file,
+//! folder, push-stream and URL uploads. It must not implement upload I/O, runtime state,
 //! Android SAF selection, credentials, or unrelated command domains.
 
 use crate::types::{err_not_implemented, SpResult};
-use crate::upload::{NewUploadParams, NewUploadStreamParams, UploadStatus};
+use crate::upload::{
+    DirectoryUpload, DirectoryUploadParams, NewUploadParams, NewUploadStreamParams,
+    NewUrlUploadParams, StreamAck, UploadGroupStatus, UploadStatus,
+};
 
 #[tauri::command]
 pub async fn upload_new(app: tauri::AppHandle, params: NewUploadParams) -> SpResult<String> {
@@ -24,6 +27,36 @@ pub async fn upload_new(app: tauri::AppHandle, params: NewUploadParams) -> SpRes
     result
 }
 
+#[tauri::command]
+pub async fn upload_directory(
+    app: tauri::AppHandle,
+    params: DirectoryUploadParams,
+) -> SpResult<DirectoryUpload> {
+    crate::logger::info(
+        "bridge",
+        &format!(
+            "upload_directory dir={} prefix={} include={:?} exclude={:?}",
+            params.source_dir, params.target_prefix, params.include, params.exclude
+        ),
+    );
+    let result = crate::upload::start_directory_upload(app, params).await;
+    match &result {
+        Ok(group) => crate::logger::info(
+            "bridge",
+            &format!(
+                "upload_directory ok group={} files={}",
+                group.group_id,
+                group.transfer_ids.len()
+            ),
+        ),
+        Err(error) => crate::logger::error(
+            "bridge",
+            &format!("upload_directory err: {}", error.message),
+        ),
+    }
+    result
+}
+
 #[tauri::command]
 pub async fn upload_new_stream(
     app: tauri::AppHandle,
@@ -47,18 +80,67 @@ pub async fn upload_new_stream(
     result
 }
 
+#[tauri::command]
+pub async fn upload_from_url(
+    app: tauri::AppHandle,
+    params: NewUrlUploadParams,
+) -> SpResult<String> {
+    crate::logger::info(
+        "bridge",
+        &format!("upload_from_url url={} key={:?}", params.url, params.key),
+    );
+    let result = crate::upload::start_upload_from_url(app, params).await;
+    match &result {
+        Ok(id) => crate::logger::info("bridge", &format!("upload_from_url ok id={id}")),
+        Err(error) => {
+            crate::logger::error("bridge", &format!("upload_from_url err: {}", error.message))
+        }
+    }
+    result
+}
+
 #[tauri::command]
 pub async fn upload_stream_write(
     _app: tauri::AppHandle,
     transfer_id: String,
+    offset: u64,
     chunk: Vec<u8>,
-) -> SpResult<()> {
-    crate::upload::stream_write(&transfer_id, chunk)
+) -> SpResult<StreamAck> {
+    crate::upload::stream_write(&transfer_id, offset, chunk)
 }
 
 #[tauri::command]
-pub async fn upload_stream_finish(_app: tauri::AppHandle, transfer_id: String) -> SpResult<()> {
-    crate::upload::stream_finish(&transfer_id)
+pub async fn upload_stream_finish(
+    _app: tauri::AppHandle,
+    transfer_id: String,
+    offset: u64,
+) -> SpResult<StreamAck> {
+    crate::upload::stream_finish(&transfer_id, offset)
+}
+
+#[tauri::command]
+pub async fn upload_stream_reconnect(
+    _app: tauri::AppHandle,
+    transfer_id: String,
+) -> SpResult<StreamAck> {
+    let result = crate::upload::stream_reconnect(&transfer_id);
+    match &result {
+        Ok(ack) => crate::logger::info(
+            "bridge",
+            &format!(
+                "upload_stream_reconnect id={transfer_id} committed={}",
+                ack.committed_offset
+            ),
+        ),
+        Err(error) => crate::logger::warn(
+            "bridge",
+            &format!(
+                "upload_stream_reconnect id={transfer_id} err: {}",
+                error.message
+            ),
+        ),
+    }
+    result
 }
 
 #[tauri::command]
@@ -79,3 +161,8 @@ pub async fn upload_ctrl(
 pub async fn upload_status(transfer_id: String) -> SpResult<UploadStatus> {
     crate::upload::status(&transfer_id)
 }
+
+#[tauri::command]
+pub async fn upload_group_status(group_id: String) -> SpResult<UploadGroupStatus> {
+    crate::upload::group_status(&group_id)
+}
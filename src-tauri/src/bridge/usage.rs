@@ -9,7 +9,8 @@ use crate::types::{DailyLedger, SpResult};
 #[tauri::command]
 pub async fn usage_merge_day(date: String) -> SpResult<DailyLedger> {
     crate::logger::info("bridge", &format!("usage_merge_day date={date}"));
-    let result = crate::usage::UsageSync::merge_and_write_day(&date).await;
+    let profile = crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
+    let result = crate::usage::UsageSync::merge_and_write_day(&profile, &date).await;
     if let Err(error) = &result {
         crate::logger::error("bridge", &format!("usage_merge_day err: {}", error.message));
     }
@@ -0,0 +1,309 @@
+//! Transparent compression of object contents.
+//!
+//! This module owns the codecs an upload may compress with, the
+//! `Content-Encoding` values that name them, the metadata recording an
+//! object's size before compression, and the streaming encoder and file
+//! decoder the transfer engines use. It must not talk to storage, read
+//! settings, or decide which uploads are compressed.
+
+use crate::types::{ErrorKind, SpError, SpResult};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// User metadata name (`x-amz-meta-swiftpan-original-size`) holding the
+/// decimal size of a compressed object's content before compression.
+pub const ORIGINAL_SIZE_METADATA_KEY: &str = "swiftpan-original-size";
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The `Content-Encoding` an object compressed this way is stored with.
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// The codec a `Content-Encoding` value names, if this module has one.
+    /// `identity` and unknown codings are `None`.
+    pub fn from_content_encoding(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The codec a stored name refers to.
+    pub fn parse(value: &str) -> SpResult<Self> {
+        Self::from_content_encoding(value).ok_or_else(|| SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!("unknown compression {value:?}"),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        })
+    }
+}
+
+/// The size before compression recorded among `user_metadata`, if any.
+pub fn recorded_original_size<'a>(
+    user_metadata: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> Option<u64> {
+    user_metadata
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(ORIGINAL_SIZE_METADATA_KEY))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+/// The user metadata pair recording `original_size`.
+pub fn original_size_metadata(original_size: u64) -> (String, String) {
+    (
+        ORIGINAL_SIZE_METADATA_KEY.to_string(),
+        original_size.to_string(),
+    )
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+/// Compresses content pushed in pieces of any size. Each call returns the
+/// compressed bytes ready so far; [`Compressor::finish`] returns the rest.
+pub struct Compressor {
+    encoder: Encoder,
+}
+
+impl Compressor {
+    pub fn new(compression: Compression) -> SpResult<Self> {
+        let encoder = match compression {
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::default()))
+            }
+            Compression::Zstd => Encoder::Zstd(
+                zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)
+                    .map_err(|error| encode_error(&error))?,
+            ),
+        };
+        Ok(Self { encoder })
+    }
+
+    pub fn push(&mut self, input: &[u8]) -> SpResult<Vec<u8>> {
+        let output = match &mut self.encoder {
+            Encoder::Gzip(encoder) => {
+                encoder
+                    .write_all(input)
+                    .map_err(|error| encode_error(&error))?;
+                encoder.get_mut()
+            }
+            Encoder::Zstd(encoder) => {
+                encoder
+                    .write_all(input)
+                    .map_err(|error| encode_error(&error))?;
+                encoder.get_mut()
+            }
+        };
+        Ok(std::mem::take(output))
+    }
+
+    /// Ends the content. The compressor must not be pushed to afterwards.
+    pub fn finish(&mut self) -> SpResult<Vec<u8>> {
+        let output = match &mut self.encoder {
+            Encoder::Gzip(encoder) => {
+                encoder.try_finish().map_err(|error| encode_error(&error))?;
+                encoder.get_mut()
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.do_finish().map_err(|error| encode_error(&error))?;
+                encoder.get_mut()
+            }
+        };
+        Ok(std::mem::take(output))
+    }
+}
+
+/// Decodes the compressed file at `source` into `target`, off the async
+/// runtime, and returns the decoded size. Content that does not decode is
+/// an [`ErrorKind::Integrity`] error; `target` is then left incomplete.
+pub async fn decompress_file(
+    compression: Compression,
+    source: &Path,
+    target: &Path,
+) -> SpResult<u64> {
+    let (source, target) = (PathBuf::from(source), PathBuf::from(target));
+    tokio::task::spawn_blocking(move || {
+        let file_error = |action: &str, path: &Path, error: std::io::Error| SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!("{action} {}: {error}", path.display()),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        };
+        let input = std::io::BufReader::new(
+            std::fs::File::open(&source).map_err(|error| file_error("open", &source, error))?,
+        );
+        let mut decoder: Box<dyn Read> = match compression {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+            Compression::Zstd => Box::new(
+                zstd::stream::read::Decoder::with_buffer(input)
+                    .map_err(|error| decode_error(compression, &error))?,
+            ),
+        };
+        let mut output = std::io::BufWriter::new(
+            std::fs::File::create(&target).map_err(|error| file_error("create", &target, error))?,
+        );
+        let mut buffer = vec![0; 1024 * 1024];
+        let mut written = 0u64;
+        loop {
+            let read = decoder
+                .read(&mut buffer)
+                .map_err(|error| decode_error(compression, &error))?;
+            if read == 0 {
+                break;
+            }
+            output
+                .write_all(&buffer[..read])
+                .map_err(|error| file_error("write", &target, error))?;
+            written += read as u64;
+        }
+        output
+            .flush()
+            .map_err(|error| file_error("write", &target, error))?;
+        Ok(written)
+    })
+    .await
+    .map_err(|error| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("decompression task failed: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    })?
+}
+
+fn encode_error(error: &std::io::Error) -> SpError {
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("compress content: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
+
+fn decode_error(compression: Compression, error: &std::io::Error) -> SpError {
+    // The codecs report bad content under several I/O error kinds; the
+    // source is a local file just written, so any failure is the content's.
+    SpError {
+        kind: ErrorKind::Integrity,
+        message: format!("decode {} content: {error}", compression.content_encoding()),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressed(compression: Compression, content: &[u8]) -> Vec<u8> {
+        let mut compressor = Compressor::new(compression).expect("compressor should start");
+        let mut output = Vec::new();
+        for piece in content.chunks(7_000) {
+            output.extend(compressor.push(piece).expect("piece should compress"));
+        }
+        output.extend(compressor.finish().expect("compressor should finish"));
+        output
+    }
+
+    async fn decompressed(compression: Compression, stored: &[u8]) -> SpResult<Vec<u8>> {
+        let directory = tempfile::tempdir().expect("temporary directory should exist");
+        let (source, target) = (directory.path().join("in"), directory.path().join("out"));
+        std::fs::write(&source, stored).expect("compressed fixture should write");
+        let len = decompress_file(compression, &source, &target).await?;
+        let content = std::fs::read(&target).expect("decoded file should exist");
+        assert_eq!(len, content.len() as u64);
+        Ok(content)
+    }
+
+    #[tokio::test]
+    async fn pushed_pieces_decode_to_the_original_content() {
+        let text = b"timestamp,level,message\n".repeat(20_000);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            for content in [Vec::new(), b"x".to_vec(), text.clone()] {
+                let stored = compressed(compression, &content);
+                assert_eq!(
+                    decompressed(compression, &stored)
+                        .await
+                        .expect("content should decode"),
+                    content,
+                    "{compression:?}"
+                );
+            }
+            assert!(compressed(compression, &text).len() < text.len() / 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupt_or_truncated_content_is_an_integrity_error() {
+        let text = b"{\"event\":\"upload\"}\n".repeat(5_000);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let stored = compressed(compression, &text);
+            let truncated = decompressed(compression, &stored[..stored.len() / 2])
+                .await
+                .expect_err("truncated content must not decode");
+            assert!(
+                matches!(truncated.kind, ErrorKind::Integrity),
+                "{truncated:?}"
+            );
+            let other = match compression {
+                Compression::Gzip => compressed(Compression::Zstd, &text),
+                Compression::Zstd => compressed(Compression::Gzip, &text),
+            };
+            let foreign = decompressed(compression, &other)
+                .await
+                .expect_err("another codec's output must not decode");
+            assert!(matches!(foreign.kind, ErrorKind::Integrity), "{foreign:?}");
+        }
+    }
+
+    #[test]
+    fn content_encodings_and_recorded_sizes_parse_leniently() {
+        assert_eq!(
+            Compression::from_content_encoding(" GZIP "),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_content_encoding("zstd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_content_encoding("identity"), None);
+        assert_eq!(Compression::from_content_encoding("br"), None);
+
+        let metadata: std::collections::BTreeMap<_, _> =
+            [original_size_metadata(1234)].into_iter().collect();
+        assert_eq!(recorded_original_size(&metadata), Some(1234));
+        let garbled: std::collections::BTreeMap<_, _> =
+            [(ORIGINAL_SIZE_METADATA_KEY.to_string(), "many".to_string())]
+                .into_iter()
+                .collect();
+        assert_eq!(recorded_original_size(&garbled), None);
+    }
+}
@@ -0,0 +1,637 @@
+//! Client-side encryption of object contents.
+//!
+//! This module owns the sealed object format used by profiles with
+//! encryption turned on, key derivation from a profile's passphrase, and the
+//! offset arithmetic that lets uploads send, and downloads read, arbitrary
+//! byte ranges of a sealed object. It must not read local files, talk to
+//! storage, access the vault, or decide which profiles encrypt.
+//!
+//! A sealed object is a header followed by the plaintext in chunks of
+//! [`CHUNK_LEN`] bytes, each sealed with XChaCha20-Poly1305 and carrying its
+//! own 16-byte tag:
+//!
+//! ```text
+//! magic (8) | argon2 mem_kib, iterations, parallelism (3 x u32 BE) | salt (16) | nonce prefix (16)
+//! chunk 0 ciphertext + tag | chunk 1 ciphertext + tag | ... | last chunk ciphertext + tag
+//! ```
+//!
+//! A chunk's nonce is the object's nonce prefix followed by the chunk index
+//! as a big-endian `u64`, and a one-byte flag marking the last chunk is
+//! authenticated with it, so reordered, dropped or truncated chunks fail to
+//! open. Empty content is a single empty last chunk.
+
+use crate::sp_backend::{derive_argon2_key, KdfParams, ProfileEncryption};
+use crate::types::{ErrorKind, SpError, SpResult};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+
+/// User metadata name (`x-amz-meta-swiftpan-encryption`) marking a sealed
+/// object; its value names the format.
+pub const ENCRYPTION_METADATA_KEY: &str = "swiftpan-encryption";
+pub const ENCRYPTION_SCHEME: &str = "xchacha20poly1305-v1";
+
+/// Plaintext bytes per sealed chunk.
+pub const CHUNK_LEN: u64 = 64 * 1024;
+pub const HEADER_LEN: u64 = 52;
+const TAG_LEN: u64 = 16;
+const SEALED_CHUNK_LEN: u64 = CHUNK_LEN + TAG_LEN;
+const MAGIC: &[u8; 8] = b"SPENC\0\0\x01";
+
+/// Upper bounds on the Argon2 cost a header may ask for, so a crafted object
+/// cannot make a download allocate gigabytes or spin for minutes.
+const MAX_KDF_MEM_KIB: u32 = 1024 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 64;
+const MAX_KDF_PARALLELISM: u32 = 16;
+
+/// Keys already derived in this process, by a digest of passphrase and
+/// parameters. Argon2 is deliberately slow, and every upload and download of
+/// an encrypted profile needs a key.
+static DERIVED_KEYS: Lazy<Mutex<HashMap<[u8; 32], [u8; 32]>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `user_metadata` marks the object as sealed.
+pub fn is_sealed<'a>(user_metadata: impl IntoIterator<Item = (&'a String, &'a String)>) -> bool {
+    user_metadata
+        .into_iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case(ENCRYPTION_METADATA_KEY))
+}
+
+/// The user metadata pair that marks a sealed object.
+pub fn sealed_marker() -> (String, String) {
+    (
+        ENCRYPTION_METADATA_KEY.to_string(),
+        ENCRYPTION_SCHEME.to_string(),
+    )
+}
+
+/// Size of the sealed object holding `plain_len` bytes.
+pub fn sealed_len(plain_len: u64) -> u64 {
+    HEADER_LEN + plain_len + TAG_LEN * chunk_count(plain_len)
+}
+
+/// Size of the plaintext a sealed object of `sealed_len` bytes holds.
+pub fn plain_len(sealed_len: u64) -> SpResult<u64> {
+    let body = sealed_len
+        .checked_sub(HEADER_LEN)
+        .filter(|body| *body >= TAG_LEN)
+        .ok_or_else(|| format_error("sealed object is shorter than its header"))?;
+    let (full_chunks, rest) = (body / SEALED_CHUNK_LEN, body % SEALED_CHUNK_LEN);
+    match rest {
+        0 => Ok(full_chunks * CHUNK_LEN),
+        rest if rest == TAG_LEN && full_chunks > 0 => {
+            Err(format_error("sealed object ends in an empty chunk"))
+        }
+        rest if rest >= TAG_LEN => Ok(full_chunks * CHUNK_LEN + rest - TAG_LEN),
+        _ => Err(format_error("sealed object ends inside a chunk tag")),
+    }
+}
+
+fn chunk_count(plain_len: u64) -> u64 {
+    plain_len.div_ceil(CHUNK_LEN).max(1)
+}
+
+fn sealed_chunk_start(index: u64) -> u64 {
+    HEADER_LEN + index * SEALED_CHUNK_LEN
+}
+
+/// Chunks holding any byte of the sealed `range`.
+fn covering_chunks(range: &Range<u64>, plain_len: u64) -> Range<u64> {
+    if range.end <= HEADER_LEN || range.start >= range.end {
+        return 0..0;
+    }
+    let last_chunk = chunk_count(plain_len) - 1;
+    let first = (range.start.saturating_sub(HEADER_LEN) / SEALED_CHUNK_LEN).min(last_chunk);
+    let last = ((range.end - 1 - HEADER_LEN) / SEALED_CHUNK_LEN).min(last_chunk);
+    first..last + 1
+}
+
+/// The plaintext [`ContentSealer::seal_range`] needs to produce the sealed
+/// `range` of an object holding `plain_len` bytes.
+pub fn plaintext_for_sealed_range(range: &Range<u64>, plain_len: u64) -> Range<u64> {
+    let chunks = covering_chunks(range, plain_len);
+    if chunks.is_empty() {
+        return 0..0;
+    }
+    (chunks.start * CHUNK_LEN).min(plain_len)..(chunks.end * CHUNK_LEN).min(plain_len)
+}
+
+/// A key derived from a profile passphrase.
+#[derive(Clone)]
+pub struct ContentKey {
+    key: [u8; 32],
+    kdf: KdfParams,
+}
+
+impl ContentKey {
+    /// The key new objects of the profile are sealed with.
+    pub async fn for_profile(encryption: &ProfileEncryption) -> SpResult<Self> {
+        Self::derive(&encryption.passphrase, &encryption.kdf).await
+    }
+
+    /// The key the object with `header` was sealed with, provided it was
+    /// sealed under the profile's passphrase.
+    pub async fn for_header(
+        encryption: &ProfileEncryption,
+        header: &SealedHeader,
+    ) -> SpResult<Self> {
+        Self::derive(&encryption.passphrase, &header.kdf).await
+    }
+
+    async fn derive(passphrase: &str, kdf: &KdfParams) -> SpResult<Self> {
+        let mut digest = Sha256::new();
+        digest.update(kdf.mem_kib.to_be_bytes());
+        digest.update(kdf.iterations.to_be_bytes());
+        digest.update(kdf.parallelism.to_be_bytes());
+        digest.update(kdf.salt);
+        digest.update(passphrase.as_bytes());
+        let cache_key: [u8; 32] = digest.finalize().into();
+        if let Some(key) = derived_keys()?.get(&cache_key) {
+            return Ok(Self {
+                key: *key,
+                kdf: kdf.clone(),
+            });
+        }
+        let (passphrase, params) = (passphrase.to_string(), kdf.clone());
+        let key = tokio::task::spawn_blocking(move || derive_argon2_key(&passphrase, &params))
+            .await
+            .map_err(|error| SpError {
+                kind: ErrorKind::NotRetriable,
+                message: format!("key derivation task failed: {error}"),
+                retry_after_ms: None,
+                context: None,
+                at: now_ms(),
+            })??;
+        derived_keys()?.insert(cache_key, key);
+        Ok(Self {
+            key,
+            kdf: kdf.clone(),
+        })
+    }
+
+    /// A short fingerprint telling keys apart without revealing them.
+    pub fn id(&self) -> String {
+        let tag = keyed_digest(&self.key, b"swiftpan content key id");
+        tag[..8].iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new((&self.key).into())
+    }
+}
+
+fn derived_keys() -> SpResult<std::sync::MutexGuard<'static, HashMap<[u8; 32], [u8; 32]>>> {
+    DERIVED_KEYS.lock().map_err(|_| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: "content key cache lock poisoned".into(),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    })
+}
+
+fn keyed_digest(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// The header at the start of every sealed object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedHeader {
+    kdf: KdfParams,
+    nonce_prefix: [u8; 16],
+}
+
+impl SealedHeader {
+    /// Parses the first [`HEADER_LEN`] bytes of a sealed object.
+    pub fn parse(bytes: &[u8]) -> SpResult<Self> {
+        if bytes.len() < HEADER_LEN as usize || &bytes[..8] != MAGIC {
+            return Err(format_error("object does not start with a sealed header"));
+        }
+        let word = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().expect("4 bytes"));
+        let kdf = KdfParams {
+            algo: "argon2id".into(),
+            mem_kib: word(8),
+            iterations: word(12),
+            parallelism: word(16),
+            salt: bytes[20..36].try_into().expect("16 bytes"),
+        };
+        if kdf.mem_kib > MAX_KDF_MEM_KIB
+            || kdf.iterations > MAX_KDF_ITERATIONS
+            || kdf.parallelism > MAX_KDF_PARALLELISM
+        {
+            return Err(format_error(
+                "sealed header asks for an unreasonable key derivation",
+            ));
+        }
+        Ok(Self {
+            kdf,
+            nonce_prefix: bytes[36..52].try_into().expect("16 bytes"),
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&self.kdf.mem_kib.to_be_bytes());
+        header.extend_from_slice(&self.kdf.iterations.to_be_bytes());
+        header.extend_from_slice(&self.kdf.parallelism.to_be_bytes());
+        header.extend_from_slice(&self.kdf.salt);
+        header.extend_from_slice(&self.nonce_prefix);
+        header
+    }
+}
+
+fn chunk_nonce(nonce_prefix: &[u8; 16], index: u64) -> [u8; 24] {
+    let mut nonce = [0; 24];
+    nonce[..16].copy_from_slice(nonce_prefix);
+    nonce[16..].copy_from_slice(&index.to_be_bytes());
+    nonce
+}
+
+/// Seals the content of one object.
+pub struct ContentSealer {
+    key: ContentKey,
+    header: SealedHeader,
+}
+
+impl ContentSealer {
+    /// A sealer with a random nonce prefix, for content sealed in one pass.
+    pub fn new(key: ContentKey) -> Self {
+        let mut nonce_prefix = [0; 16];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        Self::with_nonce_prefix(key, nonce_prefix)
+    }
+
+    /// A sealer whose nonce prefix follows from the key and the multipart
+    /// `upload_id`, so parts re-sent after a restart are sealed exactly as
+    /// the ones the bucket already holds.
+    pub fn for_multipart_upload(key: ContentKey, upload_id: &str) -> Self {
+        let mut data = b"swiftpan multipart nonce\0".to_vec();
+        data.extend_from_slice(upload_id.as_bytes());
+        let digest = keyed_digest(&key.key, &data);
+        Self::with_nonce_prefix(key, digest[..16].try_into().expect("16 bytes"))
+    }
+
+    fn with_nonce_prefix(key: ContentKey, nonce_prefix: [u8; 16]) -> Self {
+        let header = SealedHeader {
+            kdf: key.kdf.clone(),
+            nonce_prefix,
+        };
+        Self { key, header }
+    }
+
+    fn seal_chunk(
+        &self,
+        cipher: &XChaCha20Poly1305,
+        index: u64,
+        chunk: &[u8],
+        last: bool,
+    ) -> Vec<u8> {
+        cipher
+            .encrypt(
+                (&chunk_nonce(&self.header.nonce_prefix, index)).into(),
+                Payload {
+                    msg: chunk,
+                    aad: &[u8::from(last)],
+                },
+            )
+            .expect("XChaCha20-Poly1305 seals messages of any chunk size")
+    }
+
+    /// Bytes `range` of the sealed object holding `plain_len` bytes.
+    /// `plaintext` is the range [`plaintext_for_sealed_range`] names.
+    pub fn seal_range(&self, range: Range<u64>, plain_len: u64, plaintext: &[u8]) -> Vec<u8> {
+        let chunks = covering_chunks(&range, plain_len);
+        let last_chunk = chunk_count(plain_len) - 1;
+        let cipher = self.key.cipher();
+        let (base, mut sealed) = if chunks.start == 0 {
+            (0, self.header.encode())
+        } else {
+            (sealed_chunk_start(chunks.start), Vec::new())
+        };
+        let chunk_len = CHUNK_LEN as usize;
+        for (position, index) in chunks.clone().enumerate() {
+            let start = (position * chunk_len).min(plaintext.len());
+            let end = (start + chunk_len).min(plaintext.len());
+            sealed.extend(self.seal_chunk(
+                &cipher,
+                index,
+                &plaintext[start..end],
+                index == last_chunk,
+            ));
+        }
+        let start = (range.start - base) as usize;
+        let end = ((range.end - base) as usize).min(sealed.len());
+        sealed.truncate(end);
+        sealed.drain(..start.min(end));
+        sealed
+    }
+}
+
+/// Seals content whose length is only known once it ends.
+pub struct StreamSealer {
+    sealer: ContentSealer,
+    cipher: XChaCha20Poly1305,
+    pending: Vec<u8>,
+    next_index: u64,
+    header_sent: bool,
+}
+
+impl StreamSealer {
+    pub fn new(sealer: ContentSealer) -> Self {
+        let cipher = sealer.key.cipher();
+        Self {
+            sealer,
+            cipher,
+            pending: Vec::with_capacity(CHUNK_LEN as usize),
+            next_index: 0,
+            header_sent: false,
+        }
+    }
+
+    /// Takes the next plaintext bytes and returns the sealed bytes now
+    /// ready. The last full chunk is held back until more content arrives,
+    /// since only then is it known not to be the last one.
+    pub fn push(&mut self, mut plaintext: &[u8]) -> Vec<u8> {
+        let mut sealed = self.take_header();
+        while !plaintext.is_empty() {
+            if self.pending.len() == CHUNK_LEN as usize {
+                sealed.extend(self.seal_pending(false));
+            }
+            let take = (CHUNK_LEN as usize - self.pending.len()).min(plaintext.len());
+            self.pending.extend_from_slice(&plaintext[..take]);
+            plaintext = &plaintext[take..];
+        }
+        sealed
+    }
+
+    /// Seals what is left as the last chunk.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut sealed = self.take_header();
+        sealed.extend(self.seal_pending(true));
+        sealed
+    }
+
+    fn take_header(&mut self) -> Vec<u8> {
+        if std::mem::replace(&mut self.header_sent, true) {
+            Vec::new()
+        } else {
+            self.sealer.header.encode()
+        }
+    }
+
+    fn seal_pending(&mut self, last: bool) -> Vec<u8> {
+        let sealed = self
+            .sealer
+            .seal_chunk(&self.cipher, self.next_index, &self.pending, last);
+        self.pending.clear();
+        self.next_index += 1;
+        sealed
+    }
+}
+
+/// Opens byte ranges of one sealed object.
+pub struct ContentOpener {
+    cipher: XChaCha20Poly1305,
+    nonce_prefix: [u8; 16],
+    sealed_len: u64,
+    plain_len: u64,
+}
+
+impl ContentOpener {
+    pub fn new(key: &ContentKey, header: &SealedHeader, sealed_len: u64) -> SpResult<Self> {
+        Ok(Self {
+            cipher: key.cipher(),
+            nonce_prefix: header.nonce_prefix,
+            sealed_len,
+            plain_len: plain_len(sealed_len)?,
+        })
+    }
+
+    pub fn plain_len(&self) -> u64 {
+        self.plain_len
+    }
+
+    /// The sealed bytes holding plaintext `range`, which starts on a chunk
+    /// boundary. An empty range at the start stands for the lone chunk of
+    /// empty content.
+    pub fn sealed_range(&self, range: &Range<u64>) -> Range<u64> {
+        let first = range.start / CHUNK_LEN;
+        let end = range.end.div_ceil(CHUNK_LEN).max(first + 1);
+        sealed_chunk_start(first)..sealed_chunk_start(end).min(self.sealed_len)
+    }
+
+    /// Opens the chunks in `sealed`, which [`Self::sealed_range`] named for
+    /// plaintext starting at `plain_start`.
+    pub fn open_range(&self, plain_start: u64, sealed: &[u8]) -> SpResult<Vec<u8>> {
+        let last_chunk = chunk_count(self.plain_len) - 1;
+        let mut plaintext = Vec::with_capacity(sealed.len());
+        for (position, chunk) in sealed.chunks(SEALED_CHUNK_LEN as usize).enumerate() {
+            let index = plain_start / CHUNK_LEN + position as u64;
+            let opened = self
+                .cipher
+                .decrypt(
+                    (&chunk_nonce(&self.nonce_prefix, index)).into(),
+                    Payload {
+                        msg: chunk,
+                        aad: &[u8::from(index == last_chunk)],
+                    },
+                )
+                .map_err(|_| SpError {
+                    kind: ErrorKind::Integrity,
+                    message: format!(
+                        "chunk {index} of the encrypted object did not authenticate; \
+                         the passphrase is wrong or the object was altered"
+                    ),
+                    retry_after_ms: None,
+                    context: None,
+                    at: now_ms(),
+                })?;
+            plaintext.extend(opened);
+        }
+        Ok(plaintext)
+    }
+}
+
+fn format_error(message: &str) -> SpError {
+    SpError {
+        kind: ErrorKind::Integrity,
+        message: message.into(),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> ContentKey {
+        ContentKey {
+            key: [9; 32],
+            kdf: KdfParams {
+                algo: "argon2id".into(),
+                mem_kib: 32,
+                iterations: 1,
+                parallelism: 1,
+                salt: [4; 16],
+            },
+        }
+    }
+
+    fn sealed(sealer: &ContentSealer, plaintext: &[u8]) -> Vec<u8> {
+        let plain_len = plaintext.len() as u64;
+        sealer.seal_range(0..sealed_len(plain_len), plain_len, plaintext)
+    }
+
+    fn open_all(sealed: &[u8]) -> SpResult<Vec<u8>> {
+        let header = SealedHeader::parse(sealed)?;
+        let opener = ContentOpener::new(&key(), &header, sealed.len() as u64)?;
+        let range = opener.sealed_range(&(0..opener.plain_len()));
+        opener.open_range(0, &sealed[range.start as usize..range.end as usize])
+    }
+
+    #[test]
+    fn sealed_and_plain_lengths_invert_at_chunk_edges() {
+        let chunk = CHUNK_LEN;
+        for plain in [0, 1, chunk - 1, chunk, chunk + 1, 3 * chunk, 3 * chunk + 7] {
+            assert_eq!(plain_len(sealed_len(plain)).ok(), Some(plain), "{plain}");
+        }
+        assert_eq!(sealed_len(0), HEADER_LEN + TAG_LEN);
+        assert!(plain_len(HEADER_LEN + 3).is_err());
+        assert!(plain_len(sealed_len(chunk) + TAG_LEN).is_err());
+        assert!(plain_len(sealed_len(chunk) + 5).is_err());
+    }
+
+    #[test]
+    fn streamed_and_ranged_sealing_produce_the_same_object() {
+        let plaintext = crate::test_support::patterned_bytes(3 * CHUNK_LEN as usize + 1000, 7);
+        let plain_len = plaintext.len() as u64;
+        let sealer = ContentSealer::for_multipart_upload(key(), "upload-1");
+        let whole = sealed(&sealer, &plaintext);
+
+        let mut stream = StreamSealer::new(ContentSealer::for_multipart_upload(key(), "upload-1"));
+        let mut streamed = Vec::new();
+        for piece in plaintext.chunks(50_000) {
+            streamed.extend(stream.push(piece));
+        }
+        streamed.extend(stream.finish());
+        assert_eq!(streamed, whole);
+        assert_eq!(whole.len() as u64, sealed_len(plain_len));
+
+        for range in [
+            0..10,
+            30..HEADER_LEN + 5,
+            70_000..150_000,
+            150_000..whole.len() as u64,
+        ] {
+            let needed = plaintext_for_sealed_range(&range, plain_len);
+            let part = sealer.seal_range(
+                range.clone(),
+                plain_len,
+                &plaintext[needed.start as usize..needed.end as usize],
+            );
+            assert_eq!(
+                part,
+                whole[range.start as usize..range.end as usize],
+                "{range:?}"
+            );
+        }
+        assert_eq!(
+            open_all(&whole).expect("sealed object should open"),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn ranges_open_from_any_chunk_boundary() {
+        let plaintext = crate::test_support::patterned_bytes(2 * CHUNK_LEN as usize + 10, 3);
+        let whole = sealed(&ContentSealer::new(key()), &plaintext);
+        let header = SealedHeader::parse(&whole).expect("header should parse");
+        let opener = ContentOpener::new(&key(), &header, whole.len() as u64).expect("opener");
+
+        let plain = CHUNK_LEN..opener.plain_len();
+        let range = opener.sealed_range(&plain);
+        let opened = opener
+            .open_range(
+                plain.start,
+                &whole[range.start as usize..range.end as usize],
+            )
+            .expect("tail chunks should open");
+
+        assert_eq!(opened, plaintext[CHUNK_LEN as usize..]);
+    }
+
+    #[test]
+    fn empty_content_is_one_authenticated_chunk() {
+        let whole = sealed(&ContentSealer::new(key()), &[]);
+
+        assert_eq!(whole.len() as u64, sealed_len(0));
+        assert_eq!(open_all(&whole).expect("empty object should open"), b"");
+    }
+
+    #[test]
+    fn altered_truncated_or_foreign_content_does_not_open() {
+        let plaintext = crate::test_support::patterned_bytes(2 * CHUNK_LEN as usize, 5);
+        let whole = sealed(&ContentSealer::new(key()), &plaintext);
+
+        let mut flipped = whole.clone();
+        flipped[HEADER_LEN as usize + 3] ^= 1;
+        let truncated = &whole[..sealed_len(CHUNK_LEN) as usize];
+        let mut other_key = key();
+        other_key.key[0] ^= 1;
+        let header = SealedHeader::parse(&whole).expect("header should parse");
+        let foreign = ContentOpener::new(&other_key, &header, whole.len() as u64)
+            .expect("opener")
+            .open_range(0, &whole[HEADER_LEN as usize..]);
+
+        for error in [
+            open_all(&flipped).expect_err("flipped bit"),
+            open_all(truncated).expect_err("dropped last chunk"),
+            foreign.expect_err("wrong key"),
+            SealedHeader::parse(b"plain text").expect_err("no header"),
+        ] {
+            assert!(matches!(error.kind, ErrorKind::Integrity), "{error:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn profile_keys_derive_once_and_follow_the_header_salt() {
+        let encryption = ProfileEncryption {
+            passphrase: "correct horse battery staple".into(),
+            kdf: key().kdf,
+        };
+        let first = ContentKey::for_profile(&encryption).await.expect("derive");
+        let again = ContentKey::for_profile(&encryption).await.expect("cached");
+        let header = SealedHeader {
+            kdf: KdfParams {
+                salt: [5; 16],
+                ..key().kdf
+            },
+            nonce_prefix: [0; 16],
+        };
+        let older = ContentKey::for_header(&encryption, &header)
+            .await
+            .expect("derive for header");
+
+        assert_eq!(first.id(), again.id());
+        assert_ne!(first.id(), older.id());
+        let sealer = ContentSealer::new(first);
+        let reparsed = SealedHeader::parse(&sealer.header.encode()).expect("header round trip");
+        assert_eq!(reparsed, sealer.header);
+    }
+}
@@ -1,20 +1,27 @@
 //! Tauri-independent download execution engine.
 //!
-//! This module owns remote metadata reads, ranged object reads, staged-file
-//! writes, pause/cancel polling, and final staged-file rename. Its boundary is
-//! an OpenDAL [`Operator`] plus observer callbacks. It must not construct R2
+//! This module owns remote metadata reads, ranged object reads with per-chunk
+//! retries, opening sealed objects chunk by chunk, staged-file writes,
+//! pause/cancel polling, checking the staged file against the object's
+//! recorded SHA-256, decoding compressed objects when asked, and final
+//! staged-file rename. Its boundary is an OpenDAL [`Operator`] plus observer callbacks. It must not construct R2
 //! credentials, access the global transfer registry, write SQLite snapshots,
 //! emit Tauri events, or materialize Android SAF targets.
 
 use super::{next_download_range, now_ms, part_path_for};
+use crate::compression::{self, Compression};
+use crate::content_crypto::{self, ContentKey, ContentOpener, SealedHeader, CHUNK_LEN};
+use crate::retry::{with_retry, RetryPolicy};
+use crate::sp_backend::ProfileEncryption;
 use crate::types::{err_invalid, ErrorKind, SpError, SpResult};
 use opendal::Operator;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 pub(crate) struct DownloadEngineRequest {
     pub(crate) key: String,
@@ -22,6 +29,13 @@ pub(crate) struct DownloadEngineRequest {
     pub(crate) chunk_size: u64,
     pub(crate) expected_etag: Option<String>,
     pub(crate) recorded_bytes_done: u64,
+    /// Applied to the metadata read and to each chunk separately.
+    pub(crate) retry: RetryPolicy,
+    /// Opens objects sealed under the profile's passphrase.
+    pub(crate) encryption: Option<ProfileEncryption>,
+    /// Decode an object stored with a known `Content-Encoding`. Otherwise
+    /// the staged file holds the stored bytes as they are.
+    pub(crate) decompress: bool,
 }
 
 pub(crate) struct DownloadControl {
@@ -50,15 +64,61 @@ pub(crate) async fn download_to_stage(
     control: DownloadControl,
     observer: &mut impl DownloadEngineObserver,
 ) -> SpResult<DownloadEngineOutput> {
-    let head = operator.stat(&request.key).await.map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("Stat: {error}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: now_ms(),
-    })?;
-    let total = head.content_length();
+    let part_path = part_path_for(&request.temp_path);
+    let stat = with_retry(&request.retry, "Stat", &control.cancelled, || async {
+        operator
+            .stat(&request.key)
+            .await
+            .map_err(|error| crate::storage::opendal_error("Stat", &request.key, &error))
+    })
+    .await;
+    let head = match stat {
+        Err(error) if matches!(error.kind, ErrorKind::Cancelled) => {
+            return cancel_download(&part_path, observer).await;
+        }
+        other => other?,
+    };
+    let stored_total = head.content_length();
     let observed_etag = head.etag().map(str::to_string);
+    let sealed = head.user_metadata().is_some_and(content_crypto::is_sealed);
+    let opener = if sealed {
+        match open_sealed(operator, &request, stored_total, &control).await {
+            Err(error) if matches!(error.kind, ErrorKind::Cancelled) => {
+                return cancel_download(&part_path, observer).await;
+            }
+            other => Some(other?),
+        }
+    } else {
+        None
+    };
+    // The recorded hash of a sealed object covers what is stored, not the
+    // plaintext staged here; every chunk was authenticated instead.
+    let recorded_sha256 = match opener {
+        Some(_) => None,
+        None => head
+            .user_metadata()
+            .and_then(crate::objects::recorded_sha256),
+    };
+    // Offsets and progress below count plaintext bytes.
+    let total = opener
+        .as_ref()
+        .map_or(stored_total, ContentOpener::plain_len);
+    let chunk_size = match opener {
+        Some(_) => (request.chunk_size / CHUNK_LEN).max(1) * CHUNK_LEN,
+        None => request.chunk_size,
+    };
+    // Compressed objects are staged as stored, so that resuming and the hash
+    // check work on stored offsets, and decoded once complete.
+    let compression = head
+        .content_encoding()
+        .and_then(Compression::from_content_encoding)
+        .filter(|_| request.decompress && opener.is_none());
+    let decoded_total = match compression {
+        Some(_) => head
+            .user_metadata()
+            .and_then(compression::recorded_original_size),
+        None => Some(total),
+    };
     observer.remote_metadata(total, observed_etag.as_deref())?;
 
     if let Some(expected) = request.expected_etag.as_ref() {
@@ -89,10 +149,11 @@ pub(crate) async fn download_to_stage(
             })?;
     }
 
-    let part_path = part_path_for(&request.temp_path);
     let finished_local = match tokio::fs::metadata(&request.temp_path).await {
         Ok(metadata) => {
-            metadata.len() == total && request.recorded_bytes_done == total && total > 0
+            Some(metadata.len()) == decoded_total
+                && request.recorded_bytes_done == total
+                && total > 0
         }
         Err(_) => false,
     };
@@ -125,8 +186,33 @@ pub(crate) async fn download_to_stage(
                 context: None,
                 at: now_ms(),
             })?;
+        // Sealed content is opened whole chunks at a time, so a resumed
+        // download restarts at the chunk the staged file ends in.
+        if opener.is_some() && offset % CHUNK_LEN != 0 {
+            offset -= offset % CHUNK_LEN;
+            file.set_len(offset).await.map_err(|error| SpError {
+                kind: ErrorKind::NotRetriable,
+                message: format!("truncate temp: {error}"),
+                retry_after_ms: None,
+                context: None,
+                at: now_ms(),
+            })?;
+        }
 
         observer.download_started(offset)?;
+        // Empty sealed content still has one chunk, whose tag proves the
+        // object was not cut short.
+        if let Some(opener) = opener.as_ref().filter(|opener| opener.plain_len() == 0) {
+            read_plain(
+                operator,
+                &request,
+                Some(opener),
+                0..0,
+                stored_total,
+                &control,
+            )
+            .await?;
+        }
 
         let mut was_paused = false;
         while offset < total {
@@ -148,30 +234,24 @@ pub(crate) async fn download_to_stage(
                 was_paused = false;
             }
 
-            let range = next_download_range(offset, total, request.chunk_size)
+            let range = next_download_range(offset, total, chunk_size)
                 .ok_or_else(|| err_invalid("invalid download range"))?;
             let range_start = range.start;
-            let data = operator
-                .read_with(&request.key)
-                .range(range)
-                .await
-                .map_err(|error| SpError {
-                    kind: ErrorKind::RetryableNet,
-                    message: format!("GetObject range: {error}"),
-                    retry_after_ms: Some(500),
-                    context: None,
-                    at: now_ms(),
-                })?;
-            if data.is_empty() {
-                return Err(SpError {
-                    kind: ErrorKind::RetryableNet,
-                    message: format!("unexpected EOF at byte {offset} of {total}"),
-                    retry_after_ms: Some(500),
-                    context: None,
-                    at: now_ms(),
-                });
-            }
-            let chunk_bytes = data.to_bytes();
+            let read = read_plain(
+                operator,
+                &request,
+                opener.as_ref(),
+                range,
+                stored_total,
+                &control,
+            )
+            .await;
+            let chunk_bytes = match read {
+                Err(error) if matches!(error.kind, ErrorKind::Cancelled) => {
+                    return cancel_download(&part_path, observer).await;
+                }
+                other => other?,
+            };
             file.write_all(&chunk_bytes)
                 .await
                 .map_err(|error| SpError {
@@ -195,7 +275,26 @@ pub(crate) async fn download_to_stage(
         if control.cancelled.load(Ordering::Relaxed) {
             return cancel_download(&part_path, observer).await;
         }
-        tokio::fs::rename(&part_path, &request.temp_path)
+        // A resumed download may combine bytes of two versions of the
+        // object; only a match proves the staged file is one of them.
+        if let Some(expected) = recorded_sha256 {
+            let actual = staged_sha256(&part_path).await?;
+            if actual != expected {
+                let _ = tokio::fs::remove_file(&part_path).await;
+                return Err(crate::objects::integrity_error(
+                    &request.key,
+                    &expected,
+                    &actual,
+                ));
+            }
+        }
+        let staged_path = match compression {
+            Some(compression) => {
+                decode_staged(&request.key, compression, &part_path, decoded_total).await?
+            }
+            None => part_path,
+        };
+        tokio::fs::rename(&staged_path, &request.temp_path)
             .await
             .map_err(|error| SpError {
                 kind: ErrorKind::NotRetriable,
@@ -209,6 +308,163 @@ pub(crate) async fn download_to_stage(
     Ok(DownloadEngineOutput { total })
 }
 
+async fn staged_sha256(path: &Path) -> SpResult<String> {
+    let read_error = |error: std::io::Error| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("read staged download: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    };
+    let mut file = tokio::fs::File::open(path).await.map_err(read_error)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Decodes the complete staged download next to it and returns the decoded
+/// file. Content that does not decode, or decodes to another size than the
+/// one recorded at upload, is discarded so that a retry downloads it again.
+async fn decode_staged(
+    key: &str,
+    compression: Compression,
+    part_path: &Path,
+    decoded_total: Option<u64>,
+) -> SpResult<PathBuf> {
+    let decoded_path = part_path.with_extension("decoded");
+    let decoded = compression::decompress_file(compression, part_path, &decoded_path).await;
+    let failure = match decoded {
+        Ok(len) if decoded_total.map_or(true, |expected| expected == len) => None,
+        Ok(len) => Some(SpError {
+            kind: ErrorKind::Integrity,
+            message: format!(
+                "{key} decoded to {len} bytes; {} were recorded at upload",
+                decoded_total.unwrap_or_default()
+            ),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        }),
+        Err(error) => Some(error),
+    };
+    if let Some(error) = failure {
+        let _ = tokio::fs::remove_file(&decoded_path).await;
+        if matches!(error.kind, ErrorKind::Integrity) {
+            let _ = tokio::fs::remove_file(part_path).await;
+        }
+        return Err(error);
+    }
+    let _ = tokio::fs::remove_file(part_path).await;
+    Ok(decoded_path)
+}
+
+/// Reads the header of a sealed object and derives the key that opens it.
+async fn open_sealed(
+    operator: &Operator,
+    request: &DownloadEngineRequest,
+    stored_total: u64,
+    control: &DownloadControl,
+) -> SpResult<ContentOpener> {
+    let Some(encryption) = request.encryption.as_ref() else {
+        return Err(SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!(
+                "{} is encrypted; set the profile's encryption passphrase to download it",
+                request.key
+            ),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        });
+    };
+    content_crypto::plain_len(stored_total)?;
+    let header = with_retry(
+        &request.retry,
+        "GetObject range",
+        &control.cancelled,
+        || {
+            read_chunk(
+                operator,
+                &request.key,
+                0..content_crypto::HEADER_LEN,
+                stored_total,
+            )
+        },
+    )
+    .await?;
+    let header = SealedHeader::parse(&header.to_bytes())?;
+    let key = ContentKey::for_header(encryption, &header).await?;
+    ContentOpener::new(&key, &header, stored_total)
+}
+
+/// Reads plaintext `range`, through the chunks that hold it when the object
+/// is sealed.
+async fn read_plain(
+    operator: &Operator,
+    request: &DownloadEngineRequest,
+    opener: Option<&ContentOpener>,
+    range: std::ops::Range<u64>,
+    stored_total: u64,
+    control: &DownloadControl,
+) -> SpResult<bytes::Bytes> {
+    let stored = opener.map_or(range.clone(), |opener| opener.sealed_range(&range));
+    let data = with_retry(
+        &request.retry,
+        "GetObject range",
+        &control.cancelled,
+        || read_chunk(operator, &request.key, stored.clone(), stored_total),
+    )
+    .await?
+    .to_bytes();
+    match opener {
+        // Chunks only open whole; a short read is retried like an empty one.
+        Some(_) if (data.len() as u64) < stored.end - stored.start => Err(SpError {
+            kind: ErrorKind::RetryableNet,
+            message: format!(
+                "short read of {} bytes at byte {} of {stored_total}",
+                data.len(),
+                stored.start
+            ),
+            retry_after_ms: Some(500),
+            context: None,
+            at: now_ms(),
+        }),
+        Some(opener) => Ok(opener.open_range(range.start, &data)?.into()),
+        None => Ok(data),
+    }
+}
+
+async fn read_chunk(
+    operator: &Operator,
+    key: &str,
+    range: std::ops::Range<u64>,
+    total: u64,
+) -> SpResult<opendal::Buffer> {
+    let offset = range.start;
+    let data = operator
+        .read_with(key)
+        .range(range)
+        .await
+        .map_err(|error| crate::storage::opendal_error("GetObject range", key, &error))?;
+    if data.is_empty() {
+        return Err(SpError {
+            kind: ErrorKind::RetryableNet,
+            message: format!("unexpected EOF at byte {offset} of {total}"),
+            retry_after_ms: Some(500),
+            context: None,
+            at: now_ms(),
+        });
+    }
+    Ok(data)
+}
+
 async fn cancel_download(
     part_path: &std::path::Path,
     observer: &mut impl DownloadEngineObserver,
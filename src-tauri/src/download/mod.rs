@@ -5,11 +5,12 @@
 //! adapters. It must not contain range I/O, target-path rules, or persistence
 //! implementation details; those belong to the dedicated child modules.
 
+use crate::sp_backend::{SpBackend, DEFAULT_PROFILE_NAME};
+use crate::storage;
 use crate::transfer_db::{self, TransferKind, TransferLifecycle, TransferPhase, TransferSnapshot};
 use crate::transfer_fsm::TransferStateEvent;
 use crate::types::*;
 use crate::usage::UsageSync;
-use crate::{sp_backend::SpBackend, storage};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::{
@@ -47,6 +48,10 @@ pub struct NewDownloadParams {
     pub android_tree_uri: Option<String>,
     pub android_relative_path: Option<String>,
     pub mime: Option<String>,
+    /// Decode an object stored with a gzip or zstd `Content-Encoding`, so
+    /// the target holds the original content.
+    #[serde(default)]
+    pub decompress: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -227,10 +232,15 @@ pub fn init(app: &tauri::AppHandle) -> SpResult<()> {
                 snapshot.transfer_id.clone(),
                 Transfer {
                     key: snapshot.key.clone(),
+                    profile: snapshot
+                        .profile
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_PROFILE_NAME.into()),
                     target,
                     temp_path,
                     chunk: 4 * 1024 * 1024,
                     expected_etag: snapshot.expected_etag.clone(),
+                    decompress: snapshot.decompress,
                     observed_etag: snapshot.observed_etag.clone(),
                     bytes_total: snapshot.bytes_total,
                     bytes_done: snapshot.bytes_done,
@@ -270,6 +280,7 @@ pub fn init(app: &tauri::AppHandle) -> SpResult<()> {
 
 pub async fn start_download(app: tauri::AppHandle, params: NewDownloadParams) -> SpResult<String> {
     let target = DownloadTarget::from_params(&params)?;
+    let profile = SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
     let id = uuid::Uuid::new_v4().to_string();
     let paused = Arc::new(AtomicBool::new(false));
     let cancelled = Arc::new(AtomicBool::new(false));
@@ -298,10 +309,12 @@ pub async fn start_download(app: tauri::AppHandle, params: NewDownloadParams) ->
             id.clone(),
             Transfer {
                 key: params.key.clone(),
+                profile,
                 target,
                 temp_path,
                 chunk: params.chunk_size.max(1024 * 1024),
                 expected_etag: params.expected_etag.clone(),
+                decompress: params.decompress,
                 observed_etag: None,
                 bytes_total: None,
                 bytes_done: 0,
@@ -324,7 +337,7 @@ pub async fn start_download(app: tauri::AppHandle, params: NewDownloadParams) ->
 async fn run_download(app: &tauri::AppHandle, id: &str, recovered: bool) -> SpResult<()> {
     let (key, target, temp_path, chunk, expected_etag, bytes_done, paused, cancelled) =
         load_runtime_fields(id)?;
-    let entry_phase = {
+    let (entry_phase, profile, decompress) = {
         let g = DL.lock().map_err(|_| SpError {
             kind: ErrorKind::NotRetriable,
             message: "download state lock poisoned".into(),
@@ -339,11 +352,12 @@ async fn run_download(app: &tauri::AppHandle, id: &str, recovered: bool) -> SpRe
             context: None,
             at: now_ms(),
         })?;
-        if recovered {
+        let phase = if recovered {
             t.phase.unwrap_or(TransferPhase::PreparingTarget)
         } else {
             TransferPhase::PreparingTarget
-        }
+        };
+        (phase, t.profile.clone(), t.decompress)
     };
     let start_event = if recovered {
         DownloadEvent::Resumed {
@@ -361,19 +375,30 @@ async fn run_download(app: &tauri::AppHandle, id: &str, recovered: bool) -> SpRe
     emit_download(app, &start_event);
 
     let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = storage::build_operator(&bundle.r2).await?;
-    let mut observer = RuntimeDownloadObserver { app, id };
-    let output = download_to_stage(
-        &operator,
-        DownloadEngineRequest {
-            key,
-            temp_path: temp_path.clone(),
-            chunk_size: chunk,
-            expected_etag,
-            recorded_bytes_done: bytes_done,
-        },
-        DownloadControl { paused, cancelled },
-        &mut observer,
+    let operator = storage::build_profile_operator(&bundle, &profile).await?;
+    let mut observer = RuntimeDownloadObserver {
+        app,
+        id,
+        profile: &profile,
+    };
+    // The engine retries each chunk read as a whole.
+    let output = crate::retry::retrying_steps(
+        cancelled.clone(),
+        download_to_stage(
+            &operator,
+            DownloadEngineRequest {
+                key,
+                temp_path: temp_path.clone(),
+                chunk_size: chunk,
+                expected_etag,
+                recorded_bytes_done: bytes_done,
+                retry: crate::retry::RetryPolicy::from_settings(&crate::settings::get().network),
+                encryption: bundle.encryption_for(&profile).cloned(),
+                decompress,
+            },
+            DownloadControl { paused, cancelled },
+            &mut observer,
+        ),
     )
     .await?;
 
@@ -394,20 +419,20 @@ async fn run_download(app: &tauri::AppHandle, id: &str, recovered: bool) -> SpRe
 struct RuntimeDownloadObserver<'a> {
     app: &'a tauri::AppHandle,
     id: &'a str,
+    profile: &'a str,
 }
 
 impl DownloadEngineObserver for RuntimeDownloadObserver<'_> {
     fn remote_metadata(&mut self, total: u64, observed_etag: Option<&str>) -> SpResult<()> {
         let mut class_b = std::collections::HashMap::new();
         class_b.insert("HeadObject".into(), 1u64);
-        let _ = UsageSync::record_local_delta(UsageDelta {
-            class_a: Default::default(),
-            class_b,
-            ingress_bytes: 0,
-            egress_bytes: 0,
-            added_storage_bytes: 0,
-            deleted_storage_bytes: 0,
-        });
+        let _ = UsageSync::record_local_delta(
+            self.profile,
+            UsageDelta {
+                class_b,
+                ..UsageDelta::default()
+            },
+        );
         mutate_transfer(self.id, |transfer| {
             transfer.bytes_total = Some(total);
             transfer.observed_etag = observed_etag.map(str::to_string);
@@ -465,14 +490,14 @@ impl DownloadEngineObserver for RuntimeDownloadObserver<'_> {
     fn chunk_done(&mut self, range_start: u64, len: u64, offset: u64) -> SpResult<()> {
         let mut class_b = std::collections::HashMap::new();
         class_b.insert("GetObject".into(), 1u64);
-        let _ = UsageSync::record_local_delta(UsageDelta {
-            class_a: Default::default(),
-            class_b,
-            ingress_bytes: 0,
-            egress_bytes: len,
-            added_storage_bytes: 0,
-            deleted_storage_bytes: 0,
-        });
+        let _ = UsageSync::record_local_delta(
+            self.profile,
+            UsageDelta {
+                class_b,
+                egress_bytes: len,
+                ..UsageDelta::default()
+            },
+        );
         mutate_transfer(self.id, |transfer| {
             transfer.bytes_done = offset;
         })?;
@@ -652,7 +677,10 @@ pub fn status(transfer_id: &str) -> SpResult<DownloadStatus> {
 }
 
 pub fn list_active_snapshots() -> SpResult<Vec<TransferSnapshot>> {
-    let persisted = transfer_db::list_active_snapshots()?;
+    let persisted = transfer_db::list_active_snapshots()?
+        .into_iter()
+        .filter(|snapshot| snapshot.kind == TransferKind::Download)
+        .collect::<Vec<_>>();
     let runtime = {
         let g = DL.lock().map_err(|_| SpError {
             kind: ErrorKind::NotRetriable,
@@ -674,7 +702,10 @@ pub fn list_active_snapshots() -> SpResult<Vec<TransferSnapshot>> {
         return Ok(persisted);
     }
     let mut merged = std::collections::HashMap::new();
-    for snapshot in persisted {
+    for snapshot in persisted
+        .into_iter()
+        .filter(|snapshot| snapshot.kind == TransferKind::Download)
+    {
         merged.insert(snapshot.transfer_id.clone(), snapshot);
     }
     for snapshot in runtime {
@@ -700,7 +731,10 @@ pub fn list_snapshots() -> SpResult<Vec<TransferSnapshot>> {
             .collect::<Vec<_>>()
     };
     let mut merged = std::collections::HashMap::new();
-    for snapshot in persisted {
+    for snapshot in persisted
+        .into_iter()
+        .filter(|snapshot| snapshot.kind == TransferKind::Download)
+    {
         merged.insert(snapshot.transfer_id.clone(), snapshot);
     }
     for snapshot in runtime {
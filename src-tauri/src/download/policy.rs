@@ -26,7 +26,10 @@ pub(super) fn last_fail_reason_for(
 }
 
 pub(super) fn should_keep_failed_artifacts(reason: Option<&ErrorKind>) -> bool {
-    matches!(reason, Some(ErrorKind::RetryableNet))
+    matches!(
+        reason,
+        Some(ErrorKind::RetryableNet | ErrorKind::RateLimited)
+    )
 }
 
 pub(super) fn lifecycle_after_restart(lifecycle: &TransferLifecycle) -> TransferLifecycle {
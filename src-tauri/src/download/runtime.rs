@@ -17,10 +17,13 @@ use std::sync::{atomic::AtomicBool, Arc, Mutex};
 
 pub(super) struct Transfer {
     pub(super) key: String,
+    pub(super) profile: String,
     pub(super) target: DownloadTarget,
     pub(super) temp_path: PathBuf,
     pub(super) chunk: u64,
     pub(super) expected_etag: Option<String>,
+    /// Decode a compressed object into the target.
+    pub(super) decompress: bool,
     pub(super) observed_etag: Option<String>,
     pub(super) bytes_total: Option<u64>,
     pub(super) bytes_done: u64,
@@ -59,6 +62,8 @@ pub(super) fn snapshot_from_transfer(id: &str, transfer: &Transfer) -> TransferS
         temp_path: Some(transfer.temp_path.to_string_lossy().to_string()),
         expected_etag: transfer.expected_etag.clone(),
         observed_etag: transfer.observed_etag.clone(),
+        profile: Some(transfer.profile.clone()),
+        decompress: transfer.decompress,
         created_at_ms: transfer.created_at_ms,
         updated_at_ms: transfer.updated_at_ms,
     }
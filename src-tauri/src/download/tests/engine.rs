@@ -1,11 +1,15 @@
 use super::super::*;
-use crate::test_support::{limit_read_responses, patterned_bytes, report_etag};
+use crate::retry::RetryPolicy;
+use crate::test_support::{
+    fail_next_reads, instant_retries, limit_read_responses, patterned_bytes, report_etag,
+};
 use opendal::services::Memory;
 use sha2::{Digest, Sha256};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
+use std::time::Duration;
 
 #[derive(Default)]
 struct RecordingObserver {
@@ -101,6 +105,9 @@ async fn engine_downloads_boundary_sized_objects_exactly() {
                 chunk_size: CHUNK as u64,
                 expected_etag: None,
                 recorded_bytes_done: 0,
+                retry: instant_retries(),
+                encryption: None,
+                decompress: false,
             },
             control,
             &mut observer,
@@ -147,6 +154,9 @@ async fn engine_resumes_from_existing_partial_file_without_rewriting_prefix() {
             chunk_size: CHUNK as u64,
             expected_etag: None,
             recorded_bytes_done: resume_offset as u64,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -191,6 +201,9 @@ async fn oversized_partial_file_is_discarded_before_restarting_from_zero() {
             chunk_size: 257,
             expected_etag: None,
             recorded_bytes_done: 2048,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -232,6 +245,9 @@ async fn cancellation_removes_staged_artifacts_and_notifies_observer() {
             chunk_size: 1024,
             expected_etag: None,
             recorded_bytes_done: 1024,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -273,6 +289,9 @@ async fn cancellation_preserves_destination_that_existed_before_download() {
             chunk_size: 1024,
             expected_etag: None,
             recorded_bytes_done: 1024,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -315,6 +334,9 @@ async fn complete_but_stale_partial_file_is_not_promoted_by_length_alone() {
             chunk_size: 1024,
             expected_etag: None,
             recorded_bytes_done: remote.len() as u64,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -355,6 +377,9 @@ async fn changed_remote_etag_rejects_resume_before_touching_partial_file() {
             chunk_size: 1024,
             expected_etag: Some("etag-old".into()),
             recorded_bytes_done: 1024,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -392,6 +417,9 @@ async fn required_etag_that_backend_omits_fails_closed() {
             chunk_size: 512,
             expected_etag: Some("required-etag".into()),
             recorded_bytes_done: 0,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -425,6 +453,9 @@ async fn repeated_nonempty_short_reads_still_reconstruct_the_object() {
             chunk_size: 1024,
             expected_etag: None,
             recorded_bytes_done: 0,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         control,
         &mut observer,
@@ -440,3 +471,149 @@ async fn repeated_nonempty_short_reads_still_reconstruct_the_object() {
     );
     assert!(observer.chunks.iter().all(|(_, len, _)| *len <= 317));
 }
+
+#[tokio::test]
+async fn transient_chunk_failures_are_retried_without_failing_the_download() {
+    let storage = memory_operator();
+    let original = patterned_bytes(4096 + 11, 73);
+    storage
+        .write("flaky.bin", original.clone())
+        .await
+        .expect("remote fixture should upload");
+    let operator = fail_next_reads(storage, 2);
+    let temp = tempfile::tempdir().expect("temp directory should build");
+    let destination = temp.path().join("flaky.bin");
+    let (control, _, _) = controls();
+    let mut observer = RecordingObserver::default();
+
+    download_to_stage(
+        &operator,
+        DownloadEngineRequest {
+            key: "flaky.bin".into(),
+            temp_path: destination.clone(),
+            chunk_size: 1024,
+            expected_etag: None,
+            recorded_bytes_done: 0,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
+        },
+        control,
+        &mut observer,
+    )
+    .await
+    .expect("two transient failures fit in the retry budget");
+
+    assert_eq!(
+        tokio::fs::read(destination)
+            .await
+            .expect("destination should exist"),
+        original
+    );
+    assert_eq!(observer.chunks.len(), 5);
+}
+
+#[tokio::test]
+async fn cancelling_during_a_retry_wait_cancels_the_download() {
+    let storage = memory_operator();
+    storage
+        .write("stalled.bin", patterned_bytes(2048, 79))
+        .await
+        .expect("remote fixture should upload");
+    let operator = fail_next_reads(storage, u32::MAX);
+    let temp = tempfile::tempdir().expect("temp directory should build");
+    let destination = temp.path().join("stalled.bin");
+    let (control, _, cancelled) = controls();
+    let mut observer = RecordingObserver::default();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancelled.store(true, Ordering::Relaxed);
+    });
+
+    let error = download_to_stage(
+        &operator,
+        DownloadEngineRequest {
+            key: "stalled.bin".into(),
+            temp_path: destination.clone(),
+            chunk_size: 1024,
+            expected_etag: None,
+            recorded_bytes_done: 0,
+            retry: RetryPolicy {
+                max_attempts: 10,
+                base_delay: Duration::from_secs(30),
+                max_delay: Duration::from_secs(30),
+            },
+            encryption: None,
+            decompress: false,
+        },
+        control,
+        &mut observer,
+    )
+    .await
+    .expect_err("cancel must interrupt the backoff");
+
+    assert!(matches!(error.kind, ErrorKind::Cancelled));
+    assert!(observer.cancelled);
+    assert!(!part_path_for(&destination).exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn staged_file_must_match_the_recorded_sha256_before_the_rename() {
+    const CHUNK: usize = 16 * 1024;
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    let original = patterned_bytes(3 * CHUNK + 9, 12);
+    operator
+        .write_with("camera/hashed.arw", original.clone())
+        .user_metadata([(
+            crate::objects::SHA256_METADATA_KEY.to_string(),
+            format!("{:x}", Sha256::digest(&original)),
+        )])
+        .await
+        .expect("fixture should upload");
+    let temp = tempfile::tempdir().expect("temp directory should build");
+    let request = |destination: &std::path::Path, recorded_bytes_done| DownloadEngineRequest {
+        key: "camera/hashed.arw".into(),
+        temp_path: destination.to_path_buf(),
+        chunk_size: CHUNK as u64,
+        expected_etag: None,
+        recorded_bytes_done,
+        retry: instant_retries(),
+        encryption: None,
+        decompress: false,
+    };
+
+    let verified = temp.path().join("verified.arw");
+    download_to_stage(
+        &operator,
+        request(&verified, 0),
+        controls().0,
+        &mut RecordingObserver::default(),
+    )
+    .await
+    .expect("matching content should be published");
+    assert_eq!(
+        tokio::fs::read(&verified).await.expect("file should exist"),
+        original
+    );
+
+    // A partial file left by an earlier version of the object.
+    let mixed = temp.path().join("mixed.arw");
+    let mut stale_prefix = original[..CHUNK].to_vec();
+    stale_prefix[7] ^= 0x55;
+    tokio::fs::write(part_path_for(&mixed), &stale_prefix)
+        .await
+        .expect("partial fixture should write");
+    let error = download_to_stage(
+        &operator,
+        request(&mixed, CHUNK as u64),
+        controls().0,
+        &mut RecordingObserver::default(),
+    )
+    .await
+    .expect_err("mixed content must not be published");
+
+    assert!(matches!(error.kind, ErrorKind::Integrity));
+    assert!(!mixed.exists());
+    assert!(!part_path_for(&mixed).exists());
+}
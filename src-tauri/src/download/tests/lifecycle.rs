@@ -41,6 +41,7 @@ fn terminal_download_states_remain_terminal_on_restart() {
 #[test]
 fn only_retryable_network_failures_keep_partial_download_artifacts() {
     assert!(should_keep_failed_artifacts(Some(&ErrorKind::RetryableNet)));
+    assert!(should_keep_failed_artifacts(Some(&ErrorKind::RateLimited)));
 
     for reason in [
         ErrorKind::Cancelled,
@@ -50,6 +51,10 @@ fn only_retryable_network_failures_keep_partial_download_artifacts() {
         ErrorKind::DiskFull,
         ErrorKind::TaskExists,
         ErrorKind::NotImplemented,
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::Conflict,
+        ErrorKind::Integrity,
     ] {
         assert!(
             !should_keep_failed_artifacts(Some(&reason)),
@@ -9,6 +9,7 @@ fn params() -> NewDownloadParams {
         android_tree_uri: None,
         android_relative_path: None,
         mime: None,
+        decompress: false,
     }
 }
 
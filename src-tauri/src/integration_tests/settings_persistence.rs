@@ -1,4 +1,4 @@
-use crate::settings::{load_from_path, save_to_path, AppSettings};
+use crate::settings::{load_from_path, save_to_path, AppSettings, NetworkSettings};
 
 fn configured_settings() -> AppSettings {
     AppSettings {
@@ -6,7 +6,15 @@ fn configured_settings() -> AppSettings {
         max_concurrency: 6,
         default_download_dir: Some("/storage/photos".into()),
         upload_thumbnail: false,
+        per_task_parts: 8,
         android_tree_uri: Some("content://tree/photos".into()),
+        recycle_bin: true,
+        trash_retention_days: 3,
+        network: NetworkSettings {
+            proxy_url: Some("http://proxy.corp:3128".into()),
+            read_timeout_secs: 120,
+            ..NetworkSettings::default()
+        },
     }
 }
 
@@ -31,7 +39,12 @@ fn settings_survive_real_file_persistence_and_fresh_reload() {
     assert_eq!(reloaded.max_concurrency, expected.max_concurrency);
     assert_eq!(reloaded.default_download_dir, expected.default_download_dir);
     assert_eq!(reloaded.upload_thumbnail, expected.upload_thumbnail);
+    assert_eq!(reloaded.per_task_parts, expected.per_task_parts);
     assert_eq!(reloaded.android_tree_uri, expected.android_tree_uri);
+    assert_eq!(reloaded.recycle_bin, expected.recycle_bin);
+    assert_eq!(reloaded.trash_retention_days, expected.trash_retention_days);
+    assert_eq!(persisted_json["network"]["readTimeoutSecs"], 120);
+    assert_eq!(reloaded.network, expected.network);
 }
 
 #[test]
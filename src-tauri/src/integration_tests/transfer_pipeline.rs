@@ -2,7 +2,7 @@ use crate::download::{
     download_to_stage_for_integration, IntegrationDownloadControl, IntegrationDownloadObserver,
     IntegrationDownloadRequest,
 };
-use crate::test_support::{inject_early_eof, patterned_bytes};
+use crate::test_support::{inject_early_eof, instant_retries, patterned_bytes};
 use crate::types::{ErrorKind, SpError, SpResult};
 use crate::upload::{
     upload_file_for_integration, IntegrationUploadControl, IntegrationUploadObserver,
@@ -160,6 +160,9 @@ async fn download_resumes_with_fresh_runtime_after_interrupted_first_run() {
                 chunk_size: CHUNK as u64,
                 expected_etag: None,
                 recorded_bytes_done: 0,
+                retry: instant_retries(),
+                encryption: None,
+                decompress: false,
             },
             IntegrationDownloadControl { paused, cancelled },
             &mut interrupted_observer,
@@ -192,6 +195,9 @@ async fn download_resumes_with_fresh_runtime_after_interrupted_first_run() {
             chunk_size: CHUNK as u64,
             expected_etag: None,
             recorded_bytes_done: persisted_bytes_done,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         IntegrationDownloadControl { paused, cancelled },
         &mut recovered_observer,
@@ -235,6 +241,9 @@ async fn download_rejects_early_eof_instead_of_promoting_partial_file() {
             chunk_size: CHUNK as u64,
             expected_etag: None,
             recorded_bytes_done: 0,
+            retry: instant_retries(),
+            encryption: None,
+            decompress: false,
         },
         IntegrationDownloadControl { paused, cancelled },
         &mut observer,
@@ -279,6 +288,9 @@ async fn production_upload_and_download_engines_preserve_bytes_and_metadata() {
                 part_size: CHUNK as u64,
                 content_type: None,
                 content_disposition: Some("attachment; filename=\"DSC.ARW\"".into()),
+                encryption: None,
+                compression: None,
+                condition: Default::default(),
             },
             IntegrationUploadControl {
                 paused: upload_paused,
@@ -312,6 +324,9 @@ async fn production_upload_and_download_engines_preserve_bytes_and_metadata() {
                 chunk_size: CHUNK as u64,
                 expected_etag: None,
                 recorded_bytes_done: 0,
+                retry: instant_retries(),
+                encryption: None,
+                decompress: false,
             },
             IntegrationDownloadControl {
                 paused: download_paused,
@@ -336,3 +351,246 @@ async fn production_upload_and_download_engines_preserve_bytes_and_metadata() {
         );
     }
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sealed_uploads_download_as_plaintext_and_resume_on_chunk_boundaries() {
+    use crate::content_crypto::{self, CHUNK_LEN};
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    let encryption = crate::test_support::cheap_encryption("pipeline secret");
+    let directory = tempfile::tempdir().expect("temporary directory should exist");
+    let chunk = CHUNK_LEN as usize;
+
+    for size in [0, 1, chunk, 3 * chunk + 17] {
+        let source = directory.path().join(format!("source-{size}.arw"));
+        let destination = directory.path().join(format!("downloaded-{size}.arw"));
+        let part_path = destination.with_extension("arw.part");
+        let key = format!("camera/sealed-{size}.ARW");
+        let original = patterned_bytes(size, 97);
+        tokio::fs::write(&source, &original)
+            .await
+            .expect("source fixture should write");
+        let (paused, cancelled) = control_flags();
+        upload_file_for_integration(
+            &operator,
+            IntegrationUploadRequest {
+                key: key.clone(),
+                source_path: source,
+                part_size: 1024 * 1024,
+                content_type: None,
+                content_disposition: None,
+                encryption: Some(encryption.clone()),
+                compression: None,
+                condition: Default::default(),
+            },
+            IntegrationUploadControl { paused, cancelled },
+            &mut UploadObserver::default(),
+        )
+        .await
+        .expect("sealed upload should complete");
+        let stored = operator
+            .read(&key)
+            .await
+            .expect("object should exist")
+            .to_vec();
+        assert_eq!(stored.len() as u64, content_crypto::sealed_len(size as u64));
+        if size >= chunk {
+            assert!(!stored.windows(64).any(|window| window == &original[..64]));
+        }
+
+        let download = |recorded_bytes_done, encryption| IntegrationDownloadRequest {
+            key: key.clone(),
+            temp_path: destination.clone(),
+            // Not a multiple of the chunk length; reads round down to one.
+            chunk_size: CHUNK_LEN + 100,
+            expected_etag: None,
+            recorded_bytes_done,
+            retry: instant_retries(),
+            encryption,
+            decompress: false,
+        };
+        let mut recorded_bytes_done = 0;
+        if size > chunk {
+            let (paused, cancelled) = control_flags();
+            let mut interrupted = InterruptAfterFirstChunk::default();
+            download_to_stage_for_integration(
+                &operator,
+                download(0, Some(encryption.clone())),
+                IntegrationDownloadControl { paused, cancelled },
+                &mut interrupted,
+            )
+            .await
+            .expect_err("first run must be interrupted");
+            assert_eq!(interrupted.persisted_bytes_done, CHUNK_LEN);
+            // A torn write leaves the staged file inside a chunk.
+            let staged = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&part_path)
+                .expect("staged file should exist");
+            staged
+                .set_len(CHUNK_LEN - 10)
+                .expect("staged file should shrink");
+            recorded_bytes_done = CHUNK_LEN - 10;
+        }
+        let (paused, cancelled) = control_flags();
+        let mut observer = DownloadObserver::default();
+        let output = download_to_stage_for_integration(
+            &operator,
+            download(recorded_bytes_done, Some(encryption.clone())),
+            IntegrationDownloadControl { paused, cancelled },
+            &mut observer,
+        )
+        .await
+        .expect("sealed download should complete");
+
+        assert_eq!(output.total, size as u64);
+        assert_eq!(observer.remote_total, Some(size as u64));
+        assert_eq!(
+            tokio::fs::read(&destination)
+                .await
+                .expect("downloaded file should exist"),
+            original
+        );
+
+        tokio::fs::remove_file(&destination)
+            .await
+            .expect("destination should be removable");
+        for (encryption, expected) in [
+            (None, "no passphrase"),
+            (
+                Some(crate::test_support::cheap_encryption("wrong secret")),
+                "wrong passphrase",
+            ),
+        ] {
+            let (paused, cancelled) = control_flags();
+            let error = download_to_stage_for_integration(
+                &operator,
+                download(0, encryption.clone()),
+                IntegrationDownloadControl { paused, cancelled },
+                &mut DownloadObserver::default(),
+            )
+            .await
+            .expect_err(expected);
+            match encryption {
+                None => assert!(matches!(error.kind, ErrorKind::NotRetriable), "{error:?}"),
+                Some(_) => assert!(matches!(error.kind, ErrorKind::Integrity), "{error:?}"),
+            }
+            assert!(!destination.exists());
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compressed_uploads_decode_on_request_and_reject_damaged_content() {
+    use crate::compression::{self, Compression};
+    let server = crate::test_support::start_path_style_s3("logs");
+    let operator = crate::test_support::stand_in_operator(&server, "logs");
+    let directory = tempfile::tempdir().expect("temporary directory should exist");
+    let original = b"2026-10-17T08:00:00Z INFO upload finished\n".repeat(60_000);
+
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        let key = format!("logs/app-{compression:?}.log");
+        let source = directory.path().join(format!("{compression:?}.log"));
+        let destination = directory
+            .path()
+            .join(format!("{compression:?}-downloaded.log"));
+        tokio::fs::write(&source, &original)
+            .await
+            .expect("source fixture should write");
+        let (paused, cancelled) = control_flags();
+        let mut upload_observer = UploadObserver::default();
+        upload_file_for_integration(
+            &operator,
+            IntegrationUploadRequest {
+                key: key.clone(),
+                source_path: source,
+                part_size: 512 * 1024,
+                content_type: None,
+                content_disposition: None,
+                encryption: None,
+                compression: Some(compression),
+                condition: Default::default(),
+            },
+            IntegrationUploadControl { paused, cancelled },
+            &mut upload_observer,
+        )
+        .await
+        .expect("compressed upload should complete");
+        assert_eq!(upload_observer.uploaded_bytes, original.len() as u64);
+
+        let head = operator.stat(&key).await.expect("object should exist");
+        assert_eq!(
+            head.content_encoding(),
+            Some(compression.content_encoding())
+        );
+        assert_eq!(
+            head.user_metadata()
+                .and_then(compression::recorded_original_size),
+            Some(original.len() as u64)
+        );
+        let stored = operator
+            .read(&key)
+            .await
+            .expect("object should read")
+            .to_vec();
+        assert!(stored.len() < original.len() / 10);
+
+        let download = |decompress| IntegrationDownloadRequest {
+            key: key.clone(),
+            temp_path: destination.clone(),
+            chunk_size: 4096,
+            expected_etag: None,
+            recorded_bytes_done: 0,
+            retry: instant_retries(),
+            encryption: None,
+            decompress,
+        };
+        for (decompress, expected) in [(true, &original), (false, &stored)] {
+            let (paused, cancelled) = control_flags();
+            download_to_stage_for_integration(
+                &operator,
+                download(decompress),
+                IntegrationDownloadControl { paused, cancelled },
+                &mut DownloadObserver::default(),
+            )
+            .await
+            .expect("download should complete");
+            assert_eq!(
+                &tokio::fs::read(&destination)
+                    .await
+                    .expect("downloaded file should exist"),
+                expected,
+                "decompress: {decompress}"
+            );
+            tokio::fs::remove_file(&destination)
+                .await
+                .expect("destination should be removable");
+        }
+
+        // A truncated body, and a body that decodes to another size than the
+        // one recorded, must not become the downloaded file.
+        for (body, recorded_size) in [
+            (stored[..stored.len() / 2].to_vec(), original.len() as u64),
+            (stored.clone(), original.len() as u64 + 1),
+        ] {
+            operator
+                .write_with(&key, body)
+                .content_encoding(compression.content_encoding())
+                .user_metadata([compression::original_size_metadata(recorded_size)])
+                .await
+                .expect("damaged object should write");
+            let (paused, cancelled) = control_flags();
+            let error = download_to_stage_for_integration(
+                &operator,
+                download(true),
+                IntegrationDownloadControl { paused, cancelled },
+                &mut DownloadObserver::default(),
+            )
+            .await
+            .expect_err("damaged content must not download");
+            assert!(matches!(error.kind, ErrorKind::Integrity), "{error:?}");
+            assert!(!destination.exists());
+            assert!(!destination.with_extension("log.part").exists());
+        }
+    }
+}
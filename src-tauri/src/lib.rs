@@ -31,15 +31,25 @@ pub fn run() {
             crate::bridge::backend_import_credentials_package,
             crate::bridge::backend_set_credentials,
             crate::bridge::backend_patch_credentials,
+            crate::bridge::backend_list_profiles,
+            crate::bridge::backend_add_profile,
+            crate::bridge::backend_switch_profile,
+            crate::bridge::backend_delete_profile,
+            crate::bridge::backend_set_profile_encryption,
             crate::bridge::vault_status,     // legacy shim
             crate::bridge::vault_set_manual, // legacy shim
             crate::bridge::r2_sanity_check,
+            crate::bridge::r2_diagnostics,
             crate::bridge::upload_new,
+            crate::bridge::upload_directory,
             crate::bridge::upload_new_stream,
+            crate::bridge::upload_from_url,
             crate::bridge::upload_stream_write,
             crate::bridge::upload_stream_finish,
+            crate::bridge::upload_stream_reconnect,
             crate::bridge::upload_ctrl,
             crate::bridge::upload_status,
+            crate::bridge::upload_group_status,
             crate::bridge::download_new,
             crate::bridge::download_ctrl,
             crate::bridge::download_status,
@@ -58,6 +68,24 @@ pub fn run() {
             crate::bridge::list_objects,
             crate::bridge::list_all_objects,
             crate::bridge::delete_object,
+            crate::bridge::stat_object,
+            crate::bridge::update_object_metadata,
+            crate::bridge::verify_object,
+            crate::bridge::create_folder,
+            crate::bridge::copy_object,
+            crate::bridge::move_object,
+            crate::bridge::move_prefix,
+            crate::bridge::delete_prefix,
+            crate::bridge::cancel_prefix_op,
+            crate::bridge::trash_list,
+            crate::bridge::trash_restore,
+            crate::bridge::trash_purge,
+            crate::bridge::object_index_search,
+            crate::bridge::object_index_refresh,
+            crate::bridge::object_index_status,
+            crate::bridge::lifecycle_get,
+            crate::bridge::lifecycle_validate,
+            crate::bridge::lifecycle_set,
             crate::bridge::ui_status_bar_height,
             crate::bridge::generate_thumbnail_and_upload,
             crate::bridge::thumbnail_get_cached_data,
@@ -89,13 +117,16 @@ pub fn run() {
             if let Err(e) = crate::download::init(&app.handle()) {
                 crate::logger::warn("app", &format!("download init failed: {}", e.message));
             }
+            if let Err(e) = crate::upload::init(app.handle()) {
+                crate::logger::warn("app", &format!("upload init failed: {}", e.message));
+            }
             // Pre-build the storage operator if credentials are available.
             tauri::async_runtime::spawn(async move {
                 if let Ok(bundle) = crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked()
                 {
                     crate::logger::info("app", "prebuilding storage operator at startup");
                     // Soft timeout so app doesn't stall if construction hangs
-                    let build = crate::storage::build_operator(&bundle.r2);
+                    let build = crate::storage::build_operator(&bundle.active_profile, &bundle.r2);
                     match tokio::time::timeout(std::time::Duration::from_secs(10), build).await {
                         Ok(Ok(_)) => crate::logger::info("app", "prebuild storage operator ok"),
                         Ok(Err(e)) => crate::logger::error(
@@ -130,6 +161,70 @@ pub fn run() {
                     );
                 }
             });
+            // Keep the local object index reconciled with the active bucket.
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    if let Ok(bundle) =
+                        crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked()
+                    {
+                        let result = match crate::storage::build_operator(
+                            &bundle.active_profile,
+                            &bundle.r2,
+                        )
+                        .await
+                        {
+                            Ok(operator) => {
+                                crate::object_index::reconcile_if_stale(
+                                    &operator,
+                                    &bundle.active_profile,
+                                )
+                                .await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = result {
+                            crate::logger::warn(
+                                "app",
+                                &format!("object index reconcile skipped: {}", e.message),
+                            );
+                        }
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(5 * 60)).await;
+                }
+            });
+            // Purge recycle-bin entries past the configured retention.
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    if let Ok(bundle) =
+                        crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked()
+                    {
+                        let days = crate::settings::get().trash_retention_days;
+                        let result = match crate::storage::build_operator(
+                            &bundle.active_profile,
+                            &bundle.r2,
+                        )
+                        .await
+                        {
+                            Ok(operator) => {
+                                crate::objects::purge_trash_older_than(
+                                    &operator,
+                                    &bundle.active_profile,
+                                    days,
+                                )
+                                .await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = result {
+                            crate::logger::warn(
+                                "app",
+                                &format!("recycle bin purge skipped: {}", e.message),
+                            );
+                        }
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(6 * 60 * 60)).await;
+                }
+            });
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -139,9 +234,14 @@ pub fn run() {
 }
 pub mod background;
 pub mod bridge;
+pub mod compression;
+pub mod content_crypto;
 pub mod download;
+pub mod lifecycle;
 pub mod logger;
+pub mod object_index;
 pub mod objects;
+pub mod retry;
 pub mod settings;
 pub mod share;
 pub mod sp_backend;
@@ -0,0 +1,261 @@
+//! Bucket lifecycle rules.
+//!
+//! This module owns the subset of the S3 lifecycle configuration SwiftPan can
+//! edit: prefix filters, age-based expiration and aborting incomplete
+//! multipart uploads. It validates rules, converts them to and from the S3
+//! XML document, and exchanges that document through signed `?lifecycle`
+//! requests. It must not decide which rules a bucket should have; rules come
+//! only from the user.
+
+use crate::storage::raw_s3::{self, RawS3Request};
+use crate::types::{err_invalid, LifecycleRule, R2Config, SpError, SpResult, ANALYTICS_PREFIX};
+use base64::Engine;
+use md5::{Digest, Md5};
+use std::collections::HashSet;
+
+/// Per-bucket limit of the S3 API.
+const MAX_RULES: usize = 1000;
+const MAX_RULE_ID_LEN: usize = 255;
+const MAX_PREFIX_LEN: usize = 1024;
+const S3_XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// Reads the bucket's lifecycle rules. A bucket without a configuration has
+/// no rules.
+pub async fn get_rules(cfg: &R2Config) -> SpResult<Vec<LifecycleRule>> {
+    let response = match raw_s3::send(cfg, lifecycle_request(http::Method::GET, Vec::new())).await {
+        Ok(response) => response,
+        Err(error) if is_missing_configuration(&error) => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    parse_configuration(&String::from_utf8_lossy(&response.body))
+        .ok_or_else(|| err_invalid("the bucket returned an unreadable lifecycle configuration"))
+}
+
+/// Replaces every lifecycle rule of the bucket. An empty list removes the
+/// configuration.
+pub async fn put_rules(cfg: &R2Config, rules: &[LifecycleRule]) -> SpResult<()> {
+    validate_rules(rules)?;
+    if rules.is_empty() {
+        raw_s3::send(cfg, lifecycle_request(http::Method::DELETE, Vec::new())).await?;
+        crate::logger::info("lifecycle", "removed lifecycle configuration");
+        return Ok(());
+    }
+    let body = render_configuration(rules).into_bytes();
+    let content_md5 = base64::engine::general_purpose::STANDARD.encode(Md5::digest(&body));
+    let mut request = lifecycle_request(http::Method::PUT, body);
+    request.headers = vec![
+        ("content-type".into(), "application/xml".into()),
+        ("content-md5".into(), content_md5),
+    ];
+    raw_s3::send(cfg, request).await?;
+    crate::logger::info(
+        "lifecycle",
+        &format!("saved {} lifecycle rule(s)", rules.len()),
+    );
+    Ok(())
+}
+
+/// Checks a complete rule set before it is sent. Reports the first problem.
+pub fn validate_rules(rules: &[LifecycleRule]) -> SpResult<()> {
+    if rules.len() > MAX_RULES {
+        return Err(err_invalid(&format!(
+            "a bucket can have at most {MAX_RULES} lifecycle rules"
+        )));
+    }
+    let mut ids = HashSet::new();
+    for rule in rules {
+        let id = rule.id.trim();
+        if id.is_empty() {
+            return Err(err_invalid("every lifecycle rule needs an id"));
+        }
+        if id.chars().count() > MAX_RULE_ID_LEN {
+            return Err(err_invalid(&format!(
+                "lifecycle rule ids are limited to {MAX_RULE_ID_LEN} characters"
+            )));
+        }
+        if !ids.insert(id) {
+            return Err(err_invalid(&format!("duplicate lifecycle rule id {id}")));
+        }
+        if !rule.unsupported.is_empty() {
+            return Err(err_invalid(&format!(
+                "rule {id} uses settings SwiftPan cannot edit ({}); remove it or edit it with the provider's console",
+                rule.unsupported.join(", ")
+            )));
+        }
+        if rule.prefix.len() > MAX_PREFIX_LEN {
+            return Err(err_invalid(&format!(
+                "rule {id}: prefix is longer than {MAX_PREFIX_LEN} bytes"
+            )));
+        }
+        if rule.expiration_days.is_none() && rule.abort_incomplete_multipart_days.is_none() {
+            return Err(err_invalid(&format!(
+                "rule {id} needs an expiration or a multipart-upload cleanup age"
+            )));
+        }
+        if rule.expiration_days == Some(0) || rule.abort_incomplete_multipart_days == Some(0) {
+            return Err(err_invalid(&format!(
+                "rule {id}: ages are counted in whole days and must be at least 1"
+            )));
+        }
+        if rule.enabled
+            && rule.expiration_days.is_some()
+            && ANALYTICS_PREFIX.starts_with(rule.prefix.as_str())
+        {
+            return Err(err_invalid(&format!(
+                "rule {id} would expire the usage ledger under {ANALYTICS_PREFIX}; use a narrower prefix"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn lifecycle_request(method: http::Method, body: Vec<u8>) -> RawS3Request {
+    RawS3Request {
+        method,
+        key: String::new(),
+        query: vec![("lifecycle".into(), String::new())],
+        headers: Vec::new(),
+        body: body.into(),
+    }
+}
+
+fn is_missing_configuration(error: &SpError) -> bool {
+    error
+        .context
+        .as_ref()
+        .and_then(|context| context.get("code"))
+        .and_then(|code| code.as_str())
+        == Some("NoSuchLifecycleConfiguration")
+}
+
+fn render_configuration(rules: &[LifecycleRule]) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><LifecycleConfiguration xmlns=\"{S3_XMLNS}\">"
+    );
+    for rule in rules {
+        xml.push_str(&format!(
+            "<Rule><ID>{}</ID><Filter><Prefix>{}</Prefix></Filter><Status>{}</Status>",
+            escape(rule.id.trim()),
+            escape(&rule.prefix),
+            if rule.enabled { "Enabled" } else { "Disabled" }
+        ));
+        if let Some(days) = rule.expiration_days {
+            xml.push_str(&format!("<Expiration><Days>{days}</Days></Expiration>"));
+        }
+        if let Some(days) = rule.abort_incomplete_multipart_days {
+            xml.push_str(&format!(
+                "<AbortIncompleteMultipartUpload><DaysAfterInitiation>{days}</DaysAfterInitiation></AbortIncompleteMultipartUpload>"
+            ));
+        }
+        xml.push_str("</Rule>");
+    }
+    xml.push_str("</LifecycleConfiguration>");
+    xml
+}
+
+/// Parses a `LifecycleConfiguration` document. Elements outside the editable
+/// subset are listed in [`LifecycleRule::unsupported`] rather than dropped.
+fn parse_configuration(xml: &str) -> Option<Vec<LifecycleRule>> {
+    let (_, configuration) = children(xml)?
+        .into_iter()
+        .find(|(name, _)| *name == "LifecycleConfiguration")?;
+    let mut rules = Vec::new();
+    for (name, rule) in children(configuration)? {
+        if name == "Rule" {
+            rules.push(parse_rule(rule)?);
+        }
+    }
+    Some(rules)
+}
+
+fn parse_rule(xml: &str) -> Option<LifecycleRule> {
+    let mut rule = LifecycleRule::default();
+    for (name, body) in children(xml)? {
+        match name {
+            "ID" => rule.id = unescape(body),
+            "Status" => rule.enabled = body.trim() == "Enabled",
+            // The original rule format puts the prefix directly in the rule.
+            "Prefix" => rule.prefix = unescape(body),
+            "Filter" => {
+                for (name, body) in children(body)? {
+                    match name {
+                        "Prefix" => rule.prefix = unescape(body),
+                        other => rule.unsupported.push(format!("Filter/{other}")),
+                    }
+                }
+            }
+            "Expiration" => {
+                for (name, body) in children(body)? {
+                    match name {
+                        "Days" => rule.expiration_days = Some(body.trim().parse().ok()?),
+                        "ExpiredObjectDeleteMarker" if body.trim() == "false" => {}
+                        other => rule.unsupported.push(format!("Expiration/{other}")),
+                    }
+                }
+            }
+            "AbortIncompleteMultipartUpload" => {
+                for (name, body) in children(body)? {
+                    match name {
+                        "DaysAfterInitiation" => {
+                            rule.abort_incomplete_multipart_days = Some(body.trim().parse().ok()?)
+                        }
+                        other => rule
+                            .unsupported
+                            .push(format!("AbortIncompleteMultipartUpload/{other}")),
+                    }
+                }
+            }
+            other => rule.unsupported.push(other.to_string()),
+        }
+    }
+    Some(rule)
+}
+
+/// Top-level child elements of `xml` as `(name, inner text)` pairs. Enough
+/// for lifecycle documents, which never nest an element in one of the same
+/// name; returns `None` for unbalanced markup.
+fn children(xml: &str) -> Option<Vec<(&str, &str)>> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        let after = &rest[open + 1..];
+        let end = after.find('>')?;
+        let tag = &after[..end];
+        rest = &after[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if tag.starts_with('/') {
+            return None;
+        }
+        if let Some(tag) = tag.strip_suffix('/') {
+            elements.push((tag.split_whitespace().next()?, ""));
+            continue;
+        }
+        let name = tag.split_whitespace().next()?;
+        let close = format!("</{name}>");
+        let inner_end = rest.find(&close)?;
+        elements.push((name, &rest[..inner_end]));
+        rest = &rest[inner_end + close.len()..];
+    }
+    Some(elements)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests;
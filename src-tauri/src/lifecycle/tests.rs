@@ -0,0 +1,152 @@
+use super::*;
+use crate::test_support::start_path_style_s3;
+use crate::types::{ErrorKind, StorageProvider};
+
+fn rule(id: &str, prefix: &str) -> LifecycleRule {
+    LifecycleRule {
+        id: id.into(),
+        enabled: true,
+        prefix: prefix.into(),
+        expiration_days: Some(30),
+        abort_incomplete_multipart_days: None,
+        unsupported: Vec::new(),
+    }
+}
+
+fn stand_in_config(endpoint: String) -> R2Config {
+    R2Config {
+        endpoint,
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "photos".into(),
+        region: Some("us-east-1".into()),
+        provider: StorageProvider::Minio,
+    }
+}
+
+#[test]
+fn validation_rejects_incomplete_duplicate_and_ledger_expiring_rules() {
+    let no_action = LifecycleRule {
+        expiration_days: None,
+        ..rule("idle", "tmp/")
+    };
+    let zero_days = LifecycleRule {
+        expiration_days: Some(0),
+        ..rule("zero", "tmp/")
+    };
+    let cases = [
+        (vec![rule(" ", "tmp/")], "needs an id"),
+        (vec![rule("a", "tmp/"), rule("a", "logs/")], "duplicate"),
+        (vec![no_action], "needs an expiration"),
+        (vec![zero_days], "at least 1"),
+        (vec![rule("everything", "")], "usage ledger"),
+        (vec![rule("analytics", "analytics/")], "usage ledger"),
+    ];
+
+    for (rules, expected) in cases {
+        let error = validate_rules(&rules).expect_err("rule set should be rejected");
+        assert!(matches!(error.kind, ErrorKind::NotRetriable));
+        assert!(
+            error.message.contains(expected),
+            "{} should mention {expected}",
+            error.message
+        );
+    }
+
+    let cleanup_only = LifecycleRule {
+        expiration_days: None,
+        abort_incomplete_multipart_days: Some(7),
+        ..rule("uploads", "")
+    };
+    validate_rules(&[cleanup_only, rule("tmp", "tmp/")]).expect("rules should be valid");
+}
+
+#[test]
+fn foreign_rule_elements_are_reported_instead_of_dropped() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<LifecycleConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Rule>
+    <ID>archive &amp; expire</ID>
+    <Filter><And><Prefix>logs/</Prefix><Tag><Key>a</Key><Value>b</Value></Tag></And></Filter>
+    <Status>Disabled</Status>
+    <Transition><Days>10</Days><StorageClass>GLACIER</StorageClass></Transition>
+    <Expiration><Days>90</Days></Expiration>
+  </Rule>
+  <Rule>
+    <ID>legacy</ID>
+    <Prefix>tmp/</Prefix>
+    <Status>Enabled</Status>
+    <AbortIncompleteMultipartUpload><DaysAfterInitiation>2</DaysAfterInitiation></AbortIncompleteMultipartUpload>
+  </Rule>
+</LifecycleConfiguration>"#;
+
+    let rules = parse_configuration(xml).expect("document should parse");
+
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].id, "archive & expire");
+    assert!(!rules[0].enabled);
+    assert_eq!(rules[0].expiration_days, Some(90));
+    assert_eq!(rules[0].unsupported, vec!["Filter/And", "Transition"]);
+    assert_eq!(
+        rules[1],
+        LifecycleRule {
+            id: "legacy".into(),
+            enabled: true,
+            prefix: "tmp/".into(),
+            expiration_days: None,
+            abort_incomplete_multipart_days: Some(2),
+            unsupported: Vec::new(),
+        }
+    );
+    assert!(validate_rules(&rules).is_err());
+    assert!(parse_configuration("<LifecycleConfiguration><Rule>").is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rules_round_trip_through_the_bucket_and_an_empty_set_deletes_them() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    assert_eq!(get_rules(&cfg).await.expect("missing config reads"), vec![]);
+
+    let rules = vec![
+        LifecycleRule {
+            abort_incomplete_multipart_days: Some(3),
+            ..rule("tmp <30d>", "tmp/")
+        },
+        LifecycleRule {
+            enabled: false,
+            ..rule("old-exports", "exports/2024 & older/")
+        },
+    ];
+    put_rules(&cfg, &rules).await.expect("rules should save");
+
+    assert_eq!(get_rules(&cfg).await.expect("rules should read"), rules);
+    let put = server
+        .requests()
+        .into_iter()
+        .find(|request| request.method == "PUT")
+        .expect("a PutBucketLifecycleConfiguration request should be sent");
+    assert_eq!(put.target, "/photos?lifecycle=");
+    let expected_md5 = base64::engine::general_purpose::STANDARD.encode(Md5::digest(&put.body));
+    assert_eq!(put.header("content-md5"), Some(expected_md5.as_str()));
+
+    put_rules(&cfg, &[]).await.expect("empty set should delete");
+    assert_eq!(get_rules(&cfg).await.expect("deleted config reads"), vec![]);
+    assert!(server
+        .requests()
+        .iter()
+        .any(|request| request.method == "DELETE" && request.target == "/photos?lifecycle="));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_rules_are_not_sent() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+
+    let error = put_rules(&cfg, &[rule("", "tmp/")])
+        .await
+        .expect_err("invalid rules must be rejected");
+
+    assert!(matches!(error.kind, ErrorKind::NotRetriable));
+    assert!(server.requests().is_empty());
+}
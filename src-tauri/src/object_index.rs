@@ -0,0 +1,897 @@
+//! Local per-profile index of bucket objects for search.
+//!
+//! This module owns the `object_index` table in the transfer database: row
+//! updates after the app's own uploads, deletes, copies and moves,
+//! reconciliation against recursive listings, and filtered, sorted, paginated
+//! search. The index is a cache. Whatever the incremental updates miss is
+//! corrected by the next reconcile, which marks every listed key and sweeps
+//! the rest. It must not mutate remote objects or expose Tauri commands.
+
+use crate::thumbnail;
+use crate::transfer_db::{db_err, i64_to_u64, load_pool, run_db, u64_to_i64};
+use crate::types::{
+    err_invalid, FileEntry, ObjectIndexPage, ObjectIndexQuery, ObjectIndexSort, ObjectIndexStatus,
+    ObjectIndexSync, SpResult, ANALYTICS_PREFIX,
+};
+use futures::TryStreamExt;
+use opendal::Operator;
+use sqlx::{Pool, QueryBuilder, Row, Sqlite};
+
+/// A full reconcile is due once the last one is older than this.
+pub const FULL_SYNC_INTERVAL_MS: i64 = 30 * 60 * 1000;
+/// Listed entries are written in transactions of this many rows.
+const RECONCILE_BATCH: usize = 500;
+const DEFAULT_PAGE_SIZE: u32 = 200;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Clone)]
+struct IndexRow {
+    key: String,
+    size: Option<u64>,
+    last_modified_ms: Option<i64>,
+    etag: Option<String>,
+}
+
+/// Columns derived from the key alone.
+struct KeyFacts<'a> {
+    kind: &'static str,
+    name: &'a str,
+    extension: Option<String>,
+    thumbnail_key: Option<String>,
+}
+
+fn key_facts(key: &str) -> KeyFacts<'_> {
+    let name = key.trim_end_matches('/').rsplit('/').next().unwrap_or(key);
+    let kind = if thumbnail::is_thumbnail_key(key) {
+        "thumbnail"
+    } else if crate::objects::is_trash_key(key) {
+        "trash"
+    } else if key.ends_with('/') {
+        "folder"
+    } else {
+        "file"
+    };
+    let extension = (kind != "folder")
+        .then(|| name.rsplit_once('.'))
+        .flatten()
+        .filter(|(stem, extension)| !stem.is_empty() && !extension.is_empty())
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    KeyFacts {
+        kind,
+        name,
+        extension,
+        // Trashed files keep their thumbnail so a restore can bring it back.
+        thumbnail_key: matches!(kind, "file" | "trash").then(|| thumbnail::thumbnail_key_for(key)),
+    }
+}
+
+/// Records an object the app has just written. Listing metadata replaces
+/// these values on the next reconcile.
+pub fn record_object(
+    profile: &str,
+    key: &str,
+    size: Option<u64>,
+    last_modified_ms: Option<i64>,
+    etag: Option<&str>,
+) -> SpResult<()> {
+    let profile = profile.to_string();
+    let row = IndexRow {
+        key: key.to_string(),
+        size,
+        last_modified_ms,
+        etag: etag.map(str::to_string),
+    };
+    run_db(async move {
+        let pool = load_pool().await?;
+        upsert_rows_in_pool(&pool, &profile, &[row], now_ms()).await
+    })
+}
+
+/// Drops an object and its thumbnail from the index.
+pub fn forget_object(profile: &str, key: &str) -> SpResult<()> {
+    let profile = profile.to_string();
+    let key = key.to_string();
+    run_db(async move {
+        let pool = load_pool().await?;
+        forget_object_in_pool(&pool, &profile, &key).await
+    })
+}
+
+/// Drops everything under `prefix`, including thumbnails of dropped files.
+pub fn forget_prefix(profile: &str, prefix: &str) -> SpResult<()> {
+    let profile = profile.to_string();
+    let prefix = prefix.to_string();
+    run_db(async move {
+        let pool = load_pool().await?;
+        forget_prefix_in_pool(&pool, &profile, &prefix).await
+    })
+}
+
+pub fn forget_profile(profile: &str) -> SpResult<()> {
+    let profile = profile.to_string();
+    run_db(async move {
+        let pool = load_pool().await?;
+        let mut tx = pool.begin().await.map_err(db_err)?;
+        for table in ["object_index", "object_index_state"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE profile = ?"))
+                .bind(&profile)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_err)?;
+        }
+        tx.commit().await.map_err(db_err)
+    })
+}
+
+/// Mirrors a server-side copy or move of one object, thumbnail included.
+/// Nothing happens if the source was never indexed.
+pub fn relocate_object(
+    profile: &str,
+    from_key: &str,
+    to_key: &str,
+    keep_source: bool,
+) -> SpResult<()> {
+    let profile = profile.to_string();
+    let moves = vec![(from_key.to_string(), to_key.to_string())];
+    run_db(async move {
+        let pool = load_pool().await?;
+        rekey_rows_in_pool(&pool, &profile, &moves, keep_source).await
+    })
+}
+
+/// Mirrors a completed `move_prefix`. Prefixes are taken as folders whether
+/// or not they end in `/`, like `move_prefix` takes them.
+pub fn relocate_prefix(profile: &str, from_prefix: &str, to_prefix: &str) -> SpResult<()> {
+    let profile = profile.to_string();
+    let from_prefix = format!("{}/", from_prefix.trim_end_matches('/'));
+    let to_prefix = format!("{}/", to_prefix.trim_end_matches('/'));
+    run_db(async move {
+        let pool = load_pool().await?;
+        relocate_prefix_in_pool(&pool, &profile, &from_prefix, &to_prefix).await
+    })
+}
+
+/// Lists `prefix` recursively, upserts every entry and removes indexed keys
+/// under `prefix` that the listing no longer contains. An empty prefix is a
+/// full reconcile and also refreshes thumbnails.
+pub async fn reconcile(
+    operator: &Operator,
+    profile: &str,
+    prefix: &str,
+) -> SpResult<ObjectIndexSync> {
+    let pool = load_pool().await?;
+    reconcile_in_pool(&pool, operator, profile, prefix).await
+}
+
+/// Runs a full reconcile when the last one is older than
+/// [`FULL_SYNC_INTERVAL_MS`].
+pub async fn reconcile_if_stale(
+    operator: &Operator,
+    profile: &str,
+) -> SpResult<Option<ObjectIndexSync>> {
+    let pool = load_pool().await?;
+    let status = status_in_pool(&pool, profile).await?;
+    if let Some(last) = status.last_full_sync_ms {
+        if now_ms() - last < FULL_SYNC_INTERVAL_MS {
+            return Ok(None);
+        }
+    }
+    reconcile_in_pool(&pool, operator, profile, "")
+        .await
+        .map(Some)
+}
+
+pub fn search(profile: &str, query: &ObjectIndexQuery) -> SpResult<ObjectIndexPage> {
+    let profile = profile.to_string();
+    let query = query.clone();
+    run_db(async move {
+        let pool = load_pool().await?;
+        search_in_pool(&pool, &profile, &query).await
+    })
+}
+
+pub fn status(profile: &str) -> SpResult<ObjectIndexStatus> {
+    let profile = profile.to_string();
+    run_db(async move {
+        let pool = load_pool().await?;
+        status_in_pool(&pool, &profile).await
+    })
+}
+
+async fn upsert_rows_in_pool(
+    pool: &Pool<Sqlite>,
+    profile: &str,
+    rows: &[IndexRow],
+    seen_at_ms: i64,
+) -> SpResult<()> {
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    for row in rows {
+        let facts = key_facts(&row.key);
+        // A row touched after this listing started keeps its newer mark.
+        sqlx::query(
+            r#"
+INSERT INTO object_index (
+  profile,
+  key,
+  kind,
+  name,
+  extension,
+  size,
+  last_modified_ms,
+  etag,
+  thumbnail_key,
+  seen_at_ms
+)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+ON CONFLICT(profile, key) DO UPDATE SET
+  size = excluded.size,
+  last_modified_ms = excluded.last_modified_ms,
+  etag = excluded.etag,
+  seen_at_ms = max(object_index.seen_at_ms, excluded.seen_at_ms)
+            "#,
+        )
+        .bind(profile)
+        .bind(&row.key)
+        .bind(facts.kind)
+        .bind(facts.name)
+        .bind(facts.extension)
+        .bind(row.size.map(u64_to_i64).transpose()?)
+        .bind(row.last_modified_ms)
+        .bind(&row.etag)
+        .bind(facts.thumbnail_key)
+        .bind(seen_at_ms)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    }
+    tx.commit().await.map_err(db_err)
+}
+
+async fn forget_object_in_pool(pool: &Pool<Sqlite>, profile: &str, key: &str) -> SpResult<()> {
+    let thumbnail_key = key_facts(key).thumbnail_key.unwrap_or_default();
+    sqlx::query("DELETE FROM object_index WHERE profile = ? AND key IN (?, ?)")
+        .bind(profile)
+        .bind(key)
+        .bind(thumbnail_key)
+        .execute(pool)
+        .await
+        .map_err(db_err)?;
+    Ok(())
+}
+
+async fn forget_prefix_in_pool(pool: &Pool<Sqlite>, profile: &str, prefix: &str) -> SpResult<()> {
+    let (low, high) = prefix_range(prefix);
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    sqlx::query(
+        r#"
+DELETE FROM object_index
+WHERE profile = ?
+  AND key IN (
+    SELECT thumbnail_key
+    FROM object_index
+    WHERE profile = ? AND key >= ? AND key < ? AND thumbnail_key IS NOT NULL
+  )
+        "#,
+    )
+    .bind(profile)
+    .bind(profile)
+    .bind(&low)
+    .bind(&high)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    sqlx::query("DELETE FROM object_index WHERE profile = ? AND key >= ? AND key < ?")
+        .bind(profile)
+        .bind(&low)
+        .bind(&high)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)
+}
+
+async fn relocate_prefix_in_pool(
+    pool: &Pool<Sqlite>,
+    profile: &str,
+    from_prefix: &str,
+    to_prefix: &str,
+) -> SpResult<()> {
+    let (low, high) = prefix_range(from_prefix);
+    let keys: Vec<String> = sqlx::query_scalar(
+        "SELECT key FROM object_index WHERE profile = ? AND key >= ? AND key < ? AND kind IN ('file', 'folder')",
+    )
+    .bind(profile)
+    .bind(&low)
+    .bind(&high)
+    .fetch_all(pool)
+    .await
+    .map_err(db_err)?;
+    let moves = keys
+        .into_iter()
+        .map(|key| {
+            let to_key = format!("{to_prefix}{}", &key[from_prefix.len()..]);
+            (key, to_key)
+        })
+        .collect::<Vec<_>>();
+    rekey_rows_in_pool(pool, profile, &moves, false).await
+}
+
+async fn rekey_rows_in_pool(
+    pool: &Pool<Sqlite>,
+    profile: &str,
+    moves: &[(String, String)],
+    keep_source: bool,
+) -> SpResult<()> {
+    let seen_at_ms = now_ms();
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    for (from_key, to_key) in moves {
+        let mut pairs = vec![(from_key.clone(), to_key.clone())];
+        if let (Some(from_thumbnail), Some(to_thumbnail)) = (
+            key_facts(from_key).thumbnail_key,
+            key_facts(to_key).thumbnail_key,
+        ) {
+            pairs.push((from_thumbnail, to_thumbnail));
+        }
+        for (from, to) in pairs {
+            let facts = key_facts(&to);
+            // `WHERE true` keeps SQLite from parsing ON CONFLICT as a join clause.
+            sqlx::query(
+                r#"
+INSERT INTO object_index (
+  profile,
+  key,
+  kind,
+  name,
+  extension,
+  size,
+  last_modified_ms,
+  etag,
+  thumbnail_key,
+  seen_at_ms
+)
+SELECT profile, ?, ?, ?, ?, size, last_modified_ms, etag, ?, ?
+FROM object_index
+WHERE profile = ? AND key = ? AND true
+ON CONFLICT(profile, key) DO UPDATE SET
+  size = excluded.size,
+  last_modified_ms = excluded.last_modified_ms,
+  etag = excluded.etag,
+  seen_at_ms = excluded.seen_at_ms
+                "#,
+            )
+            .bind(&to)
+            .bind(facts.kind)
+            .bind(facts.name)
+            .bind(facts.extension)
+            .bind(facts.thumbnail_key)
+            .bind(seen_at_ms)
+            .bind(profile)
+            .bind(&from)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+            if !keep_source {
+                sqlx::query("DELETE FROM object_index WHERE profile = ? AND key = ?")
+                    .bind(profile)
+                    .bind(&from)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_err)?;
+            }
+        }
+    }
+    tx.commit().await.map_err(db_err)
+}
+
+async fn reconcile_in_pool(
+    pool: &Pool<Sqlite>,
+    operator: &Operator,
+    profile: &str,
+    prefix: &str,
+) -> SpResult<ObjectIndexSync> {
+    let started_at_ms = now_ms();
+    let list_error = |error: opendal::Error| {
+        crate::storage::opendal_error(&format!("index list {prefix}"), prefix, &error)
+    };
+    let mut lister = operator
+        .lister_with(prefix)
+        .recursive(true)
+        .await
+        .map_err(list_error)?;
+    let mut batch = Vec::with_capacity(RECONCILE_BATCH);
+    let mut indexed = 0u64;
+    while let Some(entry) = lister.try_next().await.map_err(list_error)? {
+        let key = entry.path();
+        if key == "/" {
+            continue;
+        }
+        let metadata = entry.metadata();
+        batch.push(IndexRow {
+            key: key.to_string(),
+            size: (!key.ends_with('/')).then(|| metadata.content_length()),
+            last_modified_ms: metadata
+                .last_modified()
+                .map(|timestamp| timestamp.timestamp_millis()),
+            etag: metadata.etag().map(str::to_string),
+        });
+        if batch.len() >= RECONCILE_BATCH {
+            upsert_rows_in_pool(pool, profile, &batch, started_at_ms).await?;
+            indexed += batch.len() as u64;
+            batch.clear();
+        }
+    }
+    upsert_rows_in_pool(pool, profile, &batch, started_at_ms).await?;
+    indexed += batch.len() as u64;
+
+    let (low, high) = prefix_range(prefix);
+    let removed = sqlx::query(
+        "DELETE FROM object_index WHERE profile = ? AND key >= ? AND key < ? AND seen_at_ms < ?",
+    )
+    .bind(profile)
+    .bind(&low)
+    .bind(&high)
+    .bind(started_at_ms)
+    .execute(pool)
+    .await
+    .map_err(db_err)?
+    .rows_affected();
+    let finished_at_ms = now_ms();
+    if prefix.is_empty() {
+        sqlx::query(
+            r#"
+INSERT INTO object_index_state (profile, last_full_sync_ms)
+VALUES (?, ?)
+ON CONFLICT(profile) DO UPDATE SET last_full_sync_ms = excluded.last_full_sync_ms
+            "#,
+        )
+        .bind(profile)
+        .bind(finished_at_ms)
+        .execute(pool)
+        .await
+        .map_err(db_err)?;
+    }
+    crate::logger::info(
+        "object_index",
+        &format!("reconcile prefix={prefix} indexed={indexed} removed={removed}"),
+    );
+    Ok(ObjectIndexSync {
+        prefix: prefix.to_string(),
+        indexed,
+        removed,
+        finished_at_ms,
+    })
+}
+
+async fn search_in_pool(
+    pool: &Pool<Sqlite>,
+    profile: &str,
+    query: &ObjectIndexQuery,
+) -> SpResult<ObjectIndexPage> {
+    if let (Some(min), Some(max)) = (query.min_size, query.max_size) {
+        if min > max {
+            return Err(err_invalid("min_size is larger than max_size"));
+        }
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM object_index o");
+    push_filters(&mut count, profile, query)?;
+    let total: i64 = count
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .map_err(db_err)?;
+
+    let mut select = QueryBuilder::<Sqlite>::new(
+        r#"
+SELECT
+  o.key,
+  o.kind,
+  o.size,
+  o.last_modified_ms,
+  o.etag,
+  CASE WHEN EXISTS (
+    SELECT 1 FROM object_index t WHERE t.profile = o.profile AND t.key = o.thumbnail_key
+  ) THEN o.thumbnail_key END AS present_thumbnail_key
+FROM object_index o"#,
+    );
+    push_filters(&mut select, profile, query)?;
+    let column = match query.sort {
+        ObjectIndexSort::Key => "o.key",
+        ObjectIndexSort::Name => "o.name COLLATE NOCASE",
+        ObjectIndexSort::Size => "o.size",
+        ObjectIndexSort::Modified => "o.last_modified_ms",
+    };
+    let direction = if query.descending { "DESC" } else { "ASC" };
+    select.push(format!(
+        " ORDER BY {column} {direction}, o.key {direction} LIMIT "
+    ));
+    select.push_bind(i64::from(limit));
+    select.push(" OFFSET ");
+    select.push_bind(u64_to_i64(query.offset)?);
+    let rows = select.build().fetch_all(pool).await.map_err(db_err)?;
+
+    let items = rows
+        .into_iter()
+        .map(row_to_file_entry)
+        .collect::<SpResult<Vec<_>>>()?;
+    let total = i64_to_u64(total)?;
+    let end = query.offset + items.len() as u64;
+    Ok(ObjectIndexPage {
+        items,
+        total,
+        next_offset: (end < total).then_some(end),
+    })
+}
+
+fn push_filters(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    profile: &str,
+    query: &ObjectIndexQuery,
+) -> SpResult<()> {
+    builder.push(" WHERE o.profile = ");
+    builder.push_bind(profile.to_string());
+    if query.include_folders {
+        builder.push(" AND o.kind IN ('file', 'folder')");
+    } else {
+        builder.push(" AND o.kind = 'file'");
+    }
+    if let Some(name) = query.name.as_deref().filter(|name| !name.is_empty()) {
+        if name.contains(['*', '?', '[']) {
+            builder.push(" AND lower(o.name) GLOB ");
+            builder.push_bind(name.to_lowercase());
+        } else {
+            builder.push(" AND o.name LIKE ");
+            builder.push_bind(format!("%{}%", escape_like(name)));
+            builder.push(" ESCAPE '\\'");
+        }
+    }
+    let extensions = query
+        .extensions
+        .iter()
+        .map(|extension| {
+            extension
+                .trim()
+                .trim_start_matches('.')
+                .to_ascii_lowercase()
+        })
+        .filter(|extension| !extension.is_empty())
+        .collect::<Vec<_>>();
+    if !extensions.is_empty() {
+        builder.push(" AND o.extension IN (");
+        let mut separated = builder.separated(", ");
+        for extension in extensions {
+            separated.push_bind(extension);
+        }
+        builder.push(")");
+    }
+    if let Some(prefix) = query.prefix.as_deref().filter(|prefix| !prefix.is_empty()) {
+        let (low, high) = prefix_range(prefix);
+        builder.push(" AND o.key >= ");
+        builder.push_bind(low);
+        builder.push(" AND o.key < ");
+        builder.push_bind(high);
+    }
+    if let Some(min_size) = query.min_size {
+        builder.push(" AND o.size >= ");
+        builder.push_bind(u64_to_i64(min_size)?);
+    }
+    if let Some(max_size) = query.max_size {
+        builder.push(" AND o.size <= ");
+        builder.push_bind(u64_to_i64(max_size)?);
+    }
+    if let Some(after) = query.modified_after_ms {
+        builder.push(" AND o.last_modified_ms >= ");
+        builder.push_bind(after);
+    }
+    if let Some(before) = query.modified_before_ms {
+        builder.push(" AND o.last_modified_ms < ");
+        builder.push_bind(before);
+    }
+    Ok(())
+}
+
+async fn status_in_pool(pool: &Pool<Sqlite>, profile: &str) -> SpResult<ObjectIndexStatus> {
+    let objects: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM object_index WHERE profile = ? AND kind IN ('file', 'folder')",
+    )
+    .bind(profile)
+    .fetch_one(pool)
+    .await
+    .map_err(db_err)?;
+    let last_full_sync_ms: Option<i64> =
+        sqlx::query_scalar("SELECT last_full_sync_ms FROM object_index_state WHERE profile = ?")
+            .bind(profile)
+            .fetch_optional(pool)
+            .await
+            .map_err(db_err)?;
+    Ok(ObjectIndexStatus {
+        profile: profile.to_string(),
+        objects: i64_to_u64(objects)?,
+        last_full_sync_ms,
+    })
+}
+
+fn row_to_file_entry(row: sqlx::sqlite::SqliteRow) -> SpResult<FileEntry> {
+    let key: String = row.try_get("key").map_err(db_err)?;
+    let is_prefix = row.try_get::<String, _>("kind").map_err(db_err)? == "folder";
+    Ok(FileEntry {
+        size: row
+            .try_get::<Option<i64>, _>("size")
+            .map_err(db_err)?
+            .map(i64_to_u64)
+            .transpose()?,
+        last_modified_ms: row.try_get("last_modified_ms").map_err(db_err)?,
+        etag: row.try_get("etag").map_err(db_err)?,
+        thumbnail_key: row.try_get("present_thumbnail_key").map_err(db_err)?,
+        protected: if is_prefix {
+            ANALYTICS_PREFIX.starts_with(&key)
+        } else {
+            key.starts_with(ANALYTICS_PREFIX)
+        },
+        is_prefix,
+        key,
+    })
+}
+
+/// Half-open key range `[prefix, prefix + U+10FFFF)` covering every key that
+/// starts with `prefix`, so lookups stay on the primary key index.
+fn prefix_range(prefix: &str) -> (String, String) {
+    (prefix.to_string(), format!("{prefix}{}", char::MAX))
+}
+
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if matches!(character, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        local_http::LocalHttpServer, stand_in_operator, start_path_style_s3,
+    };
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::str::FromStr;
+
+    async fn open_index_pool(directory: &tempfile::TempDir) -> Pool<Sqlite> {
+        let path = directory.path().join("transfers.sqlite3");
+        let options =
+            SqliteConnectOptions::from_str(&format!("sqlite://{}", path.to_string_lossy()))
+                .expect("temporary sqlite URL should parse")
+                .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .expect("temporary sqlite database should open");
+        for migration in crate::transfer_db::migrations() {
+            sqlx::raw_sql(migration.sql)
+                .execute(&pool)
+                .await
+                .expect("migration should apply");
+        }
+        pool
+    }
+
+    async fn stand_in_bucket(keys: &[(&str, usize)]) -> (LocalHttpServer, Operator) {
+        let server = start_path_style_s3("index");
+        let operator = stand_in_operator(&server, "index");
+        for (key, size) in keys {
+            if key.ends_with('/') {
+                operator.create_dir(key).await.expect("marker should write");
+            } else {
+                operator
+                    .write(key, vec![0u8; *size])
+                    .await
+                    .expect("fixture should write");
+            }
+        }
+        (server, operator)
+    }
+
+    fn keys(page: &ObjectIndexPage) -> Vec<&str> {
+        page.items.iter().map(|item| item.key.as_str()).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reconcile_sweeps_vanished_keys_only_under_the_listed_prefix() {
+        let directory = tempfile::tempdir().expect("temporary directory should exist");
+        let pool = open_index_pool(&directory).await;
+        let (_server, operator) = stand_in_bucket(&[
+            ("photos/a.jpg", 10),
+            ("photos/b.jpg", 20),
+            ("docs/readme.md", 5),
+        ])
+        .await;
+
+        let full = reconcile_in_pool(&pool, &operator, "main", "")
+            .await
+            .expect("full reconcile should succeed");
+        assert_eq!((full.indexed, full.removed), (3, 0));
+
+        operator.delete("photos/b.jpg").await.expect("delete");
+        operator.delete("docs/readme.md").await.expect("delete");
+        let partial = reconcile_in_pool(&pool, &operator, "main", "photos/")
+            .await
+            .expect("prefix reconcile should succeed");
+        assert_eq!((partial.indexed, partial.removed), (1, 1));
+
+        let page = search_in_pool(&pool, "main", &ObjectIndexQuery::default())
+            .await
+            .expect("search should succeed");
+        assert_eq!(keys(&page), vec!["docs/readme.md", "photos/a.jpg"]);
+        let status = status_in_pool(&pool, "main").await.expect("status");
+        assert_eq!(status.objects, 2);
+        assert!(status.last_full_sync_ms.is_some());
+        assert_eq!(
+            status_in_pool(&pool, "other")
+                .await
+                .expect("status")
+                .objects,
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn search_combines_filters_sorts_and_pages() {
+        let directory = tempfile::tempdir().expect("temporary directory should exist");
+        let pool = open_index_pool(&directory).await;
+        let photo_thumbnail = thumbnail::thumbnail_key_for("trip/IMG_0001.JPG");
+        let (_server, operator) = stand_in_bucket(&[
+            ("trip/", 0),
+            ("trip/IMG_0001.JPG", 300),
+            ("trip/IMG_0002.jpg", 100),
+            ("trip/notes.txt", 50),
+            ("trip/100%_done.txt", 70),
+            ("work/IMG_9999.png", 200),
+            (photo_thumbnail.as_str(), 4),
+        ])
+        .await;
+        reconcile_in_pool(&pool, &operator, "main", "")
+            .await
+            .expect("reconcile should succeed");
+
+        let images = ObjectIndexQuery {
+            name: Some("img_*".into()),
+            extensions: vec![".JPG".into()],
+            sort: ObjectIndexSort::Size,
+            descending: true,
+            ..Default::default()
+        };
+        let page = search_in_pool(&pool, "main", &images)
+            .await
+            .expect("search");
+        assert_eq!(keys(&page), vec!["trip/IMG_0001.JPG", "trip/IMG_0002.jpg"]);
+        assert_eq!(
+            page.items[0].thumbnail_key.as_deref(),
+            Some(photo_thumbnail.as_str())
+        );
+        assert_eq!(page.items[1].thumbnail_key, None);
+
+        let literal = ObjectIndexQuery {
+            name: Some("0%_".into()),
+            ..Default::default()
+        };
+        let page = search_in_pool(&pool, "main", &literal)
+            .await
+            .expect("search");
+        assert_eq!(keys(&page), vec!["trip/100%_done.txt"]);
+
+        let sized = ObjectIndexQuery {
+            prefix: Some("trip/".into()),
+            min_size: Some(60),
+            max_size: Some(300),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let first = search_in_pool(&pool, "main", &sized).await.expect("search");
+        assert_eq!(keys(&first), vec!["trip/100%_done.txt"]);
+        assert_eq!((first.total, first.next_offset), (3, Some(1)));
+        let last = search_in_pool(
+            &pool,
+            "main",
+            &ObjectIndexQuery {
+                offset: 2,
+                ..sized.clone()
+            },
+        )
+        .await
+        .expect("search");
+        assert_eq!(keys(&last), vec!["trip/IMG_0002.jpg"]);
+        assert_eq!(last.next_offset, None);
+
+        let folders = ObjectIndexQuery {
+            name: Some("trip".into()),
+            include_folders: true,
+            ..Default::default()
+        };
+        let page = search_in_pool(&pool, "main", &folders)
+            .await
+            .expect("search");
+        assert_eq!(keys(&page), vec!["trip/"]);
+        assert!(page.items[0].is_prefix);
+
+        let future = ObjectIndexQuery {
+            modified_after_ms: Some(now_ms() + 60_000),
+            ..Default::default()
+        };
+        let page = search_in_pool(&pool, "main", &future)
+            .await
+            .expect("search");
+        assert_eq!(page.total, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn moved_and_deleted_prefixes_carry_thumbnail_rows() {
+        let directory = tempfile::tempdir().expect("temporary directory should exist");
+        let pool = open_index_pool(&directory).await;
+        let old_thumbnail = thumbnail::thumbnail_key_for("a/photo.jpg");
+        let (_server, operator) = stand_in_bucket(&[
+            ("a/", 0),
+            ("a/photo.jpg", 10),
+            ("ab/other.jpg", 10),
+            (old_thumbnail.as_str(), 2),
+        ])
+        .await;
+        reconcile_in_pool(&pool, &operator, "main", "")
+            .await
+            .expect("reconcile should succeed");
+
+        relocate_prefix_in_pool(&pool, "main", "a/", "b/")
+            .await
+            .expect("relocate should succeed");
+        let query = ObjectIndexQuery {
+            include_folders: true,
+            ..Default::default()
+        };
+        let page = search_in_pool(&pool, "main", &query).await.expect("search");
+        assert_eq!(keys(&page), vec!["ab/other.jpg", "b/", "b/photo.jpg"]);
+        assert_eq!(
+            page.items[2].thumbnail_key,
+            Some(thumbnail::thumbnail_key_for("b/photo.jpg"))
+        );
+
+        forget_prefix_in_pool(&pool, "main", "b/")
+            .await
+            .expect("forget should succeed");
+        let remaining: Vec<String> =
+            sqlx::query_scalar("SELECT key FROM object_index WHERE profile = 'main' ORDER BY key")
+                .fetch_all(&pool)
+                .await
+                .expect("rows should load");
+        assert_eq!(remaining, vec!["ab/other.jpg".to_string()]);
+    }
+
+    #[test]
+    fn key_facts_split_names_and_extensions() {
+        let file = key_facts("a/b/Archive.TAR.GZ");
+        assert_eq!((file.kind, file.name), ("file", "Archive.TAR.GZ"));
+        assert_eq!(file.extension.as_deref(), Some("gz"));
+        let hidden = key_facts("a/.env");
+        assert_eq!(hidden.extension, None);
+        let folder = key_facts("a/b.d/");
+        assert_eq!((folder.kind, folder.name), ("folder", "b.d"));
+        assert_eq!((folder.extension, folder.thumbnail_key), (None, None));
+        let thumbnail_key = thumbnail::thumbnail_key_for("x");
+        let thumbnail = key_facts(&thumbnail_key);
+        assert_eq!(thumbnail.kind, "thumbnail");
+        assert_eq!(thumbnail.thumbnail_key, None);
+        let trashed = key_facts("__trash__/0000000000001/a.jpg");
+        assert_eq!(trashed.kind, "trash");
+        assert!(trashed.thumbnail_key.is_some());
+    }
+}
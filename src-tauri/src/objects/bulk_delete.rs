@@ -0,0 +1,126 @@
+//! Recursive folder delete over S3 DeleteObjects.
+//!
+//! A folder is listed once and everything is removed in batches of at most
+//! [`DELETE_BATCH_SIZE`] keys, each file together with the key its thumbnail
+//! would have; DeleteObjects treats a missing key as deleted. Storage
+//! accounting is recorded as one aggregated delta for the listed objects
+//! that were actually deleted, including when the operation is cancelled or
+//! fails partway.
+
+use super::{list_prefix_objects, validate_delete_key, validate_folder_prefix};
+use crate::types::{PrefixOpProgress, SpResult, UsageDelta};
+use crate::usage::UsageSync;
+use crate::{storage, thumbnail};
+use opendal::Operator;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// S3 rejects DeleteObjects requests with more keys than this.
+const DELETE_BATCH_SIZE: usize = 1000;
+
+/// Deletes every user object and folder marker under `prefix`, together with
+/// thumbnails. Protected analytics files are skipped. Returns the final progress report;
+/// `cancel` is checked between batches.
+pub async fn delete_prefix(
+    operator: &Operator,
+    profile: &str,
+    op_id: &str,
+    prefix: &str,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(&PrefixOpProgress),
+) -> SpResult<PrefixOpProgress> {
+    delete_prefix_in_batches(
+        operator,
+        profile,
+        op_id,
+        prefix,
+        DELETE_BATCH_SIZE,
+        cancel,
+        on_progress,
+    )
+    .await
+}
+
+async fn delete_prefix_in_batches(
+    operator: &Operator,
+    profile: &str,
+    op_id: &str,
+    prefix: &str,
+    batch_size: usize,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&PrefixOpProgress),
+) -> SpResult<PrefixOpProgress> {
+    let prefix = validate_folder_prefix(prefix)?;
+    validate_delete_key(&prefix)?;
+
+    let objects = list_prefix_objects(operator, &prefix).await?;
+    // Each object is followed by its thumbnail so a batch never strands one.
+    // Thumbnails are not listed, so their few kilobytes go uncounted.
+    let mut targets = Vec::with_capacity(objects.len() * 2);
+    for (key, size) in &objects {
+        targets.push((key.clone(), *size, false));
+        if !key.ends_with('/') {
+            targets.push((thumbnail::thumbnail_key_for(key), 0, true));
+        }
+    }
+
+    let mut progress = PrefixOpProgress {
+        op_id: op_id.to_string(),
+        op: "delete_prefix".into(),
+        prefix: prefix.clone(),
+        processed: 0,
+        total: objects.len() as u64,
+        current_key: None,
+        cancelled: false,
+    };
+    on_progress(&progress);
+    let mut deleted_bytes = 0u64;
+    let mut result = Ok(());
+    for batch in targets.chunks(batch_size.max(1)) {
+        if cancel.load(Ordering::Relaxed) {
+            progress.cancelled = true;
+            on_progress(&progress);
+            break;
+        }
+        let keys = batch
+            .iter()
+            .map(|(key, _, _)| key.clone())
+            .collect::<Vec<_>>();
+        if let Err(error) = operator.delete_iter(keys).await {
+            crate::logger::error("objects", &format!("DeleteObjects error: {error}"));
+            result = Err(storage::opendal_error("DeleteObjects", &prefix, &error));
+            break;
+        }
+        for (key, size, is_thumbnail) in batch {
+            deleted_bytes += size;
+            if !is_thumbnail {
+                progress.processed += 1;
+                progress.current_key = Some(key.clone());
+                if !key.ends_with('/') {
+                    let _ = crate::transfer_db::delete_thumbnail_cache(key);
+                }
+            }
+        }
+        on_progress(&progress);
+    }
+
+    if deleted_bytes > 0 {
+        let _ = UsageSync::record_local_delta(
+            profile,
+            UsageDelta {
+                deleted_storage_bytes: deleted_bytes,
+                ..UsageDelta::default()
+            },
+        );
+    }
+    crate::logger::info(
+        "objects",
+        &format!(
+            "delete_prefix done prefix={prefix} deleted={}/{} bytes={deleted_bytes}",
+            progress.processed, progress.total
+        ),
+    );
+    result.map(|()| progress)
+}
+
+#[cfg(test)]
+mod tests;
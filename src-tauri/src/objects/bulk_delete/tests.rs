@@ -0,0 +1,175 @@
+use super::*;
+use crate::test_support::{stand_in_operator, start_path_style_s3};
+use crate::types::{ErrorKind, ANALYTICS_PREFIX};
+use futures::TryStreamExt;
+
+async fn write_fixtures(operator: &Operator, keys: &[&str]) {
+    for key in keys {
+        operator
+            .write(key, vec![1, 2])
+            .await
+            .expect("fixture should write");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn folder_delete_batches_keys_and_takes_thumbnails_along() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    write_fixtures(
+        &operator,
+        &[
+            "trip/a.jpg",
+            "trip/b.jpg",
+            "trip/day1/c.jpg",
+            "trip/day1/d.jpg",
+            "trip/day2/e.jpg",
+            "tripod.txt",
+        ],
+    )
+    .await;
+    for key in ["trip/a.jpg", "trip/day2/e.jpg"] {
+        operator
+            .write(&thumbnail::thumbnail_key_for(key), vec![9])
+            .await
+            .expect("thumbnail fixture should write");
+    }
+    let mut reports = Vec::new();
+
+    let done = delete_prefix_in_batches(
+        &operator,
+        "default",
+        "op-1",
+        "trip/",
+        4,
+        &AtomicBool::new(false),
+        |progress| reports.push(progress.processed),
+    )
+    .await
+    .expect("folder delete should succeed");
+
+    assert_eq!((done.processed, done.total, done.cancelled), (5, 5, false));
+    assert_eq!(reports.first(), Some(&0));
+    assert_eq!(reports.last(), Some(&5));
+    assert!(operator.stat("tripod.txt").await.is_ok());
+    assert!(operator.stat("trip/day1/c.jpg").await.is_err());
+    assert!(operator
+        .stat(&thumbnail::thumbnail_key_for("trip/day2/e.jpg"))
+        .await
+        .is_err());
+    let requests = server.requests();
+    let batch_requests = requests
+        .iter()
+        .filter(|request| request.method == "POST" && request.query().contains("delete"))
+        .count();
+    assert_eq!(
+        batch_requests, 3,
+        "5 files and their thumbnail keys in batches of 4"
+    );
+    assert!(
+        requests
+            .iter()
+            .all(|request| !request.query().contains("__thumbnail__")),
+        "thumbnails are deleted by key, not listed"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelling_a_folder_delete_stops_between_batches() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    write_fixtures(&operator, &["old/1", "old/2", "old/3", "old/4", "old/5"]).await;
+    let cancel = AtomicBool::new(false);
+
+    let done = delete_prefix_in_batches(
+        &operator,
+        "default",
+        "op-2",
+        "old/",
+        2,
+        &cancel,
+        |progress| {
+            if progress.processed >= 2 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        },
+    )
+    .await
+    .expect("cancelled delete still reports progress");
+
+    assert!(done.cancelled);
+    assert_eq!((done.processed, done.total), (2, 5));
+    assert!(operator.stat("old/1").await.is_err());
+    assert!(operator.stat("old/3").await.is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn folder_delete_never_touches_protected_analytics() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let ledger = format!("{ANALYTICS_PREFIX}2026-07-27.json");
+    write_fixtures(&operator, &[ledger.as_str(), "analytics/notes.txt"]).await;
+
+    let rejected = delete_prefix(
+        &operator,
+        "default",
+        "op-3",
+        ANALYTICS_PREFIX,
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .await
+    .expect_err("the analytics prefix itself is protected");
+    assert!(matches!(rejected.kind, ErrorKind::NotRetriable));
+
+    let done = delete_prefix(
+        &operator,
+        "default",
+        "op-4",
+        "analytics/",
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .await
+    .expect("parent folder delete should skip protected files");
+    assert_eq!(done.processed, 1);
+    assert!(operator.stat(&ledger).await.is_ok());
+    assert!(operator.stat("analytics/notes.txt").await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn folder_delete_removes_markers_but_not_sibling_prefixes() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    write_fixtures(&operator, &["trip/a.jpg", "tripod.txt"]).await;
+    for marker in ["trip/", "trip/empty/"] {
+        operator
+            .create_dir(marker)
+            .await
+            .expect("marker should write");
+    }
+
+    let done = delete_prefix(
+        &operator,
+        "default",
+        "op-5",
+        "trip",
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .await
+    .expect("folder delete should succeed");
+
+    assert_eq!(done.processed, 3);
+    assert!(operator.stat("tripod.txt").await.is_ok());
+    let mut lister = operator
+        .lister_with("trip")
+        .recursive(true)
+        .await
+        .expect("listing should start");
+    let mut keys = Vec::new();
+    while let Some(entry) = lister.try_next().await.expect("listing should succeed") {
+        keys.push(entry.path().to_string());
+    }
+    assert_eq!(keys, vec!["tripod.txt"]);
+}
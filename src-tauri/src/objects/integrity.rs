@@ -0,0 +1,109 @@
+//! SHA-256 content hashes recorded with objects.
+//!
+//! Uploads store the hex SHA-256 of an object's content as the user metadata
+//! pair `x-amz-meta-sha256`, because ETags are content hashes only for
+//! objects written in a single request. Downloads and [`verify_object`]
+//! compare content against that value; an object without one cannot be
+//! checked. Content sealed or compressed on upload also records the SHA-256
+//! of its source as `x-amz-meta-source-sha256`, which is what a local file
+//! is compared with.
+
+use super::now_ms;
+use crate::storage;
+use crate::types::{ErrorKind, ObjectVerification, SpError, SpResult};
+use opendal::Operator;
+use sha2::{Digest, Sha256};
+
+/// User metadata name (`x-amz-meta-sha256`) holding the lower-case hex
+/// SHA-256 of the object's content.
+pub const SHA256_METADATA_KEY: &str = "sha256";
+/// User metadata name (`x-amz-meta-source-sha256`) holding the lower-case
+/// hex SHA-256 of the source of content not stored byte for byte.
+pub const SOURCE_SHA256_METADATA_KEY: &str = "source-sha256";
+
+/// Bytes read per ranged request while re-hashing an object.
+const VERIFY_CHUNK_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The recorded hash among `user_metadata`, lower-cased. A value that is not
+/// 64 hex digits was not written by this app and is ignored.
+pub fn recorded_sha256<'a>(
+    user_metadata: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> Option<String> {
+    recorded_digest(user_metadata, SHA256_METADATA_KEY)
+}
+
+/// Like [`recorded_sha256`] for the hash of the source.
+pub fn recorded_source_sha256<'a>(
+    user_metadata: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> Option<String> {
+    recorded_digest(user_metadata, SOURCE_SHA256_METADATA_KEY)
+}
+
+fn recorded_digest<'a>(
+    user_metadata: impl IntoIterator<Item = (&'a String, &'a String)>,
+    key: &str,
+) -> Option<String> {
+    user_metadata
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.trim().to_ascii_lowercase())
+        .filter(|value| value.len() == 64 && value.bytes().all(|byte| byte.is_ascii_hexdigit()))
+}
+
+/// The error for content that does not hash to `expected`.
+pub fn integrity_error(key: &str, expected: &str, actual: &str) -> SpError {
+    SpError {
+        kind: ErrorKind::Integrity,
+        message: format!("content of {key} does not match its recorded SHA-256"),
+        retry_after_ms: None,
+        context: Some(serde_json::json!({
+            "key": key,
+            "expected_sha256": expected,
+            "actual_sha256": actual,
+        })),
+        at: now_ms(),
+    }
+}
+
+/// Downloads `key` in ranges, hashing as it goes, and compares the result
+/// with the recorded hash. Nothing is written locally.
+pub async fn verify_object(operator: &Operator, key: &str) -> SpResult<ObjectVerification> {
+    let metadata = operator
+        .stat(key)
+        .await
+        .map_err(|error| storage::opendal_error("HeadObject", key, &error))?;
+    let size = metadata.content_length();
+    let recorded = metadata.user_metadata().and_then(recorded_sha256);
+
+    let mut hasher = Sha256::new();
+    let mut offset = 0;
+    while offset < size {
+        let end = offset.saturating_add(VERIFY_CHUNK_BYTES).min(size);
+        let chunk = operator
+            .read_with(key)
+            .range(offset..end)
+            .await
+            .map_err(|error| storage::opendal_error("GetObject range", key, &error))?;
+        if chunk.is_empty() {
+            return Err(SpError {
+                kind: ErrorKind::RetryableNet,
+                message: format!("unexpected EOF at byte {offset} of {size}"),
+                retry_after_ms: Some(500),
+                context: None,
+                at: now_ms(),
+            });
+        }
+        offset += chunk.len() as u64;
+        for slice in chunk {
+            hasher.update(&slice);
+        }
+    }
+    let computed = format!("{:x}", hasher.finalize());
+    Ok(ObjectVerification {
+        key: key.to_string(),
+        size,
+        matches: recorded.as_ref().map(|recorded| *recorded == computed),
+        recorded_sha256: recorded,
+        computed_sha256: computed,
+    })
+}
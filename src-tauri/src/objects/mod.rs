@@ -1,57 +1,118 @@
 //! SwiftPan object-browser business logic.
 //!
 //! This module translates raw object-store entries into the file model exposed
-//! to the frontend. It owns prefix-as-directory projection, thumbnail hiding
-//! and association, analytics deletion protection, related-thumbnail cleanup,
-//! and deletion usage deltas. It must not construct credentials, configure an
-//! OpenDAL backend, own transfer execution, or expose Tauri commands.
+//! to the frontend. It owns prefix-as-directory projection, continuation-token
+//! paging, thumbnail hiding and association, analytics deletion protection,
+//! related-thumbnail cleanup, server-side copy/move, batched folder deletes,
+//! the recycle bin, metadata inspection and edit validation, recorded content
+//! hashes and their verification, and deletion usage deltas. It must
+//! not construct credentials, configure an OpenDAL backend, own transfer
+//! execution, or expose Tauri commands.
 
-use crate::thumbnail;
 use crate::types::{
-    ErrorKind, FileEntry, ListPage, SpError, SpResult, UsageDelta, ANALYTICS_PREFIX,
+    err_invalid, ErrorKind, FileEntry, ListPage, ObjectHeaders, ObjectMetadata,
+    ObjectMetadataUpdate, SpError, SpResult, UsageDelta, ANALYTICS_PREFIX,
 };
 use crate::usage::UsageSync;
+use crate::{storage, thumbnail};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use futures::TryStreamExt;
 use opendal::Operator;
 use std::collections::{BTreeSet, HashSet};
 
+mod bulk_delete;
+mod integrity;
+mod prefix_ops;
+mod relocate;
+mod trash;
+
+pub use bulk_delete::delete_prefix;
+pub use integrity::{
+    integrity_error, recorded_sha256, recorded_source_sha256, verify_object, SHA256_METADATA_KEY,
+    SOURCE_SHA256_METADATA_KEY,
+};
+pub use prefix_ops::{begin_prefix_op, cancel_prefix_op, PrefixOpGuard};
+pub use relocate::{copy_object, move_object, move_prefix};
+pub use trash::{
+    is_trash_key, list_trash, purge_trash, purge_trash_older_than, restore_trashed, trash_object,
+    trash_prefix, TRASH_PREFIX,
+};
+
 pub async fn list_objects(
     operator: &Operator,
     prefix: &str,
-    _continuation: Option<String>,
+    continuation: Option<String>,
     max_keys: i32,
 ) -> SpResult<ListPage> {
+    let start_after = continuation.as_deref().map(decode_page_token).transpose()?;
+    if start_after
+        .as_deref()
+        .is_some_and(|key| !key.starts_with(prefix))
+    {
+        return Err(err_invalid("continuation token belongs to another prefix"));
+    }
+    let mut lister = operator.lister_with(prefix);
+    if let Some(key) = start_after.as_deref() {
+        // Services without native StartAfter still get correct pages from the
+        // key filter below; they only pay for re-listing the skipped keys.
+        if operator.info().full_capability().list_with_start_after {
+            lister = lister.start_after(key);
+        }
+    }
+    let mut lister = lister
+        .await
+        .map_err(|error| storage::opendal_error("list", prefix, &error))?;
+
+    let max_keys = max_keys.max(1) as usize;
     let mut dirs = BTreeSet::new();
     let mut files: Vec<(String, opendal::Metadata)> = Vec::new();
-    let entries = operator.list(prefix).await.map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("list: {error}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: now_ms(),
-    })?;
-    let mut count = 0;
-    for entry in entries {
-        if count >= max_keys {
-            break;
-        }
+    let mut last_key: Option<String> = None;
+    let mut has_more = false;
+    while let Some(entry) = lister
+        .try_next()
+        .await
+        .map_err(|error| storage::opendal_error("list entry", prefix, &error))?
+    {
         let key = entry.path().to_string();
-        if key.ends_with('/') || thumbnail::is_thumbnail_key(&key) {
+        if key == prefix || thumbnail::is_thumbnail_key(&key) || is_trash_key(&key) {
             continue;
         }
-        let relative = key.strip_prefix(prefix).unwrap_or(&key);
-        if let Some(position) = relative.find('/') {
-            dirs.insert(format!("{}{}", prefix, &relative[..=position]));
+        if start_after
+            .as_deref()
+            .is_some_and(|after| key.as_str() <= after)
+        {
             continue;
         }
-        files.push((key, entry.metadata().clone()));
-        count += 1;
+        let relative = key.strip_prefix(prefix).unwrap_or(&key);
+        let projected_dir = relative
+            .find('/')
+            .map(|position| format!("{}{}", prefix, &relative[..=position]));
+        if let Some(dir) = projected_dir.as_ref() {
+            if dirs.contains(dir) {
+                continue;
+            }
+        }
+        if dirs.len() + files.len() >= max_keys {
+            has_more = true;
+            break;
+        }
+        match projected_dir {
+            Some(dir) => {
+                last_key = Some(dir.clone());
+                dirs.insert(dir);
+            }
+            None => {
+                last_key = Some(key.clone());
+                files.push((key, entry.metadata().clone()));
+            }
+        }
     }
 
     let mut items = dirs
         .into_iter()
         .map(|key| FileEntry {
-            protected: key.starts_with(ANALYTICS_PREFIX),
+            protected: folder_is_protected(&key),
             key,
             size: None,
             last_modified_ms: None,
@@ -81,7 +142,11 @@ pub async fn list_objects(
     let page = ListPage {
         prefix: prefix.to_string(),
         items,
-        next_token: None,
+        next_token: if has_more {
+            last_key.as_deref().map(encode_page_token)
+        } else {
+            None
+        },
     };
     crate::logger::info(
         "objects",
@@ -95,6 +160,33 @@ pub async fn list_objects(
     Ok(page)
 }
 
+/// Folders inside the analytics prefix, and the folders that lead to it,
+/// cannot be renamed or deleted by the user.
+fn folder_is_protected(key: &str) -> bool {
+    key.starts_with(ANALYTICS_PREFIX) || ANALYTICS_PREFIX.starts_with(key)
+}
+
+/// Encodes the last key of a page as an opaque continuation token.
+///
+/// A projected folder is encoded with a trailing U+10FFFF so the next page
+/// starts after every key below it instead of re-projecting the same folder.
+fn encode_page_token(last_key: &str) -> String {
+    let boundary = if last_key.ends_with('/') {
+        format!("{last_key}{}", char::MAX)
+    } else {
+        last_key.to_string()
+    };
+    URL_SAFE_NO_PAD.encode(boundary.as_bytes())
+}
+
+fn decode_page_token(token: &str) -> SpResult<String> {
+    URL_SAFE_NO_PAD
+        .decode(token.trim().as_bytes())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| err_invalid("invalid list continuation token"))
+}
+
 pub async fn list_all_objects(operator: &Operator, max_total: i32) -> SpResult<Vec<FileEntry>> {
     let mut files = Vec::new();
     let mut thumbnails = HashSet::new();
@@ -102,28 +194,35 @@ pub async fn list_all_objects(operator: &Operator, max_total: i32) -> SpResult<V
         .lister_with("")
         .recursive(true)
         .await
-        .map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("list recursive: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: now_ms(),
-        })?;
-    while let Some(entry) = lister.try_next().await.map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("list entry: {error}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: now_ms(),
-    })? {
+        .map_err(|error| storage::opendal_error("list recursive", "", &error))?;
+    while let Some(entry) = lister
+        .try_next()
+        .await
+        .map_err(|error| storage::opendal_error("list entry", "", &error))?
+    {
         let key = entry.path().to_string();
-        if key.ends_with('/') {
-            continue;
-        }
         if thumbnail::is_thumbnail_key(&key) {
             thumbnails.insert(key);
             continue;
         }
+        if is_trash_key(&key) {
+            continue;
+        }
+        if key.ends_with('/') {
+            // Only explicit folder markers show up in a recursive listing.
+            if key != "/" {
+                files.push(FileEntry {
+                    protected: folder_is_protected(&key),
+                    key,
+                    size: None,
+                    last_modified_ms: None,
+                    etag: None,
+                    thumbnail_key: None,
+                    is_prefix: true,
+                });
+            }
+            continue;
+        }
         let metadata = entry.metadata();
         files.push(FileEntry {
             size: Some(metadata.content_length()),
@@ -144,6 +243,9 @@ pub async fn list_all_objects(operator: &Operator, max_total: i32) -> SpResult<V
     let mut items = files
         .into_iter()
         .map(|mut item| {
+            if item.is_prefix {
+                return item;
+            }
             let thumbnail_key = thumbnail::thumbnail_key_for(&item.key);
             if thumbnails.contains(&thumbnail_key) {
                 item.thumbnail_key = Some(thumbnail_key);
@@ -159,17 +261,181 @@ pub async fn list_all_objects(operator: &Operator, max_total: i32) -> SpResult<V
     Ok(items)
 }
 
-pub async fn delete_object(operator: &Operator, key: &str) -> SpResult<String> {
+/// Deletes `key` and its thumbnail from the bucket of `profile`.
+pub async fn delete_object(operator: &Operator, profile: &str, key: &str) -> SpResult<String> {
     validate_delete_key(key)?;
 
-    delete_one(operator, key).await?;
+    delete_one(operator, profile, key).await?;
     if !thumbnail::is_thumbnail_key(key) {
-        let _ = delete_one(operator, &thumbnail::thumbnail_key_for(key)).await;
+        let _ = delete_one(operator, profile, &thumbnail::thumbnail_key_for(key)).await;
         let _ = crate::transfer_db::delete_thumbnail_cache(key);
     }
     Ok(key.to_string())
 }
 
+/// S3 caps user-defined metadata at 2 KiB, counting names and values.
+const MAX_USER_METADATA_BYTES: usize = 2048;
+
+pub async fn stat_object(operator: &Operator, key: &str) -> SpResult<ObjectMetadata> {
+    let metadata = operator
+        .stat(key)
+        .await
+        .map_err(|error| storage::opendal_error("HeadObject", key, &error))?;
+    Ok(ObjectMetadata {
+        key: key.to_string(),
+        size: metadata.content_length(),
+        last_modified_ms: metadata
+            .last_modified()
+            .map(|timestamp| timestamp.timestamp_millis()),
+        etag: metadata.etag().map(str::to_string),
+        content_md5: metadata.content_md5().map(str::to_string),
+        version: metadata.version().map(str::to_string),
+        headers: ObjectHeaders {
+            content_type: metadata.content_type().map(str::to_string),
+            content_disposition: metadata.content_disposition().map(str::to_string),
+            cache_control: metadata.cache_control().map(str::to_string),
+            content_encoding: metadata.content_encoding().map(str::to_string),
+            user_metadata: metadata
+                .user_metadata()
+                .map(|pairs| {
+                    pairs
+                        .iter()
+                        .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        },
+    })
+}
+
+/// Checks a metadata rewrite before it is sent: the key must be a user file,
+/// header values must be plain ASCII, and user metadata names must be valid
+/// lower-case header tokens within S3's size limit.
+pub fn validate_metadata_update(key: &str, update: &ObjectMetadataUpdate) -> SpResult<()> {
+    validate_delete_key(key)?;
+    if key.is_empty() || key.ends_with('/') || thumbnail::is_thumbnail_key(key) || is_trash_key(key)
+    {
+        return Err(err_invalid("metadata can only be edited on user files"));
+    }
+    let headers = &update.headers;
+    let standard = [
+        &headers.content_type,
+        &headers.content_disposition,
+        &headers.cache_control,
+        &headers.content_encoding,
+    ];
+    if standard
+        .into_iter()
+        .flatten()
+        .chain(headers.user_metadata.values())
+        .any(|value| !is_header_value(value))
+    {
+        return Err(err_invalid(
+            "header values must be printable ASCII without line breaks",
+        ));
+    }
+    let mut user_bytes = 0;
+    for (name, value) in &headers.user_metadata {
+        let valid_name = !name.is_empty()
+            && !name.starts_with("x-amz-meta-")
+            && name
+                .bytes()
+                .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-');
+        if !valid_name {
+            return Err(err_invalid(&format!(
+                "invalid metadata name '{name}': use lower-case letters, digits and '-'"
+            )));
+        }
+        user_bytes += name.len() + value.len();
+    }
+    if user_bytes > MAX_USER_METADATA_BYTES {
+        return Err(err_invalid("custom metadata exceeds the 2 KiB limit"));
+    }
+    Ok(())
+}
+
+fn is_header_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|byte| byte == b'\t' || (b' '..=b'~').contains(&byte))
+}
+
+/// Creates an empty folder by writing a zero-byte `prefix/` marker object.
+pub async fn create_folder(operator: &Operator, prefix: &str) -> SpResult<String> {
+    let prefix = validate_folder_prefix(prefix)?;
+    if ANALYTICS_PREFIX.starts_with(&prefix) || prefix.starts_with(ANALYTICS_PREFIX) {
+        return Err(SpError {
+            kind: ErrorKind::NotRetriable,
+            message: "creating folders inside the analytics prefix is prohibited".into(),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        });
+    }
+    operator.create_dir(&prefix).await.map_err(|error| {
+        crate::logger::error("objects", &format!("create folder error: {error}"));
+        storage::opendal_error("PutObject", &prefix, &error)
+    })?;
+    crate::logger::info("objects", &format!("create_folder ok prefix={prefix}"));
+    Ok(prefix)
+}
+
+fn validate_folder_prefix(prefix: &str) -> SpResult<String> {
+    let trimmed = prefix.trim_end_matches('/');
+    if trimmed.is_empty() || prefix.starts_with('/') {
+        return Err(err_invalid("a folder name is required"));
+    }
+    if trimmed
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(err_invalid(
+            "folder path contains an empty or relative segment",
+        ));
+    }
+    let prefix = format!("{trimmed}/");
+    if thumbnail::is_thumbnail_key(&prefix) || is_trash_key(&prefix) {
+        return Err(err_invalid("this folder is managed automatically"));
+    }
+    Ok(prefix)
+}
+
+/// Longest object key S3 accepts, in UTF-8 bytes.
+const MAX_KEY_BYTES: usize = 1024;
+
+/// Checks that `key` can name a user file: within S3's length limit, free of
+/// control characters, without empty or relative path segments, and outside
+/// the prefixes the app manages itself.
+pub fn validate_object_key(key: &str) -> SpResult<()> {
+    if key.is_empty() || key.ends_with('/') {
+        return Err(err_invalid("an object key must name a file"));
+    }
+    if key.len() > MAX_KEY_BYTES {
+        return Err(err_invalid(&format!(
+            "object key is longer than {MAX_KEY_BYTES} bytes: {key}"
+        )));
+    }
+    if key.chars().any(char::is_control) {
+        return Err(err_invalid(&format!(
+            "object key contains a control character: {key:?}"
+        )));
+    }
+    if key
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(err_invalid(&format!(
+            "object key contains an empty or relative segment: {key}"
+        )));
+    }
+    if thumbnail::is_thumbnail_key(key) || is_trash_key(key) || key.starts_with(ANALYTICS_PREFIX) {
+        return Err(err_invalid(&format!(
+            "object key is inside a prefix the app manages: {key}"
+        )));
+    }
+    Ok(())
+}
+
 pub fn validate_delete_key(key: &str) -> SpResult<()> {
     if key.starts_with(ANALYTICS_PREFIX) {
         return Err(SpError {
@@ -183,31 +449,57 @@ pub fn validate_delete_key(key: &str) -> SpResult<()> {
     Ok(())
 }
 
-async fn delete_one(operator: &Operator, key: &str) -> SpResult<()> {
+async fn delete_one(operator: &Operator, profile: &str, key: &str) -> SpResult<()> {
     let size = operator
         .stat(key)
         .await
         .ok()
         .map(|metadata| metadata.content_length());
+    remove_one(operator, key).await?;
+    let _ = UsageSync::record_local_delta(
+        profile,
+        UsageDelta {
+            deleted_storage_bytes: size.unwrap_or(0),
+            ..UsageDelta::default()
+        },
+    );
+    Ok(())
+}
+
+/// Deletes without recording a storage delta, for bytes that were copied
+/// elsewhere first.
+async fn remove_one(operator: &Operator, key: &str) -> SpResult<()> {
     operator.delete(key).await.map_err(|error| {
         crate::logger::error("objects", &format!("DeleteObject error: {error}"));
-        SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("DeleteObject: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: now_ms(),
+        storage::opendal_error("DeleteObject", key, &error)
+    })
+}
+
+/// Every user object under `prefix` with its size, sorted by key. Folder
+/// markers are included (their keys end in `/`); thumbnails and protected
+/// analytics files are left out.
+async fn list_prefix_objects(operator: &Operator, prefix: &str) -> SpResult<Vec<(String, u64)>> {
+    let list_error =
+        |error: opendal::Error| storage::opendal_error("list recursive", prefix, &error);
+    let mut objects = Vec::new();
+    let mut lister = operator
+        .lister_with(prefix)
+        .recursive(true)
+        .await
+        .map_err(list_error)?;
+    while let Some(entry) = lister.try_next().await.map_err(list_error)? {
+        let key = entry.path();
+        if key == "/"
+            || thumbnail::is_thumbnail_key(key)
+            || is_trash_key(key)
+            || key.starts_with(ANALYTICS_PREFIX)
+        {
+            continue;
         }
-    })?;
-    let _ = UsageSync::record_local_delta(UsageDelta {
-        class_a: Default::default(),
-        class_b: Default::default(),
-        ingress_bytes: 0,
-        egress_bytes: 0,
-        added_storage_bytes: 0,
-        deleted_storage_bytes: size.unwrap_or(0),
-    });
-    Ok(())
+        objects.push((key.to_string(), entry.metadata().content_length()));
+    }
+    objects.sort();
+    Ok(objects)
 }
 
 fn now_ms() -> i64 {
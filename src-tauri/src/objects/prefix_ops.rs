@@ -0,0 +1,60 @@
+//! Registry of running folder-wide operations.
+//!
+//! Folder moves and deletes can touch thousands of objects, so each one runs
+//! under a caller-chosen id that `cancel_prefix_op` can flip from another
+//! command. Operations check their flag between objects or batches and stop
+//! with a final `cancelled` progress report rather than an error.
+
+use crate::types::{err_invalid, SpResult};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+static PREFIX_OPS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Keeps an operation registered until dropped.
+pub struct PrefixOpGuard {
+    op_id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PrefixOpGuard {
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+}
+
+impl Drop for PrefixOpGuard {
+    fn drop(&mut self) {
+        if let Ok(mut ops) = PREFIX_OPS.lock() {
+            ops.remove(&self.op_id);
+        }
+    }
+}
+
+pub fn begin_prefix_op(op_id: &str) -> SpResult<PrefixOpGuard> {
+    let mut ops = PREFIX_OPS
+        .lock()
+        .map_err(|_| err_invalid("prefix operation registry poisoned"))?;
+    if ops.contains_key(op_id) {
+        return Err(err_invalid("a folder operation with this id is running"));
+    }
+    let cancelled = Arc::new(AtomicBool::new(false));
+    ops.insert(op_id.to_string(), cancelled.clone());
+    Ok(PrefixOpGuard {
+        op_id: op_id.to_string(),
+        cancelled,
+    })
+}
+
+pub fn cancel_prefix_op(op_id: &str) -> SpResult<()> {
+    let ops = PREFIX_OPS
+        .lock()
+        .map_err(|_| err_invalid("prefix operation registry poisoned"))?;
+    ops.get(op_id)
+        .ok_or_else(|| err_invalid("folder operation not found"))?
+        .store(true, Ordering::Relaxed);
+    Ok(())
+}
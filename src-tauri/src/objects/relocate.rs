@@ -0,0 +1,201 @@
+//! Server-side copy and move for objects and whole prefixes.
+//!
+//! Everything here is built on S3 CopyObject, so no object bytes pass through
+//! the client. Thumbnails are addressed by a hash of the object key, which
+//! means every relocation also relocates `thumbnail_key_for(from)` to
+//! `thumbnail_key_for(to)` and re-keys the local thumbnail cache row. A move
+//! leaves stored bytes unchanged, so only copies record added storage. A
+//! prefix move is not atomic: objects moved before a failure stay moved.
+
+use super::{list_prefix_objects, now_ms, remove_one, validate_delete_key, validate_folder_prefix};
+use crate::types::{
+    err_invalid, ErrorKind, PrefixOpProgress, SpError, SpResult, UsageDelta, ANALYTICS_PREFIX,
+};
+use crate::usage::UsageSync;
+use crate::{storage, thumbnail};
+use opendal::Operator;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Prefix moves report at most once per this many objects, plus the final one.
+pub(super) const PREFIX_PROGRESS_EVERY: u64 = 25;
+
+/// Copies `from_key` to `to_key` in the bucket of `profile`, which records
+/// the added storage.
+pub async fn copy_object(
+    operator: &Operator,
+    profile: &str,
+    from_key: &str,
+    to_key: &str,
+) -> SpResult<String> {
+    validate_relocation(from_key, to_key)?;
+    let (size, thumbnail_size) = relocate(operator, from_key, to_key, false).await?;
+    let _ = UsageSync::record_local_delta(
+        profile,
+        UsageDelta {
+            added_storage_bytes: size + thumbnail_size,
+            ..UsageDelta::default()
+        },
+    );
+    Ok(to_key.to_string())
+}
+
+pub async fn move_object(operator: &Operator, from_key: &str, to_key: &str) -> SpResult<String> {
+    validate_delete_key(from_key)?;
+    validate_relocation(from_key, to_key)?;
+    relocate(operator, from_key, to_key, true).await?;
+    Ok(to_key.to_string())
+}
+
+/// Moves every object under `from_prefix` to the same relative key under
+/// `to_prefix`. Returns the final progress report; `cancel` is checked
+/// between objects.
+pub async fn move_prefix(
+    operator: &Operator,
+    op_id: &str,
+    from_prefix: &str,
+    to_prefix: &str,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&PrefixOpProgress),
+) -> SpResult<PrefixOpProgress> {
+    let from_prefix = validate_folder_prefix(from_prefix)?;
+    let to_prefix = validate_folder_prefix(to_prefix)?;
+    validate_delete_key(&from_prefix)?;
+    validate_relocation(&from_prefix, &to_prefix)?;
+    if ANALYTICS_PREFIX.starts_with(&from_prefix) {
+        return Err(SpError {
+            kind: ErrorKind::NotRetriable,
+            message: "moving a folder that contains analytics files is prohibited".into(),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        });
+    }
+    if to_prefix.starts_with(&from_prefix) {
+        return Err(err_invalid("cannot move a folder into itself"));
+    }
+
+    let objects = list_prefix_objects(operator, &from_prefix).await?;
+    let total = objects.len() as u64;
+    let mut progress = PrefixOpProgress {
+        op_id: op_id.to_string(),
+        op: "move_prefix".into(),
+        prefix: from_prefix.clone(),
+        processed: 0,
+        total,
+        current_key: None,
+        cancelled: false,
+    };
+    on_progress(&progress);
+    for (key, _) in objects {
+        if cancel.load(Ordering::Relaxed) {
+            progress.cancelled = true;
+            on_progress(&progress);
+            break;
+        }
+        let to_key = format!("{to_prefix}{}", &key[from_prefix.len()..]);
+        if key.ends_with('/') {
+            move_marker(operator, &key, &to_key).await?;
+        } else {
+            relocate(operator, &key, &to_key, true).await?;
+        }
+        progress.processed += 1;
+        if progress.processed % PREFIX_PROGRESS_EVERY == 0 || progress.processed == total {
+            progress.current_key = Some(key);
+            on_progress(&progress);
+        }
+    }
+    crate::logger::info(
+        "objects",
+        &format!(
+            "move_prefix done from={from_prefix} to={to_prefix} moved={}/{total}",
+            progress.processed
+        ),
+    );
+    Ok(progress)
+}
+
+/// Copies `from_key` and its thumbnail to `to_key`, removing the source when
+/// moving. Returns the sizes of the object and of its thumbnail, `0` when it
+/// has none.
+pub(super) async fn relocate(
+    operator: &Operator,
+    from_key: &str,
+    to_key: &str,
+    remove_source: bool,
+) -> SpResult<(u64, u64)> {
+    let size = copy_one(operator, from_key, to_key).await?;
+    let from_thumbnail = thumbnail::thumbnail_key_for(from_key);
+    let to_thumbnail = thumbnail::thumbnail_key_for(to_key);
+    // Most objects have no thumbnail; a missing one is not an error, but any
+    // other failure would leave the thumbnail behind, or delete it on a move.
+    let thumbnail_size = match copy_one(operator, &from_thumbnail, &to_thumbnail).await {
+        Ok(size) => Some(size),
+        Err(error) if matches!(error.kind, ErrorKind::NotFound) => None,
+        Err(error) => return Err(error),
+    };
+    if remove_source {
+        remove_one(operator, from_key).await?;
+        if thumbnail_size.is_some() {
+            let _ = remove_one(operator, &from_thumbnail).await;
+        }
+        let _ = crate::transfer_db::move_thumbnail_cache(from_key, to_key, &to_thumbnail);
+    } else {
+        let _ = crate::transfer_db::copy_thumbnail_cache(from_key, to_key, &to_thumbnail);
+    }
+    Ok((size, thumbnail_size.unwrap_or(0)))
+}
+
+/// Folder markers are zero-byte and never have thumbnails, and OpenDAL
+/// refuses to copy directory paths, so they are recreated instead.
+async fn move_marker(operator: &Operator, from_key: &str, to_key: &str) -> SpResult<()> {
+    operator
+        .create_dir(to_key)
+        .await
+        .map_err(|error| storage::opendal_error("PutObject", to_key, &error))?;
+    remove_one(operator, from_key).await
+}
+
+async fn copy_one(operator: &Operator, from_key: &str, to_key: &str) -> SpResult<u64> {
+    let size = operator
+        .stat(from_key)
+        .await
+        .map_err(|error| {
+            storage::opendal_error(&format!("CopyObject source {from_key}"), from_key, &error)
+        })?
+        .content_length();
+    operator.copy(from_key, to_key).await.map_err(|error| {
+        crate::logger::error("objects", &format!("CopyObject error: {error}"));
+        storage::opendal_error("CopyObject", to_key, &error)
+    })?;
+    Ok(size)
+}
+
+fn validate_relocation(from_key: &str, to_key: &str) -> SpResult<()> {
+    if from_key.is_empty() || to_key.is_empty() {
+        return Err(err_invalid("source and destination keys are required"));
+    }
+    if from_key.ends_with('/') != to_key.ends_with('/') {
+        return Err(err_invalid(
+            "cannot relocate between a file and a folder key",
+        ));
+    }
+    if from_key == to_key {
+        return Err(err_invalid("source and destination are the same"));
+    }
+    if [from_key, to_key]
+        .iter()
+        .any(|key| thumbnail::is_thumbnail_key(key) || super::is_trash_key(key))
+    {
+        return Err(err_invalid("this object is managed automatically"));
+    }
+    if to_key.starts_with(ANALYTICS_PREFIX) {
+        return Err(SpError {
+            kind: ErrorKind::NotRetriable,
+            message: "writing into the analytics prefix is prohibited".into(),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        });
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use super::*;
 use opendal::services::Memory;
+use std::sync::atomic::AtomicBool;
 
 fn memory_operator() -> Operator {
     Operator::new(Memory::default())
@@ -61,7 +62,7 @@ async fn deleting_user_object_also_removes_its_thumbnail_but_protects_analytics(
         .expect("thumbnail fixture should write");
 
     assert_eq!(
-        delete_object(&operator, object_key)
+        delete_object(&operator, "default", object_key)
             .await
             .expect("delete should succeed"),
         object_key
@@ -69,8 +70,545 @@ async fn deleting_user_object_also_removes_its_thumbnail_but_protects_analytics(
     assert!(operator.stat(object_key).await.is_err());
     assert!(operator.stat(&thumbnail_key).await.is_err());
 
-    let error = delete_object(&operator, "analytics/daily/protected.json")
+    let error = delete_object(&operator, "default", "analytics/daily/protected.json")
         .await
         .expect_err("analytics object must be protected");
     assert!(matches!(error.kind, ErrorKind::NotRetriable));
 }
+
+#[tokio::test]
+async fn continuation_tokens_page_through_prefix_without_duplicates_or_gaps() {
+    let operator = memory_operator();
+    let mut expected = Vec::new();
+    for index in 0..7 {
+        let key = format!("raw/file-{index}.arw");
+        operator
+            .write(&key, vec![index as u8])
+            .await
+            .expect("file fixture should write");
+        operator
+            .write(&thumbnail::thumbnail_key_for(&key), vec![0])
+            .await
+            .expect("thumbnail fixture should write");
+        expected.push(key);
+    }
+    for folder in ["raw/a-folder/", "raw/z-folder/"] {
+        for index in 0..4 {
+            operator
+                .write(&format!("{folder}nested-{index}.arw"), vec![1])
+                .await
+                .expect("nested fixture should write");
+        }
+        expected.push(folder.to_string());
+    }
+    expected.sort();
+
+    let mut seen = Vec::new();
+    let mut token = None;
+    let mut pages = 0;
+    loop {
+        let page = list_objects(&operator, "raw/", token.take(), 3)
+            .await
+            .expect("page should list");
+        assert!(page.items.len() <= 3);
+        for item in &page.items {
+            assert!(!thumbnail::is_thumbnail_key(&item.key));
+            assert_eq!(item.is_prefix, item.key.ends_with('/'));
+        }
+        seen.extend(page.items.into_iter().map(|item| item.key));
+        pages += 1;
+        match page.next_token {
+            Some(next) => token = Some(next),
+            None => break,
+        }
+        assert!(pages < 10, "paging did not terminate");
+    }
+    seen.sort();
+
+    assert_eq!(seen, expected);
+    assert_eq!(pages, 3);
+}
+
+#[tokio::test]
+async fn final_page_has_no_continuation_token() {
+    let operator = memory_operator();
+    for key in ["docs/a.txt", "docs/b.txt"] {
+        operator
+            .write(key, vec![1])
+            .await
+            .expect("fixture should write");
+    }
+
+    let page = list_objects(&operator, "docs/", None, 2)
+        .await
+        .expect("listing should succeed");
+
+    assert_eq!(page.items.len(), 2);
+    assert!(page.next_token.is_none());
+}
+
+#[tokio::test]
+async fn malformed_or_foreign_continuation_tokens_are_rejected() {
+    let operator = memory_operator();
+
+    let malformed = list_objects(&operator, "docs/", Some("not base64!".into()), 10)
+        .await
+        .expect_err("malformed token must be rejected");
+    assert!(matches!(malformed.kind, ErrorKind::NotRetriable));
+
+    let foreign = list_objects(
+        &operator,
+        "docs/",
+        Some(encode_page_token("photos/a.jpg")),
+        10,
+    )
+    .await
+    .expect_err("token from another prefix must be rejected");
+    assert!(matches!(foreign.kind, ErrorKind::NotRetriable));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn moving_an_object_carries_its_thumbnail_to_the_new_key() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    operator
+        .write("inbox/IMG_1.jpg", vec![1, 2, 3])
+        .await
+        .expect("object fixture should write");
+    operator
+        .write(&thumbnail::thumbnail_key_for("inbox/IMG_1.jpg"), vec![9])
+        .await
+        .expect("thumbnail fixture should write");
+
+    let moved = move_object(&operator, "inbox/IMG_1.jpg", "2026/beach.jpg")
+        .await
+        .expect("move should succeed");
+
+    assert_eq!(moved, "2026/beach.jpg");
+    assert!(operator.stat("inbox/IMG_1.jpg").await.is_err());
+    assert!(operator
+        .stat(&thumbnail::thumbnail_key_for("inbox/IMG_1.jpg"))
+        .await
+        .is_err());
+    assert_eq!(
+        operator
+            .read("2026/beach.jpg")
+            .await
+            .expect("moved object should exist")
+            .to_vec(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(
+        operator
+            .read(&thumbnail::thumbnail_key_for("2026/beach.jpg"))
+            .await
+            .expect("thumbnail should follow the object")
+            .to_vec(),
+        vec![9]
+    );
+    assert!(server
+        .requests()
+        .iter()
+        .any(|request| request.header("x-amz-copy-source").is_some()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn copying_keeps_the_source_and_works_without_a_thumbnail() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    operator
+        .write("notes/todo.txt", b"milk".to_vec())
+        .await
+        .expect("object fixture should write");
+
+    copy_object(
+        &operator,
+        "default",
+        "notes/todo.txt",
+        "notes/todo copy.txt",
+    )
+    .await
+    .expect("copy should succeed");
+
+    assert!(operator.stat("notes/todo.txt").await.is_ok());
+    assert_eq!(
+        operator
+            .read("notes/todo copy.txt")
+            .await
+            .expect("copy should exist")
+            .to_vec(),
+        b"milk".to_vec()
+    );
+    let error = copy_object(&operator, "default", "notes/missing.txt", "notes/other.txt")
+        .await
+        .expect_err("missing source must fail");
+    assert!(matches!(error.kind, ErrorKind::NotFound));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_thumbnail_that_fails_to_copy_fails_the_move() {
+    use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+
+    let thumbnail_path = format!("/photos/{}", thumbnail::thumbnail_key_for("inbox/a.jpg"));
+    let server = LocalHttpServer::start(move |request| {
+        if request.path() == thumbnail_path {
+            StubResponse::new(403)
+        } else if request.method == "HEAD" {
+            StubResponse::new(200).header("content-length", "3")
+        } else {
+            StubResponse::new(200)
+                .body("<CopyObjectResult><ETag>\"copied\"</ETag></CopyObjectResult>")
+        }
+    });
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+
+    let error = move_object(&operator, "inbox/a.jpg", "2026/a.jpg")
+        .await
+        .expect_err("a refused thumbnail copy must not be ignored");
+
+    assert!(
+        matches!(error.kind, ErrorKind::PermissionDenied),
+        "{error:?}"
+    );
+    assert!(
+        server
+            .requests()
+            .iter()
+            .all(|request| request.method != "DELETE"),
+        "the source stays until everything is copied"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn moving_a_prefix_relocates_nested_objects_and_reports_progress() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    for key in [
+        "trip/a.jpg",
+        "trip/day1/b.jpg",
+        "trip/day2/c.jpg",
+        "tripod.txt",
+    ] {
+        operator
+            .write(key, vec![1])
+            .await
+            .expect("fixture should write");
+    }
+    let mut reports = Vec::new();
+
+    let moved = move_prefix(
+        &operator,
+        "op-1",
+        "trip",
+        "archive/trip/",
+        &AtomicBool::new(false),
+        |progress| reports.push((progress.processed, progress.total)),
+    )
+    .await
+    .expect("prefix move should succeed");
+
+    assert_eq!(moved.processed, 3);
+    assert_eq!(reports.first(), Some(&(0, 3)));
+    assert_eq!(reports.last(), Some(&(3, 3)));
+    for key in [
+        "archive/trip/a.jpg",
+        "archive/trip/day1/b.jpg",
+        "archive/trip/day2/c.jpg",
+        "tripod.txt",
+    ] {
+        assert!(operator.stat(key).await.is_ok(), "{key} should exist");
+    }
+    assert!(operator.stat("trip/a.jpg").await.is_err());
+}
+
+#[tokio::test]
+async fn prefix_moves_reject_self_nesting_and_analytics() {
+    let operator = memory_operator();
+
+    let nested = move_prefix(
+        &operator,
+        "op",
+        "trip/",
+        "trip/inner/",
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .await
+    .expect_err("moving a folder into itself must fail");
+    assert!(matches!(nested.kind, ErrorKind::NotRetriable));
+    let analytics = move_prefix(
+        &operator,
+        "op",
+        "analytics/",
+        "old-analytics/",
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .await
+    .expect_err("analytics must stay in place");
+    assert!(matches!(analytics.kind, ErrorKind::NotRetriable));
+}
+
+#[tokio::test]
+async fn empty_folders_survive_as_markers_in_both_listings() {
+    let operator = memory_operator();
+
+    let created = create_folder(&operator, "projects/empty")
+        .await
+        .expect("folder should be created");
+    operator
+        .write("projects/notes.txt", vec![1])
+        .await
+        .expect("fixture should write");
+
+    assert_eq!(created, "projects/empty/");
+    let page = list_objects(&operator, "projects/", None, 100)
+        .await
+        .expect("flat listing should succeed");
+    let keys = page
+        .items
+        .iter()
+        .map(|item| (item.key.as_str(), item.is_prefix))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        vec![("projects/empty/", true), ("projects/notes.txt", false)]
+    );
+    let all = list_all_objects(&operator, 100)
+        .await
+        .expect("recursive listing should succeed");
+    let marker = all
+        .iter()
+        .find(|item| item.key == "projects/empty/")
+        .expect("marker should be listed");
+    assert!(marker.is_prefix);
+    assert!(marker.thumbnail_key.is_none());
+}
+
+#[tokio::test]
+async fn folder_markers_on_the_analytics_path_are_protected() {
+    let operator = memory_operator();
+    for marker in ["analytics/", "analytics/daily/2026/", "projects/"] {
+        operator
+            .create_dir(marker)
+            .await
+            .expect("marker fixture should write");
+    }
+
+    let all = list_all_objects(&operator, 100)
+        .await
+        .expect("recursive listing should succeed");
+    let protected = |key: &str| {
+        all.iter()
+            .find(|item| item.key == key)
+            .expect("marker should be listed")
+            .protected
+    };
+    assert!(protected("analytics/"));
+    assert!(protected("analytics/daily/2026/"));
+    assert!(!protected("projects/"));
+
+    for (prefix, folder) in [
+        ("", "analytics/"),
+        ("analytics/daily/", "analytics/daily/2026/"),
+    ] {
+        let page = list_objects(&operator, prefix, None, 100)
+            .await
+            .expect("flat listing should succeed");
+        let entry = page
+            .items
+            .iter()
+            .find(|item| item.key == folder)
+            .expect("folder should be projected");
+        assert!(entry.protected, "{folder} should be protected");
+    }
+}
+
+#[tokio::test]
+async fn folder_names_are_validated_before_writing_a_marker() {
+    let operator = memory_operator();
+
+    for bad in ["", "/", "a//b", "a/../b", "__thumbnail__", "analytics"] {
+        assert!(
+            create_folder(&operator, bad).await.is_err(),
+            "{bad:?} should be rejected"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn moving_a_folder_carries_its_empty_subfolder_markers() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    create_folder(&operator, "trip/")
+        .await
+        .expect("folder should be created");
+    create_folder(&operator, "trip/unsorted/")
+        .await
+        .expect("subfolder should be created");
+    operator
+        .write("trip/a.jpg", vec![1])
+        .await
+        .expect("fixture should write");
+
+    let moved = move_prefix(
+        &operator,
+        "op-markers",
+        "trip/",
+        "2026/trip/",
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .await
+    .expect("prefix move should succeed");
+
+    assert_eq!(moved.processed, 3);
+    let all = list_all_objects(&operator, 100)
+        .await
+        .expect("listing should succeed");
+    let keys = all.iter().map(|item| item.key.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        vec!["2026/trip/", "2026/trip/a.jpg", "2026/trip/unsorted/"]
+    );
+}
+
+#[tokio::test]
+async fn stat_object_exposes_content_headers_and_custom_metadata() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    operator
+        .write_with("docs/report.pdf", b"%PDF".to_vec())
+        .content_type("application/pdf")
+        .content_disposition("attachment; filename=\"report.pdf\"")
+        .cache_control("no-cache")
+        .user_metadata([("Project".to_string(), "apollo".to_string())])
+        .await
+        .expect("fixture should write");
+
+    let metadata = stat_object(&operator, "docs/report.pdf")
+        .await
+        .expect("stat should succeed");
+
+    assert_eq!(metadata.size, 4);
+    assert!(metadata.etag.is_some());
+    assert_eq!(
+        metadata.headers.content_type.as_deref(),
+        Some("application/pdf")
+    );
+    assert_eq!(
+        metadata.headers.content_disposition.as_deref(),
+        Some("attachment; filename=\"report.pdf\"")
+    );
+    assert_eq!(metadata.headers.cache_control.as_deref(), Some("no-cache"));
+    assert_eq!(
+        metadata
+            .headers
+            .user_metadata
+            .get("project")
+            .map(String::as_str),
+        Some("apollo")
+    );
+}
+
+#[test]
+fn object_keys_must_be_legal_user_file_names() {
+    for key in ["a.jpg", "photos/2026/DSC00001.ARW", "notes/über café.txt"] {
+        assert!(validate_object_key(key).is_ok(), "{key} should be accepted");
+    }
+    let too_long = format!("deep/{}", "x".repeat(1024));
+    let thumbnail = thumbnail::thumbnail_key_for("a.jpg");
+    for key in [
+        "",
+        "folder/",
+        "/rooted.txt",
+        "a//b.txt",
+        "a/./b.txt",
+        "../escape.txt",
+        "line\nbreak.txt",
+        "analytics/daily/2026-07-27.json",
+        thumbnail.as_str(),
+        too_long.as_str(),
+    ] {
+        assert!(
+            validate_object_key(key).is_err(),
+            "{key:?} should be rejected"
+        );
+    }
+}
+
+#[test]
+fn metadata_updates_reject_invalid_names_values_and_protected_keys() {
+    let with_meta = |name: &str, value: &str| ObjectMetadataUpdate {
+        headers: ObjectHeaders {
+            user_metadata: [(name.to_string(), value.to_string())].into(),
+            ..Default::default()
+        },
+        expected_etag: None,
+    };
+
+    assert!(validate_metadata_update("a.jpg", &with_meta("camera", "x100v")).is_ok());
+    assert!(validate_metadata_update("a.jpg", &with_meta("Camera", "x")).is_err());
+    assert!(validate_metadata_update("a.jpg", &with_meta("x-amz-meta-camera", "x")).is_err());
+    assert!(validate_metadata_update("a.jpg", &with_meta("camera", "line\nbreak")).is_err());
+    assert!(validate_metadata_update("a.jpg", &with_meta("camera", &"x".repeat(2048))).is_err());
+    assert!(validate_metadata_update(
+        "analytics/daily/2026-07-27.json",
+        &ObjectMetadataUpdate::default()
+    )
+    .is_err());
+    assert!(validate_metadata_update(
+        &thumbnail::thumbnail_key_for("a.jpg"),
+        &ObjectMetadataUpdate::default()
+    )
+    .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn verify_object_rehashes_content_against_the_recorded_sha256() {
+    use sha2::{Digest, Sha256};
+
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    let contents = crate::test_support::patterned_bytes(9 * 1024 * 1024 + 3, 17);
+    let digest = format!("{:x}", Sha256::digest(&contents));
+    let other = format!("{:x}", Sha256::digest(b"something else"));
+    for (key, recorded) in [
+        ("intact.bin", Some(digest.to_uppercase())),
+        ("corrupt.bin", Some(other.clone())),
+        ("legacy.bin", None),
+    ] {
+        let write = operator.write_with(key, contents.clone());
+        match recorded {
+            Some(recorded) => write.user_metadata([(SHA256_METADATA_KEY.to_string(), recorded)]),
+            None => write,
+        }
+        .await
+        .expect("fixture should write");
+    }
+
+    let intact = verify_object(&operator, "intact.bin")
+        .await
+        .expect("verification should run");
+    let corrupt = verify_object(&operator, "corrupt.bin")
+        .await
+        .expect("verification should run");
+    let legacy = verify_object(&operator, "legacy.bin")
+        .await
+        .expect("verification should run");
+
+    assert_eq!(intact.size, contents.len() as u64);
+    assert_eq!(intact.computed_sha256, digest);
+    assert_eq!(intact.recorded_sha256.as_deref(), Some(digest.as_str()));
+    assert_eq!(intact.matches, Some(true));
+    assert_eq!(corrupt.recorded_sha256, Some(other));
+    assert_eq!(corrupt.matches, Some(false));
+    assert_eq!(legacy.recorded_sha256, None);
+    assert_eq!(legacy.matches, None);
+    assert!(matches!(
+        verify_object(&operator, "missing.bin")
+            .await
+            .map_err(|error| error.kind),
+        Err(ErrorKind::NotFound)
+    ));
+}
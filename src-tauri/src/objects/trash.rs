@@ -0,0 +1,217 @@
+//! Recycle bin under the hidden `__trash__/` prefix.
+//!
+//! Trashing is a server-side move to `__trash__/<deleted_at_ms>/<original_key>`,
+//! so the key alone records where an object came from and when it left. Its
+//! thumbnail moves along and comes back on restore. A folder goes file by
+//! file under one deletion time, and its markers are dropped. The bytes stay
+//! stored while trashed: trash and restore record no storage delta, and only
+//! a purge counts as deleted storage.
+
+use super::relocate::{relocate, PREFIX_PROGRESS_EVERY};
+use super::{
+    delete_object, list_prefix_objects, now_ms, remove_one, validate_delete_key,
+    validate_folder_prefix,
+};
+use crate::types::{err_invalid, ErrorKind, PrefixOpProgress, SpError, SpResult, TrashEntry};
+use crate::{object_index, storage, thumbnail};
+use futures::TryStreamExt;
+use opendal::Operator;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const TRASH_PREFIX: &str = "__trash__/";
+
+pub fn is_trash_key(key: &str) -> bool {
+    key.starts_with(TRASH_PREFIX)
+}
+
+fn trash_key_for(key: &str, deleted_at_ms: i64) -> String {
+    format!("{TRASH_PREFIX}{deleted_at_ms:013}/{key}")
+}
+
+fn parse_trash_key(trash_key: &str) -> Option<(i64, &str)> {
+    let (deleted_at, original_key) = trash_key.strip_prefix(TRASH_PREFIX)?.split_once('/')?;
+    let deleted_at_ms = deleted_at.parse().ok()?;
+    (!original_key.is_empty()).then_some((deleted_at_ms, original_key))
+}
+
+/// Moves a file into the recycle bin instead of deleting it.
+pub async fn trash_object(operator: &Operator, key: &str) -> SpResult<TrashEntry> {
+    validate_delete_key(key)?;
+    if key.is_empty() || key.ends_with('/') {
+        return Err(err_invalid("only files can be moved to the recycle bin"));
+    }
+    if thumbnail::is_thumbnail_key(key) || is_trash_key(key) {
+        return Err(err_invalid("this object is managed automatically"));
+    }
+    let deleted_at_ms = now_ms();
+    let trash_key = trash_key_for(key, deleted_at_ms);
+    let (size, _) = relocate(operator, key, &trash_key, true).await?;
+    crate::logger::info("objects", &format!("trashed {key} as {trash_key}"));
+    Ok(TrashEntry {
+        trash_key,
+        original_key: key.to_string(),
+        deleted_at_ms,
+        size,
+    })
+}
+
+/// Moves every file under `prefix` into the recycle bin under one deletion
+/// time, then removes the folder markers, which hold no data. Returns the
+/// final progress report and the entries trashed; `cancel` is checked
+/// between objects.
+pub async fn trash_prefix(
+    operator: &Operator,
+    op_id: &str,
+    prefix: &str,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&PrefixOpProgress),
+) -> SpResult<(PrefixOpProgress, Vec<TrashEntry>)> {
+    let prefix = validate_folder_prefix(prefix)?;
+    validate_delete_key(&prefix)?;
+
+    let (markers, files): (Vec<_>, Vec<_>) = list_prefix_objects(operator, &prefix)
+        .await?
+        .into_iter()
+        .partition(|(key, _)| key.ends_with('/'));
+    let total = (files.len() + markers.len()) as u64;
+    let mut progress = PrefixOpProgress {
+        op_id: op_id.to_string(),
+        op: "delete_prefix".into(),
+        prefix: prefix.clone(),
+        processed: 0,
+        total,
+        current_key: None,
+        cancelled: false,
+    };
+    on_progress(&progress);
+    let deleted_at_ms = now_ms();
+    let mut entries = Vec::with_capacity(files.len());
+    // Markers go last so a cancelled folder still shows what is left in it.
+    for (key, _) in files.into_iter().chain(markers.into_iter().rev()) {
+        if cancel.load(Ordering::Relaxed) {
+            progress.cancelled = true;
+            on_progress(&progress);
+            break;
+        }
+        if key.ends_with('/') {
+            remove_one(operator, &key).await?;
+        } else {
+            let trash_key = trash_key_for(&key, deleted_at_ms);
+            let (size, _) = relocate(operator, &key, &trash_key, true).await?;
+            entries.push(TrashEntry {
+                trash_key,
+                original_key: key.clone(),
+                deleted_at_ms,
+                size,
+            });
+        }
+        progress.processed += 1;
+        if progress.processed % PREFIX_PROGRESS_EVERY == 0 || progress.processed == total {
+            progress.current_key = Some(key);
+            on_progress(&progress);
+        }
+    }
+    crate::logger::info(
+        "objects",
+        &format!(
+            "trashed prefix={prefix} moved={}/{total}",
+            progress.processed
+        ),
+    );
+    Ok((progress, entries))
+}
+
+/// Every trashed object, most recently deleted first.
+pub async fn list_trash(operator: &Operator) -> SpResult<Vec<TrashEntry>> {
+    let list_error =
+        |error: opendal::Error| storage::opendal_error("list trash", TRASH_PREFIX, &error);
+    let mut entries = Vec::new();
+    let mut lister = operator
+        .lister_with(TRASH_PREFIX)
+        .recursive(true)
+        .await
+        .map_err(list_error)?;
+    while let Some(entry) = lister.try_next().await.map_err(list_error)? {
+        let Some((deleted_at_ms, original_key)) = parse_trash_key(entry.path()) else {
+            continue;
+        };
+        entries.push(TrashEntry {
+            trash_key: entry.path().to_string(),
+            original_key: original_key.to_string(),
+            deleted_at_ms,
+            size: entry.metadata().content_length(),
+        });
+    }
+    entries.sort_by(|left, right| {
+        right
+            .deleted_at_ms
+            .cmp(&left.deleted_at_ms)
+            .then_with(|| left.original_key.cmp(&right.original_key))
+    });
+    Ok(entries)
+}
+
+/// Moves a trashed object back to its original key and returns that key.
+/// Refuses to overwrite an object that has since been written there.
+pub async fn restore_trashed(operator: &Operator, trash_key: &str) -> SpResult<String> {
+    let (_, original_key) =
+        parse_trash_key(trash_key).ok_or_else(|| err_invalid("not a recycle bin key"))?;
+    let occupied = operator
+        .exists(original_key)
+        .await
+        .map_err(|error| storage::opendal_error("stat", original_key, &error))?;
+    if occupied {
+        return Err(SpError {
+            kind: ErrorKind::Conflict,
+            message: format!("{original_key} already exists; move or delete it first"),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        });
+    }
+    relocate(operator, trash_key, original_key, true).await?;
+    crate::logger::info("objects", &format!("restored {original_key} from trash"));
+    Ok(original_key.to_string())
+}
+
+/// Permanently deletes trashed objects deleted before `cutoff_ms` from the
+/// bucket of `profile` and returns them.
+pub async fn purge_trash(
+    operator: &Operator,
+    profile: &str,
+    cutoff_ms: i64,
+) -> SpResult<Vec<TrashEntry>> {
+    let mut purged = Vec::new();
+    for entry in list_trash(operator).await? {
+        if entry.deleted_at_ms >= cutoff_ms {
+            continue;
+        }
+        delete_object(operator, profile, &entry.trash_key).await?;
+        purged.push(entry);
+    }
+    if !purged.is_empty() {
+        crate::logger::info(
+            "objects",
+            &format!("purged {} trashed object(s)", purged.len()),
+        );
+    }
+    Ok(purged)
+}
+
+/// Purges entries deleted more than `days` ago from the bucket and from the
+/// profile's object index; `0` empties the recycle bin.
+pub async fn purge_trash_older_than(
+    operator: &Operator,
+    profile: &str,
+    days: u32,
+) -> SpResult<Vec<TrashEntry>> {
+    let cutoff_ms = now_ms() - i64::from(days) * 86_400_000;
+    let purged = purge_trash(operator, profile, cutoff_ms).await?;
+    for entry in &purged {
+        let _ = object_index::forget_object(profile, &entry.trash_key);
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests;
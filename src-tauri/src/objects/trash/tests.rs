@@ -0,0 +1,185 @@
+use super::*;
+use crate::objects::{list_all_objects, list_objects};
+use crate::test_support::{stand_in_operator, start_path_style_s3};
+use std::sync::atomic::AtomicBool;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trashed_objects_are_hidden_and_restore_with_their_thumbnail() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    operator
+        .write("trip/a.jpg", vec![1, 2, 3])
+        .await
+        .expect("fixture should write");
+    operator
+        .write(&thumbnail::thumbnail_key_for("trip/a.jpg"), vec![9])
+        .await
+        .expect("thumbnail fixture should write");
+
+    let entry = trash_object(&operator, "trip/a.jpg")
+        .await
+        .expect("trash should succeed");
+    assert_eq!(entry.original_key, "trip/a.jpg");
+    assert_eq!(entry.size, 3);
+    assert!(entry.trash_key.starts_with(TRASH_PREFIX));
+    assert!(!operator.exists("trip/a.jpg").await.expect("stat"));
+    assert!(operator
+        .exists(&thumbnail::thumbnail_key_for(&entry.trash_key))
+        .await
+        .expect("stat"));
+
+    let root = list_objects(&operator, "", None, 100)
+        .await
+        .expect("list should succeed");
+    assert!(root.items.iter().all(|item| !is_trash_key(&item.key)));
+    let everything = list_all_objects(&operator, 100)
+        .await
+        .expect("recursive list should succeed");
+    assert!(everything.iter().all(|item| !is_trash_key(&item.key)));
+    assert_eq!(
+        list_trash(&operator).await.expect("trash should list"),
+        vec![entry.clone()]
+    );
+
+    let restored = restore_trashed(&operator, &entry.trash_key)
+        .await
+        .expect("restore should succeed");
+    assert_eq!(restored, "trip/a.jpg");
+    assert_eq!(
+        operator.read("trip/a.jpg").await.expect("read").to_vec(),
+        vec![1, 2, 3]
+    );
+    assert!(operator
+        .exists(&thumbnail::thumbnail_key_for("trip/a.jpg"))
+        .await
+        .expect("stat"));
+    assert!(list_trash(&operator).await.expect("list").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restore_refuses_to_overwrite_a_newer_object() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    operator.write("a.txt", vec![1]).await.expect("write");
+    let entry = trash_object(&operator, "a.txt").await.expect("trash");
+    operator.write("a.txt", vec![2, 2]).await.expect("write");
+
+    let error = restore_trashed(&operator, &entry.trash_key)
+        .await
+        .expect_err("occupied key should not be overwritten");
+    assert!(matches!(error.kind, ErrorKind::Conflict));
+    assert_eq!(
+        operator.read("a.txt").await.expect("read").to_vec(),
+        vec![2, 2]
+    );
+    assert_eq!(list_trash(&operator).await.expect("list").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn purge_removes_only_entries_older_than_the_cutoff() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    for (key, deleted_at_ms) in [("old.txt", 1_000), ("new.txt", 5_000)] {
+        let trash_key = trash_key_for(key, deleted_at_ms);
+        operator.write(&trash_key, vec![1]).await.expect("write");
+        operator
+            .write(&thumbnail::thumbnail_key_for(&trash_key), vec![9])
+            .await
+            .expect("write");
+    }
+
+    let purged = purge_trash(&operator, "default", 2_000)
+        .await
+        .expect("purge");
+    assert_eq!(
+        purged
+            .iter()
+            .map(|entry| entry.original_key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["old.txt"]
+    );
+    let remaining = list_trash(&operator).await.expect("list");
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].original_key, "new.txt");
+    assert!(!operator
+        .exists(&thumbnail::thumbnail_key_for(&trash_key_for(
+            "old.txt", 1_000
+        )))
+        .await
+        .expect("stat"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trashing_a_folder_moves_its_files_and_drops_its_markers() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    for key in ["trip/a.jpg", "trip/day2/b.jpg", "other.jpg"] {
+        operator.write(key, vec![1, 2]).await.expect("write");
+    }
+    operator
+        .write(&thumbnail::thumbnail_key_for("trip/a.jpg"), vec![9])
+        .await
+        .expect("thumbnail fixture should write");
+    operator.create_dir("trip/day2/").await.expect("marker");
+
+    let cancelled = AtomicBool::new(true);
+    let (progress, entries) = trash_prefix(&operator, "op-0", "trip", &cancelled, |_| {})
+        .await
+        .expect("a cancelled trash should still report");
+    assert!(progress.cancelled);
+    assert!(entries.is_empty());
+    assert!(operator.exists("trip/a.jpg").await.expect("stat"));
+
+    let mut reports = 0;
+    let (progress, entries) =
+        trash_prefix(&operator, "op-1", "trip/", &AtomicBool::new(false), |_| {
+            reports += 1
+        })
+        .await
+        .expect("the folder should move to the recycle bin");
+    assert!(reports >= 2);
+    assert_eq!(progress.processed, progress.total);
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.original_key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["trip/a.jpg", "trip/day2/b.jpg"]
+    );
+    assert!(entries
+        .iter()
+        .all(|entry| entry.deleted_at_ms == entries[0].deleted_at_ms));
+    assert!(!operator.exists("trip/a.jpg").await.expect("stat"));
+    assert!(!operator.exists("trip/day2/").await.expect("stat"));
+    assert!(operator.exists("other.jpg").await.expect("stat"));
+    assert!(operator
+        .exists(&thumbnail::thumbnail_key_for(&entries[0].trash_key))
+        .await
+        .expect("stat"));
+    assert_eq!(list_trash(&operator).await.expect("list").len(), 2);
+
+    restore_trashed(&operator, &entries[1].trash_key)
+        .await
+        .expect("a file from a trashed folder should restore");
+    assert_eq!(
+        operator
+            .read("trip/day2/b.jpg")
+            .await
+            .expect("read")
+            .to_vec(),
+        vec![1, 2]
+    );
+}
+
+#[test]
+fn trash_keys_round_trip_and_reject_foreign_keys() {
+    let trash_key = trash_key_for("a/b/c.txt", 1_700_000_000_000);
+    assert_eq!(trash_key, "__trash__/1700000000000/a/b/c.txt");
+    assert_eq!(
+        parse_trash_key(&trash_key),
+        Some((1_700_000_000_000, "a/b/c.txt"))
+    );
+    assert_eq!(parse_trash_key("__trash__/soon/a.txt"), None);
+    assert_eq!(parse_trash_key("__trash__/1700000000000/"), None);
+    assert_eq!(parse_trash_key("a.txt"), None);
+}
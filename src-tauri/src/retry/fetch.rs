@@ -0,0 +1,103 @@
+//! Request-level retries for OpenDAL's HTTP client.
+
+use super::{cancel_flag, parse_retry_after, sleep_unless_cancelled, RetryPolicy};
+use crate::types::ErrorKind;
+use opendal::raw::{HttpBody, HttpFetch};
+use opendal::{Buffer, Error, ErrorKind as OdErrorKind, Result as OdResult};
+
+/// Wraps another fetcher and resends requests that failed in transit or were
+/// answered with a status that [`crate::storage::kind_for_status`] deems
+/// transient. Only requests [`RetryPolicy::for_request`] deems safe to resend
+/// are retried, and a backoff wait ends early once the transfer the request
+/// belongs to is cancelled. Every attempt goes through the inner fetcher, so
+/// each one is counted in the usage ledger. The status of the final failed
+/// response is noted for error classification.
+pub(crate) struct RetryingFetch<F> {
+    inner: F,
+    policy: RetryPolicy,
+}
+
+impl<F> RetryingFetch<F> {
+    pub(crate) fn new(inner: F, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<F: HttpFetch> HttpFetch for RetryingFetch<F> {
+    async fn fetch(&self, req: http::Request<Buffer>) -> OdResult<http::Response<HttpBody>> {
+        let (parts, body) = req.into_parts();
+        let policy = self.policy.for_request(
+            &parts.method,
+            parts.headers.keys().map(http::HeaderName::as_str),
+        );
+        let cancelled = cancel_flag();
+        let mut failed_attempts = 0;
+        loop {
+            let mut attempt = http::Request::builder()
+                .method(parts.method.clone())
+                .uri(parts.uri.clone())
+                .version(parts.version)
+                .body(body.clone())
+                .map_err(|err| {
+                    Error::new(OdErrorKind::Unexpected, "rebuild http request for retry")
+                        .set_source(err)
+                })?;
+            *attempt.headers_mut() = parts.headers.clone();
+            let outcome = self.inner.fetch(attempt).await;
+            failed_attempts += 1;
+            let delay = match &outcome {
+                Ok(response) if !response.status().is_success() => {
+                    let kind = crate::storage::kind_for_status(response.status().as_u16());
+                    let retry_after = response
+                        .headers()
+                        .get(http::header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(parse_retry_after);
+                    match kind {
+                        ErrorKind::RetryableNet | ErrorKind::RateLimited => {
+                            policy.delay_for(failed_attempts, &kind, retry_after)
+                        }
+                        _ => None,
+                    }
+                }
+                Ok(_) => None,
+                Err(err) if err.is_temporary() => {
+                    policy.delay_for(failed_attempts, &ErrorKind::RetryableNet, None)
+                }
+                Err(_) => None,
+            };
+            let Some(delay) = delay else {
+                note_failed_status(&outcome);
+                return outcome;
+            };
+            crate::logger::warn(
+                "retry",
+                &format!(
+                    "{} {} failed (attempt {failed_attempts}/{}): {}; retrying in {} ms",
+                    parts.method,
+                    parts.uri.path(),
+                    policy.max_attempts,
+                    match &outcome {
+                        Ok(response) => format!("HTTP {}", response.status()),
+                        Err(err) => err.to_string(),
+                    },
+                    delay.as_millis()
+                ),
+            );
+            // The caller sees the last failure and its own cancel flag.
+            if !sleep_unless_cancelled(delay, &cancelled).await {
+                note_failed_status(&outcome);
+                return outcome;
+            }
+        }
+    }
+}
+
+/// Notes the status of a failed final response for
+/// [`crate::storage::opendal_error`].
+fn note_failed_status(outcome: &OdResult<http::Response<HttpBody>>) {
+    crate::storage::note_response(match outcome {
+        Ok(response) if !response.status().is_success() => Some(response.status().as_u16()),
+        _ => None,
+    });
+}
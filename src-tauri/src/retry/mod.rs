@@ -0,0 +1,258 @@
+//! Retry and backoff policy for storage requests.
+//!
+//! This module owns the single decision of whether and when a failed storage
+//! call is tried again: exponential backoff with jitter, the error kinds worth
+//! retrying, server `Retry-After` hints and the attempt limit from the
+//! network settings. It is applied at two levels: [`RetryingFetch`] retries
+//! every HTTP request an operator sends, so each multipart part and listing
+//! page is retried on its own, and [`with_retry`] lets transfer engines retry
+//! a whole step such as a ranged chunk read whose body failed mid-stream.
+//! Only one level retries a given call: requests run inside
+//! [`retrying_steps`] are sent once. Requests that are unsafe to resend,
+//! such as a POST or a conditional PUT, are never retried at the request
+//! level. It must not know what the retried operation does.
+
+use crate::settings::NetworkSettings;
+use crate::types::{ErrorKind, SpError, SpResult};
+use rand::Rng;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod fetch;
+
+pub(crate) use fetch::RetryingFetch;
+
+/// Upper bound on an honoured server `Retry-After`; longer hints are
+/// shortened so a transfer never sleeps for hours.
+const MAX_SERVER_DELAY: Duration = Duration::from_secs(60);
+/// Authentication failures rarely heal by waiting; they get one retry, for
+/// clock-skew or token-refresh races.
+const MAX_AUTH_ATTEMPTS: u32 = 2;
+const CANCEL_POLL: Duration = Duration::from_millis(200);
+
+tokio::task_local! {
+    static SCOPE: RetryScope;
+}
+
+/// The transfer a task's storage requests belong to.
+#[derive(Clone)]
+struct RetryScope {
+    cancelled: Arc<AtomicBool>,
+    /// The caller retries whole steps with [`with_retry`].
+    steps_retried: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first one; 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_settings(network: &NetworkSettings) -> Self {
+        Self {
+            max_attempts: network.retry_max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// Wait before the next attempt after `failed_attempts` failures, or
+    /// `None` when `error` must be returned to the caller.
+    pub fn delay_after(&self, failed_attempts: u32, error: &SpError) -> Option<Duration> {
+        self.delay_for(
+            failed_attempts,
+            &error.kind,
+            error.retry_after_ms.map(Duration::from_millis),
+        )
+    }
+
+    /// Like [`Self::delay_after`] for failures that are not yet an
+    /// [`SpError`]. `retry_after` is a minimum wait requested by the server
+    /// or the call site.
+    pub fn delay_for(
+        &self,
+        failed_attempts: u32,
+        kind: &ErrorKind,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if failed_attempts >= self.max_attempts {
+            return None;
+        }
+        match kind {
+            ErrorKind::RetryableNet | ErrorKind::RateLimited => {}
+            ErrorKind::RetryableAuth if failed_attempts < MAX_AUTH_ATTEMPTS => {}
+            _ => return None,
+        }
+        let backoff = self.backoff(failed_attempts);
+        Some(backoff.max(retry_after.unwrap_or_default().min(MAX_SERVER_DELAY)))
+    }
+
+    /// This policy for one HTTP request, or a single attempt when the request
+    /// is unsafe to resend or the caller retries the whole step.
+    pub(crate) fn for_request<'a>(
+        &self,
+        method: &http::Method,
+        mut header_names: impl Iterator<Item = &'a str>,
+    ) -> Self {
+        let idempotent = [
+            http::Method::GET,
+            http::Method::HEAD,
+            http::Method::PUT,
+            http::Method::DELETE,
+        ]
+        .contains(method);
+        // A resent conditional write can fail its own precondition once the
+        // first attempt landed unseen.
+        let replayable = idempotent && !header_names.any(is_conditional_header);
+        if replayable && !current_scope().is_some_and(|scope| scope.steps_retried) {
+            *self
+        } else {
+            Self {
+                max_attempts: 1,
+                ..*self
+            }
+        }
+    }
+
+    /// Exponential backoff with "equal jitter": half of the step is fixed,
+    /// the other half random, so concurrent parts do not retry in lockstep.
+    fn backoff(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(16);
+        let step = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        let half = step / 2;
+        let jitter_ms = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// Runs `future` as part of a transfer: request-level backoff waits inside it
+/// end as soon as `cancelled` is set.
+pub async fn watching_cancel<F: Future>(cancelled: Arc<AtomicBool>, future: F) -> F::Output {
+    let scope = RetryScope {
+        cancelled,
+        steps_retried: false,
+    };
+    SCOPE.scope(scope, future).await
+}
+
+/// Like [`watching_cancel`] for callers that retry whole steps with
+/// [`with_retry`]; requests inside are sent once, so attempts do not
+/// multiply.
+pub async fn retrying_steps<F: Future>(cancelled: Arc<AtomicBool>, future: F) -> F::Output {
+    let scope = RetryScope {
+        cancelled,
+        steps_retried: true,
+    };
+    SCOPE.scope(scope, future).await
+}
+
+/// The cancel flag of the transfer this task runs for, or one that is never
+/// set outside a transfer.
+pub(crate) fn cancel_flag() -> Arc<AtomicBool> {
+    current_scope().map_or_else(|| Arc::new(AtomicBool::new(false)), |scope| scope.cancelled)
+}
+
+fn current_scope() -> Option<RetryScope> {
+    SCOPE.try_with(RetryScope::clone).ok()
+}
+
+fn is_conditional_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("if-") || name.starts_with("x-amz-copy-source-if-")
+}
+
+/// Runs `attempt` until it succeeds, fails with an error the policy does not
+/// retry, or runs out of attempts. Returns a `Cancelled` error as soon as
+/// `cancelled` is set, during a backoff wait or when an attempt fails after
+/// it.
+pub async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    operation: &str,
+    cancelled: &AtomicBool,
+    mut attempt: F,
+) -> SpResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = SpResult<T>>,
+{
+    let mut failed_attempts = 0;
+    loop {
+        let error = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(cancelled_error());
+        }
+        failed_attempts += 1;
+        let Some(delay) = policy.delay_after(failed_attempts, &error) else {
+            return Err(error);
+        };
+        crate::logger::warn(
+            "retry",
+            &format!(
+                "{operation} failed (attempt {failed_attempts}/{}): {}; retrying in {} ms",
+                policy.max_attempts,
+                error.message,
+                delay.as_millis()
+            ),
+        );
+        if !sleep_unless_cancelled(delay, cancelled).await {
+            return Err(cancelled_error());
+        }
+    }
+}
+
+fn cancelled_error() -> SpError {
+    SpError {
+        kind: ErrorKind::Cancelled,
+        message: "cancelled".into(),
+        retry_after_ms: None,
+        context: None,
+        at: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+/// Parses a `Retry-After` header given in seconds or as an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait_ms = at.timestamp_millis() - chrono::Utc::now().timestamp_millis();
+    Some(Duration::from_millis(wait_ms.max(0) as u64))
+}
+
+async fn sleep_unless_cancelled(delay: Duration, cancelled: &AtomicBool) -> bool {
+    let mut remaining = delay;
+    while !remaining.is_zero() {
+        if cancelled.load(Ordering::Relaxed) {
+            return false;
+        }
+        let step = remaining.min(CANCEL_POLL);
+        tokio::time::sleep(step).await;
+        remaining -= step;
+    }
+    !cancelled.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests;
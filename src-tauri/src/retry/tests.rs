@@ -0,0 +1,278 @@
+use super::*;
+use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+use crate::test_support::{instant_retries, stand_in_operator};
+use opendal::layers::HttpClientLayer;
+use opendal::raw::HttpClient;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+
+fn error(kind: ErrorKind, retry_after_ms: Option<u64>) -> SpError {
+    SpError {
+        kind,
+        message: "boom".into(),
+        retry_after_ms,
+        context: None,
+        at: 0,
+    }
+}
+
+#[test]
+fn backoff_grows_with_jitter_and_stays_under_the_cap() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+    for (failed_attempts, step_ms) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (9, 1000)]
+    {
+        for _ in 0..20 {
+            let delay = policy
+                .delay_after(failed_attempts, &error(ErrorKind::RetryableNet, None))
+                .expect("network errors are retried");
+            assert!(delay >= Duration::from_millis(step_ms / 2), "{delay:?}");
+            assert!(delay <= Duration::from_millis(step_ms), "{delay:?}");
+        }
+    }
+}
+
+#[test]
+fn only_transient_kinds_are_retried_and_auth_only_once() {
+    let policy = RetryPolicy::default();
+
+    for kind in [
+        ErrorKind::NotRetriable,
+        ErrorKind::Cancelled,
+        ErrorKind::SourceChanged,
+        ErrorKind::DiskFull,
+        ErrorKind::TaskExists,
+        ErrorKind::NotImplemented,
+    ] {
+        assert_eq!(policy.delay_after(1, &error(kind, Some(500))), None);
+    }
+    assert!(policy
+        .delay_after(1, &error(ErrorKind::RetryableAuth, None))
+        .is_some());
+    assert!(policy
+        .delay_after(2, &error(ErrorKind::RetryableAuth, None))
+        .is_none());
+    assert!(policy
+        .delay_after(3, &error(ErrorKind::RetryableNet, None))
+        .is_some());
+    assert!(policy
+        .delay_after(4, &error(ErrorKind::RetryableNet, None))
+        .is_none());
+}
+
+#[test]
+fn retry_after_hints_are_a_capped_minimum_wait() {
+    let policy = RetryPolicy::default();
+
+    assert_eq!(
+        policy.delay_after(1, &error(ErrorKind::RetryableNet, Some(5_000))),
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(
+        policy.delay_after(1, &error(ErrorKind::RetryableNet, Some(3_600_000))),
+        Some(MAX_SERVER_DELAY)
+    );
+    assert_eq!(
+        RetryPolicy::from_settings(&NetworkSettings {
+            retry_max_attempts: 0,
+            ..NetworkSettings::default()
+        })
+        .max_attempts,
+        1
+    );
+}
+
+#[test]
+fn retry_after_headers_parse_as_seconds_or_http_dates() {
+    assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(Duration::ZERO)
+    );
+    let soon = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+    let wait = parse_retry_after(&soon).expect("future date should parse");
+    assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+    assert_eq!(parse_retry_after("soon"), None);
+}
+
+#[tokio::test]
+async fn with_retry_recovers_from_transient_failures_and_stops_on_permanent_ones() {
+    let calls = AtomicUsize::new(0);
+    let value = with_retry(&instant_retries(), "flaky", &AtomicBool::new(false), || {
+        let call = calls.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if call < 2 {
+                Err(error(ErrorKind::RetryableNet, Some(1)))
+            } else {
+                Ok(call)
+            }
+        }
+    })
+    .await
+    .expect("third attempt should succeed");
+    assert_eq!(value, 2);
+
+    calls.store(0, Ordering::SeqCst);
+    let failure = with_retry(
+        &instant_retries(),
+        "denied",
+        &AtomicBool::new(false),
+        || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err::<(), _>(error(ErrorKind::NotRetriable, None)) }
+        },
+    )
+    .await
+    .expect_err("permanent errors are returned");
+    assert!(matches!(failure.kind, ErrorKind::NotRetriable));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    calls.store(0, Ordering::SeqCst);
+    with_retry(&instant_retries(), "down", &AtomicBool::new(false), || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        async { Err::<(), _>(error(ErrorKind::RetryableNet, None)) }
+    })
+    .await
+    .expect_err("retries run out");
+    assert_eq!(
+        calls.load(Ordering::SeqCst),
+        instant_retries().max_attempts as usize
+    );
+}
+
+fn retrying_operator(server: &LocalHttpServer) -> opendal::Operator {
+    stand_in_operator(server, "photos").layer(HttpClientLayer::new(HttpClient::with(
+        RetryingFetch::new(reqwest::Client::new(), instant_retries()),
+    )))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn throttled_requests_are_resent_after_retry_after() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let server = LocalHttpServer::start(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            StubResponse::new(503)
+                .header("retry-after", "0")
+                .body("<Error><Code>SlowDown</Code></Error>")
+        } else {
+            StubResponse::new(200).body("hello")
+        }
+    });
+
+    let body = retrying_operator(&server)
+        .read("cover.jpg")
+        .await
+        .expect("second attempt should succeed");
+
+    assert_eq!(body.to_vec(), b"hello");
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|request| request.method == "GET"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_errors_are_not_resent() {
+    let server = LocalHttpServer::start(|_| {
+        StubResponse::new(403).body("<Error><Code>AccessDenied</Code></Error>")
+    });
+
+    retrying_operator(&server)
+        .read("cover.jpg")
+        .await
+        .expect_err("403 must surface");
+
+    assert_eq!(server.requests().len(), 1);
+}
+
+fn unavailable_server() -> LocalHttpServer {
+    LocalHttpServer::start(|_| StubResponse::new(503).header("retry-after", "0"))
+}
+
+async fn fetch_through_retries(
+    server: &LocalHttpServer,
+    method: http::Method,
+    header: Option<(&str, &str)>,
+) -> u16 {
+    use opendal::raw::HttpFetch;
+    let mut request = http::Request::builder()
+        .method(method)
+        .uri(format!("{}/photos/cover.jpg", server.url()));
+    if let Some((name, value)) = header {
+        request = request.header(name, value);
+    }
+    let request = request
+        .body(opendal::Buffer::from("body"))
+        .expect("request should build");
+    RetryingFetch::new(reqwest::Client::new(), instant_retries())
+        .fetch(request)
+        .await
+        .expect("the stand-in should answer")
+        .status()
+        .as_u16()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn only_idempotent_unconditional_requests_are_resent() {
+    let attempts = instant_retries().max_attempts as usize;
+    for (method, header, sent) in [
+        (http::Method::PUT, None, attempts),
+        (http::Method::GET, None, attempts),
+        (http::Method::PUT, Some(("if-none-match", "*")), 1),
+        (http::Method::PUT, Some(("If-Match", "\"etag\"")), 1),
+        (http::Method::POST, None, 1),
+    ] {
+        let server = unavailable_server();
+        let status = fetch_through_retries(&server, method.clone(), header).await;
+        assert_eq!(status, 503);
+        assert_eq!(server.requests().len(), sent, "{method} {header:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn requests_are_sent_once_where_the_caller_retries_steps() {
+    let server = unavailable_server();
+
+    let status = retrying_steps(
+        Arc::new(AtomicBool::new(false)),
+        fetch_through_retries(&server, http::Method::GET, None),
+    )
+    .await;
+
+    assert_eq!(status, 503);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_cancelled_transfer_stops_waiting_for_a_throttled_request() {
+    let server = LocalHttpServer::start(|_| StubResponse::new(503).header("retry-after", "60"));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let canceller = cancelled.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        canceller.store(true, Ordering::SeqCst);
+    });
+    let started = std::time::Instant::now();
+
+    let status = watching_cancel(
+        cancelled.clone(),
+        fetch_through_retries(&server, http::Method::GET, None),
+    )
+    .await;
+
+    assert_eq!(status, 503);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(server.requests().len(), 1);
+    let failure = watching_cancel(cancelled, async {
+        with_retry(&instant_retries(), "cancelled", &cancel_flag(), || async {
+            Err::<(), _>(error(ErrorKind::RetryableNet, None))
+        })
+        .await
+    })
+    .await
+    .expect_err("a cancelled step fails");
+    assert!(matches!(failure.kind, ErrorKind::Cancelled));
+}
@@ -13,13 +13,113 @@ pub struct AppSettings {
     pub max_concurrency: u32,
     pub default_download_dir: Option<String>,
     pub upload_thumbnail: bool,
+    /// Parts of a single upload sent at the same time. Each one holds a
+    /// part-sized buffer in memory.
+    #[serde(default = "default_per_task_parts")]
+    pub per_task_parts: u8,
     // Android only: persisted Storage Access Framework Tree-URI
     pub android_tree_uri: Option<String>,
+    /// Move deleted files to the recycle bin instead of deleting them.
+    #[serde(default)]
+    pub recycle_bin: bool,
+    /// Trashed files older than this many days are purged.
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+    /// Proxy, timeouts and extra trust roots for storage traffic.
+    #[serde(default)]
+    pub network: NetworkSettings,
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
+}
+
+fn default_per_task_parts() -> u8 {
+    4
+}
+
+impl AppSettings {
+    /// The configured limits, with every limit at least one.
+    pub fn concurrency_limits(&self) -> ConcurrencyLimits {
+        ConcurrencyLimits {
+            per_task_parts: self.per_task_parts.max(1),
+            global_active_tasks: self.max_concurrency.clamp(1, u8::MAX.into()) as u8,
+        }
+    }
+}
+
+/// How the storage HTTP client reaches the bucket. Applied to every client
+/// built by `storage`; changing it rebuilds cached operators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+    /// `http://` or `https://` proxy for all storage requests. Without one,
+    /// the `HTTPS_PROXY`/`HTTP_PROXY` environment variables still apply.
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    /// Kept in the settings file, not in the encrypted credential vault.
+    pub proxy_password: Option<String>,
+    /// Hosts, domains (`.example.com`) or CIDR ranges that bypass the proxy.
+    pub no_proxy: Vec<String>,
+    /// 0 disables the timeout.
+    pub connect_timeout_secs: u64,
+    /// Longest wait for the server between reads. It also covers sending
+    /// the request body, so it must exceed the time to upload one part.
+    /// 0 disables the timeout.
+    pub read_timeout_secs: u64,
+    /// PEM-encoded certificates trusted in addition to the built-in roots,
+    /// e.g. a TLS-inspection CA. One entry may hold several certificates.
+    pub extra_root_pems: Vec<String>,
+    /// Attempts per storage request or transfer chunk, including the first.
+    pub retry_max_attempts: u32,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            no_proxy: Vec::new(),
+            connect_timeout_secs: 30,
+            read_timeout_secs: 0,
+            extra_root_pems: Vec::new(),
+            retry_max_attempts: 4,
+        }
+    }
+}
+
+impl Display for NetworkSettings {
+    // Proxy credentials must never reach the log.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let proxy = self
+            .proxy_url
+            .as_deref()
+            .map(|url| match reqwest::Url::parse(url) {
+                Ok(mut parsed) => {
+                    let _ = parsed.set_username("");
+                    let _ = parsed.set_password(None);
+                    parsed.to_string()
+                }
+                Err(_) => "<invalid>".to_string(),
+            });
+        write!(
+            f,
+            "NetworkSettings {{ proxy_url: {:?}, proxy_auth: {}, no_proxy: {:?}, connect_timeout_secs: {}, read_timeout_secs: {}, extra_root_pems: {}, retry_max_attempts: {} }}",
+            proxy,
+            self.proxy_username.is_some(),
+            self.no_proxy,
+            self.connect_timeout_secs,
+            self.read_timeout_secs,
+            self.extra_root_pems.len(),
+            self.retry_max_attempts
+        )
+    }
 }
 
 impl Display for AppSettings {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "AppSettings {{ log_level: {}, max_concurrency: {}, default_download_dir: {:?}, upload_thumbnail: {}, android_tree_uri: {:?} }}", self.log_level, self.max_concurrency, self.default_download_dir, self.upload_thumbnail, self.android_tree_uri)
+        write!(f, "AppSettings {{ log_level: {}, max_concurrency: {}, default_download_dir: {:?}, upload_thumbnail: {}, per_task_parts: {}, android_tree_uri: {:?}, recycle_bin: {}, trash_retention_days: {}, network: {} }}", self.log_level, self.max_concurrency, self.default_download_dir, self.upload_thumbnail, self.per_task_parts, self.android_tree_uri, self.recycle_bin, self.trash_retention_days, self.network)
     }
 }
 
@@ -30,7 +130,11 @@ impl Default for AppSettings {
             max_concurrency: 2,
             default_download_dir: None,
             upload_thumbnail: true,
+            per_task_parts: default_per_task_parts(),
             android_tree_uri: None,
+            recycle_bin: false,
+            trash_retention_days: default_trash_retention_days(),
+            network: NetworkSettings::default(),
         }
     }
 }
@@ -133,6 +237,7 @@ pub async fn settings_get() -> SpResult<AppSettings> {
 
 #[tauri::command]
 pub async fn settings_set(settings: AppSettings) -> SpResult<()> {
+    crate::storage::validate_network_settings(&settings.network)?;
     set(settings)
 }
 
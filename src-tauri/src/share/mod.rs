@@ -107,13 +107,7 @@ pub(crate) async fn save_ledger_with_cache(
     operator
         .write(STATIC_SHARE_PATH, bytes)
         .await
-        .map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("PutObject: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: now_ms(),
-        })?;
+        .map_err(|error| crate::storage::opendal_error("PutObject", STATIC_SHARE_PATH, &error))?;
     // save cache
     if let Some(p) = local_cache {
         if let Some(parent) = p.parent() {
@@ -134,17 +128,11 @@ pub(crate) fn prepend_share_entry(ledger: &mut ShareLedger, entry: ShareEntry) {
 pub async fn generate_share_link(params: ShareParams) -> SpResult<ShareLink> {
     // Build the storage operator and presign.
     let bundle = crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = crate::storage::build_operator(&bundle.r2).await?;
+    let operator = crate::storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     let url = operator
         .presign_read(&params.key, Duration::from_secs(params.ttl_secs))
         .await
-        .map_err(|error| SpError {
-            kind: ErrorKind::NotRetriable,
-            message: format!("Presign failed: {error}"),
-            retry_after_ms: None,
-            context: None,
-            at: now_ms(),
-        })?
+        .map_err(|error| crate::storage::opendal_error("Presign", &params.key, &error))?
         .uri()
         .to_string();
     // Response-content-disposition must be part of the signature. Keep the
@@ -169,7 +157,7 @@ pub async fn generate_share_link(params: ShareParams) -> SpResult<ShareLink> {
 
 pub async fn list_share_entries() -> SpResult<Vec<ShareEntry>> {
     let bundle = crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked()?;
-    let operator = crate::storage::build_operator(&bundle.r2).await?;
+    let operator = crate::storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
     let v = load_ledger(&operator, false).await?;
     Ok(v.items)
 }
@@ -1,7 +1,8 @@
 //! Password-based key derivation primitives.
 //!
 //! This module owns the exact Argon2id parameters-to-key operation used by
-//! portable credential packages. It must not choose package fields, read or
+//! portable credential packages and by client-side content encryption
+//! (`crate::content_crypto`). It must not choose package fields, read or
 //! write vault files, access device keys, call platform keystores, or mutate
 //! runtime credential state.
 
@@ -9,7 +10,7 @@ use super::KdfParams;
 use crate::types::{ErrorKind, SpError, SpResult};
 use argon2::{Algorithm, Argon2, Params, Version};
 
-pub(super) fn derive_argon2_key(password: &str, params: &KdfParams) -> SpResult<[u8; 32]> {
+pub(crate) fn derive_argon2_key(password: &str, params: &KdfParams) -> SpResult<[u8; 32]> {
     if params.mem_kib == 0 || params.iterations == 0 || params.parallelism == 0 {
         return Err(SpError {
             kind: ErrorKind::NotRetriable,
@@ -11,11 +11,14 @@ mod crypto;
 mod device_key;
 mod model;
 mod paths;
+mod profiles;
 mod runtime;
 mod service;
 
+pub(crate) use crypto::derive_argon2_key;
 pub use model::{
-    BackendPackage, BackendState, CredentialBundle, KdfParams, R2ConfigPatch, SpBackend,
+    BackendPackage, BackendState, CredentialBundle, KdfParams, ProfileEncryption, R2ConfigPatch,
+    SpBackend, StorageProfile, DEFAULT_PROFILE_NAME,
 };
 pub use paths::init;
 pub(crate) use paths::vault_dir;
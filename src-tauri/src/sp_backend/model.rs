@@ -5,7 +5,8 @@
 //! compatibility boundaries; this module must not perform I/O, cryptography,
 //! platform access, or mutate runtime state.
 
-use crate::types::{DeviceId, R2Config};
+use crate::types::{DeviceId, R2Config, StorageProvider};
+use std::collections::BTreeMap;
 
 pub(super) const EXPORT_SECRET: &str = "swiftpan-export-v1";
 pub(super) const VAULT_FILE_NAME: &str = "vault.sp";
@@ -15,10 +16,96 @@ pub(super) const DEVICE_KEY_FILE_NAME: &str = "device.key";
 pub(super) const DEVICE_KEY_WRAPPED_FILE_NAME: &str = "device.key.enc";
 #[cfg(target_os = "android")]
 pub(super) const ANDROID_KEY_ALIAS: &str = "com.timspizza.swiftpan.device_key.v1";
+/// Profile name given to single-config vaults written before profiles existed.
+pub const DEFAULT_PROFILE_NAME: &str = "default";
 
+/// Decrypted vault contents.
+///
+/// `r2` is always the configuration of `active_profile`; `profiles` holds every
+/// named profile including the active one. Bundles serialized before profiles
+/// existed only carry `r2` and deserialize as a lone `default` profile.
+/// `encryption` is keyed by profile name and only holds profiles that
+/// encrypt object contents.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CredentialBundle {
     pub r2: R2Config,
+    #[serde(default = "default_profile_name")]
+    pub active_profile: String,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, R2Config>,
+    #[serde(default)]
+    pub encryption: BTreeMap<String, ProfileEncryption>,
+}
+
+impl CredentialBundle {
+    pub fn new(r2: R2Config) -> Self {
+        Self {
+            r2,
+            active_profile: default_profile_name(),
+            profiles: BTreeMap::new(),
+            encryption: BTreeMap::new(),
+        }
+        .normalized()
+    }
+
+    /// Re-establishes the invariants that `profiles` contains the active `r2`
+    /// and that `encryption` only names existing profiles.
+    pub fn normalized(mut self) -> Self {
+        self.profiles
+            .insert(self.active_profile.clone(), self.r2.clone());
+        let profiles = &self.profiles;
+        self.encryption
+            .retain(|name, _| profiles.contains_key(name));
+        self
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&R2Config> {
+        if name == self.active_profile {
+            Some(&self.r2)
+        } else {
+            self.profiles.get(name)
+        }
+    }
+
+    /// The client-side encryption settings of profile `name`, if it
+    /// encrypts object contents.
+    pub fn encryption_for(&self, name: &str) -> Option<&ProfileEncryption> {
+        self.encryption.get(name)
+    }
+}
+
+fn default_profile_name() -> String {
+    DEFAULT_PROFILE_NAME.into()
+}
+
+/// Client-side encryption of one profile's object contents.
+///
+/// New objects are sealed with the key derived from `passphrase` under
+/// `kdf`. Every sealed object records the KDF parameters it was written
+/// with, so objects written before the salt changed, or on another device,
+/// open with the same passphrase.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProfileEncryption {
+    pub passphrase: String,
+    pub kdf: KdfParams,
+}
+
+impl std::fmt::Debug for ProfileEncryption {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("ProfileEncryption")
+            .field("passphrase", &"<redacted>")
+            .field("kdf", &self.kdf)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StorageProfile {
+    pub name: String,
+    pub r2: R2Config,
+    pub is_active: bool,
+    pub is_encrypted: bool,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
@@ -28,6 +115,7 @@ pub struct R2ConfigPatch {
     pub secret_access_key: Option<String>,
     pub bucket: Option<String>,
     pub region: Option<String>,
+    pub provider: Option<StorageProvider>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -47,7 +135,7 @@ pub struct BackendPackage {
     pub ciphertext_b64: String,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct KdfParams {
     pub algo: String,
     pub mem_kib: u32,
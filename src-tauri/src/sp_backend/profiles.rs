@@ -0,0 +1,274 @@
+//! Named storage profiles inside the credential vault.
+//!
+//! This module owns profile-name validation, the add/switch/delete rules
+//! and per-profile content encryption settings applied to a decrypted
+//! [`CredentialBundle`], plus the [`SpBackend`] methods that persist them.
+//! It must not encrypt or write vault files directly, build storage
+//! operators, or decide which profile a transfer runs against.
+
+use super::model::{CredentialBundle, KdfParams, ProfileEncryption, SpBackend, StorageProfile};
+use crate::types::{err_invalid, R2Config, SpResult};
+use rand::{rngs::OsRng, RngCore};
+
+const MAX_PROFILE_NAME_LEN: usize = 64;
+const MIN_PASSPHRASE_CHARS: usize = 8;
+
+impl SpBackend {
+    pub fn list_profiles() -> SpResult<Vec<StorageProfile>> {
+        let bundle = Self::get_decrypted_bundle_if_unlocked()?;
+        Ok(bundle
+            .profiles
+            .iter()
+            .map(|(name, r2)| StorageProfile {
+                name: name.clone(),
+                r2: r2.clone(),
+                is_active: *name == bundle.active_profile,
+                is_encrypted: bundle.encryption.contains_key(name),
+            })
+            .collect())
+    }
+
+    /// Adds a profile without switching to it. The first profile written to an
+    /// empty vault becomes the active one.
+    pub fn add_profile(name: &str, r2: R2Config) -> SpResult<()> {
+        let bundle = match Self::existing_bundle()? {
+            Some(bundle) => with_profile_added(bundle, name, r2)?,
+            None => CredentialBundle {
+                r2,
+                active_profile: validate_profile_name(name)?,
+                profiles: Default::default(),
+                encryption: Default::default(),
+            },
+        };
+        Self::set_with_plaintext(bundle)
+    }
+
+    pub fn switch_profile(name: &str) -> SpResult<()> {
+        let bundle = Self::get_decrypted_bundle_if_unlocked()?;
+        Self::set_with_plaintext(with_profile_switched(bundle, name)?)
+    }
+
+    pub fn delete_profile(name: &str) -> SpResult<()> {
+        let bundle = Self::get_decrypted_bundle_if_unlocked()?;
+        Self::set_with_plaintext(with_profile_deleted(bundle, name)?)
+    }
+
+    /// Turns client-side encryption of profile `name` on with `passphrase`,
+    /// or off with `None`. Each call picks a fresh salt. Objects already
+    /// written stay as they are; sealed ones only open while the profile
+    /// holds the passphrase they were written with.
+    pub fn set_profile_encryption(name: &str, passphrase: Option<&str>) -> SpResult<()> {
+        let bundle = Self::get_decrypted_bundle_if_unlocked()?;
+        let mut salt = [0; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self::set_with_plaintext(with_profile_encryption(bundle, name, passphrase, salt)?)
+    }
+}
+
+fn validate_profile_name(name: &str) -> SpResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(err_invalid("profile name must not be empty"));
+    }
+    if name.chars().count() > MAX_PROFILE_NAME_LEN {
+        return Err(err_invalid("profile name is too long"));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(err_invalid(
+            "profile name must not contain control characters",
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn with_profile_added(
+    bundle: CredentialBundle,
+    name: &str,
+    r2: R2Config,
+) -> SpResult<CredentialBundle> {
+    let mut bundle = bundle.normalized();
+    let name = validate_profile_name(name)?;
+    if bundle.profiles.contains_key(&name) {
+        return Err(err_invalid("a profile with this name already exists"));
+    }
+    bundle.profiles.insert(name, r2);
+    Ok(bundle)
+}
+
+fn with_profile_switched(bundle: CredentialBundle, name: &str) -> SpResult<CredentialBundle> {
+    let mut bundle = bundle.normalized();
+    let r2 = bundle
+        .profiles
+        .get(name)
+        .cloned()
+        .ok_or_else(|| err_invalid("storage profile not found"))?;
+    bundle.active_profile = name.to_string();
+    bundle.r2 = r2;
+    Ok(bundle)
+}
+
+fn with_profile_deleted(bundle: CredentialBundle, name: &str) -> SpResult<CredentialBundle> {
+    let mut bundle = bundle.normalized();
+    if name == bundle.active_profile {
+        return Err(err_invalid(
+            "cannot delete the active profile; switch to another profile first",
+        ));
+    }
+    if bundle.profiles.remove(name).is_none() {
+        return Err(err_invalid("storage profile not found"));
+    }
+    bundle.encryption.remove(name);
+    Ok(bundle)
+}
+
+fn with_profile_encryption(
+    bundle: CredentialBundle,
+    name: &str,
+    passphrase: Option<&str>,
+    salt: [u8; 16],
+) -> SpResult<CredentialBundle> {
+    let mut bundle = bundle.normalized();
+    if !bundle.profiles.contains_key(name) {
+        return Err(err_invalid("storage profile not found"));
+    }
+    let Some(passphrase) = passphrase else {
+        bundle.encryption.remove(name);
+        return Ok(bundle);
+    };
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(err_invalid(&format!(
+            "encryption passphrase must have at least {MIN_PASSPHRASE_CHARS} characters"
+        )));
+    }
+    bundle.encryption.insert(
+        name.to_string(),
+        ProfileEncryption {
+            passphrase: passphrase.to_string(),
+            kdf: KdfParams {
+                algo: "argon2id".into(),
+                mem_kib: 32 * 1024,
+                iterations: 3,
+                parallelism: 1,
+                salt,
+            },
+        },
+    );
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sp_backend::DEFAULT_PROFILE_NAME;
+
+    fn config(bucket: &str) -> R2Config {
+        R2Config {
+            endpoint: "https://account.r2.cloudflarestorage.com".into(),
+            access_key_id: "access-key".into(),
+            secret_access_key: "secret-key".into(),
+            bucket: bucket.into(),
+            region: None,
+            provider: Default::default(),
+        }
+    }
+
+    #[test]
+    fn legacy_single_config_bundle_loads_as_default_profile() {
+        let legacy = serde_json::json!({ "r2": config("photos") });
+        let bundle = serde_json::from_value::<CredentialBundle>(legacy)
+            .expect("legacy bundle should deserialize")
+            .normalized();
+
+        assert_eq!(bundle.active_profile, DEFAULT_PROFILE_NAME);
+        assert_eq!(
+            bundle
+                .profile(DEFAULT_PROFILE_NAME)
+                .map(|r2| r2.bucket.as_str()),
+            Some("photos")
+        );
+        assert_eq!(bundle.profiles.len(), 1);
+    }
+
+    #[test]
+    fn switching_profiles_moves_the_active_config_and_keeps_edits() {
+        let mut bundle = CredentialBundle::new(config("prod"));
+        bundle = with_profile_added(bundle, "staging", config("staging")).expect("add staging");
+        bundle.r2.region = Some("weur".into());
+
+        let bundle = with_profile_switched(bundle, "staging").expect("switch to staging");
+
+        assert_eq!(bundle.active_profile, "staging");
+        assert_eq!(bundle.r2.bucket, "staging");
+        assert_eq!(
+            bundle
+                .profile(DEFAULT_PROFILE_NAME)
+                .and_then(|r2| r2.region.as_deref()),
+            Some("weur"),
+            "edits to the previously active profile must not be lost"
+        );
+    }
+
+    #[test]
+    fn duplicate_and_blank_profile_names_are_rejected() {
+        let bundle = CredentialBundle::new(config("prod"));
+
+        assert!(with_profile_added(bundle.clone(), DEFAULT_PROFILE_NAME, config("x")).is_err());
+        assert!(with_profile_added(bundle.clone(), "   ", config("x")).is_err());
+        assert!(with_profile_added(bundle, "bad\nname", config("x")).is_err());
+    }
+
+    #[test]
+    fn active_or_unknown_profiles_cannot_be_deleted() {
+        let bundle = with_profile_added(
+            CredentialBundle::new(config("prod")),
+            "personal",
+            config("me"),
+        )
+        .expect("add personal");
+
+        assert!(with_profile_deleted(bundle.clone(), DEFAULT_PROFILE_NAME).is_err());
+        assert!(with_profile_deleted(bundle.clone(), "missing").is_err());
+        let remaining = with_profile_deleted(bundle, "personal").expect("delete personal");
+        assert!(remaining.profile("personal").is_none());
+        assert_eq!(remaining.profiles.len(), 1);
+    }
+
+    #[test]
+    fn switching_to_an_unknown_profile_is_rejected() {
+        let bundle = CredentialBundle::new(config("prod"));
+
+        assert!(with_profile_switched(bundle, "staging").is_err());
+    }
+
+    #[test]
+    fn encryption_settings_follow_their_profile() {
+        let bundle = with_profile_added(
+            CredentialBundle::new(config("prod")),
+            "private",
+            config("vault"),
+        )
+        .expect("add private");
+
+        assert!(
+            with_profile_encryption(bundle.clone(), "missing", Some("long enough"), [1; 16])
+                .is_err()
+        );
+        assert!(
+            with_profile_encryption(bundle.clone(), "private", Some("short"), [1; 16]).is_err()
+        );
+        let encrypted = with_profile_encryption(bundle, "private", Some("long enough"), [1; 16])
+            .expect("enable encryption");
+        let settings = encrypted
+            .encryption_for("private")
+            .expect("private profile should encrypt");
+        assert_eq!(settings.kdf.salt, [1; 16]);
+        assert!(encrypted.encryption_for(DEFAULT_PROFILE_NAME).is_none());
+        assert!(!format!("{settings:?}").contains("long enough"));
+
+        let disabled = with_profile_encryption(encrypted.clone(), "private", None, [2; 16])
+            .expect("disable encryption");
+        assert!(disabled.encryption_for("private").is_none());
+        let deleted = with_profile_deleted(encrypted, "private").expect("delete private");
+        assert!(deleted.encryption.is_empty());
+    }
+}
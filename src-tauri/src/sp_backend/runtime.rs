@@ -46,7 +46,7 @@ pub(super) fn current_state() -> BackendState {
     };
     let bundle = memory_bundle.or(disk_bundle);
     let is_credential_completed = bundle.as_ref().is_some_and(|bundle| {
-        !bundle.r2.endpoint.is_empty()
+        (!bundle.r2.endpoint.is_empty() || bundle.r2.provider.has_default_endpoint())
             && !bundle.r2.access_key_id.is_empty()
             && !bundle.r2.secret_access_key.is_empty()
             && !bundle.r2.bucket.is_empty()
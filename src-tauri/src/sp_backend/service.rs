@@ -26,6 +26,7 @@ impl SpBackend {
     }
 
     pub fn set_with_plaintext(bundle: CredentialBundle) -> SpResult<()> {
+        let bundle = bundle.normalized();
         let dir = vault_dir()?;
         fs::create_dir_all(&dir).map_err(|e| SpError {
             kind: ErrorKind::NotRetriable,
@@ -113,28 +114,16 @@ impl SpBackend {
 
     pub fn patch_r2_config(patch: R2ConfigPatch) -> SpResult<()> {
         // Load current bundle (from mem or disk). If vault doesn't exist yet, start from defaults
-        let mut cur = match Self::get_decrypted_bundle_if_unlocked() {
-            Ok(b) => b,
-            Err(e) => {
-                let dir = vault_dir()?;
-                let vault_exists = dir.join(VAULT_FILE_NAME).exists();
-                if !vault_exists {
-                    // Start from an empty/default R2 config and apply patch below
-                    CredentialBundle {
-                        r2: R2Config {
-                            endpoint: String::new(),
-                            access_key_id: String::new(),
-                            secret_access_key: String::new(),
-                            bucket: String::new(),
-                            region: None,
-                        },
-                    }
-                } else {
-                    // If a vault exists but couldn't be read/decrypted, bubble up the error
-                    return Err(e);
-                }
-            }
-        };
+        let mut cur = Self::existing_bundle()?.unwrap_or_else(|| {
+            CredentialBundle::new(R2Config {
+                endpoint: String::new(),
+                access_key_id: String::new(),
+                secret_access_key: String::new(),
+                bucket: String::new(),
+                region: None,
+                provider: Default::default(),
+            })
+        });
         // Apply provided fields
         if let Some(v) = patch.endpoint {
             cur.r2.endpoint = v;
@@ -151,9 +140,39 @@ impl SpBackend {
         if let Some(v) = patch.region {
             cur.r2.region = Some(v);
         }
+        if let Some(v) = patch.provider {
+            cur.r2.provider = v;
+        }
         // Persist via existing set logic
         Self::set_with_plaintext(cur)
     }
+
+    /// Replaces the active profile's configuration, keeping every other profile.
+    pub fn set_active_r2_config(r2: R2Config) -> SpResult<()> {
+        let bundle = match Self::existing_bundle()? {
+            Some(mut bundle) => {
+                bundle.r2 = r2;
+                bundle
+            }
+            None => CredentialBundle::new(r2),
+        };
+        Self::set_with_plaintext(bundle)
+    }
+
+    /// Returns the stored bundle, or `None` when no vault has been written yet.
+    /// A vault that exists but cannot be read or decrypted is an error.
+    pub(super) fn existing_bundle() -> SpResult<Option<CredentialBundle>> {
+        match Self::get_decrypted_bundle_if_unlocked() {
+            Ok(bundle) => Ok(Some(bundle)),
+            Err(e) => {
+                if vault_dir()?.join(VAULT_FILE_NAME).exists() {
+                    Err(e)
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
     fn err_not_implemented(func: &str) -> SpError {
         crate::logger::error("sp_backend", format!("{func} not implemented").as_str());
         SpError {
@@ -331,13 +350,15 @@ impl SpBackend {
                 context: None,
                 at: chrono::Utc::now().timestamp_millis(),
             })?;
-        let bundle: CredentialBundle = serde_json::from_slice(&pt).map_err(|e| SpError {
-            kind: ErrorKind::NotRetriable,
-            message: format!("decode bundle json: {e}"),
-            retry_after_ms: None,
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        })?;
+        let bundle = serde_json::from_slice::<CredentialBundle>(&pt)
+            .map_err(|e| SpError {
+                kind: ErrorKind::NotRetriable,
+                message: format!("decode bundle json: {e}"),
+                retry_after_ms: None,
+                context: None,
+                at: chrono::Utc::now().timestamp_millis(),
+            })?
+            .normalized();
         cache_credentials(bundle.clone())?;
         Ok(bundle)
     }
@@ -0,0 +1,62 @@
+//! Preconditions on multipart completions sent by OpenDAL's writer.
+//!
+//! OpenDAL sends `If-None-Match` and `If-Match` with a single PutObject but
+//! not with the CompleteMultipartUpload that finishes a multipart write, so
+//! a conditional upload large enough to be split would replace whatever
+//! another device stored meanwhile. [`completing_with`] names the headers for
+//! the writer being closed, and [`ConditionalCompletion`] adds them to the
+//! completion it sends, so the bucket enforces the condition itself.
+
+use opendal::raw::{HttpBody, HttpFetch};
+use opendal::{Buffer, Error, ErrorKind as OdErrorKind, Result as OdResult};
+use std::future::Future;
+
+tokio::task_local! {
+    static COMPLETION_HEADERS: Vec<(String, String)>;
+}
+
+/// Runs `future`, the close of one writer, with `headers` added to the
+/// multipart completion it sends.
+pub(crate) async fn completing_with<F: Future>(
+    headers: Vec<(String, String)>,
+    future: F,
+) -> F::Output {
+    COMPLETION_HEADERS.scope(headers, future).await
+}
+
+/// Wraps another fetcher and adds the headers of [`completing_with`] to
+/// CompleteMultipartUpload requests. Every other request passes unchanged.
+pub(crate) struct ConditionalCompletion<F> {
+    inner: F,
+}
+
+impl<F> ConditionalCompletion<F> {
+    pub(crate) fn new(inner: F) -> Self {
+        Self { inner }
+    }
+}
+
+impl<F: HttpFetch> HttpFetch for ConditionalCompletion<F> {
+    async fn fetch(&self, mut req: http::Request<Buffer>) -> OdResult<http::Response<HttpBody>> {
+        let completes = req.method() == http::Method::POST
+            && req
+                .uri()
+                .query()
+                .is_some_and(|query| query.split('&').any(|pair| pair.starts_with("uploadId=")));
+        if completes {
+            let headers = COMPLETION_HEADERS.try_with(Vec::clone).unwrap_or_default();
+            for (name, value) in headers {
+                let name = http::HeaderName::from_bytes(name.as_bytes());
+                let value = http::HeaderValue::from_str(&value);
+                let (Ok(name), Ok(value)) = (name, value) else {
+                    return Err(Error::new(
+                        OdErrorKind::Unexpected,
+                        "completion precondition is not a valid header",
+                    ));
+                };
+                req.headers_mut().insert(name, value);
+            }
+        }
+        self.inner.fetch(req).await
+    }
+}
@@ -0,0 +1,456 @@
+//! Step-by-step diagnostics for the configured endpoint and bucket.
+//!
+//! Each step isolates one reason a storage profile can fail: name
+//! resolution, the connection and TLS handshake, clock skew, the request
+//! signature, and the list/write/read/delete permissions of the key, probed
+//! with a scratch object that is removed again. Steps that cannot run because
+//! an earlier one failed are reported as skipped, and every failure carries a
+//! hint on what to change.
+
+use super::raw_s3::{self, BucketAddress, RawS3Request};
+use crate::settings::NetworkSettings;
+use crate::types::{
+    DiagnosticCheck, DiagnosticStatus, DiagnosticStep, DiagnosticsReport, ErrorKind, R2Config,
+    SpError, SpResult,
+};
+use std::future::Future;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+/// S3 rejects signed requests whose date is further off than this.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(15 * 60);
+const SCRATCH_PREFIX: &str = ".swiftpan-diagnostics/";
+const SCRATCH_BODY: &[u8] = b"SwiftPan connectivity probe\n";
+
+enum Outcome {
+    Pass(String),
+    Fail { detail: String, hint: String },
+    Skipped(String),
+}
+
+impl Outcome {
+    fn fail(detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::Fail {
+            detail: detail.into(),
+            hint: hint.into(),
+        }
+    }
+
+    fn passed(&self) -> bool {
+        matches!(self, Self::Pass(_))
+    }
+}
+
+#[derive(Default)]
+struct Steps(Vec<DiagnosticStep>);
+
+impl Steps {
+    fn record(&mut self, check: DiagnosticCheck, started: Instant, outcome: Outcome) -> bool {
+        let passed = outcome.passed();
+        let (status, detail, hint) = match outcome {
+            Outcome::Pass(detail) => (DiagnosticStatus::Pass, detail, None),
+            Outcome::Fail { detail, hint } => (DiagnosticStatus::Fail, detail, Some(hint)),
+            Outcome::Skipped(detail) => (DiagnosticStatus::Skipped, detail, None),
+        };
+        self.0.push(DiagnosticStep {
+            check,
+            status,
+            detail,
+            hint,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        });
+        passed
+    }
+
+    fn skip(&mut self, checks: &[DiagnosticCheck], reason: &str) {
+        for check in checks {
+            self.record(*check, Instant::now(), Outcome::Skipped(reason.to_string()));
+        }
+    }
+}
+
+/// Runs every diagnostic step against `cfg`. Fails only when the
+/// configuration cannot be turned into requests at all, e.g. an unparsable
+/// endpoint; everything the endpoint does wrong is part of the report.
+pub async fn run_diagnostics(cfg: &R2Config) -> SpResult<DiagnosticsReport> {
+    use DiagnosticCheck::*;
+
+    let address = raw_s3::bucket_address(cfg)?;
+    let network = crate::settings::get().network;
+    let client = super::http_client()?;
+    let mut steps = Steps::default();
+    let mut clock_skew_ms = None;
+
+    let started = Instant::now();
+    if !steps.record(Dns, started, resolve(&address, &network).await) {
+        steps.skip(
+            &[
+                TlsHandshake,
+                ClockSkew,
+                Signature,
+                List,
+                Write,
+                Read,
+                Delete,
+            ],
+            "the endpoint host name did not resolve",
+        );
+        return Ok(finish(cfg, &address, steps, clock_skew_ms));
+    }
+
+    let started = Instant::now();
+    let sent_at_ms = chrono::Utc::now().timestamp_millis();
+    let probe = timed(client.get(address.url()).send()).await;
+    let server_date = match &probe {
+        Ok(Ok(response)) => response
+            .headers()
+            .get(http::header::DATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok()),
+        _ => None,
+    };
+    let received_at_ms = chrono::Utc::now().timestamp_millis();
+    if !steps.record(TlsHandshake, started, connection_outcome(&address, probe)) {
+        steps.skip(
+            &[ClockSkew, Signature, List, Write, Read, Delete],
+            "no connection to the endpoint",
+        );
+        return Ok(finish(cfg, &address, steps, clock_skew_ms));
+    }
+
+    let started = Instant::now();
+    let clock = match server_date {
+        Some(date) => {
+            let skew_ms = (sent_at_ms + received_at_ms) / 2 - date.timestamp_millis();
+            clock_skew_ms = Some(skew_ms);
+            clock_outcome(skew_ms)
+        }
+        None => Outcome::Skipped("the server sent no Date header".into()),
+    };
+    steps.record(ClockSkew, started, clock);
+
+    let started = Instant::now();
+    let list = timed_s3(
+        cfg,
+        RawS3Request {
+            method: http::Method::GET,
+            key: String::new(),
+            query: vec![
+                ("list-type".into(), "2".into()),
+                ("max-keys".into(), "1".into()),
+            ],
+            headers: Vec::new(),
+            body: bytes::Bytes::new(),
+        },
+    )
+    .await;
+    let list_error = list.as_ref().err();
+    if !steps.record(Signature, started, signature_outcome(list_error)) {
+        steps.skip(
+            &[List, Write, Read, Delete],
+            "the endpoint rejected the request signature",
+        );
+        return Ok(finish(cfg, &address, steps, clock_skew_ms));
+    }
+    let list = match list_error {
+        None => Outcome::Pass(format!("listed bucket {}", cfg.bucket)),
+        Some(error) => permission_failure(
+            error,
+            "The key cannot list this bucket, so browsing and search will fail. Grant it s3:ListBucket on the bucket.",
+        ),
+    };
+    steps.record(List, started, list);
+    if error_code(list_error) == Some("NoSuchBucket") {
+        steps.skip(&[Write, Read, Delete], "the bucket does not exist");
+        return Ok(finish(cfg, &address, steps, clock_skew_ms));
+    }
+
+    let scratch_key = format!("{SCRATCH_PREFIX}{}", uuid::Uuid::new_v4());
+    let started = Instant::now();
+    let write = match timed_s3(
+        cfg,
+        object_request(http::Method::PUT, &scratch_key, SCRATCH_BODY.to_vec()),
+    )
+    .await
+    {
+        Ok(_) => Outcome::Pass(format!("wrote scratch object {scratch_key}")),
+        Err(error) => permission_failure(
+            &error,
+            "The key cannot create objects, so uploads will fail. Grant it s3:PutObject on the bucket.",
+        ),
+    };
+    if !steps.record(Write, started, write) {
+        steps.skip(&[Read, Delete], "no scratch object was written");
+        return Ok(finish(cfg, &address, steps, clock_skew_ms));
+    }
+
+    let started = Instant::now();
+    let read = match timed_s3(
+        cfg,
+        object_request(http::Method::GET, &scratch_key, Vec::new()),
+    )
+    .await
+    {
+        Ok(response) if response.body == SCRATCH_BODY => {
+            Outcome::Pass("read the scratch object back unchanged".into())
+        }
+        Ok(response) => Outcome::fail(
+            format!(
+                "read {} bytes that differ from the {} bytes written",
+                response.body.len(),
+                SCRATCH_BODY.len()
+            ),
+            "Something between this device and the bucket rewrites responses, such as a proxy or gateway. Downloads would be corrupted.",
+        ),
+        Err(error) => permission_failure(
+            &error,
+            "The key cannot read objects, so downloads and previews will fail. Grant it s3:GetObject on the bucket.",
+        ),
+    };
+    steps.record(Read, started, read);
+
+    let started = Instant::now();
+    let delete = match timed_s3(
+        cfg,
+        object_request(http::Method::DELETE, &scratch_key, Vec::new()),
+    )
+    .await
+    {
+        Ok(_) => Outcome::Pass("deleted the scratch object".into()),
+        Err(error) => permission_failure(
+            &error,
+            &format!(
+                "The key cannot delete objects, so deletes, moves and renames will fail. Grant it s3:DeleteObject, and remove {scratch_key} with another tool."
+            ),
+        ),
+    };
+    steps.record(Delete, started, delete);
+
+    Ok(finish(cfg, &address, steps, clock_skew_ms))
+}
+
+fn finish(
+    cfg: &R2Config,
+    address: &BucketAddress,
+    steps: Steps,
+    clock_skew_ms: Option<i64>,
+) -> DiagnosticsReport {
+    let steps = steps.0;
+    let failed = steps
+        .iter()
+        .filter(|step| step.status == DiagnosticStatus::Fail)
+        .map(|step| format!("{:?}", step.check))
+        .collect::<Vec<_>>();
+    crate::logger::info(
+        "diagnostics",
+        &format!(
+            "endpoint diagnostics for {} finished: {}",
+            address.url(),
+            if failed.is_empty() {
+                "all checks passed".to_string()
+            } else {
+                format!("failed {}", failed.join(", "))
+            }
+        ),
+    );
+    DiagnosticsReport {
+        url: address.url(),
+        bucket: cfg.bucket.clone(),
+        ok: failed.is_empty(),
+        clock_skew_ms,
+        steps,
+    }
+}
+
+async fn resolve(address: &BucketAddress, network: &NetworkSettings) -> Outcome {
+    if network
+        .proxy_url
+        .as_deref()
+        .is_some_and(|url| !url.trim().is_empty())
+    {
+        return Outcome::Skipped(
+            "requests go through the configured proxy, which resolves the endpoint".into(),
+        );
+    }
+    if address.host.parse::<IpAddr>().is_ok() {
+        return Outcome::Pass(format!("{} is an IP address", address.host));
+    }
+    let port = address
+        .port
+        .unwrap_or(if address.scheme == "http" { 80 } else { 443 });
+    let target = (address.host.clone(), port);
+    let lookup = tokio::task::spawn_blocking(move || {
+        target
+            .to_socket_addrs()
+            .map(|addresses| addresses.map(|addr| addr.ip()).collect::<Vec<_>>())
+    });
+    let hint = "Check the endpoint URL for typos and that this device can reach a DNS server. On a network that only allows traffic through a proxy, configure it in the network settings.";
+    match tokio::time::timeout(PROBE_TIMEOUT, lookup).await {
+        Ok(Ok(Ok(ips))) if !ips.is_empty() => Outcome::Pass(format!(
+            "{} resolves to {}",
+            address.host,
+            ips.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        Ok(Ok(Ok(_))) => Outcome::fail(format!("{} has no addresses", address.host), hint),
+        Ok(Ok(Err(error))) => {
+            Outcome::fail(format!("could not resolve {}: {error}", address.host), hint)
+        }
+        Ok(Err(error)) => Outcome::fail(format!("name lookup did not complete: {error}"), hint),
+        Err(_) => Outcome::fail(format!("resolving {} timed out", address.host), hint),
+    }
+}
+
+fn connection_outcome(
+    address: &BucketAddress,
+    probe: Result<reqwest::Result<reqwest::Response>, tokio::time::error::Elapsed>,
+) -> Outcome {
+    let authority = address.authority();
+    let error = match probe {
+        Ok(Ok(response)) if address.scheme == "https" => {
+            return Outcome::Pass(format!(
+                "TLS handshake with {authority} succeeded (HTTP {})",
+                response.status().as_u16()
+            ))
+        }
+        Ok(Ok(response)) => {
+            return Outcome::Pass(format!(
+                "connected to {authority} over plain HTTP without TLS (HTTP {})",
+                response.status().as_u16()
+            ))
+        }
+        Ok(Err(error)) => error,
+        Err(_) => {
+            return Outcome::fail(
+                format!(
+                    "no response from {authority} within {}s",
+                    PROBE_TIMEOUT.as_secs()
+                ),
+                "Check firewall rules, the port in the endpoint URL and the proxy settings.",
+            )
+        }
+    };
+    let detail = error_chain(&error);
+    let lowered = detail.to_ascii_lowercase();
+    let hint = if lowered.contains("certificate") || lowered.contains("unknownissuer") {
+        "The server's certificate is not trusted. If a corporate proxy or private CA inspects HTTPS traffic, add its root certificate in the network settings."
+    } else if lowered.contains("tls") || lowered.contains("handshake") {
+        "The TLS handshake failed. Check that the endpoint URL uses the right scheme and port for this server."
+    } else if error.is_timeout() {
+        "The endpoint did not answer in time. Check firewall rules, the port in the endpoint URL and the proxy settings."
+    } else {
+        "Could not open a connection. Check the port in the endpoint URL, firewall rules and the proxy settings."
+    };
+    Outcome::fail(detail, hint)
+}
+
+fn clock_outcome(skew_ms: i64) -> Outcome {
+    let seconds = skew_ms.unsigned_abs() / 1000;
+    let direction = if skew_ms >= 0 { "ahead of" } else { "behind" };
+    if skew_ms.unsigned_abs() > MAX_CLOCK_SKEW.as_millis() as u64 {
+        Outcome::fail(
+            format!("this device's clock is {seconds}s {direction} the server"),
+            "Signed requests are rejected when clocks differ by more than 15 minutes. Turn on automatic date and time on this device.",
+        )
+    } else {
+        Outcome::Pass(format!(
+            "this device's clock is {seconds}s {direction} the server"
+        ))
+    }
+}
+
+/// The signed list request doubles as the signature check: any answer other
+/// than an authentication error means the credentials were accepted.
+fn signature_outcome(list_error: Option<&SpError>) -> Outcome {
+    let Some(error) = list_error else {
+        return Outcome::Pass("the endpoint accepted the request signature".into());
+    };
+    let hint = match error_code(Some(error)) {
+        Some("SignatureDoesNotMatch") => "The secret access key does not belong to this access key ID. Copy the secret again; most providers show it only once.",
+        Some("InvalidAccessKeyId") | Some("InvalidToken") | Some("InvalidClientTokenId") => "The endpoint does not know this access key ID. Check that it was copied completely and belongs to the same account as the endpoint.",
+        Some("RequestTimeTooSkewed") => "The request date is too far from the server's. Turn on automatic date and time on this device.",
+        Some("AuthorizationHeaderMalformed") | Some("InvalidRegion") => "The request was signed for the wrong region. Set the region the bucket was created in.",
+        Some(_) => return Outcome::Pass("the endpoint accepted the request signature".into()),
+        None if error_status(error) == Some(401) => "The endpoint rejected the credentials. Check the access key ID and secret.",
+        None if error_status(error).is_some() => {
+            return Outcome::Pass("the endpoint accepted the request signature".into())
+        }
+        None => "The signed request did not complete. Check the earlier connection steps and try again.",
+    };
+    Outcome::fail(error.message.clone(), hint)
+}
+
+fn permission_failure(error: &SpError, denied_hint: &str) -> Outcome {
+    let hint = match (error_code(Some(error)), error_status(error)) {
+        (Some("NoSuchBucket"), _) => "The bucket does not exist at this endpoint. Check the bucket name and the provider, which decides whether the bucket is addressed by host name or path.".to_string(),
+        (Some("AccessDenied"), _) | (_, Some(403)) => denied_hint.to_string(),
+        (_, None) => "The request did not complete. Run the diagnostics again; if it keeps failing, check the connection and proxy settings.".to_string(),
+        _ => "The endpoint answered with an unexpected error. Check the provider's status page.".to_string(),
+    };
+    Outcome::fail(error.message.clone(), hint)
+}
+
+fn object_request(method: http::Method, key: &str, body: Vec<u8>) -> RawS3Request {
+    let headers = if body.is_empty() {
+        Vec::new()
+    } else {
+        vec![("content-type".into(), "text/plain".into())]
+    };
+    RawS3Request {
+        method,
+        key: key.to_string(),
+        query: Vec::new(),
+        headers,
+        body: body.into(),
+    }
+}
+
+async fn timed<T>(future: impl Future<Output = T>) -> Result<T, tokio::time::error::Elapsed> {
+    tokio::time::timeout(PROBE_TIMEOUT, future).await
+}
+
+/// Sends one unretried signed request, so each step reports the first
+/// answer the endpoint gave.
+async fn timed_s3(cfg: &R2Config, request: RawS3Request) -> SpResult<raw_s3::RawS3Response> {
+    let operation = format!("{} {}", request.method, request.key);
+    timed(raw_s3::send_once(cfg, &request))
+        .await
+        .unwrap_or_else(|_| {
+            Err(SpError {
+                kind: ErrorKind::RetryableNet,
+                message: format!(
+                    "{operation}: no response within {}s",
+                    PROBE_TIMEOUT.as_secs()
+                ),
+                retry_after_ms: None,
+                context: None,
+                at: chrono::Utc::now().timestamp_millis(),
+            })
+        })
+}
+
+fn error_code(error: Option<&SpError>) -> Option<&str> {
+    error?.context.as_ref()?.get("code")?.as_str()
+}
+
+fn error_status(error: &SpError) -> Option<u64> {
+    error.context.as_ref()?.get("status")?.as_u64()
+}
+
+/// reqwest hides the TLS or socket cause in its source chain.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,168 @@
+use super::*;
+use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+use crate::test_support::{http_date, start_path_style_s3};
+use crate::types::StorageProvider;
+
+fn stand_in_config(endpoint: String) -> R2Config {
+    R2Config {
+        endpoint,
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "photos".into(),
+        region: Some("us-east-1".into()),
+        provider: StorageProvider::Minio,
+    }
+}
+
+fn statuses(report: &DiagnosticsReport) -> Vec<(DiagnosticCheck, DiagnosticStatus)> {
+    report
+        .steps
+        .iter()
+        .map(|step| (step.check, step.status))
+        .collect()
+}
+
+fn step(report: &DiagnosticsReport, check: DiagnosticCheck) -> &DiagnosticStep {
+    report
+        .steps
+        .iter()
+        .find(|step| step.check == check)
+        .expect("every check is reported")
+}
+
+fn s3_error(status: u16, code: &str) -> StubResponse {
+    StubResponse::new(status).body(format!("<Error><Code>{code}</Code></Error>"))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_working_bucket_passes_every_step_and_leaves_no_scratch_object() {
+    use DiagnosticCheck::*;
+    let server = start_path_style_s3("photos");
+
+    let report = run_diagnostics(&stand_in_config(server.url()))
+        .await
+        .expect("diagnostics should run");
+
+    assert!(report.ok, "{report:?}");
+    assert_eq!(
+        statuses(&report),
+        [
+            Dns,
+            TlsHandshake,
+            ClockSkew,
+            Signature,
+            List,
+            Write,
+            Read,
+            Delete
+        ]
+        .map(|check| (check, DiagnosticStatus::Pass))
+    );
+    assert!(report.clock_skew_ms.expect("stand-in sends Date").abs() < 5_000);
+    assert!(step(&report, TlsHandshake).detail.contains("plain HTTP"));
+    let requests = server.requests();
+    let put = requests
+        .iter()
+        .find(|request| request.method == "PUT")
+        .expect("a scratch object should be written");
+    assert!(put.path().starts_with("/photos/.swiftpan-diagnostics/"));
+    assert!(requests
+        .iter()
+        .any(|request| request.method == "DELETE" && request.path() == put.path()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_skewed_clock_is_reported_with_the_rejected_signature() {
+    use DiagnosticCheck::*;
+    let server = LocalHttpServer::start(|request| {
+        let response = if request.header("authorization").is_some() {
+            s3_error(403, "RequestTimeTooSkewed")
+        } else {
+            s3_error(403, "AccessDenied")
+        };
+        response.header(
+            "date",
+            http_date(chrono::Utc::now() - chrono::Duration::hours(1)),
+        )
+    });
+
+    let report = run_diagnostics(&stand_in_config(server.url()))
+        .await
+        .expect("diagnostics should run");
+
+    assert!(!report.ok);
+    assert_eq!(
+        statuses(&report),
+        [
+            (Dns, DiagnosticStatus::Pass),
+            (TlsHandshake, DiagnosticStatus::Pass),
+            (ClockSkew, DiagnosticStatus::Fail),
+            (Signature, DiagnosticStatus::Fail),
+            (List, DiagnosticStatus::Skipped),
+            (Write, DiagnosticStatus::Skipped),
+            (Read, DiagnosticStatus::Skipped),
+            (Delete, DiagnosticStatus::Skipped),
+        ]
+    );
+    let skew = report.clock_skew_ms.expect("server sent a Date header");
+    assert!((3_590_000..3_610_000).contains(&skew), "{skew}");
+    let signature = step(&report, Signature);
+    assert!(signature.detail.contains("RequestTimeTooSkewed"));
+    assert!(signature
+        .hint
+        .as_deref()
+        .is_some_and(|hint| hint.contains("date and time")));
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| request.method == "GET"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_read_only_key_fails_the_write_probe_with_a_permission_hint() {
+    use DiagnosticCheck::*;
+    let server = LocalHttpServer::start(|request| match request.method.as_str() {
+        "GET" => StubResponse::new(200)
+            .body("<ListBucketResult><KeyCount>0</KeyCount></ListBucketResult>"),
+        _ => s3_error(403, "AccessDenied"),
+    });
+
+    let report = run_diagnostics(&stand_in_config(server.url()))
+        .await
+        .expect("diagnostics should run");
+
+    assert!(!report.ok);
+    assert_eq!(step(&report, Signature).status, DiagnosticStatus::Pass);
+    assert_eq!(step(&report, List).status, DiagnosticStatus::Pass);
+    let write = step(&report, Write);
+    assert_eq!(write.status, DiagnosticStatus::Fail);
+    assert!(write
+        .hint
+        .as_deref()
+        .is_some_and(|hint| hint.contains("s3:PutObject")));
+    assert_eq!(step(&report, Read).status, DiagnosticStatus::Skipped);
+    assert_eq!(step(&report, Delete).status, DiagnosticStatus::Skipped);
+    assert_eq!(report.clock_skew_ms, None);
+    assert_eq!(step(&report, ClockSkew).status, DiagnosticStatus::Skipped);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_refused_connection_stops_before_any_signed_request() {
+    use DiagnosticCheck::*;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("port should bind");
+    let endpoint = format!("http://{}", listener.local_addr().expect("bound address"));
+    drop(listener);
+
+    let report = run_diagnostics(&stand_in_config(endpoint))
+        .await
+        .expect("diagnostics should run");
+
+    assert!(!report.ok);
+    assert_eq!(step(&report, Dns).status, DiagnosticStatus::Pass);
+    let connection = step(&report, TlsHandshake);
+    assert_eq!(connection.status, DiagnosticStatus::Fail);
+    assert!(connection.hint.is_some());
+    assert!(report.steps[2..]
+        .iter()
+        .all(|step| step.status == DiagnosticStatus::Skipped));
+}
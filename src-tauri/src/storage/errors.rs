@@ -0,0 +1,151 @@
+//! Classification of storage failures.
+//!
+//! Every OpenDAL error and every HTTP status from the storage endpoint is
+//! turned into an [`ErrorKind`] here, so callers and the UI can tell a retry
+//! that may succeed from a missing object, a missing permission or a
+//! conflicting write. The resulting [`SpError`] carries the operation, the
+//! object key and, when known, the HTTP status in its context. OpenDAL
+//! errors do not expose the status, so the operator's HTTP client notes it
+//! with [`note_response`] for the task that sent the request.
+
+use crate::types::{ErrorKind, SpError};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Suggested wait before retrying a transient failure the server gave no
+/// `Retry-After` for.
+const NET_RETRY_AFTER_MS: u64 = 500;
+const RATE_LIMIT_RETRY_AFTER_MS: u64 = 1000;
+/// Tasks that ended right after a failed response leave their status behind;
+/// past this many, the notes are dropped.
+const MAX_NOTED_STATUSES: usize = 1024;
+
+/// What sent a storage request: the Tokio task, or the thread for futures
+/// driven outside a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Sender {
+    Task(tokio::task::Id),
+    Thread(std::thread::ThreadId),
+}
+
+impl Sender {
+    fn current() -> Self {
+        tokio::task::try_id().map_or_else(|| Self::Thread(std::thread::current().id()), Self::Task)
+    }
+}
+
+/// Status of the last failed response each sender received, while it is
+/// still the sender's latest response.
+static FAILED_STATUSES: Lazy<Mutex<HashMap<Sender, u16>>> = Lazy::new(Default::default);
+
+/// Notes the outcome of the latest storage request the current task sent:
+/// the status of a failed response, or `None` for a success or no response.
+pub(crate) fn note_response(failed_status: Option<u16>) {
+    let mut statuses = FAILED_STATUSES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match failed_status {
+        Some(status) => {
+            if statuses.len() >= MAX_NOTED_STATUSES {
+                statuses.clear();
+            }
+            statuses.insert(Sender::current(), status);
+        }
+        None => {
+            statuses.remove(&Sender::current());
+        }
+    }
+}
+
+/// The status of the failed response the current task received last.
+fn response_status() -> Option<u16> {
+    FAILED_STATUSES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&Sender::current())
+        .copied()
+}
+
+/// Kind for a non-success HTTP status from the storage endpoint.
+pub fn kind_for_status(status: u16) -> ErrorKind {
+    match status {
+        401 => ErrorKind::RetryableAuth,
+        403 => ErrorKind::PermissionDenied,
+        404 => ErrorKind::NotFound,
+        409 => ErrorKind::Conflict,
+        // Only sent for conditional requests: the object is no longer the
+        // version the caller saw.
+        304 | 412 => ErrorKind::SourceChanged,
+        429 => ErrorKind::RateLimited,
+        // 499 is R2's "client disconnected".
+        408 | 499 | 500 | 502 | 503 | 504 => ErrorKind::RetryableNet,
+        _ => ErrorKind::NotRetriable,
+    }
+}
+
+/// Kind for an OpenDAL error the current task just received. Failures
+/// OpenDAL marks temporary stay retryable; an unclassified failure falls
+/// back to its HTTP status, and to `RetryableNet` when no response was
+/// received.
+pub fn kind_for_opendal(error: &opendal::Error) -> ErrorKind {
+    use opendal::ErrorKind as Od;
+    match error.kind() {
+        Od::NotFound => ErrorKind::NotFound,
+        Od::PermissionDenied => ErrorKind::PermissionDenied,
+        Od::AlreadyExists => ErrorKind::Conflict,
+        Od::ConditionNotMatch => ErrorKind::SourceChanged,
+        Od::RateLimited => ErrorKind::RateLimited,
+        Od::Unsupported => ErrorKind::NotImplemented,
+        Od::ConfigInvalid
+        | Od::IsADirectory
+        | Od::NotADirectory
+        | Od::IsSameFile
+        | Od::RangeNotSatisfied => ErrorKind::NotRetriable,
+        _ if error.is_temporary() => ErrorKind::RetryableNet,
+        _ => response_status().map_or(ErrorKind::RetryableNet, kind_for_status),
+    }
+}
+
+/// Converts a failed storage call on `key` (empty for bucket-level calls)
+/// into an [`SpError`] whose message starts with `operation`.
+pub fn opendal_error(operation: &str, key: &str, error: &opendal::Error) -> SpError {
+    let kind = kind_for_opendal(error);
+    storage_error(
+        kind,
+        format!("{operation}: {error}"),
+        operation,
+        key,
+        response_status(),
+    )
+}
+
+/// Builds the error for a storage call that failed with `kind`, recording
+/// the call in [`SpError::context`].
+pub fn storage_error(
+    kind: ErrorKind,
+    message: String,
+    operation: &str,
+    key: &str,
+    status: Option<u16>,
+) -> SpError {
+    let retry_after_ms = match kind {
+        ErrorKind::RetryableNet => Some(NET_RETRY_AFTER_MS),
+        ErrorKind::RateLimited => Some(RATE_LIMIT_RETRY_AFTER_MS),
+        _ => None,
+    };
+    SpError {
+        kind,
+        message,
+        retry_after_ms,
+        context: Some(serde_json::json!({
+            "op": operation,
+            "key": key,
+            "status": status,
+        })),
+        at: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
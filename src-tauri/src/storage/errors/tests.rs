@@ -0,0 +1,139 @@
+use super::*;
+use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+use crate::test_support::{stand_in_operator, start_path_style_s3};
+
+#[test]
+fn statuses_map_to_the_kind_the_ui_acts_on() {
+    let cases = [
+        (400, ErrorKind::NotRetriable),
+        (401, ErrorKind::RetryableAuth),
+        (403, ErrorKind::PermissionDenied),
+        (404, ErrorKind::NotFound),
+        (409, ErrorKind::Conflict),
+        (412, ErrorKind::SourceChanged),
+        (429, ErrorKind::RateLimited),
+        (501, ErrorKind::NotRetriable),
+        (503, ErrorKind::RetryableNet),
+    ];
+    for (status, expected) in cases {
+        assert_eq!(
+            kind_for_status(status).as_str(),
+            expected.as_str(),
+            "HTTP {status}"
+        );
+    }
+}
+
+#[test]
+fn opendal_kinds_are_classified_before_falling_back_to_retryable() {
+    use opendal::{Error, ErrorKind as Od};
+    let cases = [
+        (Error::new(Od::NotFound, "gone"), ErrorKind::NotFound),
+        (Error::new(Od::AlreadyExists, "taken"), ErrorKind::Conflict),
+        (
+            Error::new(Od::ConditionNotMatch, "etag"),
+            ErrorKind::SourceChanged,
+        ),
+        (
+            Error::new(Od::ConfigInvalid, "bucket"),
+            ErrorKind::NotRetriable,
+        ),
+        (
+            Error::new(Od::Unexpected, "reset").set_temporary(),
+            ErrorKind::RetryableNet,
+        ),
+        (
+            Error::new(Od::Unexpected, "no response"),
+            ErrorKind::RetryableNet,
+        ),
+    ];
+    for (error, expected) in cases {
+        assert_eq!(
+            kind_for_opendal(&error).as_str(),
+            expected.as_str(),
+            "{error}"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_missing_object_is_not_found_with_its_key_in_the_context() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+
+    let error = operator
+        .stat("albums/missing.jpg")
+        .await
+        .expect_err("missing object");
+    let error = opendal_error("HeadObject", "albums/missing.jpg", &error);
+
+    assert!(matches!(error.kind, ErrorKind::NotFound));
+    assert_eq!(error.retry_after_ms, None);
+    assert!(error.message.starts_with("HeadObject: "));
+    assert_eq!(
+        error.context,
+        Some(serde_json::json!({
+            "op": "HeadObject",
+            "key": "albums/missing.jpg",
+            "status": 404,
+        }))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unclassified_responses_fall_back_to_their_status() {
+    for (status, code, expected) in [
+        (429, "TooManyRequests", ErrorKind::RateLimited),
+        (401, "Unauthorized", ErrorKind::RetryableAuth),
+        (403, "AccessDenied", ErrorKind::PermissionDenied),
+    ] {
+        let server = LocalHttpServer::start(move |_| {
+            StubResponse::new(status).body(format!("<Error><Code>{code}</Code></Error>"))
+        });
+        let operator = stand_in_operator(&server, "photos");
+
+        let error = operator.read("a.txt").await.expect_err("request fails");
+        let error = opendal_error("GetObject", "a.txt", &error);
+
+        assert_eq!(error.kind.as_str(), expected.as_str(), "HTTP {status}");
+        assert_eq!(
+            error
+                .context
+                .as_ref()
+                .and_then(|context| context["status"].as_u64()),
+            Some(u64::from(status))
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_status_comes_from_the_latest_response_not_the_message() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    operator
+        .stat("missing.jpg")
+        .await
+        .expect_err("missing object");
+    operator
+        .write("present.jpg", b"jpeg".to_vec())
+        .await
+        .expect("object should be stored");
+
+    let error = opendal_error(
+        "GetObject",
+        "present.jpg",
+        &opendal::Error::new(
+            opendal::ErrorKind::Unexpected,
+            "response: Parts { status: 401, version: HTTP/1.1 }",
+        ),
+    );
+
+    assert!(matches!(error.kind, ErrorKind::RetryableNet));
+    assert_eq!(
+        error
+            .context
+            .as_ref()
+            .map(|context| context["status"].clone()),
+        Some(serde_json::Value::Null)
+    );
+}
@@ -1,28 +1,50 @@
 //! OpenDAL storage-backend construction and lifecycle.
 //!
 //! This module turns the persisted backend configuration into a cached,
-//! instrumented [`Operator`]. It owns endpoint normalization, credentials,
-//! HTTP/TLS configuration, cache invalidation, and connectivity checks. It
-//! must not contain SwiftPan object browsing, transfer, thumbnail, sharing, or
-//! usage-ledger business rules.
+//! instrumented [`Operator`]. It owns provider dialect defaults, endpoint
+//! normalization, credentials, HTTP/TLS configuration, cache invalidation, and
+//! connectivity checks. It must not contain SwiftPan object browsing, transfer,
+//! thumbnail, sharing, or usage-ledger business rules.
 
+use crate::settings::NetworkSettings;
+use crate::sp_backend::CredentialBundle;
 use crate::types::*;
 use once_cell::sync::Lazy;
 use opendal::services::S3;
 use opendal::{layers::HttpClientLayer, raw::HttpClient, Operator};
+use std::collections::HashMap;
 use std::time::Duration;
 use tokio::sync::{Mutex, RwLock};
-// use std::time::Duration; // not currently used directly
 
-// Cache one configured operator per credential fingerprint.
-static OPERATOR_CACHE: Lazy<RwLock<Option<(String, Operator)>>> = Lazy::new(|| RwLock::new(None));
+mod completion;
+mod diagnostics;
+mod errors;
+pub(crate) mod multipart;
+mod provider;
+pub(crate) mod raw_s3;
+
+pub(crate) use completion::{completing_with, ConditionalCompletion};
+pub use diagnostics::run_diagnostics;
+pub(crate) use errors::note_response;
+pub use errors::{kind_for_opendal, kind_for_status, opendal_error, storage_error};
+pub use raw_s3::replace_object_metadata;
+
+// Cache one configured operator per storage profile, tagged with the
+// credential fingerprint it was built from.
+static OPERATOR_CACHE: Lazy<RwLock<HashMap<String, (String, Operator)>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
 // Serialize construction to avoid concurrent backend initialization races.
 static OPERATOR_BUILD_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+// The reqwest client for requests sent outside an operator, tagged with the
+// network settings it was built from so its connection pool is reused.
+static HTTP_CLIENT_CACHE: Lazy<std::sync::Mutex<Option<(String, reqwest::Client)>>> =
+    Lazy::new(|| std::sync::Mutex::new(None));
 
 fn cfg_fingerprint(cfg: &R2Config) -> String {
     // Note: this is an in-memory identifier; we don't log it to avoid leaking secrets.
     format!(
-        "{}|{}|{}|{}|{}",
+        "{}|{}|{}|{}|{}|{}",
+        cfg.provider.as_str(),
         cfg.endpoint,
         cfg.access_key_id,
         cfg.secret_access_key,
@@ -31,10 +53,11 @@ fn cfg_fingerprint(cfg: &R2Config) -> String {
     )
 }
 
-pub async fn build_operator(cfg: &R2Config) -> SpResult<Operator> {
-    // Serve from cache if config matches
-    let fp = cfg_fingerprint(cfg);
-    if let Some((cached_fp, cached)) = OPERATOR_CACHE.read().await.as_ref() {
+pub async fn build_operator(profile: &str, cfg: &R2Config) -> SpResult<Operator> {
+    // Serve from cache if config and network settings match
+    let network = crate::settings::get().network;
+    let fp = operator_fingerprint(cfg, &network);
+    if let Some((cached_fp, cached)) = OPERATOR_CACHE.read().await.get(profile) {
         if *cached_fp == fp {
             crate::logger::debug("storage", "build_operator using cached instance");
             return Ok(cached.clone());
@@ -44,7 +67,7 @@ pub async fn build_operator(cfg: &R2Config) -> SpResult<Operator> {
     // Serialize construction to avoid concurrent builds which might hang on some platforms
     let _guard = OPERATOR_BUILD_LOCK.lock().await;
     // Double-check after acquiring the lock
-    if let Some((cached_fp, cached)) = OPERATOR_CACHE.read().await.as_ref() {
+    if let Some((cached_fp, cached)) = OPERATOR_CACHE.read().await.get(profile) {
         if *cached_fp == fp {
             crate::logger::debug(
                 "storage",
@@ -56,7 +79,9 @@ pub async fn build_operator(cfg: &R2Config) -> SpResult<Operator> {
     crate::logger::debug(
         "storage",
         &format!(
-            "build_operator endpoint={} bucket={} region={}",
+            "build_operator profile={} provider={} endpoint={} bucket={} region={}",
+            profile,
+            cfg.provider.as_str(),
             cfg.endpoint,
             cfg.bucket,
             cfg.region.as_deref().unwrap_or("auto")
@@ -64,46 +89,16 @@ pub async fn build_operator(cfg: &R2Config) -> SpResult<Operator> {
     );
     // Prevent IMDS probing on mobile which can stall silently
     std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
-    let region = cfg.region.clone().unwrap_or_else(|| "auto".to_string());
-    // Sanitize endpoint to origin-only: scheme://host[:port]
-    let mut endpoint = cfg.endpoint.clone();
-    if let Some(pos) = endpoint.find('#') {
-        endpoint.truncate(pos);
-    }
-    if let Some(pos) = endpoint.find('?') {
-        endpoint.truncate(pos);
-    }
-    if let Some(scheme_pos) = endpoint.find("://") {
-        let auth_start = scheme_pos + 3;
-        if let Some(rel_pos) = endpoint[auth_start..].find('/') {
-            endpoint.truncate(auth_start + rel_pos);
-        }
-    } else if let Some(rel_pos) = endpoint.find('/') {
-        endpoint.truncate(rel_pos);
-    }
-    while endpoint.ends_with('/') {
-        endpoint.pop();
-    }
-    // Build OpenDAL S3 operator
-    let mut builder = S3::default();
-    builder = builder.access_key_id(cfg.access_key_id.as_str());
-    builder = builder.secret_access_key(cfg.secret_access_key.as_str());
-    builder = builder.endpoint(endpoint.as_str());
-    builder = builder.region(region.as_str());
-    builder = builder.bucket(cfg.bucket.as_str());
-    // Build reqwest client pinned to rustls + webpki roots for consistent TLS across desktop/mobile
-    // and wrap with our HTTP instrumentation for precise S3 Class A/B accounting.
-    let req_builder = reqwest::Client::builder().use_rustls_tls();
-    let req_client = req_builder.build().map_err(|e| SpError {
-        kind: ErrorKind::NotRetriable,
-        message: format!("HttpClient build failed: {}", e),
-        retry_after_ms: None,
-        context: None,
-        at: chrono::Utc::now().timestamp_millis(),
-    })?;
+    let builder = s3_builder(cfg)?;
+    // Wrap the shared client with our HTTP instrumentation for precise S3 Class A/B accounting.
+    let req_client = client_builder(&network)?.build().map_err(client_error)?;
     // Wrap with our InstrumentedReqwest, then construct OpenDAL HttpClient from it.
-    let instr = crate::usage::http_instrument::InstrumentedReqwest::new(req_client);
-    let http_client = HttpClient::with(instr);
+    let instr = crate::usage::http_instrument::InstrumentedReqwest::new(req_client, profile);
+    // Retries wrap instrumentation so every attempt is counted, and the
+    // retry policy sees the preconditions added to multipart completions.
+    let http_client = HttpClient::with(ConditionalCompletion::new(
+        crate::retry::RetryingFetch::new(instr, crate::retry::RetryPolicy::from_settings(&network)),
+    ));
 
     // Build operator and inject custom HTTP client via layer
     let op = Operator::new(builder)
@@ -121,84 +116,202 @@ pub async fn build_operator(cfg: &R2Config) -> SpResult<Operator> {
     crate::logger::info("storage", "build_operator ok");
     {
         let mut w = OPERATOR_CACHE.write().await;
-        *w = Some((fp, op.clone()));
+        w.insert(profile.to_string(), (fp, op.clone()));
     }
     Ok(op)
 }
 
-pub async fn network_precheck() -> SpResult<()> {
-    crate::logger::info("r2", "Starting network precheck");
-
-    // 1. 先测试基础网络连接
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| SpError {
-            kind: ErrorKind::NotRetriable,
-            message: format!("Failed to create HTTP client: {}", e),
-            retry_after_ms: None,
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        })?;
-
-    // 测试基础网络连通性
-    crate::logger::debug("r2", "Testing basic network connectivity");
-    let response = tokio::time::timeout(
-        Duration::from_secs(8),
-        client.get("https://www.cloudflare.com").send(),
+/// Network settings shape the HTTP client inside an operator, so a change to
+/// them must miss the cache just like a credential change.
+fn operator_fingerprint(cfg: &R2Config, network: &NetworkSettings) -> String {
+    format!(
+        "{}|{}",
+        cfg_fingerprint(cfg),
+        serde_json::to_string(network).unwrap_or_default()
     )
-    .await;
+}
 
-    match response {
-        Ok(Ok(resp)) => {
-            crate::logger::info("r2", &format!("Basic network test OK: {}", resp.status()));
+/// The reqwest client for storage traffic, and for the app's other outbound
+/// requests, under the current network settings. It is built once per
+/// settings change; clones share its connection pool.
+pub(crate) fn http_client() -> SpResult<reqwest::Client> {
+    let network = crate::settings::get().network;
+    let fp = serde_json::to_string(&network).unwrap_or_default();
+    let mut cache = HTTP_CLIENT_CACHE
+        .lock()
+        .map_err(|_| err_invalid("HTTP client cache poisoned"))?;
+    if let Some((cached_fp, cached)) = cache.as_ref() {
+        if *cached_fp == fp {
+            return Ok(cached.clone());
         }
-        Ok(Err(e)) => {
-            crate::logger::error("r2", &format!("Basic network test failed: {}", e));
-            return Err(SpError {
-                kind: ErrorKind::RetryableNet,
-                message: format!("Network connectivity failed: {}", e),
-                retry_after_ms: Some(3000),
-                context: None,
-                at: chrono::Utc::now().timestamp_millis(),
-            });
+    }
+    let client = client_builder(&network)?.build().map_err(client_error)?;
+    *cache = Some((fp, client.clone()));
+    Ok(client)
+}
+
+/// Client configuration shared by every storage request: rustls + webpki
+/// roots for consistent TLS across desktop/mobile, plus the user's extra
+/// roots, proxy and timeouts.
+fn client_builder(network: &NetworkSettings) -> SpResult<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder().use_rustls_tls();
+    if network.connect_timeout_secs > 0 {
+        builder = builder.connect_timeout(Duration::from_secs(network.connect_timeout_secs));
+    }
+    if network.read_timeout_secs > 0 {
+        builder = builder.read_timeout(Duration::from_secs(network.read_timeout_secs));
+    }
+    for pem in &network.extra_root_pems {
+        let certificates = reqwest::Certificate::from_pem_bundle(pem.as_bytes())
+            .map_err(|e| invalid_network_setting(&format!("extra root certificate: {e}")))?;
+        if certificates.is_empty() {
+            return Err(invalid_network_setting(
+                "extra root certificate: no PEM certificate found",
+            ));
         }
-        Err(_) => {
-            crate::logger::error("r2", "Basic network test timed out");
-            return Err(SpError {
-                kind: ErrorKind::RetryableNet,
-                message: "Network connectivity timeout".to_string(),
-                retry_after_ms: Some(3000),
-                context: None,
-                at: chrono::Utc::now().timestamp_millis(),
-            });
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    if let Some(url) = network
+        .proxy_url
+        .as_deref()
+        .filter(|url| !url.trim().is_empty())
+    {
+        let mut proxy = reqwest::Proxy::all(url.trim())
+            .map_err(|e| invalid_network_setting(&format!("proxy URL: {e}")))?;
+        if let Some(username) = network.proxy_username.as_deref().filter(|u| !u.is_empty()) {
+            proxy = proxy.basic_auth(
+                username,
+                network.proxy_password.as_deref().unwrap_or_default(),
+            );
         }
+        let no_proxy = network
+            .no_proxy
+            .iter()
+            .map(|entry| entry.trim())
+            .filter(|entry| !entry.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+        proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&no_proxy));
+        builder = builder.proxy(proxy);
     }
+    Ok(builder)
+}
 
+/// Rejects network settings the storage client could not be built from, so
+/// a bad proxy URL or certificate fails when saved rather than on each request.
+pub fn validate_network_settings(network: &NetworkSettings) -> SpResult<()> {
+    client_builder(network)?.build().map_err(client_error)?;
     Ok(())
 }
 
-pub async fn sanity_check(operator: &Operator) -> SpResult<()> {
-    crate::logger::debug("r2", "sanity_check(list 1) start");
-    network_precheck().await?;
-    let l = operator.list("").await.map_err(|e| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("list root: {}", e),
-        retry_after_ms: Some(500),
+fn invalid_network_setting(message: &str) -> SpError {
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("invalid network setting: {message}"),
+        retry_after_ms: None,
+        context: None,
+        at: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+fn client_error(e: reqwest::Error) -> SpError {
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("HttpClient build failed: {}", e),
+        retry_after_ms: None,
+        context: None,
+        at: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+/// Configure the OpenDAL S3 builder with the provider's dialect defaults.
+fn s3_builder(cfg: &R2Config) -> SpResult<S3> {
+    let defaults = provider::defaults_for(cfg.provider);
+    let region = provider::resolve_region(cfg)?;
+    let endpoint = provider::resolve_endpoint(cfg, &region)?;
+    let mut builder = S3::default()
+        .access_key_id(cfg.access_key_id.as_str())
+        .secret_access_key(cfg.secret_access_key.as_str())
+        .endpoint(endpoint.as_str())
+        .region(region.as_str())
+        .bucket(cfg.bucket.as_str());
+    // Dotted bucket names break TLS wildcard matching under virtual hosts.
+    if defaults.virtual_host_style && !cfg.bucket.contains('.') {
+        builder = builder.enable_virtual_host_style();
+    }
+    if !defaults.conditional_writes {
+        builder = builder.disable_write_with_if_match();
+    }
+    if let Some(algorithm) = defaults.checksum_algorithm {
+        builder = builder.checksum_algorithm(algorithm);
+    }
+    Ok(builder)
+}
+
+/// Build the operator for a specific profile instead of the active one.
+///
+/// Transfers record the profile they started under and resolve it here on
+/// resume, so switching profiles never redirects them to another bucket.
+pub async fn build_profile_operator(
+    bundle: &CredentialBundle,
+    profile: &str,
+) -> SpResult<Operator> {
+    let cfg = bundle.profile(profile).ok_or_else(|| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("storage profile '{profile}' no longer exists"),
+        retry_after_ms: None,
         context: None,
         at: chrono::Utc::now().timestamp_millis(),
     })?;
+    build_operator(profile, cfg).await
+}
+
+/// The name of the vault profile configured with `cfg`, preferring the
+/// active one. `None` while the vault is locked or for credentials that were
+/// never saved, such as those being tested before saving.
+pub(crate) fn profile_for_config(cfg: &R2Config) -> Option<String> {
+    let bundle = crate::sp_backend::SpBackend::get_decrypted_bundle_if_unlocked().ok()?;
+    let fingerprint = cfg_fingerprint(cfg);
+    if cfg_fingerprint(&bundle.r2) == fingerprint {
+        return Some(bundle.active_profile);
+    }
+    bundle
+        .profiles
+        .into_iter()
+        .find(|(_, profile_cfg)| cfg_fingerprint(profile_cfg) == fingerprint)
+        .map(|(name, _)| name)
+}
+
+pub async fn sanity_check(operator: &Operator) -> SpResult<()> {
+    crate::logger::debug("r2", "sanity_check(list 1) start");
+    let l = operator
+        .list("")
+        .await
+        .map_err(|e| opendal_error("list root", "", &e))?;
     let _ = l.first();
     crate::logger::info("r2", "sanity_check ok (list 1)");
     Ok(())
 }
 
-/// Invalidate the cached operator, forcing the next build to reconstruct it.
+/// Invalidate every cached operator, forcing the next build to reconstruct it.
 pub async fn invalidate_cached_operator() {
     let mut w = OPERATOR_CACHE.write().await;
-    *w = None;
+    w.clear();
     crate::logger::info("storage", "storage operator cache invalidated");
 }
 
+/// Drop the cached operator of a single profile, e.g. after it was deleted.
+pub async fn evict_cached_operator(profile: &str) {
+    let mut w = OPERATOR_CACHE.write().await;
+    if w.remove(profile).is_some() {
+        crate::logger::info(
+            "storage",
+            &format!("storage operator cache evicted profile={profile}"),
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests;
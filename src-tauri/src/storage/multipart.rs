@@ -0,0 +1,314 @@
+//! S3 multipart uploads addressed by their `UploadId`.
+//!
+//! OpenDAL's writer runs multipart uploads internally and never reveals the
+//! `UploadId`, so an upload interrupted by an app restart could only start
+//! over. These signed requests let the upload engine create a session,
+//! persist its id, ask the bucket which parts it already holds (ListParts)
+//! and finish or abort the session later. Which parts to send is the
+//! caller's decision. Part copies rewrite objects too large for a single
+//! CopyObject.
+
+use super::raw_s3::{self, RawS3Request};
+use crate::types::{err_invalid, R2Config, SpResult};
+
+/// Largest part number S3 accepts.
+pub(crate) const MAX_PARTS: u64 = 10_000;
+
+/// A part the bucket has stored for an open multipart upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletedPart {
+    pub part_number: u32,
+    pub etag: String,
+    pub size: u64,
+}
+
+/// Starts a multipart upload and returns its `UploadId`. `headers` become the
+/// content headers of the finished object.
+pub(crate) async fn create(
+    cfg: &R2Config,
+    key: &str,
+    headers: Vec<(String, String)>,
+) -> SpResult<String> {
+    let response = raw_s3::send(
+        cfg,
+        RawS3Request {
+            method: http::Method::POST,
+            key: key.to_string(),
+            query: vec![("uploads".into(), String::new())],
+            headers,
+            body: bytes::Bytes::new(),
+        },
+    )
+    .await?;
+    let body = String::from_utf8_lossy(&response.body);
+    element(&body, "UploadId")
+        .map(str::to_string)
+        .filter(|upload_id| !upload_id.is_empty())
+        .ok_or_else(|| err_invalid("CreateMultipartUpload response carried no UploadId"))
+}
+
+/// Uploads one part and returns the ETag the bucket assigned to it.
+pub(crate) async fn upload_part(
+    cfg: &R2Config,
+    key: &str,
+    upload_id: &str,
+    part_number: u32,
+    body: bytes::Bytes,
+) -> SpResult<CompletedPart> {
+    let size = body.len() as u64;
+    let response = raw_s3::send(
+        cfg,
+        RawS3Request {
+            method: http::Method::PUT,
+            key: key.to_string(),
+            query: upload_query(upload_id, Some(part_number)),
+            headers: Vec::new(),
+            body,
+        },
+    )
+    .await?;
+    let etag = response
+        .headers
+        .get(http::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| err_invalid("UploadPart response carried no ETag"))?;
+    Ok(CompletedPart {
+        part_number,
+        etag,
+        size,
+    })
+}
+
+/// Rewrites `key` onto itself through a multipart upload whose parts, of at
+/// most `part_size` bytes, are copied from `source` with UploadPartCopy.
+/// This is how an object too large for one CopyObject gets new `headers`.
+/// With `source_etag`, every part copy and the completion require the
+/// object to still have that ETag. A failed rewrite is aborted and leaves
+/// the object as it was.
+pub(crate) async fn copy_onto_itself(
+    cfg: &R2Config,
+    key: &str,
+    source: &str,
+    size: u64,
+    part_size: u64,
+    headers: Vec<(String, String)>,
+    source_etag: Option<&str>,
+) -> SpResult<()> {
+    let upload_id = create(cfg, key, headers).await?;
+    let copied = async {
+        let mut parts = Vec::new();
+        for (index, start) in (0..size).step_by(part_size as usize).enumerate() {
+            let end = (start + part_size).min(size) - 1;
+            parts.push(
+                upload_part_copy(
+                    cfg,
+                    key,
+                    &upload_id,
+                    index as u32 + 1,
+                    source,
+                    (start, end),
+                    source_etag,
+                )
+                .await?,
+            );
+        }
+        let preconditions = source_etag
+            .map(|etag| vec![("if-match".to_string(), etag.to_string())])
+            .unwrap_or_default();
+        complete(cfg, key, &upload_id, &parts, preconditions).await
+    }
+    .await;
+    if copied.is_err() {
+        let _ = abort(cfg, key, &upload_id).await;
+    }
+    copied
+}
+
+/// Copies bytes `range.0..=range.1` of `source` into one part.
+async fn upload_part_copy(
+    cfg: &R2Config,
+    key: &str,
+    upload_id: &str,
+    part_number: u32,
+    source: &str,
+    range: (u64, u64),
+    source_etag: Option<&str>,
+) -> SpResult<CompletedPart> {
+    let mut headers = vec![
+        ("x-amz-copy-source".to_string(), source.to_string()),
+        (
+            "x-amz-copy-source-range".to_string(),
+            format!("bytes={}-{}", range.0, range.1),
+        ),
+    ];
+    if let Some(etag) = source_etag {
+        headers.push(("x-amz-copy-source-if-match".to_string(), etag.to_string()));
+    }
+    let response = raw_s3::send(
+        cfg,
+        RawS3Request {
+            method: http::Method::PUT,
+            key: key.to_string(),
+            query: upload_query(upload_id, Some(part_number)),
+            headers,
+            body: bytes::Bytes::new(),
+        },
+    )
+    .await?;
+    let body = String::from_utf8_lossy(&response.body);
+    // Like CopyObject, a part copy can fail after a 200 status line.
+    if body.contains("<Error>") {
+        return Err(raw_s3::status_error(
+            "UploadPartCopy",
+            key,
+            500,
+            &response.body,
+        ));
+    }
+    let etag = element(&body, "ETag")
+        .map(unescape)
+        .ok_or_else(|| err_invalid("UploadPartCopy response carried no ETag"))?;
+    Ok(CompletedPart {
+        part_number,
+        etag,
+        size: range.1 - range.0 + 1,
+    })
+}
+
+/// Every part the bucket holds for `upload_id`, in part-number order.
+pub(crate) async fn list_parts(
+    cfg: &R2Config,
+    key: &str,
+    upload_id: &str,
+) -> SpResult<Vec<CompletedPart>> {
+    let mut parts = Vec::new();
+    let mut marker = None::<String>;
+    loop {
+        let mut query = upload_query(upload_id, None);
+        if let Some(marker) = &marker {
+            query.push(("part-number-marker".into(), marker.clone()));
+        }
+        let response = raw_s3::send(
+            cfg,
+            RawS3Request {
+                method: http::Method::GET,
+                key: key.to_string(),
+                query,
+                headers: Vec::new(),
+                body: bytes::Bytes::new(),
+            },
+        )
+        .await?;
+        let body = String::from_utf8_lossy(&response.body);
+        for chunk in body.split("<Part>").skip(1) {
+            let part = chunk.split("</Part>").next().unwrap_or_default();
+            let parsed = (|| {
+                Some(CompletedPart {
+                    part_number: element(part, "PartNumber")?.trim().parse().ok()?,
+                    etag: unescape(element(part, "ETag")?),
+                    size: element(part, "Size")?.trim().parse().ok()?,
+                })
+            })();
+            parts.push(parsed.ok_or_else(|| err_invalid("ListParts returned an unreadable part"))?);
+        }
+        let truncated = element(&body, "IsTruncated").is_some_and(|value| value.trim() == "true");
+        let next = element(&body, "NextPartNumberMarker").map(|value| value.trim().to_string());
+        match next {
+            Some(next) if truncated && marker.as_ref() != Some(&next) => marker = Some(next),
+            _ => break,
+        }
+    }
+    parts.sort_by_key(|part| part.part_number);
+    Ok(parts)
+}
+
+/// Assembles the object from `parts`, which must be in part-number order.
+/// `preconditions` are `If-Match` / `If-None-Match` headers the bucket
+/// checks against the object the upload would replace.
+pub(crate) async fn complete(
+    cfg: &R2Config,
+    key: &str,
+    upload_id: &str,
+    parts: &[CompletedPart],
+    preconditions: Vec<(String, String)>,
+) -> SpResult<()> {
+    let mut body = String::from("<CompleteMultipartUpload>");
+    for part in parts {
+        body.push_str(&format!(
+            "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+            part.part_number,
+            escape(&part.etag)
+        ));
+    }
+    body.push_str("</CompleteMultipartUpload>");
+    let response = raw_s3::send(
+        cfg,
+        RawS3Request {
+            method: http::Method::POST,
+            key: key.to_string(),
+            query: upload_query(upload_id, None),
+            headers: std::iter::once(("content-type".into(), "application/xml".into()))
+                .chain(preconditions)
+                .collect(),
+            body: body.into(),
+        },
+    )
+    .await?;
+    // Like CopyObject, completion can fail after a 200 status line.
+    if String::from_utf8_lossy(&response.body).contains("<Error>") {
+        return Err(raw_s3::status_error(
+            "CompleteMultipartUpload",
+            key,
+            500,
+            &response.body,
+        ));
+    }
+    Ok(())
+}
+
+/// Discards the session and every part stored for it.
+pub(crate) async fn abort(cfg: &R2Config, key: &str, upload_id: &str) -> SpResult<()> {
+    raw_s3::send(
+        cfg,
+        RawS3Request {
+            method: http::Method::DELETE,
+            key: key.to_string(),
+            query: upload_query(upload_id, None),
+            headers: Vec::new(),
+            body: bytes::Bytes::new(),
+        },
+    )
+    .await
+    .map(|_| ())
+}
+
+fn upload_query(upload_id: &str, part_number: Option<u32>) -> Vec<(String, String)> {
+    let mut query = vec![("uploadId".to_string(), upload_id.to_string())];
+    if let Some(part_number) = part_number {
+        query.push(("partNumber".into(), part_number.to_string()));
+    }
+    query
+}
+
+/// Inner text of the first `<name>` element. Multipart responses never nest
+/// an element in one of the same name.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let (_, rest) = xml.split_once(&format!("<{name}>"))?;
+    rest.split_once(&format!("</{name}>"))
+        .map(|(inner, _)| inner)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
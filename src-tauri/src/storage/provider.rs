@@ -0,0 +1,164 @@
+//! Per-provider S3 dialect defaults.
+//!
+//! This module maps a [`StorageProvider`] to the addressing style, region,
+//! endpoint shape and request quirks that `build_operator` applies to the
+//! OpenDAL S3 builder. It must not build HTTP clients, cache operators, or
+//! carry pricing; cost estimates belong to the usage ledger.
+
+use crate::types::{err_invalid, R2Config, SpResult, StorageProvider};
+
+pub(super) struct ProviderDefaults {
+    /// Address buckets as `bucket.host` instead of `host/bucket`.
+    pub(super) virtual_host_style: bool,
+    /// Region used when the config leaves it empty or at R2's `auto`.
+    pub(super) default_region: Option<&'static str>,
+    /// Keep a path after the endpoint origin, e.g. MinIO behind a proxy prefix.
+    pub(super) keep_endpoint_path: bool,
+    /// Whether the service honours `If-Match` / `If-None-Match` on PUT.
+    pub(super) conditional_writes: bool,
+    pub(super) checksum_algorithm: Option<&'static str>,
+}
+
+pub(super) fn defaults_for(provider: StorageProvider) -> ProviderDefaults {
+    match provider {
+        StorageProvider::R2 | StorageProvider::R2Eu | StorageProvider::R2Fedramp => {
+            ProviderDefaults {
+                virtual_host_style: false,
+                default_region: Some("auto"),
+                keep_endpoint_path: false,
+                conditional_writes: true,
+                checksum_algorithm: None,
+            }
+        }
+        StorageProvider::AwsS3 => ProviderDefaults {
+            virtual_host_style: true,
+            default_region: Some("us-east-1"),
+            keep_endpoint_path: false,
+            conditional_writes: true,
+            // Buckets with Object Lock reject PUTs that carry no checksum.
+            checksum_algorithm: Some("crc32c"),
+        },
+        StorageProvider::Minio => ProviderDefaults {
+            virtual_host_style: false,
+            default_region: Some("us-east-1"),
+            keep_endpoint_path: true,
+            conditional_writes: true,
+            checksum_algorithm: None,
+        },
+        StorageProvider::BackblazeB2 => ProviderDefaults {
+            virtual_host_style: true,
+            // The signing region is the cluster name embedded in the endpoint.
+            default_region: None,
+            keep_endpoint_path: false,
+            conditional_writes: false,
+            checksum_algorithm: None,
+        },
+        StorageProvider::Wasabi => ProviderDefaults {
+            virtual_host_style: true,
+            default_region: Some("us-east-1"),
+            keep_endpoint_path: false,
+            conditional_writes: false,
+            checksum_algorithm: None,
+        },
+    }
+}
+
+pub(super) fn resolve_region(cfg: &R2Config) -> SpResult<String> {
+    let explicit = cfg
+        .region
+        .as_deref()
+        .map(str::trim)
+        .filter(|region| !region.is_empty());
+    let is_r2 = matches!(
+        cfg.provider,
+        StorageProvider::R2 | StorageProvider::R2Eu | StorageProvider::R2Fedramp
+    );
+    // Forms default to R2's "auto", which no other provider can sign for.
+    if let Some(region) = explicit.filter(|region| is_r2 || *region != "auto") {
+        return Ok(region.to_string());
+    }
+    if cfg.provider == StorageProvider::BackblazeB2 {
+        return b2_region_from_endpoint(&cfg.endpoint).ok_or_else(|| {
+            err_invalid("Backblaze B2 needs a region or an s3.<region>.backblazeb2.com endpoint")
+        });
+    }
+    Ok(defaults_for(cfg.provider)
+        .default_region
+        .unwrap_or("us-east-1")
+        .to_string())
+}
+
+pub(super) fn resolve_endpoint(cfg: &R2Config, region: &str) -> SpResult<String> {
+    let raw = cfg.endpoint.trim();
+    if raw.is_empty() {
+        return match cfg.provider {
+            StorageProvider::AwsS3 => Ok(format!("https://s3.{region}.amazonaws.com")),
+            StorageProvider::Wasabi => Ok(format!("https://s3.{region}.wasabisys.com")),
+            _ => Err(err_invalid(
+                "endpoint is required for this storage provider",
+            )),
+        };
+    }
+    let endpoint = normalize_endpoint(raw, defaults_for(cfg.provider).keep_endpoint_path);
+    match cfg.provider {
+        StorageProvider::R2Eu => Ok(with_r2_jurisdiction(&endpoint, "eu")),
+        StorageProvider::R2Fedramp => Ok(with_r2_jurisdiction(&endpoint, "fedramp")),
+        _ => Ok(endpoint),
+    }
+}
+
+/// Drops query, fragment and trailing slashes; drops the path as well unless
+/// the provider is commonly deployed under one.
+fn normalize_endpoint(raw: &str, keep_path: bool) -> String {
+    let mut endpoint = raw.to_string();
+    if let Some(pos) = endpoint.find('#') {
+        endpoint.truncate(pos);
+    }
+    if let Some(pos) = endpoint.find('?') {
+        endpoint.truncate(pos);
+    }
+    if !keep_path {
+        if let Some(scheme_pos) = endpoint.find("://") {
+            let auth_start = scheme_pos + 3;
+            if let Some(rel_pos) = endpoint[auth_start..].find('/') {
+                endpoint.truncate(auth_start + rel_pos);
+            }
+        } else if let Some(rel_pos) = endpoint.find('/') {
+            endpoint.truncate(rel_pos);
+        }
+    }
+    while endpoint.ends_with('/') {
+        endpoint.pop();
+    }
+    endpoint
+}
+
+/// Turns `https://<account>.r2.cloudflarestorage.com` into the jurisdiction
+/// host `https://<account>.<jurisdiction>.r2.cloudflarestorage.com`. Endpoints
+/// that already name a jurisdiction or are custom domains are left alone.
+fn with_r2_jurisdiction(endpoint: &str, jurisdiction: &str) -> String {
+    const R2_SUFFIX: &str = ".r2.cloudflarestorage.com";
+    let (scheme, host) = match endpoint.find("://") {
+        Some(pos) => endpoint.split_at(pos + 3),
+        None => ("", endpoint),
+    };
+    match host.strip_suffix(R2_SUFFIX) {
+        Some(account) if !account.is_empty() && !account.contains('.') => {
+            format!("{scheme}{account}.{jurisdiction}{R2_SUFFIX}")
+        }
+        _ => endpoint.to_string(),
+    }
+}
+
+fn b2_region_from_endpoint(endpoint: &str) -> Option<String> {
+    let host = endpoint
+        .split("://")
+        .last()
+        .unwrap_or(endpoint)
+        .split(['/', ':'])
+        .next()?;
+    host.strip_prefix("s3.")?
+        .strip_suffix(".backblazeb2.com")
+        .filter(|region| !region.is_empty() && !region.contains('.'))
+        .map(str::to_string)
+}
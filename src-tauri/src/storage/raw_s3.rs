@@ -0,0 +1,432 @@
+//! SigV4-signed S3 requests for operations OpenDAL does not expose.
+//!
+//! OpenDAL covers reads, writes, listing and plain copies. CopyObject with a
+//! `REPLACE` metadata directive, bucket subresources and multipart uploads
+//! whose `UploadId` must outlive the process have no OpenDAL API, so they are
+//! addressed here with the same provider rules as the operator
+//! and signed directly. Requests are counted in the usage ledger like
+//! operator traffic. Callers own the S3 semantics of each request.
+
+use super::provider;
+use crate::types::{ErrorKind, ObjectMetadataUpdate, R2Config, SpError, SpResult};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub(crate) struct RawS3Request {
+    pub(crate) method: http::Method,
+    /// Object key, or empty for a bucket-level request.
+    pub(crate) key: String,
+    pub(crate) query: Vec<(String, String)>,
+    pub(crate) headers: Vec<(String, String)>,
+    /// Shared, not copied, by each retry attempt.
+    pub(crate) body: bytes::Bytes,
+}
+
+pub(crate) struct RawS3Response {
+    pub(crate) headers: http::HeaderMap,
+    pub(crate) body: Vec<u8>,
+}
+
+/// Largest object one CopyObject can copy. Larger objects are rewritten
+/// with UploadPartCopy instead.
+pub(crate) const MAX_COPY_OBJECT_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Rewrites an object's content headers and user metadata with a
+/// CopyObject onto itself, or part copies when its `size` is above
+/// [`MAX_COPY_OBJECT_BYTES`]. The object body never leaves the bucket.
+pub async fn replace_object_metadata(
+    cfg: &R2Config,
+    key: &str,
+    size: u64,
+    update: &ObjectMetadataUpdate,
+) -> SpResult<()> {
+    replace_metadata_copying(cfg, key, size, update, MAX_COPY_OBJECT_BYTES).await
+}
+
+/// [`replace_object_metadata`] with copies of at most `max_copy` bytes.
+pub(super) async fn replace_metadata_copying(
+    cfg: &R2Config,
+    key: &str,
+    size: u64,
+    update: &ObjectMetadataUpdate,
+    max_copy: u64,
+) -> SpResult<()> {
+    let source = format!("/{}/{}", cfg.bucket, encode_path(key));
+    let mut headers = Vec::new();
+    let standard = [
+        ("content-type", &update.headers.content_type),
+        ("content-disposition", &update.headers.content_disposition),
+        ("cache-control", &update.headers.cache_control),
+        ("content-encoding", &update.headers.content_encoding),
+    ];
+    for (name, value) in standard {
+        if let Some(value) = value {
+            headers.push((name.to_string(), value.clone()));
+        }
+    }
+    for (name, value) in &update.headers.user_metadata {
+        headers.push((format!("x-amz-meta-{name}"), value.clone()));
+    }
+    if size > max_copy {
+        return super::multipart::copy_onto_itself(
+            cfg,
+            key,
+            &source,
+            size,
+            max_copy,
+            headers,
+            update.expected_etag.as_deref(),
+        )
+        .await;
+    }
+    headers.push(("x-amz-copy-source".to_string(), source));
+    headers.push((
+        "x-amz-metadata-directive".to_string(),
+        "REPLACE".to_string(),
+    ));
+    if let Some(etag) = &update.expected_etag {
+        headers.push(("x-amz-copy-source-if-match".to_string(), etag.clone()));
+    }
+    let response = send(
+        cfg,
+        RawS3Request {
+            method: http::Method::PUT,
+            key: key.to_string(),
+            query: Vec::new(),
+            headers,
+            body: bytes::Bytes::new(),
+        },
+    )
+    .await?;
+    // CopyObject can fail after a 200 status line; the error is in the body.
+    if String::from_utf8_lossy(&response.body).contains("<Error>") {
+        return Err(status_error("CopyObject", key, 500, &response.body));
+    }
+    Ok(())
+}
+
+/// Sends a signed request and maps non-2xx responses to [`SpError`]. The
+/// error context carries the HTTP status and the S3 error `code`, if any.
+/// Transient failures of requests that are safe to resend are retried under
+/// the network settings' policy, each attempt signed afresh.
+pub(crate) async fn send(cfg: &R2Config, request: RawS3Request) -> SpResult<RawS3Response> {
+    let policy = crate::retry::RetryPolicy::from_settings(&crate::settings::get().network)
+        .for_request(
+            &request.method,
+            request.headers.iter().map(|(name, _)| name.as_str()),
+        );
+    let operation = format!("{} {}", request.method, request.key);
+    crate::retry::with_retry(&policy, &operation, &crate::retry::cancel_flag(), || {
+        send_once(cfg, &request)
+    })
+    .await
+}
+
+/// One signed attempt without retries, for callers that report each
+/// outcome as it happened.
+pub(crate) async fn send_once(cfg: &R2Config, request: &RawS3Request) -> SpResult<RawS3Response> {
+    let region = provider::resolve_region(cfg)?;
+    let address = bucket_address(cfg)?;
+    let host = address.authority();
+    let path = address.path;
+    let canonical_uri = if request.key.is_empty() {
+        if path.is_empty() {
+            "/".to_string()
+        } else {
+            path
+        }
+    } else {
+        format!("{path}/{}", encode_path(&request.key))
+    };
+    let query = canonical_query(&request.query);
+    let url = if query.is_empty() {
+        format!("{}://{host}{canonical_uri}", address.scheme)
+    } else {
+        format!("{}://{host}{canonical_uri}?{query}", address.scheme)
+    };
+
+    let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let payload_hash = hex(&Sha256::digest(&request.body));
+    let mut headers = request
+        .headers
+        .iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+    headers.push(("host".into(), host));
+    headers.push(("x-amz-content-sha256".into(), payload_hash.clone()));
+    headers.push(("x-amz-date".into(), amz_date.clone()));
+    let authorization = authorization(
+        &SigningKey {
+            access_key_id: &cfg.access_key_id,
+            secret_access_key: &cfg.secret_access_key,
+            region: &region,
+        },
+        request.method.as_str(),
+        &canonical_uri,
+        &query,
+        &headers,
+        &payload_hash,
+        &amz_date,
+    );
+
+    let mut header_map = http::HeaderMap::new();
+    for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+        let name = http::HeaderName::from_bytes(name.as_bytes()).map_err(header_error)?;
+        let value = http::HeaderValue::from_str(value).map_err(header_error)?;
+        header_map.append(name, value);
+    }
+    header_map.insert(
+        http::header::AUTHORIZATION,
+        http::HeaderValue::from_str(&authorization).map_err(header_error)?,
+    );
+    let uri = url.parse::<http::Uri>().map_err(header_error)?;
+    if let Some(profile) = super::profile_for_config(cfg) {
+        crate::usage::http_instrument::record_raw_request(
+            &profile,
+            &request.method,
+            &uri,
+            &header_map,
+            request.body.len() as u64,
+        );
+    }
+
+    let response = super::http_client()?
+        .request(request.method.clone(), &url)
+        .headers(header_map)
+        .body(request.body.clone())
+        .send()
+        .await
+        .map_err(|error| {
+            super::storage_error(
+                ErrorKind::RetryableNet,
+                format!("{} {}: {error}", request.method, request.key),
+                request.method.as_str(),
+                &request.key,
+                None,
+            )
+        })?;
+    let status = response.status().as_u16();
+    let response_headers = response.headers().clone();
+    let retry_after = response_headers
+        .get(http::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(crate::retry::parse_retry_after);
+    let body = response
+        .bytes()
+        .await
+        .map_err(|error| {
+            super::storage_error(
+                ErrorKind::RetryableNet,
+                format!("read response: {error}"),
+                request.method.as_str(),
+                &request.key,
+                Some(status),
+            )
+        })?
+        .to_vec();
+    if !(200..300).contains(&status) {
+        let mut error = status_error(request.method.as_str(), &request.key, status, &body);
+        if let (Some(wait), Some(_)) = (retry_after, error.retry_after_ms) {
+            error.retry_after_ms = Some(wait.as_millis() as u64);
+        }
+        return Err(error);
+    }
+    Ok(RawS3Response {
+        headers: response_headers,
+        body,
+    })
+}
+
+/// Where bucket requests are sent under the provider's addressing rules.
+pub(crate) struct BucketAddress {
+    pub(crate) scheme: String,
+    /// Host name without the port; includes the bucket under virtual-host
+    /// addressing.
+    pub(crate) host: String,
+    pub(crate) port: Option<u16>,
+    /// Bucket path without a trailing slash; empty under virtual-host
+    /// addressing on a root endpoint.
+    pub(crate) path: String,
+}
+
+impl BucketAddress {
+    pub(crate) fn authority(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{port}", self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// URL of the bucket root, e.g. for an unsigned reachability probe.
+    pub(crate) fn url(&self) -> String {
+        format!("{}://{}{}/", self.scheme, self.authority(), self.path)
+    }
+}
+
+pub(crate) fn bucket_address(cfg: &R2Config) -> SpResult<BucketAddress> {
+    let region = provider::resolve_region(cfg)?;
+    let endpoint = provider::resolve_endpoint(cfg, &region)?;
+    let virtual_host =
+        provider::defaults_for(cfg.provider).virtual_host_style && !cfg.bucket.contains('.');
+    let base = reqwest::Url::parse(&endpoint).map_err(|error| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("invalid endpoint {endpoint}: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    })?;
+    let mut host = base.host_str().unwrap_or_default().to_string();
+    let mut path = base.path().trim_end_matches('/').to_string();
+    if virtual_host {
+        host = format!("{}.{host}", cfg.bucket);
+    } else {
+        path = format!("{path}/{}", cfg.bucket);
+    }
+    Ok(BucketAddress {
+        scheme: base.scheme().to_string(),
+        host,
+        port: base.port(),
+        path,
+    })
+}
+
+pub(super) struct SigningKey<'a> {
+    pub(super) access_key_id: &'a str,
+    pub(super) secret_access_key: &'a str,
+    pub(super) region: &'a str,
+}
+
+/// Builds the SigV4 `Authorization` header. `headers` must already contain
+/// `host`, `x-amz-date` and `x-amz-content-sha256`; every header is signed.
+pub(super) fn authorization(
+    key: &SigningKey<'_>,
+    method: &str,
+    canonical_uri: &str,
+    canonical_query: &str,
+    headers: &[(String, String)],
+    payload_hash: &str,
+    amz_date: &str,
+) -> String {
+    let mut sorted = headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_ascii_lowercase(),
+                value.split_whitespace().collect::<Vec<_>>().join(" "),
+            )
+        })
+        .collect::<Vec<_>>();
+    sorted.sort();
+    let canonical_headers = sorted
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect::<String>();
+    let signed_headers = sorted
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{method}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+    );
+    let date = &amz_date[..8];
+    let scope = format!("{date}/{}/s3/aws4_request", key.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let signing_key = [key.region, "s3", "aws4_request"].iter().fold(
+        hmac_sha256(
+            format!("AWS4{}", key.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        ),
+        |derived, part| hmac_sha256(&derived, part.as_bytes()),
+    );
+    let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope},SignedHeaders={signed_headers},Signature={signature}",
+        key.access_key_id
+    )
+}
+
+pub(super) fn canonical_query(query: &[(String, String)]) -> String {
+    let mut pairs = query
+        .iter()
+        .map(|(name, value)| (encode(name, true), encode(value, true)))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn encode_path(key: &str) -> String {
+    encode(key, false)
+}
+
+/// RFC 3986 encoding as SigV4 expects: unreserved characters pass through and
+/// `/` is kept only in paths.
+fn encode(value: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub(super) fn status_error(operation: &str, key: &str, status: u16, body: &[u8]) -> SpError {
+    let body = String::from_utf8_lossy(body);
+    let service_code = body
+        .split_once("<Code>")
+        .and_then(|(_, rest)| rest.split_once("</Code>"))
+        .map(|(code, _)| code.to_string());
+    let code = service_code
+        .clone()
+        .unwrap_or_else(|| format!("HTTP {status}"));
+    let mut error = super::storage_error(
+        super::kind_for_status(status),
+        format!("{operation}: {code}"),
+        operation,
+        key,
+        Some(status),
+    );
+    if let Some(serde_json::Value::Object(context)) = error.context.as_mut() {
+        context.insert("code".into(), service_code.into());
+    }
+    error
+}
+
+fn header_error(error: impl std::fmt::Display) -> SpError {
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("invalid request header: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests;
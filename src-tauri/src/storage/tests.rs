@@ -1,4 +1,20 @@
 use super::*;
+use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+use crate::test_support::start_path_style_s3;
+
+/// Self-signed EC certificate standing in for a TLS-inspection CA.
+const INSPECTION_CA_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBpDCCAUmgAwIBAgIUA1hg5vCrLdAz5Px4xdTO2oo7gTUwCgYIKoZIzj0EAwIw
+JjEkMCIGA1UEAwwbU3dpZnRQYW4gVGVzdCBJbnNwZWN0aW9uIENBMCAXDTI2MTAx
+NzA1NDE1MloYDzIxMjYwOTIzMDU0MTUyWjAmMSQwIgYDVQQDDBtTd2lmdFBhbiBU
+ZXN0IEluc3BlY3Rpb24gQ0EwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASd/UG1
+DeRLmOa4nBQEBF9HiClH3b5BMOxIoSbSV0gza2dqn6s0sl9pH59Z6eKAJ1KMJwcS
+z8KHmZ1nnTKSCe73o1MwUTAdBgNVHQ4EFgQU8+9uGDYo+X9TFfHr2+OOU2xNBYgw
+HwYDVR0jBBgwFoAU8+9uGDYo+X9TFfHr2+OOU2xNBYgwDwYDVR0TAQH/BAUwAwEB
+/zAKBggqhkjOPQQDAgNJADBGAiEAhi5r5oB7DmWfE3OmVXAxyuIi+VotERkyuv+C
+ZzPz8soCIQDe+uoB67FvU8QR14QcJ1oHrBTCs5xov38IUIbWCr/sog==
+-----END CERTIFICATE-----
+";
 
 fn config() -> R2Config {
     R2Config {
@@ -7,6 +23,7 @@ fn config() -> R2Config {
         secret_access_key: "secret-key".into(),
         bucket: "photos".into(),
         region: None,
+        provider: StorageProvider::R2,
     }
 }
 
@@ -45,10 +62,14 @@ fn every_r2_configuration_field_participates_in_operator_fingerprint() {
     bucket.bucket = "backups".into();
     variants.push(bucket);
 
-    let mut region = original;
+    let mut region = original.clone();
     region.region = Some("custom-region".into());
     variants.push(region);
 
+    let mut provider = original;
+    provider.provider = StorageProvider::Minio;
+    variants.push(provider);
+
     for variant in variants {
         assert_ne!(
             cfg_fingerprint(&variant),
@@ -57,3 +78,255 @@ fn every_r2_configuration_field_participates_in_operator_fingerprint() {
         );
     }
 }
+
+#[tokio::test]
+async fn operators_are_cached_independently_per_profile() {
+    let prod = config();
+    let mut staging = config();
+    staging.bucket = "staging-photos".into();
+
+    build_operator("cache-test-prod", &prod)
+        .await
+        .expect("prod operator should build");
+    build_operator("cache-test-staging", &staging)
+        .await
+        .expect("staging operator should build");
+    {
+        let cache = OPERATOR_CACHE.read().await;
+        assert_eq!(
+            cache.get("cache-test-prod").map(|(fp, _)| fp.clone()),
+            Some(operator_fingerprint(&prod, &crate::settings::get().network))
+        );
+        assert_eq!(
+            cache.get("cache-test-staging").map(|(fp, _)| fp.clone()),
+            Some(operator_fingerprint(
+                &staging,
+                &crate::settings::get().network
+            ))
+        );
+    }
+
+    evict_cached_operator("cache-test-staging").await;
+
+    let cache = OPERATOR_CACHE.read().await;
+    assert!(cache.contains_key("cache-test-prod"));
+    assert!(!cache.contains_key("cache-test-staging"));
+}
+
+fn provider_config(provider: StorageProvider, endpoint: &str, region: Option<&str>) -> R2Config {
+    R2Config {
+        endpoint: endpoint.into(),
+        region: region.map(str::to_string),
+        provider,
+        ..config()
+    }
+}
+
+#[test]
+fn r2_jurisdiction_presets_rewrite_account_endpoints() {
+    let eu = provider_config(
+        StorageProvider::R2Eu,
+        "https://account.r2.cloudflarestorage.com/photos?x=1",
+        None,
+    );
+    let fedramp = provider_config(
+        StorageProvider::R2Fedramp,
+        "https://account.r2.cloudflarestorage.com",
+        None,
+    );
+    let already_eu = provider_config(
+        StorageProvider::R2Eu,
+        "https://account.eu.r2.cloudflarestorage.com",
+        None,
+    );
+
+    assert_eq!(
+        provider::resolve_endpoint(&eu, "auto").expect("eu endpoint"),
+        "https://account.eu.r2.cloudflarestorage.com"
+    );
+    assert_eq!(
+        provider::resolve_endpoint(&fedramp, "auto").expect("fedramp endpoint"),
+        "https://account.fedramp.r2.cloudflarestorage.com"
+    );
+    assert_eq!(
+        provider::resolve_endpoint(&already_eu, "auto").expect("eu endpoint"),
+        "https://account.eu.r2.cloudflarestorage.com"
+    );
+    assert_eq!(provider::resolve_region(&eu).expect("eu region"), "auto");
+}
+
+#[test]
+fn non_r2_providers_replace_the_auto_region_with_a_signable_default() {
+    let aws = provider_config(StorageProvider::AwsS3, "", Some("auto"));
+    let wasabi = provider_config(StorageProvider::Wasabi, "", Some("eu-central-2"));
+
+    let aws_region = provider::resolve_region(&aws).expect("aws region");
+    assert_eq!(aws_region, "us-east-1");
+    assert_eq!(
+        provider::resolve_endpoint(&aws, &aws_region).expect("aws endpoint"),
+        "https://s3.us-east-1.amazonaws.com"
+    );
+    assert_eq!(
+        provider::resolve_endpoint(&wasabi, "eu-central-2").expect("wasabi endpoint"),
+        "https://s3.eu-central-2.wasabisys.com"
+    );
+}
+
+#[test]
+fn backblaze_region_comes_from_the_cluster_endpoint() {
+    let b2 = provider_config(
+        StorageProvider::BackblazeB2,
+        "https://s3.us-west-004.backblazeb2.com",
+        None,
+    );
+    let custom = provider_config(StorageProvider::BackblazeB2, "https://b2.example.com", None);
+
+    assert_eq!(
+        provider::resolve_region(&b2).expect("b2 region"),
+        "us-west-004"
+    );
+    assert!(provider::resolve_region(&custom).is_err());
+}
+
+#[test]
+fn endpoint_is_required_when_the_provider_has_no_default() {
+    for provider in [
+        StorageProvider::R2,
+        StorageProvider::Minio,
+        StorageProvider::BackblazeB2,
+    ] {
+        let cfg = provider_config(provider, "  ", Some("us-east-1"));
+        assert!(provider::resolve_endpoint(&cfg, "us-east-1").is_err());
+    }
+}
+
+#[test]
+fn minio_keeps_a_reverse_proxy_path_prefix() {
+    let cfg = provider_config(StorageProvider::Minio, "http://nas.local:9000/minio/", None);
+
+    assert_eq!(
+        provider::resolve_endpoint(&cfg, "us-east-1").expect("minio endpoint"),
+        "http://nas.local:9000/minio"
+    );
+}
+
+#[tokio::test]
+async fn minio_preset_round_trips_through_a_path_style_stand_in() {
+    let server = start_path_style_s3("photos");
+    // Forms default the region to R2's "auto"; MinIO must still sign for us-east-1.
+    let cfg = provider_config(StorageProvider::Minio, &server.url(), Some("auto"));
+    let operator = Operator::new(s3_builder(&cfg).expect("minio builder should configure"))
+        .expect("minio operator should build")
+        .finish();
+
+    operator
+        .write("albums/cover.jpg", b"jpeg-bytes".to_vec())
+        .await
+        .expect("write through stand-in should succeed");
+    let metadata = operator
+        .stat("albums/cover.jpg")
+        .await
+        .expect("stat through stand-in should succeed");
+    let body = operator
+        .read("albums/cover.jpg")
+        .await
+        .expect("read through stand-in should succeed");
+
+    assert_eq!(metadata.content_length(), 10);
+    assert_eq!(body.to_vec(), b"jpeg-bytes");
+    let requests = server.requests();
+    assert!(requests.len() >= 3);
+    for request in requests {
+        assert!(
+            request.path().starts_with("/photos/"),
+            "request was not path-style: {}",
+            request.target
+        );
+        assert!(request
+            .header("authorization")
+            .is_some_and(|value| value.contains("/us-east-1/s3/aws4_request")));
+    }
+}
+
+#[test]
+fn network_settings_participate_in_operator_fingerprint() {
+    let cfg = config();
+    let baseline = operator_fingerprint(&cfg, &NetworkSettings::default());
+    let proxied = NetworkSettings {
+        proxy_url: Some("http://proxy.corp:3128".into()),
+        ..NetworkSettings::default()
+    };
+    let extra_root = NetworkSettings {
+        extra_root_pems: vec![INSPECTION_CA_PEM.into()],
+        ..NetworkSettings::default()
+    };
+
+    assert_ne!(operator_fingerprint(&cfg, &proxied), baseline);
+    assert_ne!(operator_fingerprint(&cfg, &extra_root), baseline);
+}
+
+#[test]
+fn malformed_proxy_or_certificate_settings_are_rejected() {
+    let valid = NetworkSettings {
+        proxy_url: Some("https://proxy.corp:8443".into()),
+        no_proxy: vec!["localhost".into(), ".internal.corp".into()],
+        read_timeout_secs: 120,
+        extra_root_pems: vec![INSPECTION_CA_PEM.into()],
+        ..NetworkSettings::default()
+    };
+    validate_network_settings(&valid).expect("settings should build a client");
+
+    let cases = [
+        NetworkSettings {
+            proxy_url: Some("http://proxy.corp:notaport".into()),
+            ..NetworkSettings::default()
+        },
+        NetworkSettings {
+            extra_root_pems: vec!["not a certificate".into()],
+            ..NetworkSettings::default()
+        },
+    ];
+    for network in cases {
+        let error = validate_network_settings(&network).expect_err("settings must be rejected");
+        assert!(matches!(error.kind, ErrorKind::NotRetriable));
+        assert!(error.message.starts_with("invalid network setting"));
+    }
+}
+
+#[tokio::test]
+async fn storage_client_authenticates_to_the_proxy_and_honours_no_proxy() {
+    let proxy = LocalHttpServer::start(|_| StubResponse::new(200));
+    let direct = LocalHttpServer::start(|_| StubResponse::new(200));
+    let client = client_builder(&NetworkSettings {
+        proxy_url: Some(proxy.url()),
+        proxy_username: Some("alice".into()),
+        proxy_password: Some("s3cret".into()),
+        no_proxy: vec!["127.0.0.1".into()],
+        ..NetworkSettings::default()
+    })
+    .expect("client should configure")
+    .build()
+    .expect("client should build");
+
+    client
+        .get("http://bucket.example/photos/cover.jpg")
+        .send()
+        .await
+        .expect("proxied request should succeed");
+    client
+        .get(format!("{}/health", direct.url()))
+        .send()
+        .await
+        .expect("direct request should succeed");
+
+    let proxied = proxy.requests();
+    assert_eq!(proxied.len(), 1);
+    assert_eq!(proxied[0].target, "http://bucket.example/photos/cover.jpg");
+    assert_eq!(
+        proxied[0].header("proxy-authorization"),
+        Some("Basic YWxpY2U6czNjcmV0")
+    );
+    let direct_requests = direct.requests();
+    assert_eq!(direct_requests.len(), 1);
+    assert_eq!(direct_requests[0].target, "/health");
+}
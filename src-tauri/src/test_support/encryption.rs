@@ -0,0 +1,30 @@
+use crate::content_crypto::{ContentKey, ContentOpener, SealedHeader};
+use crate::sp_backend::{KdfParams, ProfileEncryption};
+use crate::types::SpResult;
+
+/// Profile encryption at the lowest Argon2 cost, so tests derive keys
+/// without waiting.
+pub(crate) fn cheap_encryption(passphrase: &str) -> ProfileEncryption {
+    ProfileEncryption {
+        passphrase: passphrase.into(),
+        kdf: KdfParams {
+            algo: "argon2id".into(),
+            mem_kib: 32,
+            iterations: 1,
+            parallelism: 1,
+            salt: [3; 16],
+        },
+    }
+}
+
+/// The plaintext of a whole sealed object.
+pub(crate) async fn open_sealed(
+    sealed: &[u8],
+    encryption: &ProfileEncryption,
+) -> SpResult<Vec<u8>> {
+    let header = SealedHeader::parse(sealed)?;
+    let key = ContentKey::for_header(encryption, &header).await?;
+    let opener = ContentOpener::new(&key, &header, sealed.len() as u64)?;
+    let range = opener.sealed_range(&(0..opener.plain_len()));
+    opener.open_range(0, &sealed[range.start as usize..range.end as usize])
+}
@@ -1,5 +1,14 @@
 mod bytes;
+mod encryption;
+pub(crate) mod local_http;
+mod retry;
+mod s3_stand_in;
 mod storage_faults;
 
 pub(crate) use bytes::patterned_bytes;
-pub(crate) use storage_faults::{inject_early_eof, limit_read_responses, report_etag};
+pub(crate) use encryption::{cheap_encryption, open_sealed};
+pub(crate) use retry::instant_retries;
+pub(crate) use s3_stand_in::{http_date, stand_in_operator, start_path_style_s3};
+pub(crate) use storage_faults::{
+    fail_next_reads, inject_early_eof, limit_read_responses, report_etag,
+};
@@ -0,0 +1,12 @@
+use crate::retry::RetryPolicy;
+use std::time::Duration;
+
+/// The production attempt count with millisecond backoff, so failure-path
+/// tests exercise retries without waiting for them.
+pub(crate) fn instant_retries() -> RetryPolicy {
+    RetryPolicy {
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(4),
+        ..RetryPolicy::default()
+    }
+}
@@ -0,0 +1,539 @@
+//! In-process, MinIO-like S3 stand-in served over a real socket.
+//!
+//! It only understands path-style addressing (`/<bucket>/<key>`), so a client
+//! that falls back to virtual-host style fails loudly with `NoSuchBucket`. It
+//! implements enough of PUT/GET/HEAD/DELETE, CopyObject, DeleteObjects,
+//! ListObjectsV2, multipart uploads (create, UploadPart, ListParts, complete,
+//! abort) and the bucket `?lifecycle` subresource for backend, transfer and
+//! object-operation tests, and keeps the standard content headers plus
+//! `x-amz-meta-*` pairs of each object. PUT and multipart completion honour
+//! `If-None-Match: *` and `If-Match`. ETags follow S3: the MD5 of the
+//! body for a single PUT, and the MD5 of the part MD5s with a `-<parts>`
+//! suffix for a completed multipart upload. It performs no signature or
+//! Content-MD5 verification.
+
+use super::local_http::{LocalHttpServer, RecordedRequest, StubResponse};
+use md5::{Digest, Md5};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Request headers that are stored with an object and replayed on GET/HEAD.
+const STORED_HEADERS: [&str; 4] = [
+    "content-type",
+    "content-disposition",
+    "cache-control",
+    "content-encoding",
+];
+
+#[derive(Clone)]
+struct StoredObject {
+    body: Vec<u8>,
+    etag: String,
+    headers: Vec<(String, String)>,
+}
+
+impl StoredObject {
+    fn from_request(body: Vec<u8>, request: &RecordedRequest) -> Self {
+        let headers = request
+            .headers
+            .iter()
+            .filter(|(name, _)| {
+                STORED_HEADERS.contains(&name.as_str()) || name.starts_with("x-amz-meta-")
+            })
+            .cloned()
+            .collect();
+        Self {
+            etag: etag_for(&body),
+            body,
+            headers,
+        }
+    }
+}
+
+type Objects = BTreeMap<String, StoredObject>;
+
+/// An open multipart upload: the headers of its create request and the
+/// parts received so far.
+struct PendingUpload {
+    key: String,
+    created: StoredObject,
+    parts: BTreeMap<u32, Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Bucket {
+    objects: Objects,
+    /// Lifecycle configuration XML exactly as last PUT.
+    lifecycle: Option<Vec<u8>>,
+    uploads: BTreeMap<String, PendingUpload>,
+    next_upload: u64,
+}
+
+pub(crate) fn start_path_style_s3(bucket: &str) -> LocalHttpServer {
+    let bucket = bucket.to_string();
+    let state: Arc<Mutex<Bucket>> = Arc::new(Mutex::new(Bucket::default()));
+    LocalHttpServer::start(move |request| {
+        let mut state = state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        handle(&bucket, &mut state, request).header("date", http_date(chrono::Utc::now()))
+    })
+}
+
+/// An S3 operator pointed at a stand-in started for `bucket`. Like the
+/// operators the app builds, it sends the preconditions of multipart
+/// completions and notes the status of failed responses; it sends each
+/// request once and records no usage.
+pub(crate) fn stand_in_operator(server: &LocalHttpServer, bucket: &str) -> opendal::Operator {
+    let builder = opendal::services::S3::default()
+        .endpoint(&server.url())
+        .bucket(bucket)
+        .region("us-east-1")
+        .access_key_id("stand-in")
+        .secret_access_key("stand-in")
+        .disable_config_load();
+    let http_client = opendal::raw::HttpClient::with(crate::storage::ConditionalCompletion::new(
+        crate::retry::RetryingFetch::new(
+            reqwest::Client::new(),
+            crate::retry::RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        ),
+    ));
+    opendal::Operator::new(builder)
+        .expect("stand-in operator should build")
+        .layer(opendal::layers::HttpClientLayer::new(http_client))
+        .finish()
+}
+
+fn handle(bucket: &str, state: &mut Bucket, request: &RecordedRequest) -> StubResponse {
+    let path = percent_decode(request.path());
+    let Some(rest) = path.strip_prefix(&format!("/{bucket}")) else {
+        return s3_error(404, "NoSuchBucket");
+    };
+    let key = rest.strip_prefix('/').unwrap_or(rest);
+    if key.is_empty() && has_query_key(request.query(), "lifecycle") {
+        return lifecycle(state, request);
+    }
+    if !key.is_empty() {
+        if let Some(response) = multipart(state, key, request) {
+            return response;
+        }
+    }
+    let objects = &mut state.objects;
+    match (request.method.as_str(), key.is_empty()) {
+        ("GET", true) => list_objects_v2(objects, request.query()),
+        ("POST", true) if has_query_key(request.query(), "delete") => {
+            delete_objects(objects, &request.body)
+        }
+        ("PUT", false) if request.header("x-amz-copy-source").is_some() => {
+            copy_object(bucket, objects, key, request)
+        }
+        ("PUT", false) => {
+            if let Some(refused) = precondition_failure(objects.get(key), request) {
+                return refused;
+            }
+            let object = StoredObject::from_request(request.body.clone(), request);
+            let etag = object.etag.clone();
+            objects.insert(key.to_string(), object);
+            StubResponse::new(200).header("etag", etag)
+        }
+        ("GET", false) | ("HEAD", false) => match objects.get(key) {
+            Some(object) => read_object(object, request.header("range")),
+            None => s3_error(404, "NoSuchKey"),
+        },
+        ("DELETE", false) => {
+            objects.remove(key);
+            StubResponse::new(204)
+        }
+        _ => s3_error(501, "NotImplemented"),
+    }
+}
+
+fn lifecycle(state: &mut Bucket, request: &RecordedRequest) -> StubResponse {
+    match request.method.as_str() {
+        "GET" => match &state.lifecycle {
+            Some(xml) => StubResponse::new(200)
+                .header("content-type", "application/xml")
+                .body(xml.clone()),
+            None => s3_error(404, "NoSuchLifecycleConfiguration"),
+        },
+        "PUT" => {
+            state.lifecycle = Some(request.body.clone());
+            StubResponse::new(200)
+        }
+        "DELETE" => {
+            state.lifecycle = None;
+            StubResponse::new(204)
+        }
+        _ => s3_error(405, "MethodNotAllowed"),
+    }
+}
+
+/// Handles a multipart request on `key`, or returns `None` for any other
+/// object request.
+fn multipart(state: &mut Bucket, key: &str, request: &RecordedRequest) -> Option<StubResponse> {
+    let query = request.query();
+    if request.method == "POST" && has_query_key(query, "uploads") {
+        state.next_upload += 1;
+        let upload_id = format!("upload-{}", state.next_upload);
+        state.uploads.insert(
+            upload_id.clone(),
+            PendingUpload {
+                key: key.to_string(),
+                created: StoredObject::from_request(Vec::new(), request),
+                parts: BTreeMap::new(),
+            },
+        );
+        return Some(
+            StubResponse::new(200)
+                .header("content-type", "application/xml")
+                .body(format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?><InitiateMultipartUploadResult>\
+                     <Key>{key}</Key><UploadId>{upload_id}</UploadId></InitiateMultipartUploadResult>"
+                )),
+        );
+    }
+    let upload_id = query_value(query, "uploadId")?;
+    let Some(upload) = state
+        .uploads
+        .get_mut(&upload_id)
+        .filter(|upload| upload.key == key)
+    else {
+        return Some(s3_error(404, "NoSuchUpload"));
+    };
+    let response = match request.method.as_str() {
+        "PUT" => match query_value(query, "partNumber").and_then(|value| value.parse().ok()) {
+            Some(part_number) if request.header("x-amz-copy-source").is_some() => {
+                let body = match part_copy_source(&state.objects, request) {
+                    Ok(body) => body,
+                    Err(refused) => return Some(refused),
+                };
+                let etag = etag_for(&body);
+                upload.parts.insert(part_number, body);
+                StubResponse::new(200)
+                    .header("content-type", "application/xml")
+                    .body(format!(
+                        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><CopyPartResult>\
+                         <ETag>{etag}</ETag></CopyPartResult>"
+                    ))
+            }
+            Some(part_number) => {
+                upload.parts.insert(part_number, request.body.clone());
+                StubResponse::new(200).header("etag", etag_for(&request.body))
+            }
+            None => s3_error(400, "InvalidArgument"),
+        },
+        "GET" => {
+            let parts = upload
+                .parts
+                .iter()
+                .map(|(number, body)| {
+                    format!(
+                        "<Part><PartNumber>{number}</PartNumber><ETag>{}</ETag>\
+                         <Size>{}</Size></Part>",
+                        etag_for(body),
+                        body.len()
+                    )
+                })
+                .collect::<String>();
+            StubResponse::new(200)
+                .header("content-type", "application/xml")
+                .body(format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListPartsResult>\
+                     <UploadId>{upload_id}</UploadId><IsTruncated>false</IsTruncated>{parts}\
+                     </ListPartsResult>"
+                ))
+        }
+        "POST" => {
+            if let Some(refused) = precondition_failure(state.objects.get(key), request) {
+                return Some(refused);
+            }
+            let requested = String::from_utf8_lossy(&request.body);
+            let mut body = Vec::new();
+            let mut part_digests = Md5::new();
+            let mut part_count = 0;
+            for chunk in requested.split("<PartNumber>").skip(1) {
+                let number = chunk
+                    .split_once("</PartNumber>")
+                    .and_then(|(number, _)| number.trim().parse::<u32>().ok());
+                match number.and_then(|number| upload.parts.get(&number)) {
+                    Some(part) => {
+                        body.extend_from_slice(part);
+                        part_digests.update(Md5::digest(part));
+                        part_count += 1;
+                    }
+                    None => return Some(s3_error(400, "InvalidPart")),
+                }
+            }
+            let mut object = upload.created.clone();
+            object.body = body;
+            object.etag = format!("\"{:x}-{part_count}\"", part_digests.finalize());
+            let etag = object.etag.clone();
+            state.uploads.remove(&upload_id);
+            state.objects.insert(key.to_string(), object);
+            StubResponse::new(200)
+                .header("content-type", "application/xml")
+                .body(format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?><CompleteMultipartUploadResult>\
+                     <Key>{key}</Key><ETag>{etag}</ETag></CompleteMultipartUploadResult>"
+                ))
+        }
+        "DELETE" => {
+            state.uploads.remove(&upload_id);
+            StubResponse::new(204)
+        }
+        _ => s3_error(405, "MethodNotAllowed"),
+    };
+    Some(response)
+}
+
+/// The bytes an UploadPartCopy copies: the `x-amz-copy-source-range` of the
+/// `x-amz-copy-source` object, or the error S3 answers with.
+fn part_copy_source(objects: &Objects, request: &RecordedRequest) -> Result<Vec<u8>, StubResponse> {
+    let source = percent_decode(request.header("x-amz-copy-source").unwrap_or_default());
+    let source_key = source
+        .trim_start_matches('/')
+        .split_once('/')
+        .map(|(_, key)| key)
+        .unwrap_or_default();
+    let object = objects
+        .get(source_key)
+        .ok_or_else(|| s3_error(404, "NoSuchKey"))?;
+    if request
+        .header("x-amz-copy-source-if-match")
+        .is_some_and(|expected| expected != object.etag)
+    {
+        return Err(s3_error(412, "PreconditionFailed"));
+    }
+    let (start, end) = request
+        .header("x-amz-copy-source-range")
+        .and_then(|range| range.strip_prefix("bytes="))
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)))
+        .filter(|&(start, end)| start <= end && end < object.body.len())
+        .ok_or_else(|| s3_error(416, "InvalidRange"))?;
+    Ok(object.body[start..=end].to_vec())
+}
+
+/// The error S3 answers a conditional write with when its precondition does
+/// not hold for `current`.
+fn precondition_failure(
+    current: Option<&StoredObject>,
+    request: &RecordedRequest,
+) -> Option<StubResponse> {
+    if request.header("if-none-match") == Some("*") && current.is_some() {
+        return Some(s3_error(412, "PreconditionFailed"));
+    }
+    let expected = request.header("if-match")?;
+    match current {
+        None => Some(s3_error(404, "NoSuchKey")),
+        Some(object) if object.etag != expected => Some(s3_error(412, "PreconditionFailed")),
+        Some(_) => None,
+    }
+}
+
+fn query_value(query: &str, key: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == key).then(|| percent_decode(value))
+    })
+}
+
+fn has_query_key(query: &str, key: &str) -> bool {
+    query
+        .split('&')
+        .any(|pair| pair.split_once('=').map_or(pair, |(name, _)| name) == key)
+}
+
+fn copy_object(
+    bucket: &str,
+    objects: &mut Objects,
+    key: &str,
+    request: &RecordedRequest,
+) -> StubResponse {
+    let source = percent_decode(request.header("x-amz-copy-source").unwrap_or_default());
+    let source_key = source
+        .trim_start_matches('/')
+        .strip_prefix(&format!("{bucket}/"))
+        .unwrap_or_default()
+        .to_string();
+    let Some(source) = objects.get(&source_key).cloned() else {
+        return s3_error(404, "NoSuchKey");
+    };
+    let etag = source.etag.clone();
+    if request
+        .header("x-amz-copy-source-if-match")
+        .is_some_and(|expected| expected != etag)
+    {
+        return s3_error(412, "PreconditionFailed");
+    }
+    let replace = request
+        .header("x-amz-metadata-directive")
+        .is_some_and(|directive| directive.eq_ignore_ascii_case("REPLACE"));
+    if source_key == key && !replace {
+        return s3_error(400, "InvalidRequest");
+    }
+    let copied = if replace {
+        StoredObject::from_request(source.body, request)
+    } else {
+        source
+    };
+    objects.insert(key.to_string(), copied);
+    StubResponse::new(200)
+        .header("content-type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><CopyObjectResult>\
+             <ETag>{etag}</ETag></CopyObjectResult>"
+        ))
+}
+
+fn read_object(object: &StoredObject, range: Option<&str>) -> StubResponse {
+    let body = object.body.as_slice();
+    let base = |status| {
+        let mut response = StubResponse::new(status)
+            .header("etag", &object.etag)
+            .header("last-modified", "Wed, 01 Jan 2025 00:00:00 GMT");
+        if !object
+            .headers
+            .iter()
+            .any(|(name, _)| name == "content-type")
+        {
+            response = response.header("content-type", "application/octet-stream");
+        }
+        for (name, value) in &object.headers {
+            response = response.header(name, value.clone());
+        }
+        response
+    };
+    let Some((start, end)) = range.and_then(|value| parse_range(value, body.len())) else {
+        return base(200).body(body.to_vec());
+    };
+    base(206)
+        .header(
+            "content-range",
+            format!("bytes {start}-{end}/{}", body.len()),
+        )
+        .body(body[start..=end].to_vec())
+}
+
+fn parse_range(value: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let start = start.parse::<usize>().ok()?;
+    let end = if end.is_empty() {
+        len.checked_sub(1)?
+    } else {
+        end.parse::<usize>().ok()?.min(len.checked_sub(1)?)
+    };
+    (start <= end).then_some((start, end))
+}
+
+fn list_objects_v2(objects: &Objects, query: &str) -> StubResponse {
+    let params: BTreeMap<String, String> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.to_string(), percent_decode(value)))
+        .collect();
+    let prefix = params.get("prefix").cloned().unwrap_or_default();
+    let delimiter = params.get("delimiter").cloned().unwrap_or_default();
+    let start_after = params.get("start-after").cloned().unwrap_or_default();
+    let mut contents = String::new();
+    let mut common_prefixes = Vec::<String>::new();
+    for (key, object) in objects.range(prefix.clone()..) {
+        if !key.starts_with(&prefix) {
+            break;
+        }
+        if !start_after.is_empty() && key.as_str() <= start_after.as_str() {
+            continue;
+        }
+        let relative = &key[prefix.len()..];
+        if !delimiter.is_empty() {
+            if let Some(position) = relative.find(&delimiter) {
+                let common = format!("{prefix}{}", &relative[..position + delimiter.len()]);
+                if common_prefixes.last() != Some(&common) {
+                    common_prefixes.push(common);
+                }
+                continue;
+            }
+        }
+        contents.push_str(&format!(
+            "<Contents><Key>{key}</Key><Size>{}</Size><ETag>{}</ETag>\
+             <LastModified>2025-01-01T00:00:00.000Z</LastModified></Contents>",
+            object.body.len(),
+            object.etag
+        ));
+    }
+    let common = common_prefixes
+        .iter()
+        .map(|value| format!("<CommonPrefixes><Prefix>{value}</Prefix></CommonPrefixes>"))
+        .collect::<String>();
+    StubResponse::new(200)
+        .header("content-type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult>\
+             <Prefix>{prefix}</Prefix><IsTruncated>false</IsTruncated>{contents}{common}\
+             </ListBucketResult>"
+        ))
+}
+
+fn delete_objects(objects: &mut Objects, body: &[u8]) -> StubResponse {
+    let body = String::from_utf8_lossy(body);
+    let mut deleted = String::new();
+    for chunk in body.split("<Key>").skip(1) {
+        let Some((raw_key, _)) = chunk.split_once("</Key>") else {
+            continue;
+        };
+        objects.remove(&xml_unescape(raw_key));
+        deleted.push_str(&format!("<Deleted><Key>{raw_key}</Key></Deleted>"));
+    }
+    StubResponse::new(200)
+        .header("content-type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><DeleteResult>{deleted}</DeleteResult>"
+        ))
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// IMF-fixdate, as S3 sends in `Date` headers.
+pub(crate) fn http_date(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn s3_error(status: u16, code: &str) -> StubResponse {
+    StubResponse::new(status)
+        .header("content-type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{code}</Code></Error>"
+        ))
+}
+
+fn etag_for(body: &[u8]) -> String {
+    format!("\"{:x}\"", Md5::digest(body))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&value[index + 1..index + 3], 16) {
+                out.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        out.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
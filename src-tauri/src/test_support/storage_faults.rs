@@ -9,6 +9,8 @@ use opendal::raw::{
     RpList, RpRead, RpStat, RpWrite,
 };
 use opendal::{Buffer, Operator};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 struct EarlyEofLayer {
@@ -194,3 +196,73 @@ pub(crate) fn report_etag(operator: Operator, etag: &str) -> Operator {
         etag: etag.to_string(),
     })
 }
+
+#[derive(Debug, Clone)]
+struct FlakyReadLayer {
+    remaining_failures: Arc<AtomicU32>,
+}
+
+impl<A: Access> Layer<A> for FlakyReadLayer {
+    type LayeredAccess = FlakyReadAccessor<A>;
+
+    fn layer(&self, inner: A) -> Self::LayeredAccess {
+        FlakyReadAccessor {
+            inner,
+            remaining_failures: self.remaining_failures.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FlakyReadAccessor<A> {
+    inner: A,
+    remaining_failures: Arc<AtomicU32>,
+}
+
+impl<A: Access> LayeredAccess for FlakyReadAccessor<A> {
+    type Inner = A;
+    type Reader = A::Reader;
+    type Writer = A::Writer;
+    type Lister = A::Lister;
+    type Deleter = A::Deleter;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        let failed = self
+            .remaining_failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok();
+        if failed {
+            return Err(
+                opendal::Error::new(opendal::ErrorKind::Unexpected, "connection reset")
+                    .set_temporary(),
+            );
+        }
+        self.inner.read(path, args).await
+    }
+
+    async fn write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::Writer)> {
+        self.inner.write(path, args).await
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        self.inner.list(path, args).await
+    }
+
+    async fn delete(&self) -> opendal::Result<(RpDelete, Self::Deleter)> {
+        self.inner.delete().await
+    }
+}
+
+/// Fails the next `failures` reads with a transient error, then reads
+/// normally.
+pub(crate) fn fail_next_reads(operator: Operator, failures: u32) -> Operator {
+    operator.layer(FlakyReadLayer {
+        remaining_failures: Arc::new(AtomicU32::new(failures)),
+    })
+}
@@ -23,13 +23,10 @@ pub async fn generate_and_store(
         return Ok(None);
     };
     let key = thumbnail_key_for(object_key);
-    operator.write(&key, bytes).await.map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("PutObject: {error}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: chrono::Utc::now().timestamp_millis(),
-    })?;
+    operator
+        .write(&key, bytes)
+        .await
+        .map_err(|error| crate::storage::opendal_error("PutObject", &key, &error))?;
     Ok(Some(key))
 }
 
@@ -41,13 +38,7 @@ pub async fn read_stored(
     match operator.read(&key).await {
         Ok(bytes) => Ok(Some(bytes.to_vec())),
         Err(error) if matches!(error.kind(), opendal::ErrorKind::NotFound) => Ok(None),
-        Err(error) => Err(SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("ReadObject: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        }),
+        Err(error) => Err(crate::storage::opendal_error("ReadObject", &key, &error)),
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::storage::multipart::CompletedPart;
 use crate::types::*;
 use once_cell::sync::OnceCell;
 use sqlx::{Pool, Row, Sqlite};
@@ -29,10 +30,39 @@ pub struct TransferSnapshot {
     pub temp_path: Option<String>,
     pub expected_etag: Option<String>,
     pub observed_etag: Option<String>,
+    /// Storage profile the transfer was started under. `None` only for
+    /// snapshots written before profiles existed.
+    pub profile: Option<String>,
+    /// Downloads only: decode a compressed object into the target.
+    #[serde(default)]
+    pub decompress: bool,
     pub created_at_ms: i64,
     pub updated_at_ms: i64,
 }
 
+/// What a file upload needs to continue after a restart. Parts are those
+/// the bucket confirmed, in part-number order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadSession {
+    pub transfer_id: String,
+    pub source_path: String,
+    pub part_size: u64,
+    pub content_type: Option<String>,
+    pub content_disposition: Option<String>,
+    /// Multipart `UploadId`; `None` until the upload is created remotely.
+    pub upload_id: Option<String>,
+    /// Source size and modification time the parts were read from.
+    pub source_size: Option<u64>,
+    pub source_mtime_ms: Option<i64>,
+    /// Fingerprint of the key sealing the parts; `None` for plaintext.
+    pub content_key_id: Option<String>,
+    /// Codec the source is compressed with before upload.
+    pub compression: Option<crate::compression::Compression>,
+    /// What the upload does when its key already holds an object.
+    pub conflict: crate::upload::ConflictPolicy,
+    pub parts: Vec<CompletedPart>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ThumbnailCacheEntry {
     pub object_key: String,
@@ -101,6 +131,98 @@ CREATE INDEX IF NOT EXISTS idx_thumbnail_cache_updated
             "#,
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 4,
+            description: "add_transfer_profile",
+            sql: r#"
+ALTER TABLE transfer_snapshots
+ADD COLUMN profile TEXT;
+            "#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 5,
+            description: "create_object_index",
+            sql: r#"
+CREATE TABLE IF NOT EXISTS object_index (
+  profile TEXT NOT NULL,
+  key TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  name TEXT NOT NULL,
+  extension TEXT,
+  size INTEGER,
+  last_modified_ms INTEGER,
+  etag TEXT,
+  thumbnail_key TEXT,
+  seen_at_ms INTEGER NOT NULL,
+  PRIMARY KEY (profile, key)
+);
+CREATE INDEX IF NOT EXISTS idx_object_index_name
+  ON object_index(profile, name);
+CREATE INDEX IF NOT EXISTS idx_object_index_size
+  ON object_index(profile, size);
+CREATE INDEX IF NOT EXISTS idx_object_index_modified
+  ON object_index(profile, last_modified_ms);
+CREATE TABLE IF NOT EXISTS object_index_state (
+  profile TEXT PRIMARY KEY NOT NULL,
+  last_full_sync_ms INTEGER NOT NULL
+);
+            "#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 6,
+            description: "create_upload_sessions",
+            sql: r#"
+CREATE TABLE IF NOT EXISTS upload_sessions (
+  transfer_id TEXT PRIMARY KEY NOT NULL,
+  source_path TEXT NOT NULL,
+  part_size INTEGER NOT NULL,
+  content_type TEXT,
+  content_disposition TEXT,
+  upload_id TEXT,
+  source_size INTEGER,
+  source_mtime_ms INTEGER
+);
+CREATE TABLE IF NOT EXISTS upload_parts (
+  transfer_id TEXT NOT NULL,
+  part_number INTEGER NOT NULL,
+  etag TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  PRIMARY KEY (transfer_id, part_number)
+);
+            "#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 7,
+            description: "add_upload_session_content_key",
+            sql: r#"
+ALTER TABLE upload_sessions
+ADD COLUMN content_key_id TEXT;
+            "#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 8,
+            description: "add_transfer_compression",
+            sql: r#"
+ALTER TABLE upload_sessions
+ADD COLUMN compression TEXT;
+ALTER TABLE transfer_snapshots
+ADD COLUMN decompress INTEGER NOT NULL DEFAULT 0;
+            "#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 9,
+            description: "add_upload_session_conflict_policy",
+            sql: r#"
+ALTER TABLE upload_sessions
+ADD COLUMN conflict_policy TEXT;
+            "#,
+            kind: MigrationKind::Up,
+        },
     ]
 }
 
@@ -150,10 +272,12 @@ INSERT INTO transfer_snapshots (
   temp_path,
   expected_etag,
   observed_etag,
+  profile,
+  decompress,
   created_at_ms,
   updated_at_ms
 )
-VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 ON CONFLICT(transfer_id) DO UPDATE SET
   kind = excluded.kind,
   key = excluded.key,
@@ -170,6 +294,8 @@ ON CONFLICT(transfer_id) DO UPDATE SET
   temp_path = excluded.temp_path,
   expected_etag = excluded.expected_etag,
   observed_etag = excluded.observed_etag,
+  profile = excluded.profile,
+  decompress = excluded.decompress,
   created_at_ms = excluded.created_at_ms,
   updated_at_ms = excluded.updated_at_ms
 "#;
@@ -190,6 +316,8 @@ ON CONFLICT(transfer_id) DO UPDATE SET
         .bind(snapshot.temp_path.clone())
         .bind(snapshot.expected_etag.clone())
         .bind(snapshot.observed_etag.clone())
+        .bind(snapshot.profile.clone())
+        .bind(snapshot.decompress)
         .bind(snapshot.created_at_ms)
         .bind(snapshot.updated_at_ms)
         .execute(pool)
@@ -229,6 +357,8 @@ SELECT
   temp_path,
   expected_etag,
   observed_etag,
+  profile,
+  decompress,
   created_at_ms,
   updated_at_ms
 FROM transfer_snapshots
@@ -268,6 +398,230 @@ WHERE transfer_id = ?
     })
 }
 
+/// Saves `session` and replaces its recorded parts.
+pub fn upsert_upload_session(session: &UploadSession) -> SpResult<()> {
+    let session = session.clone();
+    run_db(async move {
+        let pool = load_pool().await?;
+        upsert_upload_session_in_pool(&pool, &session).await
+    })
+}
+
+async fn upsert_upload_session_in_pool(
+    pool: &Pool<Sqlite>,
+    session: &UploadSession,
+) -> SpResult<()> {
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    sqlx::query(
+        r#"
+INSERT INTO upload_sessions (
+  transfer_id,
+  source_path,
+  part_size,
+  content_type,
+  content_disposition,
+  upload_id,
+  source_size,
+  source_mtime_ms,
+  content_key_id,
+  compression,
+  conflict_policy
+)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+ON CONFLICT(transfer_id) DO UPDATE SET
+  source_path = excluded.source_path,
+  part_size = excluded.part_size,
+  content_type = excluded.content_type,
+  content_disposition = excluded.content_disposition,
+  upload_id = excluded.upload_id,
+  source_size = excluded.source_size,
+  source_mtime_ms = excluded.source_mtime_ms,
+  content_key_id = excluded.content_key_id,
+  compression = excluded.compression,
+  conflict_policy = excluded.conflict_policy
+        "#,
+    )
+    .bind(session.transfer_id.clone())
+    .bind(session.source_path.clone())
+    .bind(u64_to_i64(session.part_size)?)
+    .bind(session.content_type.clone())
+    .bind(session.content_disposition.clone())
+    .bind(session.upload_id.clone())
+    .bind(session.source_size.map(u64_to_i64).transpose()?)
+    .bind(session.source_mtime_ms)
+    .bind(session.content_key_id.clone())
+    .bind(
+        session
+            .compression
+            .map(|compression| compression.content_encoding()),
+    )
+    .bind(
+        serde_json::to_string(&session.conflict)
+            .map_err(|error| err_invalid(&format!("encode conflict policy: {error}")))?,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    sqlx::query(
+        r#"
+DELETE FROM upload_parts
+WHERE transfer_id = ?
+        "#,
+    )
+    .bind(session.transfer_id.clone())
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    for part in &session.parts {
+        insert_upload_part(&mut tx, &session.transfer_id, part).await?;
+    }
+    tx.commit().await.map_err(db_err)
+}
+
+/// Records one part the bucket confirmed for the upload's session.
+pub fn record_upload_part(transfer_id: &str, part: &CompletedPart) -> SpResult<()> {
+    let (transfer_id, part) = (transfer_id.to_string(), part.clone());
+    run_db(async move {
+        let pool = load_pool().await?;
+        let mut connection = pool.acquire().await.map_err(db_err)?;
+        insert_upload_part(&mut connection, &transfer_id, &part).await
+    })
+}
+
+async fn insert_upload_part(
+    connection: &mut sqlx::SqliteConnection,
+    transfer_id: &str,
+    part: &CompletedPart,
+) -> SpResult<()> {
+    sqlx::query(
+        r#"
+INSERT INTO upload_parts (transfer_id, part_number, etag, size)
+VALUES (?, ?, ?, ?)
+ON CONFLICT(transfer_id, part_number) DO UPDATE SET
+  etag = excluded.etag,
+  size = excluded.size
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(i64::from(part.part_number))
+    .bind(part.etag.clone())
+    .bind(u64_to_i64(part.size)?)
+    .execute(connection)
+    .await
+    .map_err(db_err)?;
+    Ok(())
+}
+
+pub fn get_upload_session(transfer_id: &str) -> SpResult<Option<UploadSession>> {
+    let transfer_id = transfer_id.to_string();
+    run_db(async move {
+        let pool = load_pool().await?;
+        get_upload_session_in_pool(&pool, &transfer_id).await
+    })
+}
+
+async fn get_upload_session_in_pool(
+    pool: &Pool<Sqlite>,
+    transfer_id: &str,
+) -> SpResult<Option<UploadSession>> {
+    let Some(row) = sqlx::query(
+        r#"
+SELECT
+  transfer_id,
+  source_path,
+  part_size,
+  content_type,
+  content_disposition,
+  upload_id,
+  source_size,
+  source_mtime_ms,
+  content_key_id,
+  compression,
+  conflict_policy
+FROM upload_sessions
+WHERE transfer_id = ?
+        "#,
+    )
+    .bind(transfer_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(db_err)?
+    else {
+        return Ok(None);
+    };
+    let parts = sqlx::query(
+        r#"
+SELECT part_number, etag, size
+FROM upload_parts
+WHERE transfer_id = ?
+ORDER BY part_number
+        "#,
+    )
+    .bind(transfer_id)
+    .fetch_all(pool)
+    .await
+    .map_err(db_err)?
+    .into_iter()
+    .map(|part| {
+        Ok(CompletedPart {
+            part_number: u32::try_from(part.try_get::<i64, _>("part_number").map_err(db_err)?)
+                .map_err(|_| err_invalid("sqlite part number out of range"))?,
+            etag: part.try_get("etag").map_err(db_err)?,
+            size: i64_to_u64(part.try_get("size").map_err(db_err)?)?,
+        })
+    })
+    .collect::<SpResult<Vec<_>>>()?;
+    Ok(Some(UploadSession {
+        transfer_id: row.try_get("transfer_id").map_err(db_err)?,
+        source_path: row.try_get("source_path").map_err(db_err)?,
+        part_size: i64_to_u64(row.try_get("part_size").map_err(db_err)?)?,
+        content_type: row.try_get("content_type").map_err(db_err)?,
+        content_disposition: row.try_get("content_disposition").map_err(db_err)?,
+        upload_id: row.try_get("upload_id").map_err(db_err)?,
+        source_size: row
+            .try_get::<Option<i64>, _>("source_size")
+            .map_err(db_err)?
+            .map(i64_to_u64)
+            .transpose()?,
+        source_mtime_ms: row.try_get("source_mtime_ms").map_err(db_err)?,
+        content_key_id: row.try_get("content_key_id").map_err(db_err)?,
+        compression: row
+            .try_get::<Option<String>, _>("compression")
+            .map_err(db_err)?
+            .as_deref()
+            .map(crate::compression::Compression::parse)
+            .transpose()?,
+        // Sessions recorded before the column existed overwrote.
+        conflict: row
+            .try_get::<Option<String>, _>("conflict_policy")
+            .map_err(db_err)?
+            .map(|policy| {
+                serde_json::from_str(&policy).map_err(|error| {
+                    err_invalid(&format!("sqlite conflict policy is invalid: {error}"))
+                })
+            })
+            .transpose()?
+            .unwrap_or_default(),
+        parts,
+    }))
+}
+
+pub fn delete_upload_session(transfer_id: &str) -> SpResult<()> {
+    let transfer_id = transfer_id.to_string();
+    run_db(async move {
+        let pool = load_pool().await?;
+        let mut tx = pool.begin().await.map_err(db_err)?;
+        for table in ["upload_parts", "upload_sessions"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE transfer_id = ?"))
+                .bind(transfer_id.clone())
+                .execute(&mut *tx)
+                .await
+                .map_err(db_err)?;
+        }
+        tx.commit().await.map_err(db_err)
+    })
+}
+
 pub fn get_thumbnail_cache(object_key: &str) -> SpResult<Option<ThumbnailCacheEntry>> {
     let object_key = object_key.to_string();
     run_db(async move {
@@ -340,6 +694,84 @@ WHERE object_key = ?
     })
 }
 
+/// Points the cached thumbnail for `from_key` at `to_key`, keeping the
+/// source row. Used after a server-side copy so the copy renders without a
+/// thumbnail round trip.
+pub fn copy_thumbnail_cache(from_key: &str, to_key: &str, thumbnail_key: &str) -> SpResult<()> {
+    let (from_key, to_key, thumbnail_key) = (
+        from_key.to_string(),
+        to_key.to_string(),
+        thumbnail_key.to_string(),
+    );
+    run_db(async move {
+        let pool = load_pool().await?;
+        rekey_thumbnail_cache_in_pool(&pool, &from_key, &to_key, &thumbnail_key, true).await
+    })
+}
+
+/// Like [`copy_thumbnail_cache`], but drops the `from_key` row afterwards.
+pub fn move_thumbnail_cache(from_key: &str, to_key: &str, thumbnail_key: &str) -> SpResult<()> {
+    let (from_key, to_key, thumbnail_key) = (
+        from_key.to_string(),
+        to_key.to_string(),
+        thumbnail_key.to_string(),
+    );
+    run_db(async move {
+        let pool = load_pool().await?;
+        rekey_thumbnail_cache_in_pool(&pool, &from_key, &to_key, &thumbnail_key, false).await
+    })
+}
+
+async fn rekey_thumbnail_cache_in_pool(
+    pool: &Pool<Sqlite>,
+    from_key: &str,
+    to_key: &str,
+    thumbnail_key: &str,
+    keep_source: bool,
+) -> SpResult<()> {
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    // `WHERE true` keeps SQLite from parsing ON CONFLICT as a join clause.
+    sqlx::query(
+        r#"
+INSERT INTO thumbnail_cache (
+  object_key,
+  object_etag,
+  thumbnail_key,
+  data_url,
+  updated_at_ms
+)
+SELECT ?, object_etag, ?, data_url, ?
+FROM thumbnail_cache
+WHERE object_key = ? AND true
+ON CONFLICT(object_key) DO UPDATE SET
+  object_etag = excluded.object_etag,
+  thumbnail_key = excluded.thumbnail_key,
+  data_url = excluded.data_url,
+  updated_at_ms = excluded.updated_at_ms
+        "#,
+    )
+    .bind(to_key)
+    .bind(thumbnail_key)
+    .bind(chrono::Utc::now().timestamp_millis())
+    .bind(from_key)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    if !keep_source {
+        sqlx::query(
+            r#"
+DELETE FROM thumbnail_cache
+WHERE object_key = ?
+            "#,
+        )
+        .bind(from_key)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    }
+    tx.commit().await.map_err(db_err)
+}
+
 fn list_snapshots_with_clause(clause: &str) -> SpResult<Vec<TransferSnapshot>> {
     let clause = clause.to_string();
     run_db(async move {
@@ -363,6 +795,8 @@ SELECT
   temp_path,
   expected_etag,
   observed_etag,
+  profile,
+  decompress,
   created_at_ms,
   updated_at_ms
 FROM transfer_snapshots
@@ -411,6 +845,8 @@ fn row_to_snapshot(row: sqlx::sqlite::SqliteRow) -> SpResult<TransferSnapshot> {
         temp_path: row.try_get("temp_path").map_err(db_err)?,
         expected_etag: row.try_get("expected_etag").map_err(db_err)?,
         observed_etag: row.try_get("observed_etag").map_err(db_err)?,
+        profile: row.try_get("profile").map_err(db_err)?,
+        decompress: row.try_get("decompress").map_err(db_err)?,
         created_at_ms: row.try_get("created_at_ms").map_err(db_err)?,
         updated_at_ms: row.try_get("updated_at_ms").map_err(db_err)?,
     })
@@ -426,7 +862,7 @@ fn row_to_thumbnail_cache(row: sqlx::sqlite::SqliteRow) -> SpResult<ThumbnailCac
     })
 }
 
-async fn load_pool() -> SpResult<Pool<Sqlite>> {
+pub(crate) async fn load_pool() -> SpResult<Pool<Sqlite>> {
     let app = APP_HANDLE.get().ok_or_else(|| SpError {
         kind: ErrorKind::NotRetriable,
         message: "transfer db not initialized".into(),
@@ -450,7 +886,7 @@ async fn load_pool() -> SpResult<Pool<Sqlite>> {
     }
 }
 
-fn run_db<F, T>(future: F) -> SpResult<T>
+pub(crate) fn run_db<F, T>(future: F) -> SpResult<T>
 where
     F: Future<Output = SpResult<T>>,
 {
@@ -461,15 +897,15 @@ where
     }
 }
 
-fn u64_to_i64(value: u64) -> SpResult<i64> {
+pub(crate) fn u64_to_i64(value: u64) -> SpResult<i64> {
     i64::try_from(value).map_err(|_| err_invalid("u64 value overflowed sqlite integer"))
 }
 
-fn i64_to_u64(value: i64) -> SpResult<u64> {
+pub(crate) fn i64_to_u64(value: i64) -> SpResult<u64> {
     u64::try_from(value).map_err(|_| err_invalid("sqlite integer contained negative value"))
 }
 
-fn db_err(err: impl std::fmt::Display) -> SpError {
+pub(crate) fn db_err(err: impl std::fmt::Display) -> SpError {
     SpError {
         kind: ErrorKind::NotRetriable,
         message: format!("transfer db: {err}"),
@@ -534,6 +970,8 @@ mod tests {
             temp_path: Some("/downloads/DSC00001.ARW.part".into()),
             expected_etag: Some("\"original-etag\"".into()),
             observed_etag: Some("\"original-etag\"".into()),
+            profile: Some("staging".into()),
+            decompress: true,
             created_at_ms: 100,
             updated_at_ms: 200,
         }
@@ -567,6 +1005,7 @@ mod tests {
             "temp_path",
             "expected_etag",
             "observed_etag",
+            "profile",
         ] {
             assert!(
                 sql.contains(required),
@@ -604,5 +1043,108 @@ mod tests {
         assert_eq!(recovered.temp_path, expected.temp_path);
         assert_eq!(recovered.expected_etag, expected.expected_etag);
         assert_eq!(recovered.observed_etag, expected.observed_etag);
+        assert_eq!(recovered.profile.as_deref(), Some("staging"));
+        assert!(recovered.decompress);
+    }
+
+    #[tokio::test]
+    async fn upload_session_and_its_parts_survive_database_reopen() {
+        let directory = tempfile::tempdir().expect("temporary directory should exist");
+        let database_path = directory.path().join("transfers.sqlite3");
+        let pool = open_test_pool(&database_path).await;
+        apply_test_migrations(&pool).await;
+        let part = |part_number, etag: &str| CompletedPart {
+            part_number,
+            etag: etag.into(),
+            size: 8 * 1024 * 1024,
+        };
+        let mut session = UploadSession {
+            transfer_id: "upload-restart-1".into(),
+            source_path: "/photos/DSC00001.ARW".into(),
+            part_size: 8 * 1024 * 1024,
+            content_type: Some("image/x-sony-arw".into()),
+            content_disposition: None,
+            upload_id: None,
+            source_size: None,
+            source_mtime_ms: None,
+            content_key_id: None,
+            compression: None,
+            conflict: crate::upload::ConflictPolicy::default(),
+            parts: Vec::new(),
+        };
+        upsert_upload_session_in_pool(&pool, &session)
+            .await
+            .expect("new upload should be recorded");
+        session.upload_id = Some("upload-id-1".into());
+        session.source_size = Some(20_000_000);
+        session.source_mtime_ms = Some(1_700_000_000_000);
+        session.content_key_id = Some("0123456789abcdef".into());
+        session.compression = Some(crate::compression::Compression::Zstd);
+        session.conflict = crate::upload::ConflictPolicy::IfMatch {
+            etag: "\"e0\"".into(),
+        };
+        session.parts = vec![part(1, "\"e1\"")];
+        upsert_upload_session_in_pool(&pool, &session)
+            .await
+            .expect("multipart session should be recorded");
+        let mut connection = pool.acquire().await.expect("connection should open");
+        insert_upload_part(&mut connection, &session.transfer_id, &part(2, "\"e2\""))
+            .await
+            .expect("stored part should be recorded");
+        drop(connection);
+        pool.close().await;
+
+        let reopened = open_test_pool(&database_path).await;
+        let recovered = get_upload_session_in_pool(&reopened, &session.transfer_id)
+            .await
+            .expect("session should load")
+            .expect("session should exist");
+
+        session.parts.push(part(2, "\"e2\""));
+        assert_eq!(recovered, session);
+    }
+
+    #[tokio::test]
+    async fn moved_thumbnail_cache_row_follows_the_new_object_key() {
+        let directory = tempfile::tempdir().expect("temporary directory should exist");
+        let pool = open_test_pool(&directory.path().join("transfers.sqlite3")).await;
+        apply_test_migrations(&pool).await;
+        sqlx::query(
+            "INSERT INTO thumbnail_cache VALUES ('a/photo.jpg', '\"e1\"', 'old-thumb', 'data:x', 1)",
+        )
+        .execute(&pool)
+        .await
+        .expect("fixture row should insert");
+
+        rekey_thumbnail_cache_in_pool(&pool, "a/photo.jpg", "b/photo.jpg", "new-thumb", true)
+            .await
+            .expect("copy should succeed");
+        rekey_thumbnail_cache_in_pool(&pool, "a/photo.jpg", "c/photo.jpg", "moved-thumb", false)
+            .await
+            .expect("move should succeed");
+
+        let rows: Vec<(String, String, Option<String>, String)> = sqlx::query_as(
+            "SELECT object_key, thumbnail_key, object_etag, data_url FROM thumbnail_cache ORDER BY object_key",
+        )
+        .fetch_all(&pool)
+        .await
+        .expect("rows should load");
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "b/photo.jpg".to_string(),
+                    "new-thumb".to_string(),
+                    Some("\"e1\"".to_string()),
+                    "data:x".to_string()
+                ),
+                (
+                    "c/photo.jpg".to_string(),
+                    "moved-thumb".to_string(),
+                    Some("\"e1\"".to_string()),
+                    "data:x".to_string()
+                ),
+            ]
+        );
     }
 }
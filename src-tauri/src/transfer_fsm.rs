@@ -35,11 +35,17 @@ pub enum TransferLifecycle {
     Completed,
     Failed,
     Cancelled,
+    /// Finished without transferring because the destination already held
+    /// the same content.
+    Skipped,
 }
 
 impl TransferLifecycle {
     pub fn is_terminal(&self) -> bool {
-        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+        matches!(
+            self,
+            Self::Completed | Self::Failed | Self::Cancelled | Self::Skipped
+        )
     }
 
     pub fn as_str(&self) -> &'static str {
@@ -51,6 +57,7 @@ impl TransferLifecycle {
             Self::Completed => "completed",
             Self::Failed => "failed",
             Self::Cancelled => "cancelled",
+            Self::Skipped => "skipped",
         }
     }
 
@@ -63,6 +70,7 @@ impl TransferLifecycle {
             "completed" => Ok(Self::Completed),
             "failed" => Ok(Self::Failed),
             "cancelled" => Ok(Self::Cancelled),
+            "skipped" => Ok(Self::Skipped),
             _ => Err(err_invalid("invalid transfer lifecycle")),
         }
     }
@@ -134,6 +142,7 @@ pub enum TransferStateEvent {
     CancelRequest,
     CancelConfirm,
     Complete,
+    Skip,
     Fail,
 }
 
@@ -285,6 +294,15 @@ pub fn apply_transfer_event(
                 "complete transition not allowed from current state",
             )),
         },
+        TransferStateEvent::Skip => match current.lifecycle {
+            TransferLifecycle::Running => Ok(TransferState {
+                lifecycle: TransferLifecycle::Skipped,
+                phase: None,
+            }),
+            _ => Err(err_invalid(
+                "skip transition not allowed from current state",
+            )),
+        },
         TransferStateEvent::Fail => match current.lifecycle {
             TransferLifecycle::Queued
             | TransferLifecycle::Running
@@ -466,6 +484,29 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn only_a_running_transfer_can_be_skipped() {
+        let queued = TransferState::queued(TransferKind::Upload);
+        let running = run(
+            TransferKind::Upload,
+            &queued,
+            TransferPhase::PreparingSource,
+        );
+        let skipped =
+            apply_transfer_event(TransferKind::Upload, &running, TransferStateEvent::Skip)
+                .expect("running upload should be skippable");
+
+        assert_eq!(skipped.lifecycle, TransferLifecycle::Skipped);
+        assert_eq!(skipped.phase, None);
+        assert!(skipped.lifecycle.is_terminal());
+        assert!(
+            apply_transfer_event(TransferKind::Upload, &queued, TransferStateEvent::Skip).is_err()
+        );
+        assert!(
+            apply_transfer_event(TransferKind::Upload, &skipped, TransferStateEvent::Fail).is_err()
+        );
+    }
+
     #[test]
     fn string_round_trips_reject_unknown_persisted_values() {
         assert_eq!(
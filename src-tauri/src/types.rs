@@ -12,6 +12,14 @@ pub enum ErrorKind {
     DiskFull,
     TaskExists,
     NotImplemented,
+    NotFound,
+    PermissionDenied,
+    /// The request collided with another write, e.g. the key already exists.
+    Conflict,
+    /// The endpoint asked for fewer requests; retry after a pause.
+    RateLimited,
+    /// Content did not hash to the SHA-256 recorded for it.
+    Integrity,
 }
 
 impl ErrorKind {
@@ -25,6 +33,11 @@ impl ErrorKind {
             Self::DiskFull => "disk_full",
             Self::TaskExists => "task_exists",
             Self::NotImplemented => "not_implemented",
+            Self::NotFound => "not_found",
+            Self::PermissionDenied => "permission_denied",
+            Self::Conflict => "conflict",
+            Self::RateLimited => "rate_limited",
+            Self::Integrity => "integrity",
         }
     }
 
@@ -38,6 +51,11 @@ impl ErrorKind {
             "disk_full" => Ok(Self::DiskFull),
             "task_exists" => Ok(Self::TaskExists),
             "not_implemented" => Ok(Self::NotImplemented),
+            "not_found" => Ok(Self::NotFound),
+            "permission_denied" => Ok(Self::PermissionDenied),
+            "conflict" => Ok(Self::Conflict),
+            "rate_limited" => Ok(Self::RateLimited),
+            "integrity" => Ok(Self::Integrity),
             _ => Err(err_invalid("invalid error kind")),
         }
     }
@@ -60,7 +78,43 @@ pub struct R2Config {
     pub access_key_id: String,
     pub secret_access_key: String,
     pub bucket: String,
-    pub region: Option<String>, // default "auto"
+    pub region: Option<String>, // default depends on provider ("auto" for R2)
+    #[serde(default)]
+    pub provider: StorageProvider,
+}
+
+/// S3-compatible service behind an [`R2Config`]; selects addressing style,
+/// region defaults, request quirks and pricing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageProvider {
+    #[default]
+    R2,
+    R2Eu,
+    R2Fedramp,
+    AwsS3,
+    Minio,
+    BackblazeB2,
+    Wasabi,
+}
+
+impl StorageProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::R2 => "r2",
+            Self::R2Eu => "r2_eu",
+            Self::R2Fedramp => "r2_fedramp",
+            Self::AwsS3 => "aws_s3",
+            Self::Minio => "minio",
+            Self::BackblazeB2 => "backblaze_b2",
+            Self::Wasabi => "wasabi",
+        }
+    }
+
+    /// Whether an empty endpoint can be derived from the region.
+    pub fn has_default_endpoint(&self) -> bool {
+        matches!(self, Self::AwsS3 | Self::Wasabi)
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -86,17 +140,25 @@ pub struct DownloadChunkProgress {
     pub bytes_transferred: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UsageDelta {
     pub class_a: std::collections::HashMap<String, u64>,
     pub class_b: std::collections::HashMap<String, u64>,
     pub ingress_bytes: u64,
     pub egress_bytes: u64,
     pub added_storage_bytes: u64,
+    /// Source bytes of finished uploads, before compression or sealing;
+    /// `added_storage_bytes` counts what was stored.
+    #[serde(default)]
+    pub added_logical_storage_bytes: u64,
     pub deleted_storage_bytes: u64,
+    /// Source bytes of finished uploads, before compression or sealing;
+    /// `ingress_bytes` counts what was stored.
+    #[serde(default)]
+    pub logical_ingress_bytes: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DailyLedger {
     pub date: String, // YYYY-MM-DD (UTC)
     pub class_a: std::collections::HashMap<String, u64>,
@@ -106,6 +168,10 @@ pub struct DailyLedger {
     pub storage_bytes: u64,
     pub peak_storage_bytes: u64,
     pub deleted_storage_bytes: u64,
+    #[serde(default)]
+    pub logical_ingress_bytes: u64,
+    #[serde(default)]
+    pub added_logical_storage_bytes: u64,
     pub rev: u64,
     pub updated_at: String, // ISO UTC
 }
@@ -131,6 +197,193 @@ pub struct ListPage {
     pub next_token: Option<String>,
 }
 
+/// Content headers and `x-amz-meta-*` pairs that can be rewritten in place.
+/// `user_metadata` keys are stored without the `x-amz-meta-` prefix.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObjectHeaders {
+    pub content_type: Option<String>,
+    pub content_disposition: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_encoding: Option<String>,
+    #[serde(default)]
+    pub user_metadata: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectMetadata {
+    pub key: String,
+    pub size: u64,
+    pub last_modified_ms: Option<i64>,
+    pub etag: Option<String>,
+    pub content_md5: Option<String>,
+    pub version: Option<String>,
+    #[serde(flatten)]
+    pub headers: ObjectHeaders,
+}
+
+/// Result of re-hashing a stored object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectVerification {
+    pub key: String,
+    pub size: u64,
+    /// The hash recorded in the object's `x-amz-meta-sha256`, if any.
+    pub recorded_sha256: Option<String>,
+    pub computed_sha256: String,
+    /// `None` when the object carries no recorded hash to compare with.
+    pub matches: Option<bool>,
+}
+
+/// Replaces every editable header of an object. Headers left `None` are
+/// removed; `expected_etag` guards against overwriting a concurrent change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ObjectMetadataUpdate {
+    #[serde(flatten)]
+    pub headers: ObjectHeaders,
+    pub expected_etag: Option<String>,
+}
+
+/// Progress of a long-running operation over every object under a prefix.
+/// `total` is known once the prefix has been listed and does not change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefixOpProgress {
+    pub op_id: String,
+    pub op: String,
+    pub prefix: String,
+    pub processed: u64,
+    pub total: u64,
+    pub current_key: Option<String>,
+    /// Set on the final report of an operation stopped by `cancel_prefix_op`.
+    pub cancelled: bool,
+}
+
+/// An object in the recycle bin. `trash_key` identifies it for restore.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub trash_key: String,
+    pub original_key: String,
+    pub deleted_at_ms: i64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectIndexSort {
+    #[default]
+    Key,
+    Name,
+    Size,
+    Modified,
+}
+
+/// Filters for a search over the local object index. Every filter is
+/// optional; `name` is a case-insensitive substring, or a glob when it
+/// contains `*`, `?` or `[`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectIndexQuery {
+    pub name: Option<String>,
+    /// Extensions without the leading dot, matched case-insensitively.
+    pub extensions: Vec<String>,
+    pub prefix: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after_ms: Option<i64>,
+    pub modified_before_ms: Option<i64>,
+    pub include_folders: bool,
+    pub sort: ObjectIndexSort,
+    pub descending: bool,
+    pub offset: u64,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectIndexPage {
+    pub items: Vec<FileEntry>,
+    /// Number of matches across all pages.
+    pub total: u64,
+    pub next_offset: Option<u64>,
+}
+
+/// Outcome of reconciling the index against a listing of `prefix`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectIndexSync {
+    pub prefix: String,
+    pub indexed: u64,
+    pub removed: u64,
+    pub finished_at_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectIndexStatus {
+    pub profile: String,
+    pub objects: u64,
+    /// When a listing of the whole bucket last completed, if ever.
+    pub last_full_sync_ms: Option<i64>,
+}
+
+/// A bucket lifecycle rule. An empty `prefix` applies to the whole bucket;
+/// each rule needs at least one of the two day counts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleRule {
+    pub id: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub prefix: String,
+    pub expiration_days: Option<u32>,
+    pub abort_incomplete_multipart_days: Option<u32>,
+    /// Elements of a rule written by another tool that SwiftPan cannot
+    /// represent, e.g. `Transition` or `Filter/Tag`. Such rules are shown
+    /// but must be removed before the configuration can be saved here.
+    #[serde(default)]
+    pub unsupported: Vec<String>,
+}
+
+/// One step of the endpoint diagnostics, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticCheck {
+    Dns,
+    TlsHandshake,
+    ClockSkew,
+    Signature,
+    List,
+    Write,
+    Read,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticStatus {
+    Pass,
+    Fail,
+    /// Not run because an earlier step failed or it does not apply.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticStep {
+    pub check: DiagnosticCheck,
+    pub status: DiagnosticStatus,
+    /// What was observed, e.g. resolved addresses or the S3 error code.
+    pub detail: String,
+    /// What to change when the step failed.
+    pub hint: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    /// Bucket URL the probes were sent to.
+    pub url: String,
+    pub bucket: String,
+    /// True when no step failed.
+    pub ok: bool,
+    /// Local clock minus the server's `Date`, when the server sent one.
+    pub clock_skew_ms: Option<i64>,
+    pub steps: Vec<DiagnosticStep>,
+}
+
 // Helper to create a standard NotImplemented error
 pub fn err_not_implemented(msg: &str) -> SpError {
     SpError {
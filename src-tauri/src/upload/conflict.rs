@@ -0,0 +1,188 @@
+//! Upload conflict policies.
+//!
+//! This module owns what an upload does when its destination key already
+//! holds an object: resolving the key and the write precondition before the
+//! upload starts, naming the alternatives auto-rename tries, and recognizing
+//! the bucket's refusal of a conditional write. The precondition travels with
+//! the write itself, so a race between devices is caught by the bucket rather
+//! than by the check beforehand. It must not send content, mutate transfer
+//! state, or emit Tauri events.
+
+use crate::types::{ErrorKind, SpError, SpResult};
+use opendal::Operator;
+use serde::{Deserialize, Serialize};
+
+/// Alternative names auto-rename tries before giving up.
+const MAX_RENAMES: u32 = 1000;
+
+/// What an upload does when its destination key already holds an object.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Replace whatever is stored under the key.
+    #[default]
+    Overwrite,
+    /// Leave the stored object alone and finish as skipped.
+    Skip,
+    /// Fail with [`ErrorKind::Conflict`].
+    Fail,
+    /// Upload as `name (1).ext`, `name (2).ext`, … instead.
+    Rename,
+    /// Replace the object only while its ETag is still `etag`.
+    IfMatch { etag: String },
+}
+
+/// The precondition a write is sent with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) enum WriteCondition {
+    #[default]
+    None,
+    /// `If-None-Match: *`: nothing may be stored under the key.
+    Absent,
+    /// `If-Match`: the stored object must still have this ETag.
+    Matches(String),
+}
+
+impl WriteCondition {
+    /// Request headers that carry the precondition.
+    pub(crate) fn headers(&self) -> Vec<(String, String)> {
+        match self {
+            Self::None => Vec::new(),
+            Self::Absent => vec![("if-none-match".into(), "*".into())],
+            Self::Matches(etag) => vec![("if-match".into(), quoted(etag))],
+        }
+    }
+
+    /// Whether `error` is the bucket refusing a write sent with this
+    /// precondition.
+    pub(crate) fn refused(&self, error: &SpError) -> bool {
+        let code = error
+            .context
+            .as_ref()
+            .and_then(|context| context.get("code"))
+            .and_then(|code| code.as_str());
+        match (self, &error.kind) {
+            (Self::None, _) => false,
+            // 412, or 409 for a conditional write racing another one.
+            (_, ErrorKind::SourceChanged | ErrorKind::Conflict) => true,
+            // If-Match on a key that no longer exists.
+            (Self::Matches(_), ErrorKind::NotFound) => code != Some("NoSuchUpload"),
+            _ => false,
+        }
+    }
+}
+
+/// Where an upload goes under its conflict policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Destination {
+    Write {
+        key: String,
+        condition: WriteCondition,
+    },
+    /// The policy keeps the object already stored.
+    Skip,
+}
+
+/// Resolves `policy` against what the bucket holds at `key` now. The
+/// returned condition re-checks that decision when the write completes.
+pub(crate) async fn resolve_destination(
+    operator: &Operator,
+    key: &str,
+    policy: &ConflictPolicy,
+) -> SpResult<Destination> {
+    let write = |key: &str| Destination::Write {
+        key: key.to_string(),
+        condition: write_condition(operator, policy),
+    };
+    match policy {
+        ConflictPolicy::Overwrite => Ok(write(key)),
+        ConflictPolicy::Skip if stored_etag(operator, key).await?.is_some() => {
+            Ok(Destination::Skip)
+        }
+        ConflictPolicy::Fail if stored_etag(operator, key).await?.is_some() => {
+            Err(conflict_error(key, "already exists"))
+        }
+        ConflictPolicy::Skip | ConflictPolicy::Fail => Ok(write(key)),
+        ConflictPolicy::Rename => {
+            for candidate in std::iter::once(key.to_string())
+                .chain((1..=MAX_RENAMES).map(|number| renamed_key(key, number)))
+            {
+                if stored_etag(operator, &candidate).await?.is_none() {
+                    return Ok(write(&candidate));
+                }
+            }
+            Err(conflict_error(
+                key,
+                &format!("and {MAX_RENAMES} renamed copies already exist"),
+            ))
+        }
+        ConflictPolicy::IfMatch { etag } => match stored_etag(operator, key).await? {
+            Some(stored) if unquoted(&stored) == unquoted(etag) => Ok(write(key)),
+            Some(stored) => Err(conflict_error(
+                key,
+                &format!("changed: its ETag is {stored}, not {etag}"),
+            )),
+            None => Err(conflict_error(key, "no longer exists")),
+        },
+    }
+}
+
+/// The precondition that enforces `policy`, if the operator's service
+/// honours it. Providers without conditional writes only get the check done
+/// before the upload starts.
+pub(crate) fn write_condition(operator: &Operator, policy: &ConflictPolicy) -> WriteCondition {
+    let capability = operator.info().full_capability();
+    match policy {
+        ConflictPolicy::Overwrite => WriteCondition::None,
+        ConflictPolicy::Skip | ConflictPolicy::Fail | ConflictPolicy::Rename
+            if capability.write_with_if_not_exists =>
+        {
+            WriteCondition::Absent
+        }
+        ConflictPolicy::IfMatch { etag } if capability.write_with_if_match => {
+            WriteCondition::Matches(etag.clone())
+        }
+        _ => WriteCondition::None,
+    }
+}
+
+/// `key` with ` (<number>)` inserted before the extension of its last
+/// segment: `photos/a.jpg` becomes `photos/a (1).jpg`.
+pub(crate) fn renamed_key(key: &str, number: u32) -> String {
+    let (directory, name) = match key.rfind('/') {
+        Some(slash) => key.split_at(slash + 1),
+        None => ("", key),
+    };
+    // A leading dot marks a hidden file, not an extension.
+    match name.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => format!("{directory}{} ({number}){}", &name[..dot], &name[dot..]),
+        None => format!("{directory}{name} ({number})"),
+    }
+}
+
+pub(crate) fn conflict_error(key: &str, reason: &str) -> SpError {
+    crate::storage::storage_error(
+        ErrorKind::Conflict,
+        format!("{key} {reason}"),
+        "upload",
+        key,
+        None,
+    )
+}
+
+/// ETag of the object at `key`, or `None` when nothing is stored there.
+async fn stored_etag(operator: &Operator, key: &str) -> SpResult<Option<String>> {
+    match operator.stat(key).await {
+        Ok(metadata) => Ok(Some(metadata.etag().unwrap_or_default().to_string())),
+        Err(error) if error.kind() == opendal::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(crate::storage::opendal_error("stat", key, &error)),
+    }
+}
+
+fn unquoted(etag: &str) -> &str {
+    etag.trim_start_matches("W/").trim_matches('"')
+}
+
+pub(super) fn quoted(etag: &str) -> String {
+    format!("\"{}\"", unquoted(etag))
+}
@@ -0,0 +1,526 @@
+//! Content-type inference for uploads.
+//!
+//! This module owns the type an object is stored with when the caller names
+//! none: the leading bytes of the source decide when they carry a known
+//! signature, the file extension refines generic containers (a ZIP that is
+//! a `.docx`, a TIFF that is a camera RAW) and decides alone when the bytes
+//! say nothing. It must not read sources or talk to storage; callers pass
+//! the first bytes they already hold, before sealing or compression.
+
+/// Leading source bytes worth passing in; sniffing looks no further.
+pub(super) const SNIFF_BYTES: usize = 8 * 1024;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+/// Sniffed for UTF-8 text no signature or markup identifies further.
+const PLAIN_TEXT: &str = "text/plain";
+/// Fewer leading bytes than this that decode as text do not outweigh an
+/// extension.
+const TEXT_EVIDENCE_BYTES: usize = 16;
+const ZIP: &str = "application/zip";
+const OLE_STORAGE: &str = "application/x-ole-storage";
+const TIFF: &str = "image/tiff";
+const ISO_MEDIA: &str = "video/mp4";
+
+/// Types by lower-case file extension.
+const EXTENSION_TYPES: &[(&str, &str)] = &[
+    // Camera RAW
+    ("3fr", "image/x-hasselblad-3fr"),
+    ("arw", "image/x-sony-arw"),
+    ("cr2", "image/x-canon-cr2"),
+    ("cr3", "image/x-canon-cr3"),
+    ("crw", "image/x-canon-crw"),
+    ("dcr", "image/x-kodak-dcr"),
+    ("dng", "image/x-adobe-dng"),
+    ("erf", "image/x-epson-erf"),
+    ("iiq", "image/x-phaseone-iiq"),
+    ("k25", "image/x-kodak-k25"),
+    ("kdc", "image/x-kodak-kdc"),
+    ("mef", "image/x-mamiya-mef"),
+    ("mos", "image/x-leaf-mos"),
+    ("mrw", "image/x-minolta-mrw"),
+    ("nef", "image/x-nikon-nef"),
+    ("nrw", "image/x-nikon-nrw"),
+    ("orf", "image/x-olympus-orf"),
+    ("pef", "image/x-pentax-pef"),
+    ("raf", "image/x-fuji-raf"),
+    ("raw", "image/x-panasonic-raw"),
+    ("rw2", "image/x-panasonic-rw2"),
+    ("rwl", "image/x-leica-rwl"),
+    ("sr2", "image/x-sony-sr2"),
+    ("srf", "image/x-sony-srf"),
+    ("srw", "image/x-samsung-srw"),
+    ("x3f", "image/x-sigma-x3f"),
+    // Images
+    ("apng", "image/apng"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("cur", "image/x-icon"),
+    ("djvu", "image/vnd.djvu"),
+    ("gif", "image/gif"),
+    ("heic", "image/heic"),
+    ("heif", "image/heif"),
+    ("ico", "image/x-icon"),
+    ("jfif", "image/jpeg"),
+    ("jp2", "image/jp2"),
+    ("jpe", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("jxl", "image/jxl"),
+    ("png", "image/png"),
+    ("psd", "image/vnd.adobe.photoshop"),
+    ("svg", "image/svg+xml"),
+    ("tga", "image/x-tga"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("webp", "image/webp"),
+    // Video
+    ("3g2", "video/3gpp2"),
+    ("3gp", "video/3gpp"),
+    ("asf", "video/x-ms-asf"),
+    ("avi", "video/x-msvideo"),
+    ("flv", "video/x-flv"),
+    ("m2ts", "video/mp2t"),
+    ("m4v", "video/x-m4v"),
+    ("mkv", "video/x-matroska"),
+    ("mov", "video/quicktime"),
+    ("mp4", "video/mp4"),
+    ("mpeg", "video/mpeg"),
+    ("mpg", "video/mpeg"),
+    ("mts", "video/mp2t"),
+    ("ogv", "video/ogg"),
+    ("ts", "video/mp2t"),
+    ("webm", "video/webm"),
+    ("wmv", "video/x-ms-wmv"),
+    // Audio
+    ("aac", "audio/aac"),
+    ("aif", "audio/aiff"),
+    ("aiff", "audio/aiff"),
+    ("amr", "audio/amr"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("m4b", "audio/mp4"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    ("mka", "audio/x-matroska"),
+    ("mp3", "audio/mpeg"),
+    ("oga", "audio/ogg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("weba", "audio/webm"),
+    ("wma", "audio/x-ms-wma"),
+    // Documents
+    ("doc", "application/msword"),
+    ("docm", "application/vnd.ms-word.document.macroenabled.12"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("epub", "application/epub+zip"),
+    ("key", "application/vnd.apple.keynote"),
+    ("msg", "application/vnd.ms-outlook"),
+    ("numbers", "application/vnd.apple.numbers"),
+    ("odg", "application/vnd.oasis.opendocument.graphics"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("pages", "application/vnd.apple.pages"),
+    ("pdf", "application/pdf"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptm",
+        "application/vnd.ms-powerpoint.presentation.macroenabled.12",
+    ),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("ps", "application/postscript"),
+    ("rtf", "application/rtf"),
+    ("vsd", "application/vnd.visio"),
+    ("vsdx", "application/vnd.ms-visio.drawing"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsm", "application/vnd.ms-excel.sheet.macroenabled.12"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    // Text and data
+    ("c", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("cs", "text/x-csharp"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("go", "text/x-go"),
+    ("h", "text/x-c"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ics", "text/calendar"),
+    ("ini", "text/plain"),
+    ("java", "text/x-java"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("jsonl", "application/jsonl"),
+    ("kt", "text/x-kotlin"),
+    ("log", "text/plain"),
+    ("md", "text/markdown"),
+    ("mjs", "text/javascript"),
+    ("py", "text/x-python"),
+    ("rb", "text/x-ruby"),
+    ("rs", "text/x-rust"),
+    ("sh", "application/x-sh"),
+    ("sql", "application/sql"),
+    ("srt", "application/x-subrip"),
+    ("swift", "text/x-swift"),
+    ("toml", "application/toml"),
+    ("tsv", "text/tab-separated-values"),
+    ("tsx", "text/tsx"),
+    ("txt", "text/plain"),
+    ("vcf", "text/vcard"),
+    ("vtt", "text/vtt"),
+    ("xhtml", "application/xhtml+xml"),
+    ("xml", "application/xml"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("gpx", "application/gpx+xml"),
+    ("kml", "application/vnd.google-earth.kml+xml"),
+    // Archives and packages
+    ("7z", "application/x-7z-compressed"),
+    ("apk", "application/vnd.android.package-archive"),
+    ("bz2", "application/x-bzip2"),
+    ("cab", "application/vnd.ms-cab-compressed"),
+    ("deb", "application/vnd.debian.binary-package"),
+    ("dmg", "application/x-apple-diskimage"),
+    ("gz", "application/gzip"),
+    ("iso", "application/x-iso9660-image"),
+    ("jar", "application/java-archive"),
+    ("kmz", "application/vnd.google-earth.kmz"),
+    ("msi", "application/x-msi"),
+    ("rar", "application/vnd.rar"),
+    ("tar", "application/x-tar"),
+    ("tgz", "application/gzip"),
+    ("xpi", "application/x-xpinstall"),
+    ("xz", "application/x-xz"),
+    ("zip", "application/zip"),
+    ("zst", "application/zstd"),
+    // Fonts, binaries and databases
+    ("exe", "application/vnd.microsoft.portable-executable"),
+    ("dll", "application/vnd.microsoft.portable-executable"),
+    ("otf", "font/otf"),
+    ("sqlite", "application/vnd.sqlite3"),
+    ("ttf", "font/ttf"),
+    ("wasm", "application/wasm"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+];
+
+/// Stored as a ZIP archive.
+const ZIP_BASED: &[&str] = &[
+    "application/epub+zip",
+    "application/java-archive",
+    "application/vnd.android.package-archive",
+    "application/vnd.apple.keynote",
+    "application/vnd.apple.numbers",
+    "application/vnd.apple.pages",
+    "application/vnd.google-earth.kmz",
+    "application/vnd.ms-excel.sheet.macroenabled.12",
+    "application/vnd.ms-powerpoint.presentation.macroenabled.12",
+    "application/vnd.ms-visio.drawing",
+    "application/vnd.ms-word.document.macroenabled.12",
+    "application/vnd.oasis.opendocument.graphics",
+    "application/vnd.oasis.opendocument.presentation",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/x-xpinstall",
+];
+
+/// Stored as an OLE compound file.
+const OLE_BASED: &[&str] = &[
+    "application/msword",
+    "application/vnd.ms-excel",
+    "application/vnd.ms-outlook",
+    "application/vnd.ms-powerpoint",
+    "application/vnd.visio",
+    "application/x-msi",
+];
+
+/// Stored in an ISO base media file whose brand said nothing more.
+const ISO_MEDIA_BASED: &[&str] = &[
+    "audio/mp4",
+    "video/3gpp",
+    "video/3gpp2",
+    "video/quicktime",
+    "video/x-m4v",
+];
+
+/// The type to store an upload of `key` with. An explicit type wins after
+/// trimming; otherwise `head`, the first bytes of the source (up to
+/// [`SNIFF_BYTES`], possibly none), decides together with the extension.
+pub(super) fn inferred_content_type(key: &str, explicit: Option<&str>, head: &[u8]) -> String {
+    if let Some(value) = explicit.map(str::trim).filter(|value| !value.is_empty()) {
+        return value.to_string();
+    }
+    let by_extension = extension_type(key);
+    let head = &head[..head.len().min(SNIFF_BYTES)];
+    let Some(sniffed) = sniffed_type(head) else {
+        return by_extension.unwrap_or(OCTET_STREAM).to_string();
+    };
+    let chosen = match by_extension {
+        Some(by_extension) if refines(sniffed, by_extension) => by_extension,
+        // A few printable bytes are as likely the start of anything else.
+        Some(by_extension) if sniffed == PLAIN_TEXT && head.len() < TEXT_EVIDENCE_BYTES => {
+            by_extension
+        }
+        _ => sniffed,
+    };
+    // Sniffed text is UTF-8; without a charset browsers may guess otherwise.
+    if is_textual(sniffed) && chosen.starts_with("text/") && !chosen.contains(';') {
+        format!("{chosen}; charset=utf-8")
+    } else {
+        chosen.to_string()
+    }
+}
+
+fn extension_type(key: &str) -> Option<&'static str> {
+    let extension = std::path::Path::new(key)
+        .extension()
+        .and_then(|value| value.to_str())?
+        .to_ascii_lowercase();
+    EXTENSION_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, content_type)| *content_type)
+}
+
+/// Whether the extension's type is a more specific reading of what the
+/// bytes show.
+fn refines(sniffed: &str, by_extension: &str) -> bool {
+    match sniffed {
+        ZIP => ZIP_BASED.contains(&by_extension),
+        OLE_STORAGE => OLE_BASED.contains(&by_extension),
+        // Most camera RAW formats are TIFF underneath.
+        TIFF => by_extension.starts_with("image/x-"),
+        ISO_MEDIA => ISO_MEDIA_BASED.contains(&by_extension),
+        "audio/ogg" => by_extension == "video/ogg",
+        "video/x-matroska" => by_extension == "audio/x-matroska",
+        "video/webm" => by_extension == "audio/webm",
+        "video/x-ms-asf" => matches!(by_extension, "video/x-ms-wmv" | "audio/x-ms-wma"),
+        // A sniffed charset other than UTF-8 is worth more than the name.
+        _ => is_textual(sniffed) && !sniffed.contains(';') && is_textual(by_extension),
+    }
+}
+
+fn is_textual(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.ends_with("+xml")
+        || matches!(
+            content_type,
+            "application/json"
+                | "application/jsonl"
+                | "application/sql"
+                | "application/toml"
+                | "application/x-sh"
+                | "application/x-subrip"
+                | "application/xml"
+                | "application/yaml"
+        )
+}
+
+/// The type the leading bytes show, if they show one.
+fn sniffed_type(head: &[u8]) -> Option<&'static str> {
+    if head.is_empty() {
+        return None;
+    }
+    signature_type(head).or_else(|| text_type(head))
+}
+
+fn signature_type(head: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, signature: &[u8]| {
+        head.get(offset..offset + signature.len()) == Some(signature)
+    };
+    let content_type = match head {
+        // Before ICO, which a large first box would also match.
+        _ if at(4, b"ftyp") => iso_media_type(head.get(8..12)?),
+        // Images
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', ..] => "image/png",
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'B', b'M', _, _, _, _, 0, 0, 0, 0, ..] => "image/bmp",
+        [0, 0, 1 | 2, 0, count, ..] if *count > 0 => "image/x-icon",
+        [b'I', b'I', b'R', b'O', ..] => "image/x-olympus-orf",
+        [b'I', b'I', b'U', 0, ..] => "image/x-panasonic-rw2",
+        _ if at(0, b"FUJIFILMCCD-RAW") => "image/x-fuji-raf",
+        [b'I', b'I', b'*', 0, ..] | [b'M', b'M', 0, b'*', ..] => TIFF,
+        [0xFF, 0x0A, ..] => "image/jxl",
+        _ if at(0, b"\0\0\0\x0CJXL \r\n\x87\n") => "image/jxl",
+        _ if at(0, b"\0\0\0\x0CjP  \r\n\x87\n") => "image/jp2",
+        _ if at(0, b"8BPS") => "image/vnd.adobe.photoshop",
+        _ if at(0, b"AT&TFORM") => "image/vnd.djvu",
+        // Audio and video
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => {
+            if contains(head, b"webm") {
+                "video/webm"
+            } else {
+                "video/x-matroska"
+            }
+        }
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => "audio/wav",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'A', b'V', b'I', b' ', ..] => "video/x-msvideo",
+        [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => "audio/aiff",
+        _ if at(0, b"OggS") => {
+            if contains(head, b"theora") {
+                "video/ogg"
+            } else {
+                "audio/ogg"
+            }
+        }
+        _ if at(0, b"fLaC") => "audio/flac",
+        _ if at(0, b"ID3") => "audio/mpeg",
+        _ if at(0, b"MThd") => "audio/midi",
+        _ if at(0, b"#!AMR") => "audio/amr",
+        _ if at(0, b"FLV\x01") => "video/x-flv",
+        [0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, ..] => "video/x-ms-asf",
+        [0, 0, 1, 0xBA | 0xB3, ..] => "video/mpeg",
+        // Three packets in a row, so text starting with `G` does not match.
+        _ if at(0, b"G") && at(188, b"G") && at(376, b"G") => "video/mp2t",
+        // Byte order marks come before MPEG frame sync, which FF FE matches.
+        [0xEF, 0xBB, 0xBF, ..] => return text_type(&head[3..]).or(Some(PLAIN_TEXT)),
+        [0xFF, 0xFE, ..] | [0xFE, 0xFF, ..] => "text/plain; charset=utf-16",
+        // ADTS AAC has layer 0; MPEG audio layers 1 to 3.
+        [0xFF, second, ..] if second & 0xF6 == 0xF0 => "audio/aac",
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 && (second >> 1) & 3 != 0 => "audio/mpeg",
+        // Documents
+        _ if at(0, b"%PDF-") => "application/pdf",
+        _ if at(0, b"%!PS") => "application/postscript",
+        _ if at(0, b"{\\rtf") => "application/rtf",
+        [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, ..] => OLE_STORAGE,
+        [b'P', b'K', 3, 4, ..] => zip_type(head),
+        [b'P', b'K', 5 | 7, 6 | 8, ..] => ZIP,
+        // Archives
+        [0x1F, 0x8B, ..] => "application/gzip",
+        _ if at(0, b"BZh") => "application/x-bzip2",
+        _ if at(0, b"\xFD7zXZ\0") => "application/x-xz",
+        _ if at(0, b"7z\xBC\xAF\x27\x1C") => "application/x-7z-compressed",
+        _ if at(0, b"Rar!\x1A\x07") => "application/vnd.rar",
+        [0x28, 0xB5, 0x2F, 0xFD, ..] => "application/zstd",
+        _ if at(0, b"MSCF") => "application/vnd.ms-cab-compressed",
+        _ if at(0, b"!<arch>\ndebian-binary") => "application/vnd.debian.binary-package",
+        _ if at(257, b"ustar") => "application/x-tar",
+        // Fonts, binaries and databases
+        _ if at(0, b"wOFF") => "font/woff",
+        _ if at(0, b"wOF2") => "font/woff2",
+        _ if at(0, b"OTTO") => "font/otf",
+        [0, 1, 0, 0, 0, ..] => "font/ttf",
+        _ if at(0, b"SQLite format 3\0") => "application/vnd.sqlite3",
+        _ if at(0, b"\0asm") => "application/wasm",
+        _ if at(0, b"\x7FELF") => "application/x-executable",
+        [0xFE, 0xED, 0xFA, 0xCE | 0xCF, ..] | [0xCE | 0xCF, 0xFA, 0xED, 0xFE, ..] => {
+            "application/x-mach-binary"
+        }
+        [b'M', b'Z', ..] if portable_executable(head) => {
+            "application/vnd.microsoft.portable-executable"
+        }
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// The type an ISO base media file's major brand names.
+fn iso_media_type(brand: &[u8]) -> &'static str {
+    match brand {
+        b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" => "image/heic",
+        b"mif1" | b"msf1" => "image/heif",
+        b"avif" | b"avis" => "image/avif",
+        b"crx " => "image/x-canon-cr3",
+        b"qt  " => "video/quicktime",
+        b"M4A " | b"M4B " => "audio/mp4",
+        b"M4V " => "video/x-m4v",
+        [b'3', b'g', b'2', _] => "video/3gpp2",
+        [b'3', b'g', b'p', _] => "video/3gpp",
+        _ => ISO_MEDIA,
+    }
+}
+
+/// What a ZIP archive's first entries show it to be. ODF and EPUB store
+/// their type, uncompressed, as the first entry `mimetype`; Office Open XML
+/// names its parts after the application.
+fn zip_type(head: &[u8]) -> &'static str {
+    const LOCAL_HEADER: usize = 30;
+    let name_len = head
+        .get(26..28)
+        .map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize);
+    let extra_len = head
+        .get(28..30)
+        .map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize);
+    if head.get(LOCAL_HEADER..LOCAL_HEADER + name_len) == Some(b"mimetype") {
+        let content = &head[(LOCAL_HEADER + name_len + extra_len).min(head.len())..];
+        for known in ZIP_BASED {
+            if content.starts_with(known.as_bytes()) {
+                return known;
+            }
+        }
+    }
+    let office = contains(head, b"[Content_Types].xml");
+    if office && contains(head, b"word/") {
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    } else if office && contains(head, b"xl/") {
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    } else if office && contains(head, b"ppt/") {
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+    } else if contains(head, b"AndroidManifest.xml") {
+        "application/vnd.android.package-archive"
+    } else if contains(head, b"META-INF/MANIFEST.MF") {
+        "application/java-archive"
+    } else {
+        ZIP
+    }
+}
+
+/// A DOS stub whose header points at a `PE\0\0` signature.
+fn portable_executable(head: &[u8]) -> bool {
+    head.get(0x3C..0x40)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        .and_then(|offset| head.get(offset..offset.checked_add(4)?))
+        == Some(b"PE\0\0")
+}
+
+/// The type of UTF-8 text, or `None` for bytes that are not text. A
+/// character cut off by the end of `head` does not count against it.
+fn text_type(head: &[u8]) -> Option<&'static str> {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&head[..error.valid_up_to()]).ok()?
+        }
+        Err(_) => return None,
+    };
+    if text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0C' | '\x1B'))
+    {
+        return None;
+    }
+    let start = text.trim_start().get(..256).unwrap_or(text.trim_start());
+    let start = start.to_ascii_lowercase();
+    Some(
+        if start.starts_with("<svg") || (start.starts_with("<?xml") && start.contains("<svg")) {
+            "image/svg+xml"
+        } else if start.starts_with("<?xml") {
+            "application/xml"
+        } else if start.starts_with("<!doctype html") || start.starts_with("<html") {
+            "text/html"
+        } else {
+            PLAIN_TEXT
+        },
+    )
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
@@ -0,0 +1,200 @@
+//! Planning of recursive folder uploads.
+//!
+//! This module owns walking a local directory, applying include and exclude
+//! globs and the hidden-file and symlink options, and mapping each selected
+//! file to an object key under the target prefix. Every key is validated
+//! before anything is uploaded, so a folder with one unusable name starts no
+//! transfers at all. It must not register transfers, open storage, or emit
+//! Tauri events.
+
+use super::{now_ms, DirectoryUploadParams};
+use crate::types::{err_invalid, ErrorKind, SpError, SpResult};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// One file of a folder upload and the key it is uploaded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PlannedFile {
+    pub(crate) source_path: PathBuf,
+    pub(crate) key: String,
+    pub(crate) size: u64,
+}
+
+/// `*` and `?` stop at `/`; `**` crosses directories.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A glob and whether it is matched against the whole relative path.
+type Rule = (Pattern, bool);
+
+struct Rules {
+    include: Vec<Rule>,
+    exclude: Vec<Rule>,
+}
+
+impl Rules {
+    fn new(params: &DirectoryUploadParams) -> SpResult<Self> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    let compiled =
+                        Pattern::new(pattern.trim_start_matches('/')).map_err(|error| {
+                            err_invalid(&format!("invalid glob pattern '{pattern}': {error}"))
+                        })?;
+                    Ok((compiled, pattern.contains('/')))
+                })
+                .collect::<SpResult<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(&params.include)?,
+            exclude: compile(&params.exclude)?,
+        })
+    }
+
+    fn is_excluded(&self, relative: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| matches(pattern, relative))
+    }
+
+    fn is_included(&self, relative: &str) -> bool {
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| matches(pattern, relative))
+    }
+}
+
+/// A pattern with a `/`, including a leading one, is matched against the
+/// whole relative path; one without is matched against the last segment, at
+/// any depth.
+fn matches((pattern, anchored): &Rule, relative: &str) -> bool {
+    if *anchored {
+        pattern.matches_with(relative, MATCH_OPTIONS)
+    } else {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        pattern.matches_with(name, MATCH_OPTIONS)
+    }
+}
+
+/// Lists the files `params` selects, in path order, with their keys.
+/// Excluding a directory skips everything below it; include patterns only
+/// select files.
+pub(crate) fn plan_directory_upload(params: &DirectoryUploadParams) -> SpResult<Vec<PlannedFile>> {
+    let root = PathBuf::from(&params.source_dir);
+    let root_metadata = std::fs::metadata(&root).map_err(|error| io_error("stat", &root, error))?;
+    if !root_metadata.is_dir() {
+        return Err(err_invalid(&format!(
+            "{} is not a directory",
+            root.display()
+        )));
+    }
+    let rules = Rules::new(params)?;
+    let prefix = match params.target_prefix.trim_matches('/') {
+        "" => String::new(),
+        trimmed => format!("{trimmed}/"),
+    };
+
+    let mut visited = HashSet::new();
+    if params.follow_symlinks {
+        visited.insert(
+            std::fs::canonicalize(&root).map_err(|error| io_error("resolve", &root, error))?,
+        );
+    }
+    let mut planned = Vec::new();
+    let mut pending = vec![(root, String::new())];
+    while let Some((directory, relative_dir)) = pending.pop() {
+        let mut entries = std::fs::read_dir(&directory)
+            .map_err(|error| io_error("list", &directory, error))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| io_error("list", &directory, error))?;
+        entries.sort_by_key(|entry| entry.file_name());
+        let mut subdirectories = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                return Err(err_invalid(&format!(
+                    "{} is not valid UTF-8 and cannot become an object key",
+                    path.display()
+                )));
+            };
+            if name.starts_with('.') && !params.include_hidden {
+                continue;
+            }
+            let relative = format!("{relative_dir}{name}");
+            let mut file_type = entry
+                .file_type()
+                .map_err(|error| io_error("stat", &path, error))?;
+            let mut metadata = None;
+            if file_type.is_symlink() {
+                if !params.follow_symlinks {
+                    continue;
+                }
+                match std::fs::metadata(&path) {
+                    Ok(target) => {
+                        file_type = target.file_type();
+                        metadata = Some(target);
+                    }
+                    Err(error) => {
+                        crate::logger::warn(
+                            "upload",
+                            &format!("skipping broken symlink {}: {error}", path.display()),
+                        );
+                        continue;
+                    }
+                }
+            }
+            if rules.is_excluded(&relative) {
+                continue;
+            }
+            if file_type.is_dir() {
+                // A followed symlink may lead back into a directory already
+                // walked; each directory is entered once.
+                if params.follow_symlinks {
+                    let resolved = std::fs::canonicalize(&path)
+                        .map_err(|error| io_error("resolve", &path, error))?;
+                    if !visited.insert(resolved) {
+                        continue;
+                    }
+                }
+                subdirectories.push((path, format!("{relative}/")));
+            } else if file_type.is_file() && rules.is_included(&relative) {
+                let size = match metadata {
+                    Some(metadata) => metadata.len(),
+                    None => entry
+                        .metadata()
+                        .map_err(|error| io_error("stat", &path, error))?
+                        .len(),
+                };
+                let key = format!("{prefix}{relative}");
+                crate::objects::validate_object_key(&key)?;
+                planned.push(PlannedFile {
+                    source_path: path,
+                    key,
+                    size,
+                });
+            }
+        }
+        // Reversed so the stack yields subdirectories in name order.
+        pending.extend(subdirectories.into_iter().rev());
+    }
+    planned.sort_by(|left, right| left.key.cmp(&right.key));
+    Ok(planned)
+}
+
+fn io_error(action: &str, path: &Path, error: std::io::Error) -> SpError {
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("{action} {}: {error}", path.display()),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
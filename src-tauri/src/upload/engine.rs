@@ -1,14 +1,27 @@
 //! Tauri-independent file upload execution engine.
 //!
-//! This module owns reading a local file, writing chunks through OpenDAL,
-//! pause/cancel polling, remote finalization, and cancellation cleanup. Its
-//! boundary is an injected [`Operator`] plus observer callbacks. It must not
-//! construct credentials, access global runtime state, emit Tauri events,
-//! inspect application settings, or generate thumbnails.
+//! This module owns reading a local file, sealing it when the profile
+//! encrypts or compressing it when asked, writing chunks through OpenDAL,
+//! hashing the stored content as it is written, pause/cancel polling, remote
+//! finalization, and cancellation cleanup. Its
+//! boundary is an injected [`Operator`] plus observer callbacks. Transient
+//! failures of a part are retried by the operator's HTTP client (see
+//! `crate::retry`); an error here means the retries were exhausted. Parts are
+//! written one after another; file uploads larger than one part go through
+//! `resumable`, which sends several at once. It must not construct
+//! credentials, access global runtime state, emit Tauri events, inspect
+//! application settings, or generate thumbnails.
 
-use super::{now_ms, open_upload_writer};
+use super::conflict::WriteCondition;
+use super::{
+    file_sha256, inferred_content_type, now_ms, open_upload_writer, ContentHashes, StoredEncoding,
+};
+use crate::compression::{Compression, Compressor};
+use crate::content_crypto::{ContentKey, ContentSealer, StreamSealer};
+use crate::sp_backend::ProfileEncryption;
 use crate::types::{ErrorKind, SpError, SpResult};
 use opendal::Operator;
+use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -20,8 +33,67 @@ pub(crate) struct UploadEngineRequest {
     pub(crate) key: String,
     pub(crate) source_path: PathBuf,
     pub(crate) part_size: u64,
+    /// Inferred from the key and the first part when `None`.
     pub(crate) content_type: Option<String>,
     pub(crate) content_disposition: Option<String>,
+    /// Seal the content under the profile's passphrase.
+    pub(crate) encryption: Option<ProfileEncryption>,
+    /// Compress the content. Ignored for sealed content, which would not
+    /// shrink and could not be served with a `Content-Encoding`.
+    pub(crate) compression: Option<Compression>,
+    /// Precondition the object is written under.
+    pub(crate) condition: WriteCondition,
+}
+
+/// The SHA-256 of the bytes an upload engine sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UploadedContent {
+    /// Lower-case hex. For sealed or compressed content, the hash of what
+    /// is stored.
+    pub(crate) sha256: String,
+    /// The SHA-256 of the source of sealed or compressed content, for a
+    /// caller that records the hashes afterwards.
+    pub(crate) source_sha256: Option<String>,
+    /// The hashes went out with the object itself; otherwise the caller
+    /// still has to record them.
+    pub(crate) sha256_stored: bool,
+    /// The ETag the bucket assigned to the object, if it said.
+    pub(crate) etag: Option<String>,
+    /// Bytes the object holds, which the bucket lists as its size.
+    pub(crate) stored_size: u64,
+    /// How the stored bytes differ from the source.
+    pub(crate) encoding: StoredEncoding,
+    /// The `Content-Type` the object was stored with.
+    pub(crate) content_type: String,
+}
+
+/// The sealer for content uploaded in one pass, if the profile encrypts.
+pub(super) async fn stream_sealer(
+    encryption: Option<&ProfileEncryption>,
+) -> SpResult<Option<StreamSealer>> {
+    match encryption {
+        Some(encryption) => Ok(Some(StreamSealer::new(ContentSealer::new(
+            ContentKey::for_profile(encryption).await?,
+        )))),
+        None => Ok(None),
+    }
+}
+
+/// The compressor for content that is not sealed, and the encoding the
+/// object is stored with.
+pub(super) fn stored_encoding(
+    sealed: bool,
+    compression: Option<Compression>,
+    source_size: u64,
+) -> SpResult<(Option<Compressor>, StoredEncoding)> {
+    let compression = compression.filter(|_| !sealed);
+    Ok((
+        compression.map(Compressor::new).transpose()?,
+        StoredEncoding {
+            sealed,
+            compressed: compression.map(|compression| (compression, source_size)),
+        },
+    ))
 }
 
 pub(crate) struct UploadControl {
@@ -38,12 +110,17 @@ pub(crate) trait UploadEngineObserver {
     fn cancelled(&mut self) -> SpResult<()>;
 }
 
+/// Uploads the file through an OpenDAL writer. The file is hashed first, so
+/// its SHA-256 goes out with the object: as `x-amz-meta-sha256` when it is
+/// stored byte for byte, otherwise as `x-amz-meta-source-sha256`. The writer
+/// is opened once the first part has been read; when that part is the whole
+/// file, the SHA-256 of sealed or compressed content is sent as well.
 pub(crate) async fn upload_file(
     operator: &Operator,
     request: UploadEngineRequest,
     control: UploadControl,
     observer: &mut impl UploadEngineObserver,
-) -> SpResult<()> {
+) -> SpResult<UploadedContent> {
     if request.part_size == 0 {
         return Err(SpError {
             kind: ErrorKind::NotRetriable,
@@ -63,30 +140,30 @@ pub(crate) async fn upload_file(
             context: None,
             at: now_ms(),
         })?;
-    file.metadata().await.map_err(|error| SpError {
-        kind: ErrorKind::NotRetriable,
-        message: format!("stat src: {error}"),
-        retry_after_ms: None,
-        context: None,
-        at: now_ms(),
-    })?;
-
-    let mut writer = open_upload_writer(
-        operator,
-        &request.key,
-        request.content_type.as_deref(),
-        request.content_disposition.as_deref(),
-    )
-    .await
-    .map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("open writer: {error}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: now_ms(),
-    })?;
+    let source_size = file
+        .metadata()
+        .await
+        .map_err(|error| SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!("stat src: {error}"),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        })?
+        .len();
+
+    let mut sealer = stream_sealer(request.encryption.as_ref()).await?;
+    let (mut compressor, encoding) =
+        stored_encoding(sealer.is_some(), request.compression, source_size)?;
+    let source_sha256 = file_sha256(&request.source_path).await?;
+    let verbatim = encoding == StoredEncoding::default();
     observer.uploading()?;
 
+    let mut writer = None;
+    let mut content_type = None;
+    let mut hasher = Sha256::new();
+    let mut stored_size = 0u64;
+    let mut sha256_stored = false;
     let mut part_number = 1;
     let mut was_paused = false;
     loop {
@@ -111,45 +188,151 @@ pub(crate) async fn upload_file(
             was_paused = false;
         }
 
-        let mut buffer = vec![0; request.part_size as usize];
-        let read = file.read(&mut buffer).await.map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("read src: {error}"),
-            retry_after_ms: Some(200),
-            context: None,
-            at: now_ms(),
-        })?;
-        if read == 0 {
+        let buffer = read_part(&mut file, request.part_size).await?;
+        let read = buffer.len();
+        let last = (read as u64) < request.part_size;
+        let content_type = content_type.get_or_insert_with(|| {
+            inferred_content_type(&request.key, request.content_type.as_deref(), &buffer)
+        });
+        let body = match (sealer.as_mut(), compressor.as_mut()) {
+            (Some(sealer), _) => {
+                let mut sealed = sealer.push(&buffer);
+                if last {
+                    sealed.extend(sealer.finish());
+                }
+                sealed
+            }
+            (None, Some(compressor)) => {
+                let mut compressed = compressor.push(&buffer)?;
+                if last {
+                    compressed.extend(compressor.finish()?);
+                }
+                compressed
+            }
+            (None, None) => buffer,
+        };
+        hasher.update(&body);
+        stored_size += body.len() as u64;
+        if writer.is_none() {
+            let sha256 = if verbatim {
+                Some(source_sha256.clone())
+            } else {
+                last.then(|| format!("{:x}", hasher.clone().finalize()))
+            };
+            sha256_stored = sha256.is_some();
+            writer = Some(
+                open_upload_writer(
+                    operator,
+                    &request.key,
+                    Some(content_type.as_str()),
+                    request.content_disposition.as_deref(),
+                    ContentHashes {
+                        stored: sha256.as_deref(),
+                        source: (!verbatim).then_some(source_sha256.as_str()),
+                    },
+                    encoding,
+                    &request.condition,
+                )
+                .await
+                .map_err(|error| {
+                    crate::storage::opendal_error("open writer", &request.key, &error)
+                })?,
+            );
+        }
+        if !body.is_empty() {
+            if let Some(writer) = writer.as_mut() {
+                if let Err(error) = writer.write(body).await {
+                    // A retry wait cut short by cancellation fails the write.
+                    if control.cancelled.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    return Err(crate::storage::opendal_error(
+                        "writer write",
+                        &request.key,
+                        &error,
+                    ));
+                }
+            }
+        }
+        if read > 0 {
+            observer.part_done(part_number, read as u64)?;
+            part_number += 1;
+        }
+        if last {
             break;
         }
-        buffer.truncate(read);
-        writer.write(buffer).await.map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("writer write: {error}"),
-            retry_after_ms: Some(300),
-            context: None,
-            at: now_ms(),
-        })?;
-        observer.part_done(part_number, read as u64)?;
-        part_number += 1;
     }
 
     if control.cancelled.load(Ordering::Relaxed) {
-        let _ = writer.abort().await;
+        if let Some(mut writer) = writer {
+            let _ = writer.abort().await;
+        }
         observer.cancelled()?;
         return Err(cancelled_error());
     }
 
     observer.finalizing()?;
-    writer.close().await.map(|_| ()).map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("writer close: {error}"),
-        retry_after_ms: Some(300),
-        context: None,
-        at: now_ms(),
+    let mut etag = None;
+    if let Some(mut writer) = writer {
+        etag = close_upload_writer(&request.key, &request.condition, &mut writer)
+            .await?
+            .etag()
+            .map(str::to_string);
+    }
+    Ok(UploadedContent {
+        sha256: format!("{:x}", hasher.finalize()),
+        source_sha256: (!verbatim).then_some(source_sha256),
+        sha256_stored,
+        etag,
+        stored_size,
+        encoding,
+        content_type: content_type.unwrap_or_else(|| {
+            inferred_content_type(&request.key, request.content_type.as_deref(), &[])
+        }),
     })
 }
 
+/// Finishes the write with `condition` sent on its final request, so the
+/// bucket refuses it if the key changed meanwhile. A refused or failed
+/// multipart write is aborted.
+pub(super) async fn close_upload_writer(
+    key: &str,
+    condition: &WriteCondition,
+    writer: &mut opendal::Writer,
+) -> SpResult<opendal::Metadata> {
+    match crate::storage::completing_with(condition.headers(), writer.close()).await {
+        Ok(metadata) => Ok(metadata),
+        Err(error) => {
+            let _ = writer.abort().await;
+            Err(crate::storage::opendal_error("writer close", key, &error))
+        }
+    }
+}
+
+/// Reads up to `part_size` bytes, fewer only at the end of the file.
+async fn read_part(file: &mut tokio::fs::File, part_size: u64) -> SpResult<Vec<u8>> {
+    let mut buffer = vec![0; part_size as usize];
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = file
+            .read(&mut buffer[filled..])
+            .await
+            .map_err(|error| SpError {
+                kind: ErrorKind::RetryableNet,
+                message: format!("read src: {error}"),
+                retry_after_ms: Some(200),
+                context: None,
+                at: now_ms(),
+            })?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    buffer.truncate(filled);
+    Ok(buffer)
+}
+
 pub(super) fn cancelled_error() -> SpError {
     SpError {
         kind: ErrorKind::Cancelled,
@@ -1,39 +1,41 @@
 //! Upload object metadata and writer construction.
 //!
-//! This module owns content-type inference and the OpenDAL writer options that
-//! turn upload metadata into the remote object contract. It must not read local
-//! sources, access credentials, mutate transfer state, emit Tauri events, or
-//! decide when an upload starts or completes.
+//! This module owns the OpenDAL writer options that turn upload metadata
+//! into the remote object contract, including the marker on sealed content,
+//! the coding of compressed content and the precondition of a write, and
+//! the metadata rewrite that records a content hash after
+//! upload. It must not read local sources, access credentials, mutate
+//! transfer state, emit Tauri events, or decide when an upload starts or
+//! completes.
 
+use super::conflict::{quoted, WriteCondition};
+use super::content_type::inferred_content_type;
+use crate::compression::{self, Compression};
+use crate::objects::{SHA256_METADATA_KEY, SOURCE_SHA256_METADATA_KEY};
+use crate::types::{ObjectHeaders, ObjectMetadataUpdate};
 use opendal::{Operator, Writer};
 
-pub(super) fn inferred_content_type(key: &str, explicit: Option<&str>) -> String {
-    if let Some(value) = explicit.map(str::trim).filter(|value| !value.is_empty()) {
-        return value.to_string();
-    }
+/// Compressed bytes buffered per multipart part. Compressed writes are far
+/// smaller than the source parts they come from, and S3 rejects parts below
+/// 5 MiB.
+const COMPRESSED_PART_BYTES: usize = 8 * 1024 * 1024;
 
-    let extension = std::path::Path::new(key)
-        .extension()
-        .and_then(|value| value.to_str())
-        .map(str::to_ascii_lowercase);
+/// How an upload stores its source other than byte for byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct StoredEncoding {
+    /// Sealed under the profile's key; the object carries the marker.
+    pub(crate) sealed: bool,
+    /// Compressed, with the size of the source before compression.
+    pub(crate) compressed: Option<(Compression, u64)>,
+}
 
-    match extension.as_deref() {
-        Some("arw") => "image/x-sony-arw",
-        Some("cr2") => "image/x-canon-cr2",
-        Some("cr3") => "image/x-canon-cr3",
-        Some("dng") => "image/x-adobe-dng",
-        Some("nef") => "image/x-nikon-nef",
-        Some("orf") => "image/x-olympus-orf",
-        Some("raf") => "image/x-fuji-raf",
-        Some("rw2") => "image/x-panasonic-rw2",
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("png") => "image/png",
-        Some("webp") => "image/webp",
-        Some("pdf") => "application/pdf",
-        Some("txt") => "text/plain",
-        _ => "application/octet-stream",
-    }
-    .to_string()
+/// The SHA-256 digests an upload records, as lower-case hex.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ContentHashes<'a> {
+    /// Of the bytes stored, once known.
+    pub(crate) stored: Option<&'a str>,
+    /// Of the source, for content not stored byte for byte.
+    pub(crate) source: Option<&'a str>,
 }
 
 pub(super) async fn open_upload_writer(
@@ -41,8 +43,11 @@ pub(super) async fn open_upload_writer(
     key: &str,
     content_type: Option<&str>,
     content_disposition: Option<&str>,
+    hashes: ContentHashes<'_>,
+    encoding: StoredEncoding,
+    condition: &WriteCondition,
 ) -> Result<Writer, opendal::Error> {
-    let resolved_content_type = inferred_content_type(key, content_type);
+    let resolved_content_type = inferred_content_type(key, content_type, &[]);
     let mut writer = operator
         .writer_with(key)
         .content_type(&resolved_content_type);
@@ -52,5 +57,69 @@ pub(super) async fn open_upload_writer(
     {
         writer = writer.content_disposition(value);
     }
+    if let Some((compression, _)) = encoding.compressed {
+        writer = writer
+            .content_encoding(compression.content_encoding())
+            .chunk(COMPRESSED_PART_BYTES);
+    }
+    let user_metadata = upload_user_metadata(hashes, encoding);
+    if !user_metadata.is_empty() {
+        writer = writer.user_metadata(user_metadata);
+    }
+    match condition {
+        WriteCondition::None => {}
+        WriteCondition::Absent => writer = writer.if_not_exists(true),
+        WriteCondition::Matches(etag) => writer = writer.if_match(&quoted(etag)),
+    }
     writer.await
 }
+
+/// User metadata an upload writes: the content hashes once they are known,
+/// the marker on sealed content, and the size of compressed content before
+/// compression.
+pub(super) fn upload_user_metadata(
+    hashes: ContentHashes<'_>,
+    encoding: StoredEncoding,
+) -> Vec<(String, String)> {
+    let mut user_metadata = Vec::new();
+    if let Some(sha256) = hashes.stored {
+        user_metadata.push((SHA256_METADATA_KEY.to_string(), sha256.to_string()));
+    }
+    if let Some(sha256) = hashes.source {
+        user_metadata.push((SOURCE_SHA256_METADATA_KEY.to_string(), sha256.to_string()));
+    }
+    if encoding.sealed {
+        user_metadata.push(crate::content_crypto::sealed_marker());
+    }
+    if let Some((_, original_size)) = encoding.compressed {
+        user_metadata.push(compression::original_size_metadata(original_size));
+    }
+    user_metadata
+}
+
+/// The metadata of an object uploaded with these headers, plus its hashes.
+/// Applied with a CopyObject, it replaces everything else the object had,
+/// so it must repeat what the upload wrote.
+pub(super) fn content_hash_update(
+    key: &str,
+    content_type: Option<&str>,
+    content_disposition: Option<&str>,
+    hashes: ContentHashes<'_>,
+    encoding: StoredEncoding,
+) -> ObjectMetadataUpdate {
+    ObjectMetadataUpdate {
+        headers: ObjectHeaders {
+            content_type: Some(inferred_content_type(key, content_type, &[])),
+            content_disposition: content_disposition
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string),
+            content_encoding: encoding
+                .compressed
+                .map(|(compression, _)| compression.content_encoding().to_string()),
+            user_metadata: upload_user_metadata(hashes, encoding).into_iter().collect(),
+            ..ObjectHeaders::default()
+        },
+        expected_etag: None,
+    }
+}
@@ -1,31 +1,54 @@
 //! Public facade and application-layer orchestration for uploads.
 //!
 //! This module owns bridge-facing DTOs, task spawning, credential/operator
-//! coordination, Tauri event emission, and composition of upload adapters. It
-//! must not contain local-file chunk loops, MIME rules, global registry
-//! implementation, stream-channel mechanics, or Android SAF source handling.
+//! coordination, Tauri event emission, restart recovery of file uploads,
+//! starting folder uploads as a group of file transfers, skipping files
+//! already stored unchanged, acting on the conflict policy's decisions,
+//! counting the source bytes of finished uploads, and composition of upload
+//! adapters. It must not contain local-file chunk loops, MIME rules, global
+//! registry implementation, stream-channel mechanics, or Android SAF source
+//! handling.
 
+use crate::compression::Compression;
 use crate::settings;
-use crate::transfer_db::{TransferLifecycle, TransferPhase, TransferSnapshot};
+use crate::storage::multipart::CompletedPart;
+use crate::transfer_db::{
+    self, TransferKind, TransferLifecycle, TransferPhase, TransferSnapshot, UploadSession,
+};
 use crate::transfer_fsm::TransferStateEvent;
 use crate::types::*;
+use crate::usage::UsageSync;
 use crate::{sp_backend::SpBackend, storage};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::{atomic::AtomicBool, Arc};
 use tauri::Emitter;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
 
+mod conflict;
+mod content_type;
+mod directory;
 mod engine;
 mod metadata;
 mod platform;
+mod resumable;
 mod runtime;
 mod stream;
+mod unchanged;
+mod url;
 
+use conflict::*;
+use content_type::*;
+use directory::*;
 use engine::*;
 use metadata::*;
+use resumable::*;
 use runtime::*;
 use stream::*;
+use unchanged::*;
+use url::*;
+
+pub use conflict::ConflictPolicy;
 
 #[cfg(test)]
 pub(crate) use engine::{
@@ -41,6 +64,72 @@ pub struct NewUploadParams {
     pub part_size: u64,
     pub content_type: Option<String>,
     pub content_disposition: Option<String>,
+    /// Finish as skipped, without uploading, when the key already holds the
+    /// same content.
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    /// Store the content compressed, with a matching `Content-Encoding`.
+    /// Ignored when the profile encrypts.
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// What to do when the key already holds an object.
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+}
+
+/// A local folder to upload as one group of file transfers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryUploadParams {
+    pub source_dir: String,
+    /// Each file is uploaded to `<target_prefix>/<path below source_dir>`;
+    /// empty for the bucket root.
+    pub target_prefix: String,
+    /// Globs a file must match to be uploaded; empty selects every file.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs for files and directories to leave out.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Upload files and directories whose name starts with a dot.
+    #[serde(default)]
+    pub include_hidden: bool,
+    /// Upload what symlinks point to; otherwise symlinks are skipped.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Skip each file whose key already holds the same content.
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    /// Compress every file of the folder; see [`NewUploadParams`].
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// What to do when a file's key already holds an object. `IfMatch`
+    /// names a single object, so it is refused for a folder.
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+    pub part_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryUpload {
+    pub group_id: String,
+    /// In key order.
+    pub transfer_ids: Vec<String>,
+    pub bytes_total: u64,
+}
+
+/// Aggregate progress of a folder upload. Transfers removed from the
+/// transfer list no longer count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadGroupStatus {
+    pub group_id: String,
+    pub files_total: u32,
+    pub files_completed: u32,
+    pub files_failed: u32,
+    pub files_cancelled: u32,
+    /// Already stored unchanged; their bytes count as done.
+    pub files_skipped: u32,
+    pub bytes_total: u64,
+    pub bytes_done: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +139,38 @@ pub struct NewUploadStreamParams {
     pub part_size: u64,
     pub content_type: Option<String>,
     pub content_disposition: Option<String>,
+    /// Compress the stream; see [`NewUploadParams`].
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// See [`NewUploadParams`]. A stream cannot be sent again, so a write
+    /// the bucket refuses under [`ConflictPolicy::Rename`] fails.
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+}
+
+/// A remote file to copy into the bucket without storing it locally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewUrlUploadParams {
+    /// An `http` or `https` URL; redirects are followed.
+    pub url: String,
+    /// Object key; by default the file name the response gives, under
+    /// `target_prefix`.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Where a key taken from the response goes; empty for the bucket root.
+    #[serde(default)]
+    pub target_prefix: String,
+    #[serde(default)]
+    pub part_size: u64,
+    /// Replaces the response's `Content-Type`.
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Compress the body; see [`NewUploadParams`].
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// See [`NewUploadStreamParams`]; the body cannot be sent again either.
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,6 +187,17 @@ pub struct UploadStatus {
     pub last_error: Option<SpError>,
 }
 
+/// Where a push stream stands, returned for every chunk it is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamAck {
+    /// Bytes written towards the object. A sender that reconnects resumes
+    /// here; chunks it repeats are dropped.
+    pub committed_offset: u64,
+    /// Bytes taken from the sender, including those still queued. The next
+    /// chunk may start anywhere up to this offset.
+    pub accepted_offset: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum UploadEvent {
@@ -100,6 +232,21 @@ pub enum UploadEvent {
     Cancelled {
         transfer_id: String,
     },
+    /// Nothing was uploaded: the key already held the file's content, or an
+    /// object the conflict policy keeps.
+    Skipped {
+        transfer_id: String,
+    },
+    /// The key was taken; the conflict policy uploads to `key` instead.
+    Renamed {
+        transfer_id: String,
+        key: String,
+    },
+    /// The source file changed while the app was closed; the upload started
+    /// over instead of continuing.
+    SourceChanged {
+        transfer_id: String,
+    },
 }
 
 fn now_ms() -> i64 {
@@ -115,6 +262,7 @@ fn emit_part_events(
     transfer_id: &str,
     part_number: u32,
     bytes_transferred: u64,
+    etag: String,
 ) {
     emit_upload(
         app,
@@ -131,7 +279,7 @@ fn emit_part_events(
         &UploadEvent::PartDone {
             transfer_id: transfer_id.to_string(),
             part_number,
-            etag: String::new(),
+            etag,
         },
     );
 }
@@ -139,6 +287,21 @@ fn emit_part_events(
 struct RuntimeUploadObserver<'a> {
     app: &'a tauri::AppHandle,
     transfer_id: &'a str,
+    /// Recovery record of a file upload; `None` for ephemeral sources.
+    session: Option<UploadSession>,
+    /// ETag of the part most recently stored, for its `PartDone` event.
+    stored_etag: Option<String>,
+}
+
+impl<'a> RuntimeUploadObserver<'a> {
+    fn new(app: &'a tauri::AppHandle, transfer_id: &'a str) -> Self {
+        Self {
+            app,
+            transfer_id,
+            session: None,
+            stored_etag: None,
+        }
+    }
 }
 
 impl UploadEngineObserver for RuntimeUploadObserver<'_> {
@@ -180,7 +343,13 @@ impl UploadEngineObserver for RuntimeUploadObserver<'_> {
             transfer.bytes_done = transfer.bytes_done.saturating_add(bytes_transferred);
             transfer.parts_completed += 1;
         })?;
-        emit_part_events(self.app, self.transfer_id, part_number, bytes_transferred);
+        emit_part_events(
+            self.app,
+            self.transfer_id,
+            part_number,
+            bytes_transferred,
+            self.stored_etag.take().unwrap_or_default(),
+        );
         Ok(())
     }
 
@@ -204,6 +373,56 @@ impl UploadEngineObserver for RuntimeUploadObserver<'_> {
     }
 }
 
+impl ResumableUploadObserver for RuntimeUploadObserver<'_> {
+    fn session_started(
+        &mut self,
+        session: &MultipartSession,
+        completed: &[CompletedPart],
+    ) -> SpResult<()> {
+        if let Some(record) = self.session.as_mut() {
+            record.upload_id = Some(session.upload_id.clone());
+            record.part_size = session.part_size;
+            record.source_size = Some(session.source_size);
+            record.source_mtime_ms = Some(session.source_mtime_ms);
+            record.content_key_id = session.content_key_id.clone();
+            record.parts = completed.to_vec();
+            transfer_db::upsert_upload_session(record)?;
+        }
+        mutate_upload(self.transfer_id, |transfer| {
+            transfer.part_size = session.part_size;
+            transfer.bytes_total = session.stored_size();
+            transfer.bytes_done = completed.iter().map(|part| part.size).sum();
+            transfer.parts_completed = completed.len() as u32;
+        })
+    }
+
+    fn part_stored(&mut self, part: &CompletedPart) -> SpResult<()> {
+        transfer_db::record_upload_part(self.transfer_id, part)?;
+        self.stored_etag = Some(part.etag.clone());
+        Ok(())
+    }
+
+    fn source_changed(&mut self, error: &SpError) -> SpResult<()> {
+        crate::logger::warn(
+            "upload",
+            &format!(
+                "upload {} restarts from the beginning: {}",
+                self.transfer_id, error.message
+            ),
+        );
+        mutate_upload(self.transfer_id, |transfer| {
+            transfer.last_error = Some(error.clone())
+        })?;
+        emit_upload(
+            self.app,
+            &UploadEvent::SourceChanged {
+                transfer_id: self.transfer_id.to_string(),
+            },
+        );
+        Ok(())
+    }
+}
+
 impl StreamUploadObserver for RuntimeUploadObserver<'_> {
     fn uploading(&mut self) -> SpResult<()> {
         UploadEngineObserver::uploading(self)
@@ -247,7 +466,24 @@ fn finish_upload_task(app: &tauri::AppHandle, id: &str, result: SpResult<()>) {
             transfer.worker_active = false;
             transfer.last_error = Some(error.clone());
         });
-        if !matches!(error.kind, ErrorKind::Cancelled) {
+        if matches!(error.kind, ErrorKind::RetryableNet | ErrorKind::RateLimited)
+            && has_remote_session(id)
+        {
+            // Failing would drop the session row; paused, a resume picks
+            // the parts up from ListParts.
+            crate::logger::warn(
+                "upload",
+                &format!("upload {id} paused after {}", error.message),
+            );
+            let _ = pause_upload(id);
+            let _ = transition_upload(id, TransferStateEvent::Pause);
+            emit_upload(
+                app,
+                &UploadEvent::Paused {
+                    transfer_id: id.to_string(),
+                },
+            );
+        } else if !matches!(error.kind, ErrorKind::Cancelled) {
             let _ = transition_upload(id, TransferStateEvent::Fail);
             emit_upload(
                 app,
@@ -262,17 +498,81 @@ fn finish_upload_task(app: &tauri::AppHandle, id: &str, result: SpResult<()>) {
     }
 }
 
+/// Whether the upload holds a multipart session the bucket may still keep.
+fn has_remote_session(id: &str) -> bool {
+    transfer_db::get_upload_session(id)
+        .ok()
+        .flatten()
+        .is_some_and(|session| session.upload_id.is_some())
+}
+
+/// Stores the SHA-256 of a stream whose headers went out before its content
+/// was known, by rewriting the object's metadata in place, provided it is
+/// still the object just written. The object is already complete, so a
+/// failure only leaves it unverifiable and is logged instead of failing the
+/// upload. The exception is compressed content written in parts: multipart
+/// creation does not carry a `Content-Encoding`, so only this rewrite makes
+/// the object decodable.
+async fn record_content_hash(
+    cfg: &R2Config,
+    key: &str,
+    content_disposition: Option<&str>,
+    content: &UploadedContent,
+) -> SpResult<()> {
+    if content.sha256_stored {
+        return Ok(());
+    }
+    let mut update = content_hash_update(
+        key,
+        Some(&content.content_type),
+        content_disposition,
+        ContentHashes {
+            stored: Some(&content.sha256),
+            source: content.source_sha256.as_deref(),
+        },
+        content.encoding,
+    );
+    update.expected_etag = content.etag.clone();
+    match storage::replace_object_metadata(cfg, key, content.stored_size, &update).await {
+        Err(error) if content.encoding.compressed.is_some() => Err(error),
+        Err(error) => {
+            crate::logger::warn(
+                "upload",
+                &format!("could not record SHA-256 of {key}: {}", error.message),
+            );
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Records a finished upload: the object in the index with the size the
+/// bucket lists for it, and the size of its source in the usage ledger.
+fn record_finished_upload(profile: &str, key: &str, source_size: u64, content: &UploadedContent) {
+    let _ = crate::object_index::record_object(
+        profile,
+        key,
+        Some(content.stored_size),
+        Some(now_ms()),
+        None,
+    );
+    let _ = UsageSync::record_logical_upload(profile, source_size);
+}
+
 async fn complete_file_upload(
     app: &tauri::AppHandle,
     id: &str,
-    params: &NewUploadParams,
+    profile: &str,
+    key: &str,
+    source_path: &std::path::Path,
     operator: &opendal::Operator,
     should_upload_thumbnail: bool,
 ) -> SpResult<()> {
     if should_upload_thumbnail {
         let thumbnail_operator = operator.clone();
-        let source_path = params.source_path.clone();
-        let object_key = params.key.clone();
+        let source_path = source_path.to_string_lossy().to_string();
+        let object_key = key.to_string();
+        let profile = profile.to_string();
         tokio::spawn(async move {
             match crate::thumbnail::generate_and_store(
                 &thumbnail_operator,
@@ -283,7 +583,15 @@ async fn complete_file_upload(
             )
             .await
             {
-                Ok(Some(_)) => {}
+                Ok(Some(thumbnail_key)) => {
+                    let _ = crate::object_index::record_object(
+                        &profile,
+                        &thumbnail_key,
+                        None,
+                        Some(now_ms()),
+                        None,
+                    );
+                }
                 Ok(None) => crate::logger::info(
                     "upload",
                     &format!("thumbnail skipped for {object_key}; unsupported file type"),
@@ -308,6 +616,149 @@ async fn complete_file_upload(
     Ok(())
 }
 
+fn skip_upload(app: &tauri::AppHandle, id: &str, key: &str, reason: &str) -> SpResult<()> {
+    mutate_upload(id, |transfer| transfer.bytes_done = transfer.bytes_total)?;
+    transition_upload(id, TransferStateEvent::Skip)?;
+    emit_upload(
+        app,
+        &UploadEvent::Skipped {
+            transfer_id: id.to_string(),
+        },
+    );
+    crate::logger::info("upload", &format!("skipped {key}; {reason}"));
+    Ok(())
+}
+
+/// Points the upload at `renamed`, the free key its conflict policy chose
+/// instead of `key`.
+fn rename_upload(app: &tauri::AppHandle, id: &str, key: &str, renamed: &str) -> SpResult<()> {
+    mutate_upload(id, |transfer| transfer.key = renamed.to_string())?;
+    emit_upload(
+        app,
+        &UploadEvent::Renamed {
+            transfer_id: id.to_string(),
+            key: renamed.to_string(),
+        },
+    );
+    crate::logger::info("upload", &format!("{key} exists; uploading as {renamed}"));
+    Ok(())
+}
+
+/// The key and precondition of a new write under `policy`, after renaming
+/// the upload if the policy chose another key; `None` when the policy keeps
+/// the stored object.
+async fn prepare_destination(
+    app: &tauri::AppHandle,
+    id: &str,
+    operator: &opendal::Operator,
+    key: &str,
+    policy: &ConflictPolicy,
+) -> SpResult<Option<(String, WriteCondition)>> {
+    match resolve_destination(operator, key, policy).await? {
+        Destination::Skip => {
+            skip_upload(app, id, key, "the conflict policy keeps the stored object")?;
+            Ok(None)
+        }
+        Destination::Write {
+            key: resolved,
+            condition,
+        } => {
+            if resolved != key {
+                rename_upload(app, id, key, &resolved)?;
+            }
+            Ok(Some((resolved, condition)))
+        }
+    }
+}
+
+/// Settles a stream upload the bucket refused under its precondition. The
+/// content cannot be sent again, so only [`ConflictPolicy::Skip`] finishes.
+fn settle_refused_stream(
+    app: &tauri::AppHandle,
+    id: &str,
+    key: &str,
+    policy: &ConflictPolicy,
+) -> SpResult<()> {
+    match policy {
+        ConflictPolicy::Skip => skip_upload(
+            app,
+            id,
+            key,
+            "the conflict policy keeps the object stored meanwhile",
+        ),
+        _ => Err(conflict_error(
+            key,
+            "was written by another client during the upload",
+        )),
+    }
+}
+
+/// Restores file uploads recorded before the app exited, paused until the
+/// user resumes them. Uploads of ephemeral sources cannot continue and are
+/// dropped.
+pub fn init(app: &tauri::AppHandle) -> SpResult<()> {
+    transfer_db::init(app)?;
+    for snapshot in transfer_db::list_active_snapshots()? {
+        if snapshot.kind != TransferKind::Upload {
+            continue;
+        }
+        let session = transfer_db::get_upload_session(&snapshot.transfer_id)?;
+        match session {
+            Some(session) if snapshot.lifecycle_state != TransferLifecycle::Cancelling => {
+                restore_upload(&snapshot, &session)?;
+                crate::logger::warn(
+                    "upload",
+                    &format!(
+                        "recovered interrupted upload {} as paused; explicit resume required",
+                        snapshot.transfer_id
+                    ),
+                );
+            }
+            session => {
+                if let (Some(upload_id), Some(profile)) = (
+                    session.and_then(|session| session.upload_id),
+                    snapshot.profile.clone(),
+                ) {
+                    abort_recorded_session(profile, snapshot.key.clone(), upload_id);
+                }
+                crate::logger::warn(
+                    "upload",
+                    &format!(
+                        "dropped interrupted upload {} of {}; it cannot be resumed",
+                        snapshot.transfer_id, snapshot.key
+                    ),
+                );
+                transfer_db::delete_upload_session(&snapshot.transfer_id)?;
+                transfer_db::delete_snapshot(&snapshot.transfer_id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Best-effort abort of a multipart session no task will finish, so its
+/// parts stop counting against the bucket. Needs the vault to be unlocked;
+/// otherwise the session is left to the bucket's lifecycle rules.
+fn abort_recorded_session(profile: String, key: String, upload_id: String) {
+    tauri::async_runtime::spawn(async move {
+        let Some(cfg) = SpBackend::get_decrypted_bundle_if_unlocked()
+            .ok()
+            .and_then(|bundle| bundle.profile(&profile).cloned())
+        else {
+            return;
+        };
+        if let Err(error) = storage::multipart::abort(&cfg, &key, &upload_id).await {
+            crate::logger::warn(
+                "upload",
+                &format!(
+                    "could not abort multipart upload for {key}: {}",
+                    error.message
+                ),
+            );
+        }
+    });
+}
+
 pub async fn start_upload(app: tauri::AppHandle, params: NewUploadParams) -> SpResult<String> {
     let metadata = tokio::fs::metadata(&params.source_path)
         .await
@@ -318,84 +769,383 @@ pub async fn start_upload(app: tauri::AppHandle, params: NewUploadParams) -> SpR
             context: None,
             at: now_ms(),
         })?;
+    let profile = SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
+    start_file_upload(app, profile, params, metadata.len(), None)
+}
+
+/// Starts one transfer per file below `params.source_dir`. Every key is
+/// checked before the first transfer starts. At most `max_concurrency` of
+/// the group's files upload at once; the rest stay queued.
+pub async fn start_directory_upload(
+    app: tauri::AppHandle,
+    params: DirectoryUploadParams,
+) -> SpResult<DirectoryUpload> {
+    if matches!(params.conflict, ConflictPolicy::IfMatch { .. }) {
+        return Err(err_invalid(
+            "a folder upload cannot expect one ETag for all of its files",
+        ));
+    }
+    let profile = SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
+    let planning = params.clone();
+    let files = tokio::task::spawn_blocking(move || plan_directory_upload(&planning))
+        .await
+        .map_err(|error| SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!("folder scan failed: {error}"),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        })??;
+    if files.is_empty() {
+        return Err(err_invalid(&format!(
+            "no files in {} match the include and exclude rules",
+            params.source_dir
+        )));
+    }
+
+    let group_id = uuid::Uuid::new_v4().to_string();
+    let slots = Arc::new(Semaphore::new(
+        settings::get()
+            .concurrency_limits()
+            .global_active_tasks
+            .into(),
+    ));
+    let mut transfer_ids = Vec::with_capacity(files.len());
+    for file in &files {
+        let id = start_file_upload(
+            app.clone(),
+            profile.clone(),
+            NewUploadParams {
+                key: file.key.clone(),
+                source_path: file.source_path.to_string_lossy().into_owned(),
+                part_size: params.part_size,
+                content_type: None,
+                content_disposition: None,
+                skip_unchanged: params.skip_unchanged,
+                compression: params.compression,
+                conflict: params.conflict.clone(),
+            },
+            file.size,
+            Some(slots.clone()),
+        )?;
+        join_upload_group(&group_id, &id)?;
+        transfer_ids.push(id);
+    }
+    crate::logger::info(
+        "upload",
+        &format!(
+            "folder upload {group_id}: {} file(s) from {}",
+            files.len(),
+            params.source_dir
+        ),
+    );
+    Ok(DirectoryUpload {
+        group_id,
+        transfer_ids,
+        bytes_total: files.iter().map(|file| file.size).sum(),
+    })
+}
+
+/// Registers a file upload and spawns its worker. With `slots`, the worker
+/// stays queued until it holds one of them.
+fn start_file_upload(
+    app: tauri::AppHandle,
+    profile: String,
+    params: NewUploadParams,
+    source_size: u64,
+    slots: Option<Arc<Semaphore>>,
+) -> SpResult<String> {
     let id = uuid::Uuid::new_v4().to_string();
+    let part_size = params.part_size.max(8 * 1024 * 1024);
     let paused = Arc::new(AtomicBool::new(false));
     let cancelled = Arc::new(AtomicBool::new(false));
     register_upload(
         &id,
+        profile.clone(),
         params.key.clone(),
         PathBuf::from(&params.source_path),
-        params.part_size.max(8 * 1024 * 1024),
-        metadata.len(),
-        paused.clone(),
-        cancelled.clone(),
+        part_size,
+        source_size,
+        UploadControl {
+            paused: paused.clone(),
+            cancelled: cancelled.clone(),
+        },
     )?;
+    let session = UploadSession {
+        transfer_id: id.clone(),
+        source_path: params.source_path.clone(),
+        part_size,
+        content_type: params.content_type.clone(),
+        content_disposition: params.content_disposition.clone(),
+        upload_id: None,
+        source_size: None,
+        source_mtime_ms: None,
+        content_key_id: None,
+        compression: params.compression,
+        conflict: params.conflict,
+        parts: Vec::new(),
+    };
+    transfer_db::upsert_upload_session(&session)?;
+    spawn_file_upload(
+        app,
+        id.clone(),
+        profile,
+        session,
+        false,
+        params.skip_unchanged,
+        slots,
+    );
+    Ok(id)
+}
 
-    let task_id = id.clone();
-    let task_app = app.clone();
+/// Times an auto-renamed upload picks another name after the bucket refused
+/// the one it chose.
+const RENAME_RETRIES: u32 = 3;
+
+/// Waits for a free slot, giving up when the transfer is cancelled first.
+async fn wait_for_slot(
+    slots: Option<Arc<Semaphore>>,
+    control: &UploadControl,
+) -> SpResult<Option<OwnedSemaphorePermit>> {
+    let Some(slots) = slots else {
+        return Ok(None);
+    };
+    let acquire = slots.acquire_owned();
+    tokio::pin!(acquire);
+    loop {
+        tokio::select! {
+            permit = &mut acquire => {
+                return Ok(permit.ok());
+            }
+            _ = tokio::time::sleep(std::time::Duration::from_millis(200)) => {
+                if control.cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+                    return Err(cancelled_error());
+                }
+            }
+        }
+    }
+}
+
+/// Runs a file upload described by its recovery record. A source larger than
+/// one part goes through a multipart session that can continue after a
+/// restart; a smaller one is written in a single request. Compressed content
+/// no longer lines up with source ranges, so it is always written in one
+/// pass, which starts over after a restart. With `skip_unchanged`, a new
+/// upload first compares the source with the object already stored under its
+/// key; then its conflict policy decides the key and the precondition the
+/// write is sent with. Profiles that encrypt get no thumbnails, which would
+/// be plaintext copies of their images.
+fn spawn_file_upload(
+    app: tauri::AppHandle,
+    id: String,
+    profile: String,
+    session: UploadSession,
+    recovered: bool,
+    skip_unchanged: bool,
+    slots: Option<Arc<Semaphore>>,
+) {
     let _ = mutate_upload(&id, |transfer| transfer.worker_active = true);
     tokio::spawn(async move {
         let result = async {
-            let should_upload_thumbnail = settings::get().upload_thumbnail;
-            start_event(&task_app, &task_id)?;
+            let control = upload_control(&id)?;
+            let _slot = match wait_for_slot(slots, &control).await {
+                Err(error) if matches!(error.kind, ErrorKind::Cancelled) => {
+                    UploadEngineObserver::cancelled(&mut RuntimeUploadObserver::new(&app, &id))?;
+                    return Err(error);
+                }
+                other => other?,
+            };
+            if !recovered {
+                start_event(&app, &id)?;
+            }
             let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-            let operator = storage::build_operator(&bundle.r2).await?;
-            let mut observer = RuntimeUploadObserver {
-                app: &task_app,
-                transfer_id: &task_id,
+            let operator = storage::build_profile_operator(&bundle, &profile).await?;
+            let cfg = bundle.profile(&profile).cloned().ok_or_else(|| {
+                err_invalid(&format!("storage profile '{profile}' no longer exists"))
+            })?;
+            let encryption = bundle.encryption_for(&profile).cloned();
+            let compression = session.compression.filter(|_| encryption.is_none());
+            let should_upload_thumbnail = settings::get().upload_thumbnail && encryption.is_none();
+            let source_path = PathBuf::from(&session.source_path);
+            let (source_size, _) = source_identity(&source_path).await?;
+            let stored_size = match encryption {
+                Some(_) => crate::content_crypto::sealed_len(source_size),
+                None => source_size,
             };
-            upload_file(
-                &operator,
-                UploadEngineRequest {
-                    key: params.key.clone(),
-                    source_path: PathBuf::from(&params.source_path),
-                    part_size: params.part_size,
-                    content_type: params.content_type.clone(),
-                    content_disposition: params.content_disposition.clone(),
-                },
-                UploadControl { paused, cancelled },
-                &mut observer,
-            )
-            .await?;
+            let (mut key, _) = upload_target(&id)?;
+            if skip_unchanged
+                && session.upload_id.is_none()
+                && remote_copy_matches(&operator, &key, &source_path).await?
+            {
+                return skip_upload(&app, &id, &key, "already stored unchanged");
+            }
+            let mut observer = RuntimeUploadObserver::new(&app, &id);
+            let content_disposition = session.content_disposition.clone();
+            let mut session = session;
+            let mut renames_left = RENAME_RETRIES;
+            let content = loop {
+                // A recorded multipart session was created for a key the
+                // policy already settled on.
+                let condition = if session.upload_id.is_some() {
+                    write_condition(&operator, &session.conflict)
+                } else {
+                    match prepare_destination(&app, &id, &operator, &key, &session.conflict).await?
+                    {
+                        Some((resolved, condition)) => {
+                            key = resolved;
+                            condition
+                        }
+                        None => return Ok(()),
+                    }
+                };
+                let resumable = session.upload_id.is_some()
+                    || (compression.is_none() && stored_size > session.part_size);
+                let attempt = if resumable {
+                    let checkpoint = match (
+                        &session.upload_id,
+                        session.source_size,
+                        session.source_mtime_ms,
+                    ) {
+                        (Some(upload_id), Some(source_size), Some(source_mtime_ms)) => {
+                            Some(MultipartCheckpoint {
+                                session: MultipartSession {
+                                    upload_id: upload_id.clone(),
+                                    part_size: session.part_size,
+                                    source_size,
+                                    source_mtime_ms,
+                                    content_key_id: session.content_key_id.clone(),
+                                },
+                                parts: session.parts.clone(),
+                            })
+                        }
+                        _ => None,
+                    };
+                    let request = ResumableUploadRequest {
+                        key: key.clone(),
+                        source_path: source_path.clone(),
+                        part_size: session.part_size,
+                        parts_in_flight: settings::get().concurrency_limits().per_task_parts.into(),
+                        content_type: session.content_type.clone(),
+                        content_disposition: session.content_disposition.clone(),
+                        encryption: encryption.clone(),
+                        condition: condition.clone(),
+                    };
+                    observer.session = Some(session.clone());
+                    let control = upload_control(&id)?;
+                    crate::retry::watching_cancel(
+                        control.cancelled.clone(),
+                        upload_file_resumable(&cfg, request, checkpoint, control, &mut observer),
+                    )
+                    .await
+                } else {
+                    let control = upload_control(&id)?;
+                    crate::retry::watching_cancel(
+                        control.cancelled.clone(),
+                        upload_file(
+                            &operator,
+                            UploadEngineRequest {
+                                key: key.clone(),
+                                source_path: source_path.clone(),
+                                part_size: session.part_size,
+                                content_type: session.content_type.clone(),
+                                content_disposition: session.content_disposition.clone(),
+                                encryption: encryption.clone(),
+                                compression,
+                                condition: condition.clone(),
+                            },
+                            control,
+                            &mut observer,
+                        ),
+                    )
+                    .await
+                };
+                match attempt {
+                    Err(error) if condition.refused(&error) => match session.conflict {
+                        ConflictPolicy::Skip => {
+                            return skip_upload(
+                                &app,
+                                &id,
+                                &key,
+                                "the conflict policy keeps the object stored meanwhile",
+                            );
+                        }
+                        // The chosen name was taken meanwhile; try the next.
+                        ConflictPolicy::Rename if renames_left > 0 => {
+                            renames_left -= 1;
+                            session.upload_id = None;
+                            session.source_size = None;
+                            session.source_mtime_ms = None;
+                            session.content_key_id = None;
+                            session.parts.clear();
+                            transfer_db::upsert_upload_session(&session)?;
+                            mutate_upload(&id, |transfer| {
+                                transfer.bytes_done = 0;
+                                transfer.parts_completed = 0;
+                            })?;
+                        }
+                        _ => {
+                            return Err(conflict_error(
+                                &key,
+                                "was written by another client during the upload",
+                            ))
+                        }
+                    },
+                    result => break result?,
+                }
+            };
+            record_content_hash(&cfg, &key, content_disposition.as_deref(), &content).await?;
+            record_finished_upload(&profile, &key, source_size, &content);
             complete_file_upload(
-                &task_app,
-                &task_id,
-                &params,
+                &app,
+                &id,
+                &profile,
+                &key,
+                &source_path,
                 &operator,
                 should_upload_thumbnail,
             )
             .await
         }
         .await;
-        finish_upload_task(&task_app, &task_id, result);
+        finish_upload_task(&app, &id, result);
     });
-    Ok(id)
 }
 
+/// Chunks queued between the sender and the engine.
+const STREAM_CHUNKS_IN_FLIGHT: usize = 8;
+/// How long a push stream waits for a silent sender to come back.
+const STREAM_RECONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+/// How long a URL import waits for more of a response body.
+const URL_BODY_STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
 pub async fn start_upload_stream(
     app: tauri::AppHandle,
     params: NewUploadStreamParams,
 ) -> SpResult<String> {
+    let profile = SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
     let id = uuid::Uuid::new_v4().to_string();
     let paused = Arc::new(AtomicBool::new(false));
     let cancelled = Arc::new(AtomicBool::new(false));
     register_upload(
         &id,
+        profile.clone(),
         params.key.clone(),
         PathBuf::new(),
         params.part_size.max(512 * 1024),
         params.bytes_total,
-        paused.clone(),
-        cancelled.clone(),
+        UploadControl {
+            paused: paused.clone(),
+            cancelled: cancelled.clone(),
+        },
     )?;
-    let (sender, receiver) = mpsc::channel(8);
+    let (sender, receiver) = mpsc::channel(STREAM_CHUNKS_IN_FLIGHT);
     register_stream(id.clone(), sender)?;
 
     let task_id = id.clone();
     let task_app = app.clone();
     let _ = mutate_upload(&id, |transfer| transfer.worker_active = true);
     tokio::spawn(async move {
-        let result = async {
+        let result = crate::retry::watching_cancel(cancelled.clone(), async {
             start_event(&task_app, &task_id)?;
             if settings::get().upload_thumbnail {
                 crate::logger::warn(
@@ -404,24 +1154,47 @@ pub async fn start_upload_stream(
                 );
             }
             let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-            let operator = storage::build_operator(&bundle.r2).await?;
-            let mut observer = RuntimeUploadObserver {
-                app: &task_app,
-                transfer_id: &task_id,
+            let operator = storage::build_profile_operator(&bundle, &profile).await?;
+            let Some((key, condition)) = prepare_destination(
+                &task_app,
+                &task_id,
+                &operator,
+                &params.key,
+                &params.conflict,
+            )
+            .await?
+            else {
+                return Ok(());
             };
-            upload_stream(
+            let mut observer = RuntimeUploadObserver::new(&task_app, &task_id);
+            let content = match upload_stream(
                 &operator,
                 StreamUploadRequest {
-                    key: params.key,
-                    expected_bytes: params.bytes_total,
-                    content_type: params.content_type,
-                    content_disposition: params.content_disposition,
+                    key: key.clone(),
+                    expected_bytes: Some(params.bytes_total),
+                    stall_timeout: Some(STREAM_RECONNECT_TIMEOUT),
+                    content_type: params.content_type.clone(),
+                    content_disposition: params.content_disposition.clone(),
+                    encryption: bundle.encryption_for(&profile).cloned(),
+                    compression: params.compression,
+                    condition: condition.clone(),
                 },
                 receiver,
                 UploadControl { paused, cancelled },
                 &mut observer,
             )
-            .await?;
+            .await
+            {
+                Err(error) if condition.refused(&error) => {
+                    return settle_refused_stream(&task_app, &task_id, &key, &params.conflict);
+                }
+                result => result?,
+            };
+            if let Some(cfg) = bundle.profile(&profile) {
+                record_content_hash(cfg, &key, params.content_disposition.as_deref(), &content)
+                    .await?;
+            }
+            record_finished_upload(&profile, &key, params.bytes_total, &content);
             transition_upload(&task_id, TransferStateEvent::Complete)?;
             emit_upload(
                 &task_app,
@@ -430,7 +1203,7 @@ pub async fn start_upload_stream(
                 },
             );
             Ok(())
-        }
+        })
         .await;
         finish_upload_task(&task_app, &task_id, result);
         unregister_stream(&task_id);
@@ -438,6 +1211,102 @@ pub async fn start_upload_stream(
     Ok(id)
 }
 
+/// Starts copying the resource at `params.url` into the bucket. Returns once
+/// the response headers have arrived, so a URL that cannot be fetched fails
+/// here rather than as a transfer.
+pub async fn start_upload_from_url(
+    app: tauri::AppHandle,
+    params: NewUrlUploadParams,
+) -> SpResult<String> {
+    let profile = SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
+    let source = open_url(&storage::http_client()?, &params.url).await?;
+    let key = match params.key.as_deref().map(str::trim) {
+        Some(key) if !key.is_empty() => key.to_string(),
+        _ => match params.target_prefix.trim_matches('/') {
+            "" => source.file_name.clone(),
+            prefix => format!("{prefix}/{}", source.file_name),
+        },
+    };
+    crate::objects::validate_object_key(&key)?;
+    let content_type = params.content_type.clone().or(source.content_type.clone());
+    let id = uuid::Uuid::new_v4().to_string();
+    let paused = Arc::new(AtomicBool::new(false));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    // An unknown length is filled in once the body has ended.
+    register_upload(
+        &id,
+        profile.clone(),
+        key.clone(),
+        PathBuf::new(),
+        params.part_size.max(512 * 1024),
+        source.content_length.unwrap_or_default(),
+        UploadControl {
+            paused: paused.clone(),
+            cancelled: cancelled.clone(),
+        },
+    )?;
+
+    let task_id = id.clone();
+    let task_app = app.clone();
+    let _ = mutate_upload(&id, |transfer| transfer.worker_active = true);
+    tokio::spawn(async move {
+        let result = crate::retry::watching_cancel(cancelled.clone(), async {
+            start_event(&task_app, &task_id)?;
+            let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+            let operator = storage::build_profile_operator(&bundle, &profile).await?;
+            let Some((key, condition)) =
+                prepare_destination(&task_app, &task_id, &operator, &key, &params.conflict).await?
+            else {
+                return Ok(());
+            };
+            let mut observer = RuntimeUploadObserver::new(&task_app, &task_id);
+            let content = match upload_url(
+                &operator,
+                StreamUploadRequest {
+                    key: key.clone(),
+                    expected_bytes: None,
+                    stall_timeout: Some(URL_BODY_STALL_TIMEOUT),
+                    content_type: content_type.clone(),
+                    content_disposition: None,
+                    encryption: bundle.encryption_for(&profile).cloned(),
+                    compression: params.compression,
+                    condition: condition.clone(),
+                },
+                source,
+                UploadControl { paused, cancelled },
+                &mut observer,
+            )
+            .await
+            {
+                Err(error) if condition.refused(&error) => {
+                    return settle_refused_stream(&task_app, &task_id, &key, &params.conflict);
+                }
+                result => result?,
+            };
+            if let Some(cfg) = bundle.profile(&profile) {
+                record_content_hash(cfg, &key, None, &content).await?;
+            }
+            let mut received = 0;
+            mutate_upload(&task_id, |transfer| {
+                transfer.bytes_total = transfer.bytes_done;
+                received = transfer.bytes_done;
+            })?;
+            record_finished_upload(&profile, &key, received, &content);
+            transition_upload(&task_id, TransferStateEvent::Complete)?;
+            emit_upload(
+                &task_app,
+                &UploadEvent::Completed {
+                    transfer_id: task_id.clone(),
+                },
+            );
+            Ok(())
+        })
+        .await;
+        finish_upload_task(&task_app, &task_id, result);
+    });
+    Ok(id)
+}
+
 #[cfg(target_os = "android")]
 pub async fn start_upload_android_uri(
     app: tauri::AppHandle,
@@ -449,12 +1318,18 @@ pub async fn start_upload_android_uri(
     platform::start_upload_android_uri(app, key, uri, part_size, content_type).await
 }
 
-pub fn stream_write(id: &str, chunk: Vec<u8>) -> SpResult<()> {
-    runtime::stream_write(id, chunk)
+pub fn stream_write(id: &str, offset: u64, chunk: Vec<u8>) -> SpResult<StreamAck> {
+    runtime::stream_write(id, offset, chunk)
+}
+
+pub fn stream_finish(id: &str, offset: u64) -> SpResult<StreamAck> {
+    runtime::stream_finish(id, offset)
 }
 
-pub fn stream_finish(id: &str) -> SpResult<()> {
-    runtime::stream_finish(id)
+/// Reports where a sender that lost track of the stream continues from.
+/// Fails once the upload has given up waiting for it.
+pub fn stream_reconnect(id: &str) -> SpResult<StreamAck> {
+    runtime::stream_ack(id)
 }
 
 pub fn pause(app: &tauri::AppHandle, id: &str) -> SpResult<()> {
@@ -470,7 +1345,15 @@ pub fn pause(app: &tauri::AppHandle, id: &str) -> SpResult<()> {
 }
 
 pub fn resume(app: &tauri::AppHandle, id: &str) -> SpResult<()> {
-    let phase = resume_upload(id)?;
+    let (phase, needs_worker) = resume_upload(id)?;
+    let session = if needs_worker {
+        Some(
+            transfer_db::get_upload_session(id)?
+                .ok_or_else(|| err_invalid("recovered upload has no recorded source"))?,
+        )
+    } else {
+        None
+    };
     transition_upload(id, TransferStateEvent::Run(phase))?;
     emit_upload(
         app,
@@ -478,11 +1361,23 @@ pub fn resume(app: &tauri::AppHandle, id: &str) -> SpResult<()> {
             transfer_id: id.to_string(),
         },
     );
+    if let Some(session) = session {
+        let (_, profile) = upload_target(id)?;
+        spawn_file_upload(
+            app.clone(),
+            id.to_string(),
+            profile,
+            session,
+            true,
+            false,
+            None,
+        );
+    }
     Ok(())
 }
 
 pub fn cancel(app: &tauri::AppHandle, id: &str) -> SpResult<()> {
-    cancel_upload(id)?;
+    let worker_active = cancel_upload(id)?;
     transition_upload(id, TransferStateEvent::CancelRequest)?;
     emit_upload(
         app,
@@ -490,6 +1385,21 @@ pub fn cancel(app: &tauri::AppHandle, id: &str) -> SpResult<()> {
             transfer_id: id.to_string(),
         },
     );
+    if !worker_active {
+        // Nothing is running to confirm the cancellation of a restored upload.
+        let session = transfer_db::get_upload_session(id)?;
+        let (key, profile) = upload_target(id)?;
+        transition_upload(id, TransferStateEvent::CancelConfirm)?;
+        emit_upload(
+            app,
+            &UploadEvent::Cancelled {
+                transfer_id: id.to_string(),
+            },
+        );
+        if let Some(upload_id) = session.and_then(|session| session.upload_id) {
+            abort_recorded_session(profile, key, upload_id);
+        }
+    }
     Ok(())
 }
 
@@ -497,6 +1407,10 @@ pub fn status(id: &str) -> SpResult<UploadStatus> {
     upload_status(id)
 }
 
+pub fn group_status(group_id: &str) -> SpResult<UploadGroupStatus> {
+    upload_group_status(group_id)
+}
+
 pub fn list_active_snapshots() -> Vec<TransferSnapshot> {
     runtime::list_active_snapshots()
 }
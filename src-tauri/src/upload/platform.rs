@@ -8,6 +8,8 @@
 #[cfg(target_os = "android")]
 use super::*;
 #[cfg(target_os = "android")]
+use sha2::{Digest, Sha256};
+#[cfg(target_os = "android")]
 use std::sync::atomic::Ordering;
 
 #[cfg(target_os = "android")]
@@ -42,17 +44,21 @@ pub(super) async fn start_upload_android_uri(
     use std::io::Read;
     use tauri_plugin_android_fs::AndroidFsExt as _;
 
+    let profile = SpBackend::get_decrypted_bundle_if_unlocked()?.active_profile;
     let id = uuid::Uuid::new_v4().to_string();
     let paused = Arc::new(AtomicBool::new(false));
     let cancelled = Arc::new(AtomicBool::new(false));
     register_upload(
         &id,
+        profile.clone(),
         key.clone(),
         PathBuf::new(),
         part_size.max(512 * 1024),
         0,
-        paused.clone(),
-        cancelled.clone(),
+        UploadControl {
+            paused: paused.clone(),
+            cancelled: cancelled.clone(),
+        },
     )?;
 
     let task_id = id.clone();
@@ -60,7 +66,6 @@ pub(super) async fn start_upload_android_uri(
     let _ = mutate_upload(&id, |transfer| transfer.worker_active = true);
     tokio::spawn(async move {
         let result = async {
-            let should_upload_thumbnail = settings::get().upload_thumbnail;
             transition_upload(
                 &task_id,
                 TransferStateEvent::Run(TransferPhase::PreparingSource),
@@ -73,16 +78,26 @@ pub(super) async fn start_upload_android_uri(
             );
 
             let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-            let operator = crate::storage::build_operator(&bundle.r2).await?;
-            let mut writer = open_upload_writer(&operator, &key, content_type.as_deref(), None)
-                .await
-                .map_err(|error| SpError {
-                    kind: ErrorKind::RetryableNet,
-                    message: format!("open writer: {error}"),
-                    retry_after_ms: Some(500),
-                    context: None,
-                    at: now_ms(),
-                })?;
+            let operator = crate::storage::build_profile_operator(&bundle, &profile).await?;
+            let encryption = bundle.encryption_for(&profile);
+            let should_upload_thumbnail = settings::get().upload_thumbnail && encryption.is_none();
+            let mut sealer = stream_sealer(encryption).await?;
+            // The picker reports the document's type; the key decides otherwise.
+            let content_type = inferred_content_type(&key, content_type.as_deref(), &[]);
+            let mut writer = open_upload_writer(
+                &operator,
+                &key,
+                Some(&content_type),
+                None,
+                ContentHashes::default(),
+                StoredEncoding {
+                    sealed: sealer.is_some(),
+                    compressed: None,
+                },
+                &WriteCondition::None,
+            )
+            .await
+            .map_err(|error| crate::storage::opendal_error("open writer", &key, &error))?;
             transition_upload(
                 &task_id,
                 TransferStateEvent::Run(TransferPhase::UploadingRemote),
@@ -105,6 +120,8 @@ pub(super) async fn start_upload_android_uri(
             }
 
             let mut part_number = 1;
+            let mut hasher = Sha256::new();
+            let mut stored_size = 0u64;
             let mut buffer = vec![0; part_size.max(256 * 1024) as usize];
             let mut was_paused = false;
             loop {
@@ -154,21 +171,22 @@ pub(super) async fn start_upload_android_uri(
                 if read == 0 {
                     break;
                 }
-                writer
-                    .write(buffer[..read].to_vec())
-                    .await
-                    .map_err(|error| SpError {
-                        kind: ErrorKind::RetryableNet,
-                        message: format!("writer write: {error}"),
-                        retry_after_ms: Some(300),
-                        context: None,
-                        at: now_ms(),
+                let body = match sealer.as_mut() {
+                    Some(sealer) => sealer.push(&buffer[..read]),
+                    None => buffer[..read].to_vec(),
+                };
+                if !body.is_empty() {
+                    hasher.update(&body);
+                    stored_size += body.len() as u64;
+                    writer.write(body).await.map_err(|error| {
+                        crate::storage::opendal_error("writer write", &key, &error)
                     })?;
+                }
                 mutate_upload(&task_id, |transfer| {
                     transfer.bytes_done = transfer.bytes_done.saturating_add(read as u64);
                     transfer.parts_completed += 1;
                 })?;
-                emit_part_events(&task_app, &task_id, part_number, read as u64);
+                emit_part_events(&task_app, &task_id, part_number, read as u64, String::new());
                 part_number += 1;
             }
 
@@ -188,13 +206,37 @@ pub(super) async fn start_upload_android_uri(
                 &task_id,
                 TransferStateEvent::Run(TransferPhase::FinalizingRemote),
             )?;
-            writer.close().await.map_err(|error| SpError {
-                kind: ErrorKind::RetryableNet,
-                message: format!("writer close: {error}"),
-                retry_after_ms: Some(300),
-                context: None,
-                at: now_ms(),
-            })?;
+            if let Some(sealer) = sealer.as_mut() {
+                let tail = sealer.finish();
+                hasher.update(&tail);
+                stored_size += tail.len() as u64;
+                writer
+                    .write(tail)
+                    .await
+                    .map_err(|error| crate::storage::opendal_error("writer write", &key, &error))?;
+            }
+            let stored = writer
+                .close()
+                .await
+                .map_err(|error| crate::storage::opendal_error("writer close", &key, &error))?;
+            let content = UploadedContent {
+                sha256: format!("{:x}", hasher.finalize()),
+                source_sha256: None,
+                sha256_stored: false,
+                etag: stored.etag().map(str::to_string),
+                stored_size,
+                encoding: StoredEncoding {
+                    sealed: sealer.is_some(),
+                    compressed: None,
+                },
+                content_type,
+            };
+            if let Some(cfg) = bundle.profile(&profile) {
+                record_content_hash(cfg, &key, None, &content).await?;
+            }
+            let mut source_size = 0;
+            mutate_upload(&task_id, |transfer| source_size = transfer.bytes_done)?;
+            record_finished_upload(&profile, &key, source_size, &content);
 
             if should_upload_thumbnail {
                 upload_android_thumbnail(&task_app, &task_id, &key, &uri, &operator).await?;
@@ -0,0 +1,524 @@
+//! Tauri-independent resumable multipart file upload engine.
+//!
+//! This module owns multipart sessions for local files: creating one,
+//! continuing one recorded before a restart from the first part the bucket
+//! does not hold, sending up to `parts_in_flight` parts at once, verifying
+//! that the source did not change underneath the session, sealing parts when
+//! the profile encrypts, sending the source's SHA-256 with the session, and
+//! completing it under the upload's precondition or aborting it once it
+//! cannot be continued. Its boundary is the storage profile's [`R2Config`]
+//! plus observer callbacks, which persist the session. It must not construct
+//! credentials, access global runtime state, emit Tauri events, or write
+//! SQLite rows itself.
+
+use super::conflict::WriteCondition;
+use super::{
+    cancelled_error, file_sha256, inferred_content_type, now_ms, StoredEncoding, UploadControl,
+    UploadEngineObserver, UploadedContent, SNIFF_BYTES,
+};
+use crate::content_crypto::{self, ContentKey, ContentSealer};
+use crate::sp_backend::ProfileEncryption;
+use crate::storage::multipart::{self, CompletedPart, MAX_PARTS};
+use crate::types::{ErrorKind, R2Config, SpError, SpResult};
+use bytes::{Bytes, BytesMut};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::task::JoinSet;
+
+/// How often a wait for in-flight parts re-checks pause and cancel.
+const CONTROL_POLL: Duration = Duration::from_millis(200);
+
+pub(crate) struct ResumableUploadRequest {
+    pub(crate) key: String,
+    pub(crate) source_path: PathBuf,
+    pub(crate) part_size: u64,
+    /// Parts sent at the same time; zero counts as one.
+    pub(crate) parts_in_flight: usize,
+    pub(crate) content_type: Option<String>,
+    pub(crate) content_disposition: Option<String>,
+    /// Seal the parts under the profile's passphrase.
+    pub(crate) encryption: Option<ProfileEncryption>,
+    /// Precondition the session is completed under.
+    pub(crate) condition: WriteCondition,
+}
+
+/// An open multipart upload and the source it was started for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MultipartSession {
+    pub(crate) upload_id: String,
+    pub(crate) part_size: u64,
+    pub(crate) source_size: u64,
+    pub(crate) source_mtime_ms: i64,
+    /// [`ContentKey::id`] of the key the parts are sealed with; `None` for
+    /// plaintext.
+    pub(crate) content_key_id: Option<String>,
+}
+
+impl MultipartSession {
+    /// Size of the object the parts add up to.
+    pub(crate) fn stored_size(&self) -> u64 {
+        match self.content_key_id {
+            Some(_) => content_crypto::sealed_len(self.source_size),
+            None => self.source_size,
+        }
+    }
+}
+
+/// A session recorded by an earlier run, with the parts it recorded as sent.
+pub(crate) struct MultipartCheckpoint {
+    pub(crate) session: MultipartSession,
+    pub(crate) parts: Vec<CompletedPart>,
+}
+
+pub(crate) trait ResumableUploadObserver: UploadEngineObserver {
+    /// The session parts are sent under, and the parts it already holds.
+    fn session_started(
+        &mut self,
+        session: &MultipartSession,
+        completed: &[CompletedPart],
+    ) -> SpResult<()>;
+    /// Called before `part_done` once the bucket confirmed a part.
+    fn part_stored(&mut self, part: &CompletedPart) -> SpResult<()>;
+    /// The checkpoint was discarded because the source file changed; the
+    /// upload starts over.
+    fn source_changed(&mut self, error: &SpError) -> SpResult<()>;
+}
+
+pub(crate) async fn upload_file_resumable(
+    cfg: &R2Config,
+    request: ResumableUploadRequest,
+    checkpoint: Option<MultipartCheckpoint>,
+    control: UploadControl,
+    observer: &mut impl ResumableUploadObserver,
+) -> SpResult<UploadedContent> {
+    if request.part_size == 0 {
+        return Err(SpError {
+            kind: ErrorKind::NotRetriable,
+            message: "upload part size must be greater than zero".into(),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        });
+    }
+    let mut file = tokio::fs::File::open(&request.source_path)
+        .await
+        .map_err(|error| SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!("open src: {error}"),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        })?;
+    let (source_size, source_mtime_ms) = source_identity(&request.source_path).await?;
+    let content_type = source_content_type(&request, &mut file).await?;
+    let key = match request.encryption.as_ref() {
+        Some(encryption) => Some(ContentKey::for_profile(encryption).await?),
+        None => None,
+    };
+    let content_key_id = key.as_ref().map(ContentKey::id);
+
+    let resumed = match checkpoint {
+        Some(checkpoint)
+            if (
+                checkpoint.session.source_size,
+                checkpoint.session.source_mtime_ms,
+            ) != (source_size, source_mtime_ms) =>
+        {
+            let error = source_changed_error("source changed since the upload was recorded");
+            observer.source_changed(&error)?;
+            abort_quietly(cfg, &request.key, &checkpoint.session.upload_id).await;
+            None
+        }
+        // Parts sealed under another key, or not at all, cannot be mixed
+        // with the ones sent now.
+        Some(checkpoint) if checkpoint.session.content_key_id != content_key_id => {
+            let error =
+                source_changed_error("encryption settings changed since the upload was recorded");
+            observer.source_changed(&error)?;
+            abort_quietly(cfg, &request.key, &checkpoint.session.upload_id).await;
+            None
+        }
+        Some(checkpoint) => resume_session(cfg, &request.key, checkpoint).await?,
+        None => None,
+    };
+    let (session, completed) = match resumed {
+        Some(resumed) => resumed,
+        None => {
+            // Hashed up front, the source's SHA-256 goes out with the
+            // session instead of a metadata rewrite after completion.
+            let sha256 = file_sha256(&request.source_path).await?;
+            let mut session = MultipartSession {
+                upload_id: multipart::create(
+                    cfg,
+                    &request.key,
+                    create_headers(&request, &content_type, &sha256),
+                )
+                .await?,
+                part_size: request.part_size,
+                source_size,
+                source_mtime_ms,
+                content_key_id,
+            };
+            session.part_size = session
+                .part_size
+                .max(session.stored_size().div_ceil(MAX_PARTS));
+            (session, Vec::new())
+        }
+    };
+    let sealer = key.map(|key| ContentSealer::for_multipart_upload(key, &session.upload_id));
+    send_parts(
+        cfg,
+        &request,
+        PartSource {
+            file: &mut file,
+            sealer: sealer.as_ref(),
+        },
+        session,
+        completed,
+        control,
+        observer,
+    )
+    .await
+    .map(|content| UploadedContent {
+        content_type,
+        ..content
+    })
+}
+
+/// The type to store the source with, sniffed from its first bytes unless
+/// the request names one. A resumed session repeats what its creation
+/// sent, since the source it continues is unchanged.
+async fn source_content_type(
+    request: &ResumableUploadRequest,
+    file: &mut tokio::fs::File,
+) -> SpResult<String> {
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    if request.content_type.is_none() {
+        file.take(SNIFF_BYTES as u64)
+            .read_to_end(&mut head)
+            .await
+            .map_err(read_error)?;
+    }
+    Ok(inferred_content_type(
+        &request.key,
+        request.content_type.as_deref(),
+        &head,
+    ))
+}
+
+/// Where part bodies come from: the source file, sealed when the profile
+/// encrypts.
+struct PartSource<'a> {
+    file: &'a mut tokio::fs::File,
+    sealer: Option<&'a ContentSealer>,
+}
+
+/// Continues the recorded session from the parts ListParts reports. A part
+/// only counts when its size is right and, if this app recorded an ETag for
+/// it, the ETag matches; the upload continues from the first part missing.
+/// Returns `None` when the bucket no longer knows the session.
+async fn resume_session(
+    cfg: &R2Config,
+    key: &str,
+    checkpoint: MultipartCheckpoint,
+) -> SpResult<Option<(MultipartSession, Vec<CompletedPart>)>> {
+    let session = checkpoint.session;
+    let listed = match multipart::list_parts(cfg, key, &session.upload_id).await {
+        Ok(listed) => listed,
+        // Completed, aborted or expired by a lifecycle rule.
+        Err(error) if matches!(error.kind, ErrorKind::NotFound) => {
+            crate::logger::warn(
+                "upload",
+                &format!("multipart upload for {key} is gone; starting over"),
+            );
+            return Ok(None);
+        }
+        Err(error) => return Err(error),
+    };
+    let total_parts = session.stored_size().div_ceil(session.part_size).max(1);
+    let mut completed = Vec::new();
+    for expected in 1..=total_parts {
+        let part_number = expected as u32;
+        let expected_size = part_len(&session, part_number);
+        let recorded = checkpoint
+            .parts
+            .iter()
+            .find(|part| part.part_number == part_number);
+        let Some(listed) = listed.iter().find(|part| {
+            part.part_number == part_number
+                && part.size == expected_size
+                && recorded.map_or(true, |recorded| recorded.etag == part.etag)
+        }) else {
+            break;
+        };
+        completed.push(listed.clone());
+    }
+    Ok(Some((session, completed)))
+}
+
+/// Sends the parts `completed` does not hold, keeping up to
+/// `parts_in_flight` uploads running. Each upload owns one part-sized
+/// buffer, which is reused for a later part once the request released it,
+/// so memory stays bounded by the limit. Parts finish out of order; every
+/// one is reported as it is stored, and completion lists them in order.
+/// Pausing stops new parts from starting; the ones in flight finish.
+/// Parts are read in order, so what is stored is hashed as it is sent; the
+/// parts a resumed session already holds are read, and sealed again, from
+/// the file first.
+/// The object's headers went out with the session, so the hash is returned
+/// for the caller to record.
+async fn send_parts(
+    cfg: &R2Config,
+    request: &ResumableUploadRequest,
+    mut source: PartSource<'_>,
+    session: MultipartSession,
+    mut completed: Vec<CompletedPart>,
+    control: UploadControl,
+    observer: &mut impl ResumableUploadObserver,
+) -> SpResult<UploadedContent> {
+    observer.session_started(&session, &completed)?;
+    observer.uploading()?;
+    let result = async {
+        let total_parts = session.stored_size().div_ceil(session.part_size).max(1) as u32;
+        let parts_in_flight = request.parts_in_flight.max(1);
+        let mut next_part = completed.len() as u32 + 1;
+        let mut in_flight = JoinSet::new();
+        let mut spare_buffers = Vec::<BytesMut>::new();
+        let mut hasher = Sha256::new();
+        for part_number in 1..next_part {
+            let body = read_part(&mut source, &session, part_number, spare_buffers.pop()).await?;
+            hasher.update(&body);
+            if let Ok(buffer) = body.try_into_mut() {
+                spare_buffers.push(buffer);
+            }
+        }
+        let mut was_paused = false;
+        loop {
+            if control.cancelled.load(Ordering::Relaxed) {
+                return Err(cancelled_error());
+            }
+            if control.paused.load(Ordering::Relaxed) {
+                if !was_paused {
+                    observer.paused()?;
+                    was_paused = true;
+                }
+            } else if was_paused {
+                observer.resumed()?;
+                was_paused = false;
+            }
+            while !was_paused && next_part <= total_parts && in_flight.len() < parts_in_flight {
+                let body = read_part(&mut source, &session, next_part, spare_buffers.pop()).await?;
+                hasher.update(&body);
+                let (cfg, key, upload_id) =
+                    (cfg.clone(), request.key.clone(), session.upload_id.clone());
+                let part_number = next_part;
+                // Spawned parts leave the caller's task, and with it the
+                // transfer its request retries watch for cancellation.
+                let cancelled = crate::retry::cancel_flag();
+                in_flight.spawn(crate::retry::watching_cancel(cancelled, async move {
+                    let stored =
+                        multipart::upload_part(&cfg, &key, &upload_id, part_number, body.clone())
+                            .await;
+                    (stored, body)
+                }));
+                next_part += 1;
+            }
+            if in_flight.is_empty() {
+                if next_part > total_parts {
+                    break;
+                }
+                tokio::time::sleep(CONTROL_POLL).await;
+                continue;
+            }
+            let Ok(Some(joined)) = tokio::time::timeout(CONTROL_POLL, in_flight.join_next()).await
+            else {
+                continue;
+            };
+            let (stored, body) = joined.map_err(|error| SpError {
+                kind: ErrorKind::NotRetriable,
+                message: format!("part upload task failed: {error}"),
+                retry_after_ms: None,
+                context: None,
+                at: now_ms(),
+            })?;
+            let part = stored?;
+            if let Ok(buffer) = body.try_into_mut() {
+                spare_buffers.push(buffer);
+            }
+            observer.part_stored(&part)?;
+            observer.part_done(part.part_number, part.size)?;
+            completed.push(part);
+        }
+        if control.cancelled.load(Ordering::Relaxed) {
+            return Err(cancelled_error());
+        }
+        if source_identity(&request.source_path).await?
+            != (session.source_size, session.source_mtime_ms)
+        {
+            return Err(source_changed_error("source changed during upload"));
+        }
+        observer.finalizing()?;
+        completed.sort_by_key(|part| part.part_number);
+        multipart::complete(
+            cfg,
+            &request.key,
+            &session.upload_id,
+            &completed,
+            request.condition.headers(),
+        )
+        .await?;
+        Ok(UploadedContent {
+            sha256: format!("{:x}", hasher.finalize()),
+            source_sha256: None,
+            // Sent when the session was created.
+            sha256_stored: true,
+            etag: None,
+            stored_size: session.stored_size(),
+            encoding: StoredEncoding {
+                sealed: source.sealer.is_some(),
+                compressed: None,
+            },
+            // Filled in by the caller, which sniffed it.
+            content_type: String::new(),
+        })
+    }
+    .await;
+
+    if let Err(error) = &result {
+        // Anything else leaves the session for a resume to continue.
+        if matches!(error.kind, ErrorKind::Cancelled | ErrorKind::SourceChanged)
+            || request.condition.refused(error)
+        {
+            abort_quietly(cfg, &request.key, &session.upload_id).await;
+        }
+        if matches!(error.kind, ErrorKind::Cancelled) {
+            observer.cancelled()?;
+        }
+    }
+    result
+}
+
+/// Reads one part into `buffer`, or into a new buffer if no spare exists.
+/// A sealed part is read as the plaintext chunks it covers and sealed into
+/// a buffer of its own.
+async fn read_part(
+    source: &mut PartSource<'_>,
+    session: &MultipartSession,
+    part_number: u32,
+    buffer: Option<BytesMut>,
+) -> SpResult<Bytes> {
+    let offset = u64::from(part_number - 1) * session.part_size;
+    let stored = offset..offset + part_len(session, part_number);
+    let plain = match source.sealer {
+        Some(_) => content_crypto::plaintext_for_sealed_range(&stored, session.source_size),
+        None => stored.clone(),
+    };
+    let mut buffer = buffer.unwrap_or_default();
+    buffer.clear();
+    buffer.resize((plain.end - plain.start) as usize, 0);
+    source
+        .file
+        .seek(std::io::SeekFrom::Start(plain.start))
+        .await
+        .map_err(read_error)?;
+    source
+        .file
+        .read_exact(&mut buffer)
+        .await
+        .map_err(read_error)?;
+    Ok(match source.sealer {
+        Some(sealer) => Bytes::from(sealer.seal_range(stored, session.source_size, &buffer)),
+        None => buffer.freeze(),
+    })
+}
+
+fn part_len(session: &MultipartSession, part_number: u32) -> u64 {
+    let offset = u64::from(part_number - 1) * session.part_size;
+    session
+        .stored_size()
+        .saturating_sub(offset)
+        .min(session.part_size)
+}
+
+/// Size and modification time in milliseconds since the epoch.
+pub(crate) async fn source_identity(path: &std::path::Path) -> SpResult<(u64, i64)> {
+    let metadata = tokio::fs::metadata(path).await.map_err(|error| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("stat src: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    })?;
+    let modified_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |elapsed| elapsed.as_millis() as i64);
+    Ok((metadata.len(), modified_ms))
+}
+
+pub(super) fn create_headers(
+    request: &ResumableUploadRequest,
+    content_type: &str,
+    source_sha256: &str,
+) -> Vec<(String, String)> {
+    let mut headers = vec![("content-type".to_string(), content_type.to_string())];
+    if let Some(value) = request
+        .content_disposition
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        headers.push(("content-disposition".into(), value.to_string()));
+    }
+    // Sealed parts are not the source, and their hash depends on the
+    // session's id, so only the source's hash can be known here.
+    let hash_key = match request.encryption {
+        Some(_) => crate::objects::SOURCE_SHA256_METADATA_KEY,
+        None => crate::objects::SHA256_METADATA_KEY,
+    };
+    headers.push((format!("x-amz-meta-{hash_key}"), source_sha256.to_string()));
+    if request.encryption.is_some() {
+        let (name, value) = content_crypto::sealed_marker();
+        headers.push((format!("x-amz-meta-{name}"), value));
+    }
+    headers
+}
+
+async fn abort_quietly(cfg: &R2Config, key: &str, upload_id: &str) {
+    if let Err(error) = multipart::abort(cfg, key, upload_id).await {
+        crate::logger::warn(
+            "upload",
+            &format!(
+                "could not abort multipart upload for {key}: {}",
+                error.message
+            ),
+        );
+    }
+}
+
+fn source_changed_error(message: &str) -> SpError {
+    SpError {
+        kind: ErrorKind::SourceChanged,
+        message: message.into(),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
+
+fn read_error(error: std::io::Error) -> SpError {
+    if error.kind() == std::io::ErrorKind::UnexpectedEof {
+        return source_changed_error("source shrank during upload");
+    }
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("read src: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
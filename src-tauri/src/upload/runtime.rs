@@ -1,12 +1,20 @@
 //! In-process upload state and stream-channel registry.
 //!
 //! This module owns the global transfer table, FSM transitions, snapshot
-//! conversion, progress mutation, and streaming channel lookup. It must not
-//! open local sources, write remote objects, construct credentials, generate
-//! thumbnails, or emit Tauri events.
+//! conversion and persistence, progress mutation, restoring uploads recorded
+//! before a restart, folder-upload group membership, and streaming channel
+//! lookup. Snapshots are a recovery journal: an upload's rows are deleted
+//! once it reaches a terminal state. Groups live in memory only; uploads
+//! restored after a restart come back on their own. Push-stream chunks are
+//! placed by offset here, so a sender can repeat them after reconnecting. It
+//! must not open local sources, write remote objects, construct credentials,
+//! generate thumbnails, or emit Tauri events.
 
-use super::{now_ms, UploadStatus};
-use crate::transfer_db::{TransferKind, TransferLifecycle, TransferPhase, TransferSnapshot};
+use super::engine::UploadControl;
+use super::{now_ms, StreamAck, UploadGroupStatus, UploadStatus};
+use crate::transfer_db::{
+    self, TransferKind, TransferLifecycle, TransferPhase, TransferSnapshot, UploadSession,
+};
 use crate::transfer_fsm::{apply_transfer_event, TransferState, TransferStateEvent};
 use crate::types::{err_invalid, ErrorKind, SpError, SpResult};
 use once_cell::sync::Lazy;
@@ -17,6 +25,7 @@ use tokio::sync::mpsc;
 
 pub(super) struct UploadTransfer {
     pub(super) key: String,
+    pub(super) profile: String,
     pub(super) src: PathBuf,
     pub(super) part_size: u64,
     pub(super) bytes_total: u64,
@@ -34,30 +43,100 @@ pub(super) struct UploadTransfer {
 
 pub(super) static UPLOADS: Lazy<Mutex<HashMap<String, UploadTransfer>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
-static STREAMS: Lazy<Mutex<HashMap<String, mpsc::Sender<Option<Vec<u8>>>>>> =
+/// Folder-upload group id to the transfer ids started for it.
+static GROUPS: Lazy<Mutex<HashMap<String, Vec<String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static STREAMS: Lazy<Mutex<HashMap<String, StreamSession>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+/// The sending side of a push stream. Chunks are placed by byte offset, so
+/// a sender that lost an acknowledgement can send them again.
+pub(super) struct StreamSession {
+    sender: mpsc::Sender<Option<Vec<u8>>>,
+    /// End of the bytes handed to the engine.
+    accepted: u64,
+    finished: bool,
+}
+
+impl StreamSession {
+    pub(super) fn new(sender: mpsc::Sender<Option<Vec<u8>>>) -> Self {
+        Self {
+            sender,
+            accepted: 0,
+            finished: false,
+        }
+    }
+
+    /// Queues the part of `chunk` past what was already accepted. A repeated
+    /// chunk is dropped; one starting beyond the accepted end is refused.
+    pub(super) fn write(&mut self, offset: u64, chunk: Vec<u8>) -> SpResult<()> {
+        let end = offset
+            .checked_add(chunk.len() as u64)
+            .ok_or_else(|| err_invalid("stream chunk ends past the largest offset"))?;
+        if end <= self.accepted {
+            return Ok(());
+        }
+        if self.finished {
+            return Err(err_invalid(&format!(
+                "stream finished at offset {}; chunk ends at {end}",
+                self.accepted
+            )));
+        }
+        if offset > self.accepted {
+            return Err(stream_gap_error(offset, self.accepted));
+        }
+        let fresh = chunk[(self.accepted - offset) as usize..].to_vec();
+        self.sender
+            .try_send(Some(fresh))
+            .map_err(|error| stream_send_error("stream write", error))?;
+        self.accepted = end;
+        Ok(())
+    }
+
+    /// Ends the stream at `offset`, which must be where the accepted bytes
+    /// end. Finishing again at the same offset is a no-op.
+    pub(super) fn finish(&mut self, offset: u64) -> SpResult<()> {
+        if offset > self.accepted {
+            return Err(stream_gap_error(offset, self.accepted));
+        }
+        if offset < self.accepted {
+            return Err(err_invalid(&format!(
+                "stream finish at offset {offset}; {} bytes were already sent",
+                self.accepted
+            )));
+        }
+        if self.finished {
+            return Ok(());
+        }
+        self.sender
+            .try_send(None)
+            .map_err(|error| stream_send_error("stream finish", error))?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
 pub(super) fn register_upload(
     id: &str,
+    profile: String,
     key: String,
     source_path: PathBuf,
     part_size: u64,
     bytes_total: u64,
-    paused: Arc<AtomicBool>,
-    cancelled: Arc<AtomicBool>,
+    control: UploadControl,
 ) -> SpResult<()> {
     let queued = TransferState::queued(TransferKind::Upload);
     let timestamp = now_ms();
     let transfer = UploadTransfer {
         key,
+        profile,
         src: source_path,
         part_size,
         bytes_total,
         bytes_done: 0,
         parts_completed: 0,
         last_error: None,
-        paused,
-        cancelled,
+        paused: control.paused,
+        cancelled: control.cancelled,
         worker_active: false,
         lifecycle_state: queued.lifecycle,
         phase: queued.phase,
@@ -68,7 +147,66 @@ pub(super) fn register_upload(
         .lock()
         .map_err(|_| upload_lock_error())?
         .insert(id.to_string(), transfer);
-    Ok(())
+    persist_upload(id)
+}
+
+/// Re-registers a file upload recorded before the app exited. It comes back
+/// paused, like an interrupted download, and continues once resumed; a
+/// restore that raced an existing entry is ignored.
+pub(super) fn restore_upload(snapshot: &TransferSnapshot, session: &UploadSession) -> SpResult<()> {
+    let lifecycle = match snapshot.lifecycle_state {
+        TransferLifecycle::Queued | TransferLifecycle::Running => TransferLifecycle::Paused,
+        ref other => other.clone(),
+    };
+    // Parts are re-checked against the bucket before anything is finalized,
+    // so an upload interrupted while finalizing resumes as uploading.
+    let phase = if session.upload_id.is_some() {
+        TransferPhase::UploadingRemote
+    } else {
+        snapshot.phase.unwrap_or(TransferPhase::PreparingSource)
+    };
+    let transfer = UploadTransfer {
+        key: snapshot.key.clone(),
+        profile: snapshot
+            .profile
+            .clone()
+            .unwrap_or_else(|| crate::sp_backend::DEFAULT_PROFILE_NAME.into()),
+        src: PathBuf::from(&session.source_path),
+        part_size: session.part_size,
+        bytes_total: snapshot.bytes_total.unwrap_or_default(),
+        bytes_done: session.parts.iter().map(|part| part.size).sum(),
+        parts_completed: session.parts.len() as u32,
+        last_error: snapshot.last_error.clone(),
+        paused: Arc::new(AtomicBool::new(true)),
+        cancelled: Arc::new(AtomicBool::new(false)),
+        worker_active: false,
+        lifecycle_state: lifecycle,
+        phase: Some(phase),
+        created_at_ms: snapshot.created_at_ms,
+        updated_at_ms: now_ms(),
+    };
+    {
+        let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+        if uploads.contains_key(&snapshot.transfer_id) {
+            return Ok(());
+        }
+        uploads.insert(snapshot.transfer_id.clone(), transfer);
+    }
+    persist_upload(&snapshot.transfer_id)
+}
+
+fn persist_upload(id: &str) -> SpResult<()> {
+    let snapshot = {
+        let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+        let transfer = uploads.get(id).ok_or_else(upload_not_found)?;
+        snapshot_from_upload(id, transfer)
+    };
+    if snapshot.lifecycle_state.is_terminal() {
+        transfer_db::delete_upload_session(id)?;
+        transfer_db::delete_snapshot(id)
+    } else {
+        transfer_db::upsert_snapshot(&snapshot)
+    }
 }
 
 fn state_from_transfer(transfer: &UploadTransfer) -> TransferState {
@@ -82,23 +220,46 @@ pub(super) fn mutate_upload<F>(id: &str, mutate: F) -> SpResult<()>
 where
     F: FnOnce(&mut UploadTransfer),
 {
-    let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
-    let transfer = uploads.get_mut(id).ok_or_else(upload_not_found)?;
-    mutate(transfer);
-    transfer.updated_at_ms = now_ms();
-    Ok(())
+    {
+        let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+        let transfer = uploads.get_mut(id).ok_or_else(upload_not_found)?;
+        mutate(transfer);
+        transfer.updated_at_ms = now_ms();
+    }
+    persist_upload(id)
 }
 
 pub(super) fn transition_upload(id: &str, event: TransferStateEvent) -> SpResult<TransferState> {
-    let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
-    let transfer = uploads.get_mut(id).ok_or_else(upload_not_found)?;
-    let next = apply_transfer_event(TransferKind::Upload, &state_from_transfer(transfer), event)?;
-    transfer.lifecycle_state = next.lifecycle.clone();
-    transfer.phase = next.phase;
-    transfer.updated_at_ms = now_ms();
+    let next = {
+        let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+        let transfer = uploads.get_mut(id).ok_or_else(upload_not_found)?;
+        let next =
+            apply_transfer_event(TransferKind::Upload, &state_from_transfer(transfer), event)?;
+        transfer.lifecycle_state = next.lifecycle.clone();
+        transfer.phase = next.phase;
+        transfer.updated_at_ms = now_ms();
+        next
+    };
+    persist_upload(id)?;
     Ok(next)
 }
 
+pub(super) fn upload_control(id: &str) -> SpResult<UploadControl> {
+    let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+    let transfer = uploads.get(id).ok_or_else(upload_not_found)?;
+    Ok(UploadControl {
+        paused: transfer.paused.clone(),
+        cancelled: transfer.cancelled.clone(),
+    })
+}
+
+/// Object key and storage profile of an upload.
+pub(super) fn upload_target(id: &str) -> SpResult<(String, String)> {
+    let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+    let transfer = uploads.get(id).ok_or_else(upload_not_found)?;
+    Ok((transfer.key.clone(), transfer.profile.clone()))
+}
+
 pub(super) fn snapshot_from_upload(id: &str, transfer: &UploadTransfer) -> TransferSnapshot {
     TransferSnapshot {
         transfer_id: id.to_string(),
@@ -121,6 +282,8 @@ pub(super) fn snapshot_from_upload(id: &str, transfer: &UploadTransfer) -> Trans
         temp_path: None,
         expected_etag: None,
         observed_etag: None,
+        profile: Some(transfer.profile.clone()),
+        decompress: false,
         created_at_ms: transfer.created_at_ms,
         updated_at_ms: transfer.updated_at_ms,
     }
@@ -130,7 +293,7 @@ pub(super) fn register_stream(id: String, sender: mpsc::Sender<Option<Vec<u8>>>)
     STREAMS
         .lock()
         .map_err(|_| stream_lock_error())?
-        .insert(id, sender);
+        .insert(id, StreamSession::new(sender));
     Ok(())
 }
 
@@ -141,34 +304,35 @@ pub(super) fn unregister_stream(id: &str) {
         .remove(id);
 }
 
-pub(super) fn stream_write(id: &str, chunk: Vec<u8>) -> SpResult<()> {
-    let streams = STREAMS.lock().map_err(|_| stream_lock_error())?;
-    streams
-        .get(id)
-        .ok_or_else(upload_not_found)?
-        .try_send(Some(chunk))
-        .map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("stream write: {error}"),
-            retry_after_ms: Some(100),
-            context: None,
-            at: now_ms(),
-        })
+pub(super) fn stream_write(id: &str, offset: u64, chunk: Vec<u8>) -> SpResult<StreamAck> {
+    with_stream(id, |session| session.write(offset, chunk))?;
+    stream_ack(id)
+}
+
+pub(super) fn stream_finish(id: &str, offset: u64) -> SpResult<StreamAck> {
+    with_stream(id, |session| session.finish(offset))?;
+    stream_ack(id)
 }
 
-pub(super) fn stream_finish(id: &str) -> SpResult<()> {
-    let streams = STREAMS.lock().map_err(|_| stream_lock_error())?;
-    streams
+/// Accepted offset from the stream session, committed offset from the
+/// progress the engine reported.
+pub(super) fn stream_ack(id: &str) -> SpResult<StreamAck> {
+    let accepted_offset = with_stream(id, |session| Ok(session.accepted))?;
+    let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+    let committed_offset = uploads
         .get(id)
         .ok_or_else(upload_not_found)?
-        .try_send(None)
-        .map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("stream finish: {error}"),
-            retry_after_ms: Some(100),
-            context: None,
-            at: now_ms(),
-        })
+        .bytes_done
+        .min(accepted_offset);
+    Ok(StreamAck {
+        committed_offset,
+        accepted_offset,
+    })
+}
+
+fn with_stream<T>(id: &str, action: impl FnOnce(&mut StreamSession) -> SpResult<T>) -> SpResult<T> {
+    let mut streams = STREAMS.lock().map_err(|_| stream_lock_error())?;
+    action(streams.get_mut(id).ok_or_else(upload_not_found)?)
 }
 
 pub(super) fn pause_upload(id: &str) -> SpResult<()> {
@@ -181,25 +345,30 @@ pub(super) fn pause_upload(id: &str) -> SpResult<()> {
     Ok(())
 }
 
-pub(super) fn resume_upload(id: &str) -> SpResult<TransferPhase> {
+/// Clears the pause flag. Returns the phase to run and whether no worker is
+/// left to pick the upload up, as after a restart.
+pub(super) fn resume_upload(id: &str) -> SpResult<(TransferPhase, bool)> {
     let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
     let transfer = uploads.get(id).ok_or_else(upload_not_found)?;
     transfer
         .paused
         .store(false, std::sync::atomic::Ordering::Relaxed);
-    transfer
+    let phase = transfer
         .phase
-        .ok_or_else(|| err_invalid("paused upload missing phase"))
+        .ok_or_else(|| err_invalid("paused upload missing phase"))?;
+    let needs_worker =
+        matches!(transfer.lifecycle_state, TransferLifecycle::Paused) && !transfer.worker_active;
+    Ok((phase, needs_worker))
 }
 
-pub(super) fn cancel_upload(id: &str) -> SpResult<()> {
+/// Sets the cancel flag. Returns whether a worker will observe it.
+pub(super) fn cancel_upload(id: &str) -> SpResult<bool> {
     let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
-    uploads
-        .get(id)
-        .ok_or_else(upload_not_found)?
+    let transfer = uploads.get(id).ok_or_else(upload_not_found)?;
+    transfer
         .cancelled
         .store(true, std::sync::atomic::Ordering::Relaxed);
-    Ok(())
+    Ok(transfer.worker_active)
 }
 
 pub(super) fn upload_status(id: &str) -> SpResult<UploadStatus> {
@@ -219,6 +388,49 @@ pub(super) fn upload_status(id: &str) -> SpResult<UploadStatus> {
     })
 }
 
+pub(super) fn join_upload_group(group_id: &str, id: &str) -> SpResult<()> {
+    GROUPS
+        .lock()
+        .map_err(|_| upload_lock_error())?
+        .entry(group_id.to_string())
+        .or_default()
+        .push(id.to_string());
+    Ok(())
+}
+
+pub(super) fn upload_group_status(group_id: &str) -> SpResult<UploadGroupStatus> {
+    let members = GROUPS
+        .lock()
+        .map_err(|_| upload_lock_error())?
+        .get(group_id)
+        .cloned()
+        .ok_or_else(|| err_invalid("upload group not found"))?;
+    let uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+    let mut status = UploadGroupStatus {
+        group_id: group_id.to_string(),
+        files_total: 0,
+        files_completed: 0,
+        files_failed: 0,
+        files_cancelled: 0,
+        files_skipped: 0,
+        bytes_total: 0,
+        bytes_done: 0,
+    };
+    for transfer in members.iter().filter_map(|id| uploads.get(id)) {
+        status.files_total += 1;
+        status.bytes_total += transfer.bytes_total;
+        status.bytes_done += transfer.bytes_done.min(transfer.bytes_total);
+        match transfer.lifecycle_state {
+            TransferLifecycle::Completed => status.files_completed += 1,
+            TransferLifecycle::Failed => status.files_failed += 1,
+            TransferLifecycle::Cancelled => status.files_cancelled += 1,
+            TransferLifecycle::Skipped => status.files_skipped += 1,
+            _ => {}
+        }
+    }
+    Ok(status)
+}
+
 pub(super) fn list_active_snapshots() -> Vec<TransferSnapshot> {
     let uploads = match UPLOADS.lock() {
         Ok(guard) => guard,
@@ -233,14 +445,17 @@ pub(super) fn list_active_snapshots() -> Vec<TransferSnapshot> {
 }
 
 pub(super) fn remove_upload(id: &str) -> SpResult<()> {
-    let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
-    if let Some(transfer) = uploads.get(id) {
-        if !transfer.lifecycle_state.is_terminal() {
-            return Err(err_invalid("cannot remove active upload"));
+    {
+        let mut uploads = UPLOADS.lock().map_err(|_| upload_lock_error())?;
+        if let Some(transfer) = uploads.get(id) {
+            if !transfer.lifecycle_state.is_terminal() {
+                return Err(err_invalid("cannot remove active upload"));
+            }
         }
+        uploads.remove(id);
     }
-    uploads.remove(id);
-    Ok(())
+    transfer_db::delete_upload_session(id)?;
+    transfer_db::delete_snapshot(id)
 }
 
 fn upload_lock_error() -> SpError {
@@ -263,6 +478,35 @@ fn stream_lock_error() -> SpError {
     }
 }
 
+fn stream_gap_error(offset: u64, accepted: u64) -> SpError {
+    err_invalid(&format!(
+        "stream chunk at offset {offset} leaves a gap; continue from {accepted}"
+    ))
+}
+
+fn stream_send_error(
+    operation: &str,
+    error: mpsc::error::TrySendError<Option<Vec<u8>>>,
+) -> SpError {
+    match error {
+        mpsc::error::TrySendError::Full(_) => SpError {
+            kind: ErrorKind::RetryableNet,
+            message: format!("{operation}: {error}"),
+            retry_after_ms: Some(100),
+            context: None,
+            at: now_ms(),
+        },
+        // The engine stopped, and the upload reports why.
+        mpsc::error::TrySendError::Closed(_) => SpError {
+            kind: ErrorKind::NotRetriable,
+            message: format!("{operation}: the upload is no longer receiving"),
+            retry_after_ms: None,
+            context: None,
+            at: now_ms(),
+        },
+    }
+}
+
 fn upload_not_found() -> SpError {
     SpError {
         kind: ErrorKind::NotRetriable,
@@ -1,20 +1,42 @@
 //! Tauri-independent push-stream upload execution.
 //!
-//! This module owns consuming caller-provided byte chunks, writing them through
-//! OpenDAL, and honoring pause/cancel controls. It does not own the global
-//! channel registry, construct credentials, read application settings, emit
-//! Tauri events, or claim process-restart recovery for an ephemeral stream.
+//! This module owns consuming caller-provided byte chunks, sealing them when
+//! the profile encrypts or compressing them when asked, writing them through
+//! OpenDAL while hashing what is stored, honoring pause/cancel controls, and
+//! giving up on a sender that stays quiet too long. It does not own the
+//! global channel registry or the offsets chunks are placed by, construct
+//! credentials, read application settings, emit Tauri events, or claim
+//! process-restart recovery for an ephemeral stream.
 
-use super::{cancelled_error, now_ms, open_upload_writer, UploadControl};
+use super::conflict::WriteCondition;
+use super::{
+    cancelled_error, close_upload_writer, inferred_content_type, now_ms, open_upload_writer,
+    stored_encoding, stream_sealer, ContentHashes, StoredEncoding, UploadControl, UploadedContent,
+};
+use crate::compression::Compression;
+use crate::sp_backend::ProfileEncryption;
 use crate::types::{ErrorKind, SpError, SpResult};
-use opendal::Operator;
+use opendal::{Operator, Writer};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 pub(super) struct StreamUploadRequest {
     pub(super) key: String,
-    pub(super) expected_bytes: u64,
+    /// Length the stream must have; `None` accepts whatever arrives before
+    /// the explicit finish.
+    pub(super) expected_bytes: Option<u64>,
+    /// How long the sender may go quiet, outside a pause, before the upload
+    /// fails; `None` waits for as long as the sender is connected.
+    pub(super) stall_timeout: Option<Duration>,
+    /// Inferred from the key and the first chunk when `None`.
     pub(super) content_type: Option<String>,
     pub(super) content_disposition: Option<String>,
+    pub(super) encryption: Option<ProfileEncryption>,
+    /// Ignored when the content is sealed.
+    pub(super) compression: Option<Compression>,
+    /// Precondition the object is written under.
+    pub(super) condition: WriteCondition,
 }
 
 pub(super) trait StreamUploadObserver {
@@ -32,28 +54,43 @@ pub(super) async fn upload_stream(
     mut receiver: mpsc::Receiver<Option<Vec<u8>>>,
     control: UploadControl,
     observer: &mut impl StreamUploadObserver,
-) -> SpResult<()> {
-    let mut writer = open_upload_writer(
-        operator,
-        &request.key,
-        request.content_type.as_deref(),
-        request.content_disposition.as_deref(),
-    )
-    .await
-    .map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("open writer: {error}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: now_ms(),
-    })?;
+) -> SpResult<UploadedContent> {
+    // The content is unknown until the stream ends, after the object's
+    // headers were sent; the caller records the hash afterwards.
+    let mut sealer = stream_sealer(request.encryption.as_ref()).await?;
+    let (mut compressor, mut encoding) = stored_encoding(
+        sealer.is_some(),
+        request.compression,
+        request.expected_bytes.unwrap_or_default(),
+    )?;
+    // Without a declared length the size before compression is not known
+    // yet; the caller's metadata rewrite records it with the encoding.
+    let opening_encoding = match request.expected_bytes {
+        Some(_) => encoding,
+        None => StoredEncoding {
+            compressed: None,
+            ..encoding
+        },
+    };
     observer.uploading()?;
 
+    // Opened with the first chunk, which the content type is sniffed from.
+    let mut writer: Option<(Writer, String)> = None;
     let mut was_paused = false;
     let mut part_number = 1;
     let mut bytes_received = 0u64;
     let mut explicitly_finished = false;
-    while let Some(message) = receiver.recv().await {
+    let mut hasher = Sha256::new();
+    let mut stored_size = 0u64;
+    loop {
+        let message = match next_message(&mut receiver, request.stall_timeout, &control).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(error) => {
+                abort(&mut writer).await;
+                return Err(error);
+            }
+        };
         if control.cancelled.load(std::sync::atomic::Ordering::Relaxed) {
             break;
         }
@@ -82,22 +119,56 @@ pub(super) async fn upload_stream(
                     continue;
                 }
                 let next_total = match bytes_received.checked_add(len) {
-                    Some(total) if total <= request.expected_bytes => total,
+                    Some(total)
+                        if request
+                            .expected_bytes
+                            .map_or(true, |expected| total <= expected) =>
+                    {
+                        total
+                    }
                     _ => {
-                        let _ = writer.abort().await;
+                        abort(&mut writer).await;
                         return Err(stream_protocol_error(format!(
                             "stream exceeds declared length of {} bytes",
-                            request.expected_bytes
+                            request.expected_bytes.unwrap_or(u64::MAX)
                         )));
                     }
                 };
-                writer.write(bytes).await.map_err(|error| SpError {
-                    kind: ErrorKind::RetryableNet,
-                    message: format!("writer write: {error}"),
-                    retry_after_ms: Some(300),
-                    context: None,
-                    at: now_ms(),
-                })?;
+                if writer.is_none() {
+                    writer = Some(
+                        open_stream_writer(operator, &request, opening_encoding, &bytes).await?,
+                    );
+                }
+                let Some((writer, _)) = writer.as_mut() else {
+                    continue;
+                };
+                let body = match (sealer.as_mut(), compressor.as_mut()) {
+                    (Some(sealer), _) => sealer.push(&bytes),
+                    (None, Some(compressor)) => match compressor.push(&bytes) {
+                        Ok(compressed) => compressed,
+                        Err(error) => {
+                            let _ = writer.abort().await;
+                            return Err(error);
+                        }
+                    },
+                    (None, None) => bytes,
+                };
+                if !body.is_empty() {
+                    hasher.update(&body);
+                    stored_size += body.len() as u64;
+                    if let Err(error) = writer.write(body).await {
+                        // A retry wait cut short by cancellation fails the write.
+                        if control.cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+                            break;
+                        }
+                        let _ = writer.abort().await;
+                        return Err(crate::storage::opendal_error(
+                            "writer write",
+                            &request.key,
+                            &error,
+                        ));
+                    }
+                }
                 bytes_received = next_total;
                 observer.part_done(part_number, len)?;
                 part_number += 1;
@@ -110,34 +181,129 @@ pub(super) async fn upload_stream(
     }
 
     if control.cancelled.load(std::sync::atomic::Ordering::Relaxed) {
-        let _ = writer.abort().await;
+        abort(&mut writer).await;
         observer.cancelled()?;
         return Err(cancelled_error());
     }
     if !explicitly_finished {
-        let _ = writer.abort().await;
+        abort(&mut writer).await;
         return Err(stream_protocol_error(
             "stream sender disconnected before explicit finish",
         ));
     }
-    if bytes_received != request.expected_bytes {
-        let _ = writer.abort().await;
+    if let Some(expected) = request
+        .expected_bytes
+        .filter(|&expected| expected != bytes_received)
+    {
+        abort(&mut writer).await;
         return Err(stream_protocol_error(format!(
-            "stream ended after {bytes_received} bytes; expected {}",
-            request.expected_bytes
+            "stream ended after {bytes_received} bytes; expected {expected}"
         )));
     }
+    if let Some((_, source_size)) = encoding.compressed.as_mut() {
+        *source_size = bytes_received;
+    }
 
     observer.finalizing()?;
-    writer.close().await.map(|_| ()).map_err(|error| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("writer close: {error}"),
-        retry_after_ms: Some(300),
-        context: None,
-        at: now_ms(),
+    let (mut writer, content_type) = match writer {
+        Some(opened) => opened,
+        None => open_stream_writer(operator, &request, opening_encoding, &[]).await?,
+    };
+    let tail = match (sealer.as_mut(), compressor.as_mut()) {
+        (Some(sealer), _) => Some(sealer.finish()),
+        (None, Some(compressor)) => match compressor.finish() {
+            Ok(tail) => Some(tail),
+            Err(error) => {
+                let _ = writer.abort().await;
+                return Err(error);
+            }
+        },
+        (None, None) => None,
+    };
+    if let Some(tail) = tail.filter(|tail| !tail.is_empty()) {
+        hasher.update(&tail);
+        stored_size += tail.len() as u64;
+        if let Err(error) = writer.write(tail).await {
+            let _ = writer.abort().await;
+            return Err(crate::storage::opendal_error(
+                "writer write",
+                &request.key,
+                &error,
+            ));
+        }
+    }
+    let stored = close_upload_writer(&request.key, &request.condition, &mut writer).await?;
+    Ok(UploadedContent {
+        sha256: format!("{:x}", hasher.finalize()),
+        source_sha256: None,
+        sha256_stored: false,
+        etag: stored.etag().map(str::to_string),
+        stored_size,
+        encoding,
+        content_type,
     })
 }
 
+/// The next message from the sender; `None` once it has disconnected or the
+/// upload is cancelled while waiting.
+async fn next_message(
+    receiver: &mut mpsc::Receiver<Option<Vec<u8>>>,
+    stall_timeout: Option<Duration>,
+    control: &UploadControl,
+) -> SpResult<Option<Option<Vec<u8>>>> {
+    let Some(stall_timeout) = stall_timeout else {
+        return Ok(receiver.recv().await);
+    };
+    let mut quiet_since = tokio::time::Instant::now();
+    loop {
+        let wait = Duration::from_millis(200).min(stall_timeout);
+        if let Ok(message) = tokio::time::timeout(wait, receiver.recv()).await {
+            return Ok(message);
+        }
+        if control.cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+            return Ok(None);
+        }
+        // A paused upload expects nothing from the sender.
+        if control.paused.load(std::sync::atomic::Ordering::Relaxed) {
+            quiet_since = tokio::time::Instant::now();
+        } else if quiet_since.elapsed() >= stall_timeout {
+            return Err(stream_protocol_error(format!(
+                "stream source sent nothing for {} ms and did not reconnect",
+                stall_timeout.as_millis()
+            )));
+        }
+    }
+}
+
+/// Opens the object's writer, with the content type `head`, the start of
+/// the stream, shows unless the request names one.
+async fn open_stream_writer(
+    operator: &Operator,
+    request: &StreamUploadRequest,
+    encoding: StoredEncoding,
+    head: &[u8],
+) -> SpResult<(Writer, String)> {
+    let content_type = inferred_content_type(&request.key, request.content_type.as_deref(), head);
+    let writer = open_upload_writer(
+        operator,
+        &request.key,
+        Some(&content_type),
+        request.content_disposition.as_deref(),
+        ContentHashes::default(),
+        encoding,
+        &request.condition,
+    )
+    .await
+    .map_err(|error| crate::storage::opendal_error("open writer", &request.key, &error))?;
+    Ok((writer, content_type))
+}
+
+async fn abort(writer: &mut Option<(Writer, String)>) {
+    if let Some((writer, _)) = writer.as_mut() {
+        let _ = writer.abort().await;
+    }
+}
+
 fn stream_protocol_error(message: impl Into<String>) -> SpError {
     SpError {
         kind: ErrorKind::NotRetriable,
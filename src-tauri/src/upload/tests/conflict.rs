@@ -0,0 +1,331 @@
+use super::super::*;
+use super::fixtures::{controls, stand_in_config, QuietObserver};
+use crate::test_support::{patterned_bytes, stand_in_operator, start_path_style_s3};
+use opendal::services::Memory;
+
+const PART_SIZE: usize = 1024;
+
+fn resumable_request(
+    key: &str,
+    source: &tempfile::NamedTempFile,
+    condition: WriteCondition,
+) -> ResumableUploadRequest {
+    ResumableUploadRequest {
+        key: key.into(),
+        source_path: source.path().to_path_buf(),
+        part_size: PART_SIZE as u64,
+        parts_in_flight: 2,
+        content_type: None,
+        content_disposition: None,
+        encryption: None,
+        condition,
+    }
+}
+
+fn local_file(contents: &[u8]) -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().expect("temporary file should exist");
+    std::fs::write(file.path(), contents).expect("source should be written");
+    file
+}
+
+#[test]
+fn renamed_keys_number_the_last_segment_before_its_extension() {
+    assert_eq!(renamed_key("photos/a.jpg", 1), "photos/a (1).jpg");
+    assert_eq!(renamed_key("backup.tar.gz", 2), "backup.tar (2).gz");
+    assert_eq!(
+        renamed_key("albums.2024/README", 3),
+        "albums.2024/README (3)"
+    );
+    assert_eq!(renamed_key("config/.env", 1), "config/.env (1)");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn policies_resolve_against_the_stored_object() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    for key in ["a.jpg", "a (1).jpg"] {
+        operator
+            .write(key, b"stored".to_vec())
+            .await
+            .expect("object should be stored");
+    }
+    let etag = operator
+        .stat("a.jpg")
+        .await
+        .expect("object should exist")
+        .etag()
+        .expect("stand-in should send an ETag")
+        .to_string();
+    let write = |key: &str, condition| Destination::Write {
+        key: key.into(),
+        condition,
+    };
+
+    let resolved = |key: &'static str, policy: ConflictPolicy| {
+        let operator = operator.clone();
+        async move { resolve_destination(&operator, key, &policy).await }
+    };
+    assert_eq!(
+        resolved("a.jpg", ConflictPolicy::Overwrite).await.unwrap(),
+        write("a.jpg", WriteCondition::None)
+    );
+    assert_eq!(
+        resolved("a.jpg", ConflictPolicy::Skip).await.unwrap(),
+        Destination::Skip
+    );
+    assert_eq!(
+        resolved("b.jpg", ConflictPolicy::Skip).await.unwrap(),
+        write("b.jpg", WriteCondition::Absent)
+    );
+    assert_eq!(
+        resolved("b.jpg", ConflictPolicy::Fail).await.unwrap(),
+        write("b.jpg", WriteCondition::Absent)
+    );
+    assert_eq!(
+        resolved("a.jpg", ConflictPolicy::Rename).await.unwrap(),
+        write("a (2).jpg", WriteCondition::Absent)
+    );
+    assert_eq!(
+        resolved(
+            "a.jpg",
+            ConflictPolicy::IfMatch {
+                etag: etag.trim_matches('"').into()
+            }
+        )
+        .await
+        .unwrap(),
+        write(
+            "a.jpg",
+            WriteCondition::Matches(etag.trim_matches('"').into())
+        )
+    );
+
+    for (key, policy) in [
+        ("a.jpg", ConflictPolicy::Fail),
+        (
+            "a.jpg",
+            ConflictPolicy::IfMatch {
+                etag: "\"0123\"".into(),
+            },
+        ),
+        ("b.jpg", ConflictPolicy::IfMatch { etag: etag.clone() }),
+    ] {
+        let error = resolved(key, policy.clone())
+            .await
+            .expect_err("the policy must refuse the stored state");
+        assert!(
+            matches!(error.kind, ErrorKind::Conflict),
+            "{policy:?}: {error:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn services_without_conditional_writes_get_no_precondition() {
+    let operator = opendal::Operator::new(Memory::default())
+        .expect("memory operator should build")
+        .finish();
+    assert_eq!(
+        resolve_destination(&operator, "new.bin", &ConflictPolicy::Fail)
+            .await
+            .expect("a free key should resolve"),
+        Destination::Write {
+            key: "new.bin".into(),
+            condition: WriteCondition::None,
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_bucket_refuses_a_single_write_to_a_key_taken_meanwhile() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    operator
+        .write("taken.jpg", b"other device".to_vec())
+        .await
+        .expect("object should be stored");
+
+    let mut writer = open_upload_writer(
+        &operator,
+        "taken.jpg",
+        None,
+        None,
+        ContentHashes::default(),
+        StoredEncoding::default(),
+        &WriteCondition::Absent,
+    )
+    .await
+    .expect("writer should open");
+    writer
+        .write(b"this device".to_vec())
+        .await
+        .expect("body should buffer");
+    let error = crate::storage::opendal_error(
+        "writer close",
+        "taken.jpg",
+        &writer.close().await.expect_err("the bucket must refuse"),
+    );
+    assert!(WriteCondition::Absent.refused(&error), "{error:?}");
+    assert!(!WriteCondition::None.refused(&error));
+    assert_eq!(
+        operator.read("taken.jpg").await.unwrap().to_vec(),
+        b"other device"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multipart_completion_carries_the_precondition() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let cfg = stand_in_config(server.url());
+    let contents = patterned_bytes(3 * PART_SIZE + 5, 11);
+    let source = local_file(&contents);
+
+    // Another device stores the key after this upload settled on it.
+    operator
+        .write("shared.bin", b"other device".to_vec())
+        .await
+        .expect("object should be stored");
+    let error = upload_file_resumable(
+        &cfg,
+        resumable_request("shared.bin", &source, WriteCondition::Absent),
+        None,
+        controls(),
+        &mut QuietObserver,
+    )
+    .await
+    .expect_err("completion must be refused");
+    assert!(WriteCondition::Absent.refused(&error), "{error:?}");
+    assert_eq!(
+        operator.read("shared.bin").await.unwrap().to_vec(),
+        b"other device"
+    );
+    let requests = server.requests();
+    let completion = requests
+        .iter()
+        .find(|request| request.method == "POST" && request.query().starts_with("uploadId"))
+        .expect("completion should be sent");
+    assert_eq!(completion.header("if-none-match"), Some("*"));
+    assert!(
+        requests.iter().any(|request| request.method == "DELETE"),
+        "the refused session should be aborted"
+    );
+
+    // An ETag that still matches lets the upload replace the object.
+    let etag = operator
+        .stat("shared.bin")
+        .await
+        .expect("object should exist")
+        .etag()
+        .expect("stand-in should send an ETag")
+        .to_string();
+    upload_file_resumable(
+        &cfg,
+        resumable_request("shared.bin", &source, WriteCondition::Matches(etag.clone())),
+        None,
+        controls(),
+        &mut QuietObserver,
+    )
+    .await
+    .expect("a matching ETag should complete");
+    assert_eq!(
+        operator.read("shared.bin").await.unwrap().to_vec(),
+        contents
+    );
+
+    let error = upload_file_resumable(
+        &cfg,
+        resumable_request("shared.bin", &source, WriteCondition::Matches(etag)),
+        None,
+        controls(),
+        &mut QuietObserver,
+    )
+    .await
+    .expect_err("a stale ETag must be refused");
+    assert!(
+        WriteCondition::Matches(String::new()).refused(&error),
+        "{error:?}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sequential_writes_are_refused_by_the_bucket() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let source = local_file(&patterned_bytes(3 * PART_SIZE, 5));
+    operator
+        .write("late.bin", b"other device".to_vec())
+        .await
+        .expect("object should be stored");
+
+    let error = upload_file(
+        &operator,
+        UploadEngineRequest {
+            key: "late.bin".into(),
+            source_path: source.path().to_path_buf(),
+            part_size: PART_SIZE as u64,
+            content_type: None,
+            content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::Absent,
+        },
+        controls(),
+        &mut QuietObserver,
+    )
+    .await
+    .expect_err("the write must be refused");
+    assert!(WriteCondition::Absent.refused(&error), "{error:?}");
+    assert!(
+        !server
+            .requests()
+            .iter()
+            .any(|request| request.method == "HEAD"),
+        "the bucket, not a stat, should enforce the precondition"
+    );
+    assert_eq!(
+        operator.read("late.bin").await.unwrap().to_vec(),
+        b"other device"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn writer_completions_carry_the_precondition() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    operator
+        .write("late.bin", b"other device".to_vec())
+        .await
+        .expect("object should be stored");
+
+    // OpenDAL raises smaller chunks to the 5 MiB S3 requires of a part.
+    const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+    let mut writer = operator
+        .writer_with("late.bin")
+        .chunk(MIN_PART_SIZE)
+        .if_not_exists(true)
+        .await
+        .expect("writer should open");
+    writer
+        .write(patterned_bytes(2 * MIN_PART_SIZE + 1, 9))
+        .await
+        .expect("parts should upload");
+    let error = close_upload_writer("late.bin", &WriteCondition::Absent, &mut writer)
+        .await
+        .expect_err("completion must be refused");
+    assert!(WriteCondition::Absent.refused(&error), "{error:?}");
+    let requests = server.requests();
+    let completion = requests
+        .iter()
+        .find(|request| request.method == "POST" && request.query().starts_with("uploadId"))
+        .expect("completion should be sent");
+    assert_eq!(completion.header("if-none-match"), Some("*"));
+    assert!(
+        requests.iter().any(|request| request.method == "DELETE"),
+        "the refused session should be aborted"
+    );
+    assert_eq!(
+        operator.read("late.bin").await.unwrap().to_vec(),
+        b"other device"
+    );
+}
@@ -0,0 +1,294 @@
+use super::super::*;
+use super::fixtures::{controls, QuietObserver};
+
+#[test]
+fn explicit_content_type_takes_precedence_after_trimming() {
+    assert_eq!(
+        inferred_content_type(
+            "camera/DSC00001.ARW",
+            Some("  image/custom-raw  "),
+            b"\xFF\xD8\xFF"
+        ),
+        "image/custom-raw"
+    );
+}
+
+#[test]
+fn empty_explicit_content_type_falls_back_to_case_insensitive_extension() {
+    assert_eq!(
+        inferred_content_type("camera/DSC00001.ARW", Some("  "), &[]),
+        "image/x-sony-arw"
+    );
+    assert_eq!(
+        inferred_content_type("camera/preview.JpEg", None, &[]),
+        "image/jpeg"
+    );
+}
+
+#[test]
+fn known_raw_camera_extensions_have_stable_content_types() {
+    let cases = [
+        ("photo.arw", "image/x-sony-arw"),
+        ("photo.cr2", "image/x-canon-cr2"),
+        ("photo.cr3", "image/x-canon-cr3"),
+        ("photo.dng", "image/x-adobe-dng"),
+        ("photo.nef", "image/x-nikon-nef"),
+        ("photo.orf", "image/x-olympus-orf"),
+        ("photo.raf", "image/x-fuji-raf"),
+        ("photo.rw2", "image/x-panasonic-rw2"),
+    ];
+
+    for (key, expected) in cases {
+        assert_eq!(
+            inferred_content_type(key, None, &[]),
+            expected,
+            "wrong content type for {key}"
+        );
+    }
+}
+
+#[test]
+fn unknown_or_missing_extension_uses_binary_content_type() {
+    for key in ["archive.unknown", "README", ".hidden"] {
+        assert_eq!(
+            inferred_content_type(key, None, &[]),
+            "application/octet-stream",
+            "wrong fallback content type for {key}"
+        );
+    }
+}
+
+fn zip_entry(name: &str, content: &[u8]) -> Vec<u8> {
+    let mut entry = b"PK\x03\x04".to_vec();
+    entry.extend([0; 22]);
+    entry.extend((name.len() as u16).to_le_bytes());
+    entry.extend(0u16.to_le_bytes());
+    entry.extend(name.as_bytes());
+    entry.extend(content);
+    entry
+}
+
+#[test]
+fn leading_bytes_override_missing_or_wrong_extensions() {
+    let mut mp4 = b"\0\0\0\x20ftypisom".to_vec();
+    mp4.extend([0; 20]);
+    let mut pe = b"MZ".to_vec();
+    pe.resize(0x3C, 0);
+    pe.extend(0x40u32.to_le_bytes());
+    pe.extend(b"PE\0\0");
+    let cases: [(&str, Vec<u8>, &str); 14] = [
+        (
+            "photo.jpg",
+            b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR".to_vec(),
+            "image/png",
+        ),
+        (
+            "IMG_0001",
+            b"\xFF\xD8\xFF\xE1\0\x18Exif".to_vec(),
+            "image/jpeg",
+        ),
+        (
+            "scan",
+            b"%PDF-1.7\n%\xE2\xE3\xCF\xD3".to_vec(),
+            "application/pdf",
+        ),
+        ("clip", mp4, "video/mp4"),
+        (
+            "photo",
+            b"\0\0\0\x18ftypheic\0\0\0\0".to_vec(),
+            "image/heic",
+        ),
+        (
+            "recording",
+            b"\x1A\x45\xDF\xA3\x9F\x42\x86\x81\x01\x42\x82\x84webm".to_vec(),
+            "video/webm",
+        ),
+        ("voice", b"RIFF\x24\0\0\0WAVEfmt ".to_vec(), "audio/wav"),
+        ("song.txt", b"ID3\x04\0\0\0\0\0\0".to_vec(), "audio/mpeg"),
+        ("track", b"fLaC\0\0\0\x22".to_vec(), "audio/flac"),
+        (
+            "backup",
+            b"\x1F\x8B\x08\0\0\0\0\0".to_vec(),
+            "application/gzip",
+        ),
+        (
+            "book",
+            zip_entry("mimetype", b"application/epub+zip"),
+            "application/epub+zip",
+        ),
+        (
+            "report",
+            [
+                zip_entry("[Content_Types].xml", b"<Types/>"),
+                zip_entry("word/document.xml", b"<w:document/>"),
+            ]
+            .concat(),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        ),
+        ("setup", pe, "application/vnd.microsoft.portable-executable"),
+        (
+            "data.bin",
+            b"\0\x01\x02\x03\xFE".to_vec(),
+            "application/octet-stream",
+        ),
+    ];
+    for (key, head, expected) in cases {
+        assert_eq!(
+            inferred_content_type(key, None, &head),
+            expected,
+            "wrong content type for {key}"
+        );
+    }
+}
+
+#[test]
+fn extensions_name_what_generic_containers_hold() {
+    let tiff = b"II*\0\x08\0\0\0".as_slice();
+    let ole = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1\0\0".as_slice();
+    let zip = zip_entry("classes.dex", b"dex\n035\0");
+    let mp4 = b"\0\0\0\x1CftypM4A \0\0\0\0".as_slice();
+    let cases: [(&str, &[u8], &str); 7] = [
+        ("DSC_0001.NEF", tiff, "image/x-nikon-nef"),
+        ("photo.jpg", tiff, "image/tiff"),
+        ("budget.xls", ole, "application/vnd.ms-excel"),
+        ("budget", ole, "application/x-ole-storage"),
+        ("game.apk", &zip, "application/vnd.android.package-archive"),
+        ("game.exe", &zip, "application/zip"),
+        ("podcast.mp4", mp4, "audio/mp4"),
+    ];
+    for (key, head, expected) in cases {
+        assert_eq!(
+            inferred_content_type(key, None, head),
+            expected,
+            "wrong content type for {key}"
+        );
+    }
+}
+
+#[test]
+fn utf8_text_is_labelled_with_its_charset() {
+    let cases: [(&str, &[u8], &str); 9] = [
+        (
+            "README",
+            b"SwiftPan\n========\n",
+            "text/plain; charset=utf-8",
+        ),
+        (
+            "notes.md",
+            "# Café\n".as_bytes(),
+            "text/markdown; charset=utf-8",
+        ),
+        (
+            "settings.json",
+            b"{\"theme\": \"dark\"}\n",
+            "application/json",
+        ),
+        (
+            "index",
+            b"<!DOCTYPE html>\n<html>",
+            "text/html; charset=utf-8",
+        ),
+        (
+            "logo",
+            b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\">",
+            "image/svg+xml",
+        ),
+        // TypeScript, not an MPEG transport stream.
+        (
+            "main.ts",
+            b"export const answer = 42;\n",
+            "text/plain; charset=utf-8",
+        ),
+        // Too short to tell text from the start of a RAW file.
+        ("DSC.ARW", b"[", "image/x-sony-arw"),
+        // The head ends inside a character.
+        (
+            "greeting",
+            &"こんにちは".as_bytes()[..7],
+            "text/plain; charset=utf-8",
+        ),
+        (
+            "legacy.txt",
+            b"\xFF\xFEh\0i\0",
+            "text/plain; charset=utf-16",
+        ),
+    ];
+    for (key, head, expected) in cases {
+        assert_eq!(
+            inferred_content_type(key, None, head),
+            expected,
+            "wrong content type for {key}"
+        );
+    }
+    assert_eq!(
+        inferred_content_type("latin1", None, b"caf\xE9 au lait"),
+        "application/octet-stream"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn engines_store_the_type_the_plaintext_shows() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    let png = [
+        b"\x89PNG\r\n\x1A\n".as_slice(),
+        &crate::test_support::patterned_bytes(4096, 3),
+    ]
+    .concat();
+    let source = tempfile::NamedTempFile::new().expect("temporary file should exist");
+    std::fs::write(source.path(), &png).expect("source should be written");
+
+    let content = upload_file(
+        &operator,
+        UploadEngineRequest {
+            key: "shared/photo".into(),
+            source_path: source.path().to_path_buf(),
+            part_size: 1024,
+            content_type: None,
+            content_disposition: None,
+            encryption: Some(crate::test_support::cheap_encryption("correct horse")),
+            compression: None,
+            condition: WriteCondition::None,
+        },
+        controls(),
+        &mut QuietObserver,
+    )
+    .await
+    .expect("file upload should complete");
+    assert_eq!(content.content_type, "image/png");
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(4);
+    for chunk in [b"%PDF-1.4\n".to_vec(), vec![b'x'; 100]] {
+        sender.send(Some(chunk)).await.expect("chunk should queue");
+    }
+    sender.send(None).await.expect("finish should queue");
+    let streamed = upload_stream(
+        &operator,
+        StreamUploadRequest {
+            key: "shared/scan".into(),
+            expected_bytes: None,
+            stall_timeout: None,
+            content_type: None,
+            content_disposition: None,
+            encryption: None,
+            compression: Some(crate::compression::Compression::Gzip),
+            condition: WriteCondition::None,
+        },
+        receiver,
+        controls(),
+        &mut QuietObserver,
+    )
+    .await
+    .expect("stream upload should complete");
+    assert_eq!(streamed.content_type, "application/pdf");
+
+    for (key, expected) in [
+        ("shared/photo", "image/png"),
+        ("shared/scan", "application/pdf"),
+    ] {
+        let metadata = crate::objects::stat_object(&operator, key)
+            .await
+            .expect("object should exist");
+        assert_eq!(metadata.headers.content_type.as_deref(), Some(expected));
+    }
+}
@@ -0,0 +1,188 @@
+use super::super::*;
+use std::path::Path;
+
+fn write(root: &Path, relative: &str, len: usize) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().expect("fixture has a parent"))
+        .expect("fixture directory should be created");
+    std::fs::write(path, vec![b'x'; len]).expect("fixture should be written");
+}
+
+fn params(root: &Path, target_prefix: &str) -> DirectoryUploadParams {
+    DirectoryUploadParams {
+        source_dir: root.to_string_lossy().into_owned(),
+        target_prefix: target_prefix.into(),
+        include: Vec::new(),
+        exclude: Vec::new(),
+        include_hidden: false,
+        follow_symlinks: false,
+        skip_unchanged: false,
+        compression: None,
+        conflict: ConflictPolicy::Overwrite,
+        part_size: 8 * 1024 * 1024,
+    }
+}
+
+fn keys(planned: &[PlannedFile]) -> Vec<&str> {
+    planned.iter().map(|file| file.key.as_str()).collect()
+}
+
+#[test]
+fn folder_files_map_to_keys_below_the_target_prefix() {
+    let root = tempfile::tempdir().expect("temporary directory should exist");
+    write(root.path(), "README.md", 3);
+    write(root.path(), "src/main.rs", 5);
+    write(root.path(), "src/util/strings.rs", 7);
+    std::fs::create_dir_all(root.path().join("empty")).expect("empty directory should exist");
+
+    let planned = plan_directory_upload(&params(root.path(), "/projects/demo/"))
+        .expect("folder should be planned");
+
+    assert_eq!(
+        keys(&planned),
+        [
+            "projects/demo/README.md",
+            "projects/demo/src/main.rs",
+            "projects/demo/src/util/strings.rs",
+        ]
+    );
+    assert_eq!(planned[2].size, 7);
+    assert_eq!(
+        planned[2].source_path,
+        root.path().join("src/util/strings.rs")
+    );
+    let at_root = plan_directory_upload(&params(root.path(), "")).expect("folder should plan");
+    assert_eq!(at_root[0].key, "README.md");
+}
+
+#[test]
+fn include_and_exclude_globs_select_files_and_prune_directories() {
+    let root = tempfile::tempdir().expect("temporary directory should exist");
+    write(root.path(), "photos/a.jpg", 1);
+    write(root.path(), "photos/raw/a.ARW", 1);
+    write(root.path(), "photos/raw/b.jpg", 1);
+    write(root.path(), "node_modules/pkg/index.jpg", 1);
+    write(root.path(), "notes.txt", 1);
+    write(root.path(), "cover.jpg", 1);
+
+    let planned = plan_directory_upload(&DirectoryUploadParams {
+        include: vec!["*.jpg".into(), "photos/raw/*.ARW".into()],
+        exclude: vec!["node_modules".into(), "/cover.jpg".into()],
+        ..params(root.path(), "backup")
+    })
+    .expect("folder should be planned");
+
+    assert_eq!(
+        keys(&planned),
+        [
+            "backup/photos/a.jpg",
+            "backup/photos/raw/a.ARW",
+            "backup/photos/raw/b.jpg",
+        ]
+    );
+
+    let top_level_only = plan_directory_upload(&DirectoryUploadParams {
+        exclude: vec!["photos/*".into()],
+        ..params(root.path(), "backup")
+    })
+    .expect("folder should be planned");
+    assert_eq!(
+        keys(&top_level_only),
+        [
+            "backup/cover.jpg",
+            "backup/node_modules/pkg/index.jpg",
+            "backup/notes.txt",
+        ]
+    );
+
+    let recursive = plan_directory_upload(&DirectoryUploadParams {
+        include: vec!["photos/**/*.jpg".into()],
+        ..params(root.path(), "")
+    })
+    .expect("folder should be planned");
+    assert_eq!(keys(&recursive), ["photos/a.jpg", "photos/raw/b.jpg"]);
+}
+
+#[test]
+fn hidden_files_and_directories_are_skipped_unless_requested() {
+    let root = tempfile::tempdir().expect("temporary directory should exist");
+    write(root.path(), ".env", 1);
+    write(root.path(), ".git/HEAD", 1);
+    write(root.path(), "src/.cache", 1);
+    write(root.path(), "src/lib.rs", 1);
+
+    let visible = plan_directory_upload(&params(root.path(), "")).expect("folder should plan");
+    let everything = plan_directory_upload(&DirectoryUploadParams {
+        include_hidden: true,
+        ..params(root.path(), "")
+    })
+    .expect("folder should plan");
+
+    assert_eq!(keys(&visible), ["src/lib.rs"]);
+    assert_eq!(
+        keys(&everything),
+        [".env", ".git/HEAD", "src/.cache", "src/lib.rs"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_skipped_unless_followed_and_loops_are_walked_once() {
+    let root = tempfile::tempdir().expect("temporary directory should exist");
+    let outside = tempfile::tempdir().expect("temporary directory should exist");
+    write(root.path(), "docs/guide.md", 1);
+    write(outside.path(), "shared.txt", 4);
+    std::os::unix::fs::symlink(outside.path(), root.path().join("linked"))
+        .expect("directory symlink should be created");
+    std::os::unix::fs::symlink(
+        root.path().join("docs/guide.md"),
+        root.path().join("guide-link.md"),
+    )
+    .expect("file symlink should be created");
+    std::os::unix::fs::symlink(root.path(), root.path().join("docs/loop"))
+        .expect("looping symlink should be created");
+    std::os::unix::fs::symlink(root.path().join("missing"), root.path().join("broken"))
+        .expect("broken symlink should be created");
+
+    let skipped = plan_directory_upload(&params(root.path(), "")).expect("folder should plan");
+    let followed = plan_directory_upload(&DirectoryUploadParams {
+        follow_symlinks: true,
+        ..params(root.path(), "")
+    })
+    .expect("folder should plan");
+
+    assert_eq!(keys(&skipped), ["docs/guide.md"]);
+    assert_eq!(
+        keys(&followed),
+        ["docs/guide.md", "guide-link.md", "linked/shared.txt"]
+    );
+    assert_eq!(followed[2].size, 4);
+}
+
+#[cfg(unix)]
+#[test]
+fn one_unusable_file_name_rejects_the_whole_folder() {
+    let root = tempfile::tempdir().expect("temporary directory should exist");
+    write(root.path(), "fine.txt", 1);
+    write(root.path(), "bad\nname.txt", 1);
+
+    let error = plan_directory_upload(&params(root.path(), "uploads"))
+        .expect_err("a control character cannot be part of a key");
+
+    assert!(matches!(error.kind, ErrorKind::NotRetriable));
+    assert!(error.message.contains("control character"));
+}
+
+#[test]
+fn invalid_globs_and_missing_folders_are_rejected() {
+    let root = tempfile::tempdir().expect("temporary directory should exist");
+    write(root.path(), "a.txt", 1);
+
+    assert!(plan_directory_upload(&DirectoryUploadParams {
+        include: vec!["[".into()],
+        ..params(root.path(), "")
+    })
+    .is_err());
+    assert!(plan_directory_upload(&params(&root.path().join("missing"), "")).is_err());
+    assert!(plan_directory_upload(&params(&root.path().join("a.txt"), "")).is_err());
+}
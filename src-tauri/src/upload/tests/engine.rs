@@ -1,6 +1,7 @@
 use super::super::*;
+use super::fixtures::{controls, memory_operator};
 use crate::test_support::patterned_bytes;
-use opendal::services::Memory;
+use sha2::{Digest, Sha256};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
@@ -44,17 +45,10 @@ impl UploadEngineObserver for RecordingObserver {
     }
 }
 
-fn memory_operator() -> opendal::Operator {
-    opendal::Operator::new(Memory::default())
-        .expect("memory operator should build")
-        .finish()
-}
-
-fn controls(cancelled: bool) -> UploadControl {
-    UploadControl {
-        paused: Arc::new(AtomicBool::new(false)),
-        cancelled: Arc::new(AtomicBool::new(cancelled)),
-    }
+fn cancelled_controls() -> UploadControl {
+    let control = controls();
+    control.cancelled.store(true, Ordering::Relaxed);
+    control
 }
 
 #[tokio::test]
@@ -84,8 +78,11 @@ async fn engine_uploads_boundary_sized_files_exactly() {
                 part_size: PART_SIZE as u64,
                 content_type: Some("application/x-engine-test".into()),
                 content_disposition: Some("attachment; filename=\"fixture.bin\"".into()),
+                encryption: None,
+                compression: None,
+                condition: WriteCondition::None,
             },
-            controls(false),
+            controls(),
             &mut observer,
         )
         .await
@@ -133,8 +130,11 @@ async fn cancellation_removes_remote_object_and_notifies_observer() {
             part_size: 256,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
-        controls(true),
+        cancelled_controls(),
         &mut observer,
     )
     .await
@@ -171,8 +171,11 @@ async fn cancelling_replacement_upload_preserves_existing_remote_object() {
             part_size: 256,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
-        controls(true),
+        cancelled_controls(),
         &mut observer,
     )
     .await
@@ -241,6 +244,9 @@ async fn paused_file_upload_observes_cancellation_without_requiring_resume() {
                 part_size: 256,
                 content_type: None,
                 content_disposition: None,
+                encryption: None,
+                compression: None,
+                condition: WriteCondition::None,
             },
             UploadControl { paused, cancelled },
             &mut observer,
@@ -268,8 +274,11 @@ async fn zero_part_size_cannot_publish_empty_object_for_nonempty_source() {
             part_size: 0,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
-        controls(false),
+        controls(),
         &mut observer,
     )
     .await
@@ -280,3 +289,93 @@ async fn zero_part_size_cannot_publish_empty_object_for_nonempty_source() {
         .await
         .expect("existence check should work"));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn file_uploads_send_the_sha256_with_the_object() {
+    const PART_SIZE: usize = 4096;
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+
+    for size in [0, PART_SIZE - 1, 2 * PART_SIZE + 5] {
+        let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+        let original = patterned_bytes(size, 9);
+        std::fs::write(source.path(), &original).expect("fixture should be written");
+        let key = format!("hashed/{size}.bin");
+
+        let content = upload_file(
+            &operator,
+            UploadEngineRequest {
+                key: key.clone(),
+                source_path: source.path().to_path_buf(),
+                part_size: PART_SIZE as u64,
+                content_type: None,
+                content_disposition: None,
+                encryption: None,
+                compression: None,
+                condition: WriteCondition::None,
+            },
+            controls(),
+            &mut RecordingObserver::default(),
+        )
+        .await
+        .expect("engine upload should complete");
+
+        let expected = format!("{:x}", Sha256::digest(&original));
+        let metadata = crate::objects::stat_object(&operator, &key)
+            .await
+            .expect("object should exist");
+        assert_eq!(content.sha256, expected);
+        assert!(content.sha256_stored);
+        assert_eq!(
+            crate::objects::recorded_sha256(&metadata.headers.user_metadata),
+            Some(expected)
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compressed_uploads_send_the_source_sha256_with_the_object() {
+    let server = crate::test_support::start_path_style_s3("logs");
+    let operator = crate::test_support::stand_in_operator(&server, "logs");
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    let original = b"GET /index.html 200\n".repeat(400);
+    std::fs::write(source.path(), &original).expect("fixture should be written");
+
+    let content = upload_file(
+        &operator,
+        UploadEngineRequest {
+            key: "access.log".into(),
+            source_path: source.path().to_path_buf(),
+            part_size: 64 * 1024,
+            content_type: None,
+            content_disposition: None,
+            encryption: None,
+            compression: Some(crate::compression::Compression::Zstd),
+            condition: WriteCondition::None,
+        },
+        controls(),
+        &mut RecordingObserver::default(),
+    )
+    .await
+    .expect("engine upload should complete");
+
+    let source_sha256 = format!("{:x}", Sha256::digest(&original));
+    let metadata = crate::objects::stat_object(&operator, "access.log")
+        .await
+        .expect("object should exist");
+    assert_eq!(
+        content.source_sha256.as_deref(),
+        Some(source_sha256.as_str())
+    );
+    assert_eq!(
+        crate::objects::recorded_source_sha256(&metadata.headers.user_metadata),
+        Some(source_sha256)
+    );
+    // What is stored is the compressed stream, hashed and sized on its own.
+    assert_eq!(
+        crate::objects::recorded_sha256(&metadata.headers.user_metadata),
+        Some(content.sha256)
+    );
+    assert_eq!(content.stored_size, metadata.size);
+    assert!(content.stored_size < original.len() as u64);
+}
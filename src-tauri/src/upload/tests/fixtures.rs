@@ -1,4 +1,8 @@
+use super::super::*;
 use crate::test_support::patterned_bytes;
+use crate::types::StorageProvider;
+use opendal::services::Memory;
+use std::sync::{atomic::AtomicBool, Arc};
 
 pub(super) fn raw_camera_fixture(payload_len: usize) -> Vec<u8> {
     let header = b"II*\0SONY-ARW-TEST\0";
@@ -7,3 +11,102 @@ pub(super) fn raw_camera_fixture(payload_len: usize) -> Vec<u8> {
     bytes.extend(patterned_bytes(payload_len, 17));
     bytes
 }
+
+/// Credentials for a stand-in bucket named `photos` served at `endpoint`.
+pub(super) fn stand_in_config(endpoint: String) -> R2Config {
+    R2Config {
+        endpoint,
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "photos".into(),
+        region: Some("us-east-1".into()),
+        provider: StorageProvider::Minio,
+    }
+}
+
+pub(super) fn memory_operator() -> opendal::Operator {
+    opendal::Operator::new(Memory::default())
+        .expect("memory operator should build")
+        .finish()
+}
+
+/// Controls of an upload that is neither paused nor cancelled.
+pub(super) fn controls() -> UploadControl {
+    UploadControl {
+        paused: Arc::new(AtomicBool::new(false)),
+        cancelled: Arc::new(AtomicBool::new(false)),
+    }
+}
+
+/// Observes any upload engine and ignores everything it reports.
+pub(super) struct QuietObserver;
+
+impl UploadEngineObserver for QuietObserver {
+    fn uploading(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn paused(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn resumed(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn part_done(&mut self, _part_number: u32, _bytes_transferred: u64) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn finalizing(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn cancelled(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+}
+
+impl StreamUploadObserver for QuietObserver {
+    fn uploading(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn paused(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn resumed(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn part_done(&mut self, _part_number: u32, _bytes_transferred: u64) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn finalizing(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn cancelled(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+}
+
+impl ResumableUploadObserver for QuietObserver {
+    fn session_started(
+        &mut self,
+        _session: &MultipartSession,
+        _completed: &[CompletedPart],
+    ) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn part_stored(&mut self, _part: &CompletedPart) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn source_changed(&mut self, _error: &SpError) -> SpResult<()> {
+        Ok(())
+    }
+}
@@ -1,54 +1,183 @@
 use super::super::*;
 
-#[test]
-fn explicit_content_type_takes_precedence_after_trimming() {
+#[tokio::test(flavor = "multi_thread")]
+async fn recording_a_hash_keeps_the_headers_the_upload_wrote() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    let cfg = R2Config {
+        endpoint: server.url(),
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "photos".into(),
+        region: Some("us-east-1".into()),
+        provider: crate::types::StorageProvider::Minio,
+    };
+    let source_sha256 = "ef".repeat(32);
+    let mut writer = open_upload_writer(
+        &operator,
+        "raw/DSC00002.ARW",
+        None,
+        Some("attachment; filename=\"DSC00002.ARW\""),
+        ContentHashes {
+            stored: None,
+            source: Some(&source_sha256),
+        },
+        StoredEncoding {
+            sealed: true,
+            compressed: None,
+        },
+        &WriteCondition::None,
+    )
+    .await
+    .expect("writer should open");
+    writer.write(vec![7; 64]).await.expect("body should write");
+    writer.close().await.expect("object should be stored");
+    let sha256 = "ab".repeat(32);
+
+    storage::replace_object_metadata(
+        &cfg,
+        "raw/DSC00002.ARW",
+        64,
+        &content_hash_update(
+            "raw/DSC00002.ARW",
+            None,
+            Some(" attachment; filename=\"DSC00002.ARW\" "),
+            ContentHashes {
+                stored: Some(&sha256),
+                source: Some(&source_sha256),
+            },
+            StoredEncoding {
+                sealed: true,
+                compressed: None,
+            },
+        ),
+    )
+    .await
+    .expect("metadata should be rewritten");
+
+    let metadata = crate::objects::stat_object(&operator, "raw/DSC00002.ARW")
+        .await
+        .expect("object should exist");
+    assert_eq!(
+        metadata.headers.content_type.as_deref(),
+        Some("image/x-sony-arw")
+    );
+    assert_eq!(
+        metadata.headers.content_disposition.as_deref(),
+        Some("attachment; filename=\"DSC00002.ARW\"")
+    );
+    assert_eq!(
+        crate::objects::recorded_sha256(&metadata.headers.user_metadata),
+        Some(sha256)
+    );
     assert_eq!(
-        inferred_content_type("camera/DSC00001.ARW", Some("  image/custom-raw  ")),
-        "image/custom-raw"
+        crate::objects::recorded_source_sha256(&metadata.headers.user_metadata),
+        Some(source_sha256)
     );
+    assert!(crate::content_crypto::is_sealed(
+        &metadata.headers.user_metadata
+    ));
 }
 
-#[test]
-fn empty_explicit_content_type_falls_back_to_case_insensitive_extension() {
+#[tokio::test(flavor = "multi_thread")]
+async fn recording_a_hash_restores_the_coding_multipart_creation_drops() {
+    let server = crate::test_support::start_path_style_s3("logs");
+    let operator = crate::test_support::stand_in_operator(&server, "logs");
+    let cfg = R2Config {
+        endpoint: server.url(),
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "logs".into(),
+        region: Some("us-east-1".into()),
+        provider: crate::types::StorageProvider::Minio,
+    };
+    let encoding = StoredEncoding {
+        sealed: false,
+        compressed: Some((crate::compression::Compression::Zstd, 90_000)),
+    };
+    // Multipart creation carries the user metadata but no Content-Encoding.
+    operator
+        .write_with("app.log", vec![3; 64])
+        .user_metadata(upload_user_metadata(ContentHashes::default(), encoding))
+        .await
+        .expect("object should be stored");
+
+    storage::replace_object_metadata(
+        &cfg,
+        "app.log",
+        64,
+        &content_hash_update(
+            "app.log",
+            None,
+            None,
+            ContentHashes {
+                stored: Some(&"cd".repeat(32)),
+                source: None,
+            },
+            encoding,
+        ),
+    )
+    .await
+    .expect("metadata should be rewritten");
+
+    let metadata = crate::objects::stat_object(&operator, "app.log")
+        .await
+        .expect("object should exist");
+    assert_eq!(metadata.headers.content_encoding.as_deref(), Some("zstd"));
     assert_eq!(
-        inferred_content_type("camera/DSC00001.ARW", Some("  ")),
-        "image/x-sony-arw"
+        crate::compression::recorded_original_size(&metadata.headers.user_metadata),
+        Some(90_000)
     );
     assert_eq!(
-        inferred_content_type("camera/preview.JpEg", None),
-        "image/jpeg"
+        crate::objects::recorded_sha256(&metadata.headers.user_metadata),
+        Some("cd".repeat(32))
     );
+    assert!(!crate::content_crypto::is_sealed(
+        &metadata.headers.user_metadata
+    ));
 }
 
-#[test]
-fn known_raw_camera_extensions_have_stable_content_types() {
-    let cases = [
-        ("photo.arw", "image/x-sony-arw"),
-        ("photo.cr2", "image/x-canon-cr2"),
-        ("photo.cr3", "image/x-canon-cr3"),
-        ("photo.dng", "image/x-adobe-dng"),
-        ("photo.nef", "image/x-nikon-nef"),
-        ("photo.orf", "image/x-olympus-orf"),
-        ("photo.raf", "image/x-fuji-raf"),
-        ("photo.rw2", "image/x-panasonic-rw2"),
-    ];
-
-    for (key, expected) in cases {
-        assert_eq!(
-            inferred_content_type(key, None),
-            expected,
-            "wrong content type for {key}"
-        );
-    }
-}
+#[tokio::test(flavor = "multi_thread")]
+async fn recording_a_hash_leaves_an_object_replaced_meanwhile_alone() {
+    let server = crate::test_support::start_path_style_s3("photos");
+    let operator = crate::test_support::stand_in_operator(&server, "photos");
+    let cfg = R2Config {
+        endpoint: server.url(),
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "photos".into(),
+        region: Some("us-east-1".into()),
+        provider: crate::types::StorageProvider::Minio,
+    };
+    let written = operator
+        .write("clip.mp4", vec![1; 64])
+        .await
+        .expect("object should be stored");
+    operator
+        .write("clip.mp4", vec![2; 64])
+        .await
+        .expect("object should be replaced");
+    let mut update = content_hash_update(
+        "clip.mp4",
+        None,
+        None,
+        ContentHashes {
+            stored: Some(&"ab".repeat(32)),
+            source: None,
+        },
+        StoredEncoding::default(),
+    );
+    update.expected_etag = written.etag().map(str::to_string);
 
-#[test]
-fn unknown_or_missing_extension_uses_binary_content_type() {
-    for key in ["archive.unknown", "README", ".hidden"] {
-        assert_eq!(
-            inferred_content_type(key, None),
-            "application/octet-stream",
-            "wrong fallback content type for {key}"
-        );
-    }
+    storage::replace_object_metadata(&cfg, "clip.mp4", 64, &update)
+        .await
+        .expect_err("the replaced object must not take the hash");
+
+    let metadata = crate::objects::stat_object(&operator, "clip.mp4")
+        .await
+        .expect("object should exist");
+    assert_eq!(
+        crate::objects::recorded_sha256(&metadata.headers.user_metadata),
+        None
+    );
 }
@@ -1,5 +1,11 @@
+mod conflict;
+mod content_type;
+mod directory;
 mod engine;
 mod fixtures;
 mod metadata;
 mod object_roundtrip;
+mod resumable;
 mod stream;
+mod unchanged;
+mod url;
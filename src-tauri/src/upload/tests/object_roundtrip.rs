@@ -20,6 +20,9 @@ async fn assert_object_round_trip(
         key,
         explicit_content_type,
         Some("attachment; filename=\"fixture.bin\""),
+        ContentHashes::default(),
+        StoredEncoding::default(),
+        &WriteCondition::None,
     )
     .await
     .expect("writer should open");
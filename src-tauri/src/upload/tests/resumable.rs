@@ -0,0 +1,583 @@
+use super::super::*;
+use super::fixtures::{controls, stand_in_config};
+use crate::storage::multipart;
+use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+use crate::test_support::{patterned_bytes, stand_in_operator, start_path_style_s3};
+use sha2::{Digest, Sha256};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+const PART_SIZE: usize = 1024;
+
+#[derive(Default)]
+struct SessionObserver {
+    sessions: Vec<(MultipartSession, Vec<CompletedPart>)>,
+    stored: Vec<CompletedPart>,
+    source_changes: usize,
+    finalized: bool,
+    cancelled: bool,
+    /// Raised after this many parts were stored, to interrupt the upload.
+    cancel_after: Option<(usize, Arc<AtomicBool>)>,
+}
+
+impl UploadEngineObserver for SessionObserver {
+    fn uploading(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn paused(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn resumed(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn part_done(&mut self, _part_number: u32, _bytes_transferred: u64) -> SpResult<()> {
+        if let Some((limit, flag)) = &self.cancel_after {
+            if self.stored.len() >= *limit {
+                flag.store(true, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    fn finalizing(&mut self) -> SpResult<()> {
+        self.finalized = true;
+        Ok(())
+    }
+
+    fn cancelled(&mut self) -> SpResult<()> {
+        self.cancelled = true;
+        Ok(())
+    }
+}
+
+impl ResumableUploadObserver for SessionObserver {
+    fn session_started(
+        &mut self,
+        session: &MultipartSession,
+        completed: &[CompletedPart],
+    ) -> SpResult<()> {
+        self.sessions.push((session.clone(), completed.to_vec()));
+        Ok(())
+    }
+
+    fn part_stored(&mut self, part: &CompletedPart) -> SpResult<()> {
+        self.stored.push(part.clone());
+        Ok(())
+    }
+
+    fn source_changed(&mut self, error: &SpError) -> SpResult<()> {
+        assert!(matches!(error.kind, ErrorKind::SourceChanged));
+        self.source_changes += 1;
+        Ok(())
+    }
+}
+
+fn request(key: &str, source: &tempfile::NamedTempFile) -> ResumableUploadRequest {
+    ResumableUploadRequest {
+        key: key.into(),
+        source_path: source.path().to_path_buf(),
+        part_size: PART_SIZE as u64,
+        parts_in_flight: 1,
+        content_type: Some("image/x-sony-arw".into()),
+        content_disposition: None,
+        encryption: None,
+        condition: WriteCondition::None,
+    }
+}
+
+fn part_uploads(server: &LocalHttpServer) -> Vec<String> {
+    server
+        .requests()
+        .iter()
+        .filter(|request| request.method == "PUT" && request.query().contains("partNumber"))
+        .map(|request| request.query().to_string())
+        .collect()
+}
+
+/// Answers multipart requests for a single session, holding each UploadPart
+/// for a moment and recording the most parts it was receiving at once.
+fn part_counting_server(peak: Arc<AtomicUsize>) -> LocalHttpServer {
+    let receiving = AtomicUsize::new(0);
+    LocalHttpServer::start(move |request| {
+        if request.method == "POST" && request.query().starts_with("uploads") {
+            return StubResponse::new(200).body(
+                "<InitiateMultipartUploadResult><UploadId>parallel</UploadId></InitiateMultipartUploadResult>",
+            );
+        }
+        if request.method == "PUT" {
+            let now = receiving.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            receiving.fetch_sub(1, Ordering::SeqCst);
+            return StubResponse::new(200).header("etag", format!("\"{}\"", request.body.len()));
+        }
+        StubResponse::new(200)
+    })
+}
+
+/// Sends the first `parts` parts of `source` under a new session, as an
+/// earlier run would have before the app exited.
+async fn interrupted_session(
+    cfg: &R2Config,
+    key: &str,
+    original: &[u8],
+    source: &tempfile::NamedTempFile,
+    parts: usize,
+) -> MultipartCheckpoint {
+    let (source_size, source_mtime_ms) = source_identity(source.path())
+        .await
+        .expect("source should stat");
+    let upload_id = multipart::create(cfg, key, Vec::new())
+        .await
+        .expect("session should be created");
+    let mut stored = Vec::new();
+    for (index, chunk) in original.chunks(PART_SIZE).take(parts).enumerate() {
+        stored.push(
+            multipart::upload_part(
+                cfg,
+                key,
+                &upload_id,
+                index as u32 + 1,
+                chunk.to_vec().into(),
+            )
+            .await
+            .expect("part should upload"),
+        );
+    }
+    MultipartCheckpoint {
+        session: MultipartSession {
+            upload_id,
+            part_size: PART_SIZE as u64,
+            source_size,
+            source_mtime_ms,
+            content_key_id: None,
+        },
+        parts: stored,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multipart_upload_assembles_the_source_and_reports_every_part() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    let original = patterned_bytes(3 * PART_SIZE + 17, 11);
+    std::fs::write(source.path(), &original).expect("fixture should be written");
+    let mut observer = SessionObserver::default();
+
+    upload_file_resumable(
+        &cfg,
+        request("camera/DSC00001.ARW", &source),
+        None,
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("multipart upload should complete");
+
+    let operator = stand_in_operator(&server, "photos");
+    let uploaded = operator
+        .read("camera/DSC00001.ARW")
+        .await
+        .expect("object should exist")
+        .to_bytes();
+    let metadata = operator
+        .stat("camera/DSC00001.ARW")
+        .await
+        .expect("object should stat");
+    assert_eq!(uploaded.as_ref(), original);
+    assert_eq!(metadata.content_type(), Some("image/x-sony-arw"));
+    // The hash went out when the session was created; nothing rewrites it.
+    assert_eq!(
+        metadata
+            .user_metadata()
+            .and_then(crate::objects::recorded_sha256),
+        Some(format!("{:x}", Sha256::digest(&original)))
+    );
+    assert!(!server
+        .requests()
+        .iter()
+        .any(|request| request.header("x-amz-copy-source").is_some()));
+    assert_eq!(observer.sessions.len(), 1);
+    assert!(observer.sessions[0].1.is_empty());
+    assert_eq!(
+        observer
+            .stored
+            .iter()
+            .map(|part| (part.part_number, part.size))
+            .collect::<Vec<_>>(),
+        [(1, 1024), (2, 1024), (3, 1024), (4, 17)]
+    );
+    assert!(observer.finalized);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recorded_session_continues_from_the_first_missing_part() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    let original = patterned_bytes(4 * PART_SIZE + 5, 23);
+    std::fs::write(source.path(), &original).expect("fixture should be written");
+    let checkpoint = interrupted_session(&cfg, "big.bin", &original, &source, 2).await;
+    let upload_id = checkpoint.session.upload_id.clone();
+    let sent_before_restart = part_uploads(&server).len();
+    let mut observer = SessionObserver::default();
+
+    let content = upload_file_resumable(
+        &cfg,
+        request("big.bin", &source),
+        Some(checkpoint),
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("resumed upload should complete");
+
+    let resumed_parts = part_uploads(&server)[sent_before_restart..].to_vec();
+    assert_eq!(resumed_parts.len(), 3);
+    assert!(resumed_parts
+        .iter()
+        .all(|query| query.contains(&format!("uploadId={upload_id}"))));
+    assert!(!resumed_parts
+        .iter()
+        .any(|query| query.contains("partNumber=1&") || query.ends_with("partNumber=1")));
+    let (session, completed) = &observer.sessions[0];
+    assert_eq!(session.upload_id, upload_id);
+    assert_eq!(
+        completed
+            .iter()
+            .map(|part| part.part_number)
+            .collect::<Vec<_>>(),
+        [1, 2]
+    );
+    assert_eq!(observer.source_changes, 0);
+    let uploaded = stand_in_operator(&server, "photos")
+        .read("big.bin")
+        .await
+        .expect("object should exist")
+        .to_bytes();
+    assert_eq!(uploaded.as_ref(), original);
+    // Parts stored before the restart are hashed from the file.
+    assert_eq!(content.sha256, format!("{:x}", Sha256::digest(&original)));
+    assert!(content.sha256_stored);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_changed_source_discards_the_recorded_session_and_starts_over() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    let original = patterned_bytes(3 * PART_SIZE, 31);
+    std::fs::write(source.path(), &original).expect("fixture should be written");
+    let checkpoint = interrupted_session(&cfg, "edited.bin", &original, &source, 2).await;
+    let stale_upload = checkpoint.session.upload_id.clone();
+    let edited = patterned_bytes(3 * PART_SIZE + 100, 37);
+    std::fs::write(source.path(), &edited).expect("source should be rewritten");
+    let mut observer = SessionObserver::default();
+
+    upload_file_resumable(
+        &cfg,
+        request("edited.bin", &source),
+        Some(checkpoint),
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("fresh upload should complete");
+
+    assert_eq!(observer.source_changes, 1);
+    let (session, completed) = &observer.sessions[0];
+    assert_ne!(session.upload_id, stale_upload);
+    assert!(completed.is_empty());
+    assert!(server
+        .requests()
+        .iter()
+        .any(|request| request.method == "DELETE"
+            && request.query() == format!("uploadId={stale_upload}")));
+    let uploaded = stand_in_operator(&server, "photos")
+        .read("edited.bin")
+        .await
+        .expect("object should exist")
+        .to_bytes();
+    assert_eq!(uploaded.as_ref(), edited);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_session_the_bucket_no_longer_knows_starts_over() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    let original = patterned_bytes(2 * PART_SIZE + 1, 41);
+    std::fs::write(source.path(), &original).expect("fixture should be written");
+    let checkpoint = interrupted_session(&cfg, "expired.bin", &original, &source, 1).await;
+    multipart::abort(&cfg, "expired.bin", &checkpoint.session.upload_id)
+        .await
+        .expect("session should abort");
+    let mut observer = SessionObserver::default();
+
+    upload_file_resumable(
+        &cfg,
+        request("expired.bin", &source),
+        Some(checkpoint),
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("fresh upload should complete");
+
+    assert_eq!(observer.source_changes, 0);
+    assert!(observer.sessions[0].1.is_empty());
+    assert_eq!(observer.stored.len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_retryable_part_failure_keeps_the_session_for_resume() {
+    let server = LocalHttpServer::start(|request| {
+        if request.method == "POST" && request.query().starts_with("uploads") {
+            return StubResponse::new(200).body(
+                "<InitiateMultipartUploadResult><UploadId>kept</UploadId></InitiateMultipartUploadResult>",
+            );
+        }
+        if request.method == "PUT" {
+            return StubResponse::new(503);
+        }
+        StubResponse::new(200)
+    });
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    std::fs::write(source.path(), patterned_bytes(2 * PART_SIZE, 47))
+        .expect("fixture should be written");
+    let mut observer = SessionObserver::default();
+
+    let error = upload_file_resumable(
+        &cfg,
+        request("flaky.bin", &source),
+        None,
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect_err("failing parts should fail the upload");
+
+    assert!(matches!(error.kind, ErrorKind::RetryableNet));
+    assert_eq!(observer.sessions.len(), 1);
+    assert!(!observer.cancelled);
+    assert!(!server
+        .requests()
+        .iter()
+        .any(|request| request.method == "DELETE"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancellation_aborts_the_session_and_publishes_nothing() {
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    std::fs::write(source.path(), patterned_bytes(4 * PART_SIZE, 43))
+        .expect("fixture should be written");
+    let control = controls();
+    let mut observer = SessionObserver {
+        cancel_after: Some((1, control.cancelled.clone())),
+        ..SessionObserver::default()
+    };
+
+    let error = upload_file_resumable(
+        &cfg,
+        request("cancelled.bin", &source),
+        None,
+        control,
+        &mut observer,
+    )
+    .await
+    .expect_err("cancelled upload should fail");
+
+    assert!(matches!(error.kind, ErrorKind::Cancelled));
+    assert!(observer.cancelled);
+    assert!(!observer.finalized);
+    assert_eq!(observer.stored.len(), 1);
+    let upload_id = &observer.sessions[0].0.upload_id;
+    assert!(multipart::list_parts(&cfg, "cancelled.bin", upload_id)
+        .await
+        .is_err_and(|error| matches!(error.kind, ErrorKind::NotFound)));
+    assert!(!stand_in_operator(&server, "photos")
+        .exists("cancelled.bin")
+        .await
+        .expect("existence check should work"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn parts_are_sent_in_parallel_up_to_the_configured_limit() {
+    for parts_in_flight in [1, 3] {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let server = part_counting_server(peak.clone());
+        let cfg = stand_in_config(server.url());
+        let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+        std::fs::write(source.path(), patterned_bytes(8 * PART_SIZE + 1, 47))
+            .expect("fixture should be written");
+        let mut observer = SessionObserver::default();
+
+        upload_file_resumable(
+            &cfg,
+            ResumableUploadRequest {
+                parts_in_flight,
+                ..request("parallel.bin", &source)
+            },
+            None,
+            controls(),
+            &mut observer,
+        )
+        .await
+        .expect("parallel upload should complete");
+
+        assert_eq!(peak.load(Ordering::SeqCst), parts_in_flight);
+        let mut stored = observer
+            .stored
+            .iter()
+            .map(|part| (part.part_number, part.size))
+            .collect::<Vec<_>>();
+        stored.sort();
+        assert_eq!(stored.len(), 9);
+        assert!(stored[..8]
+            .iter()
+            .enumerate()
+            .all(|(index, part)| *part == (index as u32 + 1, PART_SIZE as u64)));
+        assert_eq!(stored[8], (9, 1));
+        let completion = server
+            .requests()
+            .into_iter()
+            .find(|request| request.method == "POST" && request.query() == "uploadId=parallel")
+            .expect("upload should be completed");
+        let listed = String::from_utf8_lossy(&completion.body)
+            .split("<PartNumber>")
+            .skip(1)
+            .filter_map(|rest| rest.split('<').next()?.parse::<u32>().ok())
+            .collect::<Vec<_>>();
+        assert_eq!(listed, (1..=9).collect::<Vec<_>>());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sealed_parts_resume_under_the_same_key_and_restart_under_another() {
+    use crate::content_crypto::{self, ContentKey, ContentSealer};
+    const SEALED_PART: u64 = 32 * 1024;
+    let server = start_path_style_s3("photos");
+    let cfg = stand_in_config(server.url());
+    let source = tempfile::NamedTempFile::new().expect("temp source should be created");
+    let original = patterned_bytes(2 * content_crypto::CHUNK_LEN as usize + 500, 53);
+    std::fs::write(source.path(), &original).expect("fixture should be written");
+    let encryption = crate::test_support::cheap_encryption("resumable secret");
+    let key = ContentKey::for_profile(&encryption)
+        .await
+        .expect("key should derive");
+    let sealed_request = |name: &str| ResumableUploadRequest {
+        part_size: SEALED_PART,
+        encryption: Some(encryption.clone()),
+        ..request(name, &source)
+    };
+
+    // An earlier run sealed and stored the first two parts.
+    let mut checkpoint = interrupted_session(&cfg, "sealed.bin", &original, &source, 0).await;
+    checkpoint.session.upload_id = multipart::create(
+        &cfg,
+        "sealed.bin",
+        create_headers(
+            &sealed_request("sealed.bin"),
+            "application/octet-stream",
+            &format!("{:x}", Sha256::digest(&original)),
+        ),
+    )
+    .await
+    .expect("sealed session should be created");
+    let sealer = ContentSealer::for_multipart_upload(key.clone(), &checkpoint.session.upload_id);
+    let plain_len = original.len() as u64;
+    for part_number in 1..=2u32 {
+        let start = u64::from(part_number - 1) * SEALED_PART;
+        let range = start..start + SEALED_PART;
+        let plain = content_crypto::plaintext_for_sealed_range(&range, plain_len);
+        let body = sealer.seal_range(
+            range,
+            plain_len,
+            &original[plain.start as usize..plain.end as usize],
+        );
+        checkpoint.parts.push(
+            multipart::upload_part(
+                &cfg,
+                "sealed.bin",
+                &checkpoint.session.upload_id,
+                part_number,
+                body.into(),
+            )
+            .await
+            .expect("sealed part should upload"),
+        );
+    }
+    checkpoint.session.part_size = SEALED_PART;
+    checkpoint.session.content_key_id = Some(key.id());
+    let mut observer = SessionObserver::default();
+
+    let content = upload_file_resumable(
+        &cfg,
+        sealed_request("sealed.bin"),
+        Some(checkpoint),
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("sealed upload should resume");
+
+    assert_eq!(observer.sessions[0].1.len(), 2);
+    assert_eq!(observer.source_changes, 0);
+    let operator = stand_in_operator(&server, "photos");
+    let stored = operator
+        .read("sealed.bin")
+        .await
+        .expect("object should exist")
+        .to_bytes();
+    assert_eq!(stored.len() as u64, content_crypto::sealed_len(plain_len));
+    assert_eq!(content.stored_size, stored.len() as u64);
+    assert_eq!(content.sha256, format!("{:x}", Sha256::digest(&stored)));
+    assert!(content.encoding.sealed);
+    let metadata = crate::objects::stat_object(&operator, "sealed.bin")
+        .await
+        .expect("object should stat");
+    assert!(content_crypto::is_sealed(&metadata.headers.user_metadata));
+    assert_eq!(
+        crate::test_support::open_sealed(&stored, &encryption)
+            .await
+            .expect("object should open"),
+        original
+    );
+
+    // Parts recorded as plaintext cannot continue a sealed upload.
+    let plaintext_checkpoint =
+        interrupted_session(&cfg, "switched.bin", &original, &source, 1).await;
+    let mut observer = SessionObserver::default();
+    upload_file_resumable(
+        &cfg,
+        sealed_request("switched.bin"),
+        Some(plaintext_checkpoint),
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("sealed upload should start over");
+
+    assert_eq!(observer.source_changes, 1);
+    assert!(observer.sessions[0].1.is_empty());
+    let stored = operator
+        .read("switched.bin")
+        .await
+        .expect("object should exist")
+        .to_bytes();
+    assert_eq!(
+        crate::test_support::open_sealed(&stored, &encryption)
+            .await
+            .expect("object should open"),
+        original
+    );
+}
@@ -1,6 +1,6 @@
 use super::super::*;
+use super::fixtures::{controls, memory_operator};
 use crate::test_support::patterned_bytes;
-use opendal::services::Memory;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
@@ -42,9 +42,7 @@ impl StreamUploadObserver for StreamObserver {
 
 #[tokio::test]
 async fn stream_engine_preserves_chunk_order_and_bytes() {
-    let operator = opendal::Operator::new(Memory::default())
-        .expect("memory operator should build")
-        .finish();
+    let operator = memory_operator();
     let chunks = [
         patterned_bytes(17, 3),
         patterned_bytes(64 * 1024 + 1, 5),
@@ -65,15 +63,16 @@ async fn stream_engine_preserves_chunk_order_and_bytes() {
         &operator,
         StreamUploadRequest {
             key: "stream/ordered.bin".into(),
-            expected_bytes: expected.len() as u64,
+            expected_bytes: Some(expected.len() as u64),
+            stall_timeout: None,
             content_type: Some("application/x-stream-test".into()),
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
         receiver,
-        UploadControl {
-            paused: Arc::new(AtomicBool::new(false)),
-            cancelled: Arc::new(AtomicBool::new(false)),
-        },
+        controls(),
         &mut observer,
     )
     .await
@@ -92,19 +91,6 @@ async fn stream_engine_preserves_chunk_order_and_bytes() {
     assert!(observer.finalizing);
 }
 
-fn memory_operator() -> opendal::Operator {
-    opendal::Operator::new(Memory::default())
-        .expect("memory operator should build")
-        .finish()
-}
-
-fn stream_control() -> UploadControl {
-    UploadControl {
-        paused: Arc::new(AtomicBool::new(false)),
-        cancelled: Arc::new(AtomicBool::new(false)),
-    }
-}
-
 #[tokio::test]
 async fn sender_disconnect_without_finish_cannot_publish_partial_stream() {
     let operator = memory_operator();
@@ -120,12 +106,16 @@ async fn sender_disconnect_without_finish_cannot_publish_partial_stream() {
         &operator,
         StreamUploadRequest {
             key: "stream/disconnected.bin".into(),
-            expected_bytes: 1024,
+            expected_bytes: Some(1024),
+            stall_timeout: None,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
         receiver,
-        stream_control(),
+        controls(),
         &mut observer,
     )
     .await
@@ -154,12 +144,16 @@ async fn explicit_finish_rejects_stream_shorter_than_declared_total() {
         &operator,
         StreamUploadRequest {
             key: "stream/too-short.bin".into(),
-            expected_bytes: DECLARED_TOTAL as u64,
+            expected_bytes: Some(DECLARED_TOTAL as u64),
+            stall_timeout: None,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
         receiver,
-        stream_control(),
+        controls(),
         &mut observer,
     )
     .await;
@@ -186,12 +180,16 @@ async fn stream_rejects_bytes_beyond_declared_total() {
         &operator,
         StreamUploadRequest {
             key: "stream/too-long.bin".into(),
-            expected_bytes: DECLARED_TOTAL as u64,
+            expected_bytes: Some(DECLARED_TOTAL as u64),
+            stall_timeout: None,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
         receiver,
-        stream_control(),
+        controls(),
         &mut observer,
     )
     .await;
@@ -222,12 +220,16 @@ async fn empty_stream_chunks_do_not_create_false_part_progress() {
         &operator,
         StreamUploadRequest {
             key: "stream/empty-chunk.bin".into(),
-            expected_bytes: payload.len() as u64,
+            expected_bytes: Some(payload.len() as u64),
+            stall_timeout: None,
             content_type: None,
             content_disposition: None,
+            encryption: None,
+            compression: None,
+            condition: WriteCondition::None,
         },
         receiver,
-        stream_control(),
+        controls(),
         &mut observer,
     )
     .await
@@ -296,9 +298,13 @@ async fn paused_stream_upload_observes_cancellation_without_requiring_resume() {
             &operator,
             StreamUploadRequest {
                 key: "stream/paused-cancel.bin".into(),
-                expected_bytes: 128,
+                expected_bytes: Some(128),
+                stall_timeout: None,
                 content_type: None,
                 content_disposition: None,
+                encryption: None,
+                compression: None,
+                condition: WriteCondition::None,
             },
             receiver,
             UploadControl { paused, cancelled },
@@ -312,3 +318,134 @@ async fn paused_stream_upload_observes_cancellation_without_requiring_resume() {
     assert!(matches!(result.kind, ErrorKind::Cancelled));
     drop(sender);
 }
+
+#[test]
+fn stream_session_places_chunks_by_offset() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(8);
+    let mut session = StreamSession::new(sender);
+    let payload = patterned_bytes(100, 23);
+
+    session.write(0, payload[..40].to_vec()).unwrap();
+    // A repeat after a lost acknowledgement is dropped.
+    session.write(0, payload[..40].to_vec()).unwrap();
+    // An overlap queues only what is new.
+    session.write(30, payload[30..70].to_vec()).unwrap();
+    let gap = session
+        .write(80, payload[80..].to_vec())
+        .expect_err("a chunk past the accepted end must be refused");
+    assert!(matches!(gap.kind, ErrorKind::NotRetriable));
+    assert!(gap.message.contains("continue from 70"), "{}", gap.message);
+    assert!(session.finish(60).is_err());
+    assert!(session.finish(100).is_err());
+
+    session.write(70, payload[70..].to_vec()).unwrap();
+    session.finish(100).unwrap();
+    session
+        .finish(100)
+        .expect("finishing again at the same offset is a no-op");
+    session
+        .write(90, payload[90..].to_vec())
+        .expect("a repeat after finishing is still dropped");
+    assert!(session.write(100, vec![1]).is_err());
+
+    let mut received = Vec::new();
+    let mut finishes = 0;
+    while let Ok(message) = receiver.try_recv() {
+        match message {
+            Some(chunk) => received.extend(chunk),
+            None => finishes += 1,
+        }
+    }
+    assert_eq!(received, payload);
+    assert_eq!(finishes, 1);
+}
+
+#[test]
+fn full_stream_channel_leaves_the_chunk_for_a_retry() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let mut session = StreamSession::new(sender);
+    session.write(0, vec![1; 10]).unwrap();
+
+    let full = session
+        .write(10, vec![2; 10])
+        .expect_err("a full channel must push back");
+    assert!(matches!(full.kind, ErrorKind::RetryableNet));
+    assert_eq!(receiver.try_recv().unwrap(), Some(vec![1; 10]));
+    session
+        .write(10, vec![2; 10])
+        .expect("the same chunk is accepted once there is room");
+    assert_eq!(receiver.try_recv().unwrap(), Some(vec![2; 10]));
+
+    drop(receiver);
+    let closed = session
+        .finish(20)
+        .expect_err("a stopped engine cannot be finished");
+    assert!(matches!(closed.kind, ErrorKind::NotRetriable));
+}
+
+#[tokio::test]
+async fn quiet_stream_sender_fails_the_upload_unless_paused() {
+    let operator = memory_operator();
+    let request = |key: &str| StreamUploadRequest {
+        key: key.into(),
+        expected_bytes: Some(256),
+        stall_timeout: Some(std::time::Duration::from_millis(300)),
+        content_type: None,
+        content_disposition: None,
+        encryption: None,
+        compression: None,
+        condition: WriteCondition::None,
+    };
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(2);
+    sender.send(Some(patterned_bytes(128, 3))).await.unwrap();
+    let error = upload_stream(
+        &operator,
+        request("stream/stalled.bin"),
+        receiver,
+        controls(),
+        &mut StreamObserver::default(),
+    )
+    .await
+    .expect_err("a sender that went quiet must fail the upload");
+    assert!(
+        error.message.contains("did not reconnect"),
+        "{}",
+        error.message
+    );
+    assert!(!operator.exists("stream/stalled.bin").await.unwrap());
+    drop(sender);
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(2);
+    let control = controls();
+    let paused = control.paused.clone();
+    let payload = patterned_bytes(256, 5);
+    let sent = payload.clone();
+    let late_sender = tokio::spawn(async move {
+        sender.send(Some(sent[..128].to_vec())).await.unwrap();
+        paused.store(true, Ordering::Relaxed);
+        tokio::time::sleep(std::time::Duration::from_millis(700)).await;
+        paused.store(false, Ordering::Relaxed);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        sender.send(Some(sent[128..].to_vec())).await.unwrap();
+        sender.send(None).await.unwrap();
+    });
+    upload_stream(
+        &operator,
+        request("stream/paused-quiet.bin"),
+        receiver,
+        control,
+        &mut StreamObserver::default(),
+    )
+    .await
+    .expect("a pause is not a stall");
+    late_sender.await.unwrap();
+    assert_eq!(
+        operator
+            .read("stream/paused-quiet.bin")
+            .await
+            .unwrap()
+            .to_vec(),
+        payload
+    );
+}
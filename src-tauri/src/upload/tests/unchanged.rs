@@ -0,0 +1,168 @@
+use super::super::*;
+use crate::objects::{SHA256_METADATA_KEY, SOURCE_SHA256_METADATA_KEY};
+use crate::storage::multipart;
+use crate::test_support::{patterned_bytes, stand_in_operator, start_path_style_s3};
+use crate::types::StorageProvider;
+use sha2::{Digest, Sha256};
+
+fn local_file(contents: &[u8]) -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().expect("temporary file should exist");
+    std::fs::write(file.path(), contents).expect("source should be written");
+    file
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn single_part_object_matches_by_size_and_md5_etag() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let contents = patterned_bytes(4096, 3);
+    operator
+        .write("albums/a.bin", contents.clone())
+        .await
+        .expect("object should be stored");
+
+    let same = local_file(&contents);
+    let mut edited = contents.clone();
+    edited[100] ^= 0xff;
+    let edited = local_file(&edited);
+    let longer = local_file(&patterned_bytes(4097, 3));
+
+    for (source, expected) in [(&same, true), (&edited, false), (&longer, false)] {
+        let matches = remote_copy_matches(&operator, "albums/a.bin", source.path())
+            .await
+            .expect("comparison should succeed");
+        assert_eq!(matches, expected);
+    }
+    let missing = remote_copy_matches(&operator, "albums/missing.bin", same.path())
+        .await
+        .expect("a missing key is a mismatch, not an error");
+    assert!(!missing);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sha256_user_metadata_decides_when_present() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let contents = patterned_bytes(2048, 7);
+    let digest = format!("{:x}", Sha256::digest(&contents));
+    let stale = format!("{:x}", Sha256::digest(b"previous version"));
+    for (key, recorded) in [("hashed.bin", digest), ("stale.bin", stale)] {
+        operator
+            .write_with(key, contents.clone())
+            .user_metadata([(SHA256_METADATA_KEY.to_string(), recorded)])
+            .await
+            .expect("object should be stored");
+    }
+    let source = local_file(&contents);
+
+    assert!(remote_copy_matches(&operator, "hashed.bin", source.path())
+        .await
+        .expect("comparison should succeed"));
+    // The MD5 ETag matches too, but a recorded hash is authoritative.
+    assert!(!remote_copy_matches(&operator, "stale.bin", source.path())
+        .await
+        .expect("comparison should succeed"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multipart_etag_without_a_recorded_hash_never_matches() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let cfg = R2Config {
+        endpoint: server.url(),
+        access_key_id: "access".into(),
+        secret_access_key: "secret".into(),
+        bucket: "photos".into(),
+        region: Some("us-east-1".into()),
+        provider: StorageProvider::Minio,
+    };
+    let contents = patterned_bytes(3000, 5);
+    let upload_id = multipart::create(&cfg, "big.bin", Vec::new())
+        .await
+        .expect("session should start");
+    let mut parts = Vec::new();
+    for (index, chunk) in contents.chunks(1500).enumerate() {
+        parts.push(
+            multipart::upload_part(
+                &cfg,
+                "big.bin",
+                &upload_id,
+                index as u32 + 1,
+                chunk.to_vec().into(),
+            )
+            .await
+            .expect("part should be stored"),
+        );
+    }
+    multipart::complete(&cfg, "big.bin", &upload_id, &parts, Vec::new())
+        .await
+        .expect("upload should complete");
+    let etag = operator
+        .stat("big.bin")
+        .await
+        .expect("object should exist")
+        .etag()
+        .map(str::to_string);
+    assert!(etag.is_some_and(|etag| etag.ends_with("-2\"")));
+
+    let source = local_file(&contents);
+    assert!(!remote_copy_matches(&operator, "big.bin", source.path())
+        .await
+        .expect("comparison should succeed"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sealed_and_compressed_objects_match_by_their_source_hash() {
+    let server = start_path_style_s3("photos");
+    let operator = stand_in_operator(&server, "photos");
+    let contents = patterned_bytes(2048, 9);
+    let source_sha256 = format!("{:x}", Sha256::digest(&contents));
+    let source = local_file(&contents);
+    let sealed_len = crate::content_crypto::sealed_len(contents.len() as u64) as usize;
+    let sealed_marker = crate::content_crypto::sealed_marker();
+    let original_size = crate::compression::original_size_metadata(contents.len() as u64);
+    let source_hash = (
+        SOURCE_SHA256_METADATA_KEY.to_string(),
+        source_sha256.clone(),
+    );
+    let stored_hash = (SHA256_METADATA_KEY.to_string(), source_sha256);
+
+    for (key, stored_len, metadata, expected) in [
+        (
+            "sealed.bin",
+            sealed_len,
+            vec![sealed_marker.clone(), source_hash.clone()],
+            true,
+        ),
+        (
+            "resized.bin",
+            sealed_len + 1,
+            vec![sealed_marker.clone(), source_hash.clone()],
+            false,
+        ),
+        // A stored-bytes hash says nothing about sealed content.
+        (
+            "stored-hash.bin",
+            sealed_len,
+            vec![sealed_marker, stored_hash],
+            false,
+        ),
+        (
+            "compressed.bin",
+            300,
+            vec![original_size, source_hash],
+            true,
+        ),
+    ] {
+        operator
+            .write_with(key, vec![0; stored_len])
+            .user_metadata(metadata)
+            .await
+            .expect("object should be stored");
+
+        let matches = remote_copy_matches(&operator, key, source.path())
+            .await
+            .expect("comparison should succeed");
+        assert_eq!(matches, expected, "{key}");
+    }
+}
@@ -0,0 +1,247 @@
+use super::super::*;
+use super::fixtures::{controls, memory_operator};
+use crate::test_support::local_http::{LocalHttpServer, StubResponse};
+use crate::test_support::patterned_bytes;
+
+#[derive(Default)]
+struct ReceivedBytes {
+    bytes: u64,
+    finalizing: bool,
+}
+
+impl StreamUploadObserver for ReceivedBytes {
+    fn uploading(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn paused(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn resumed(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+
+    fn part_done(&mut self, _part_number: u32, bytes_transferred: u64) -> SpResult<()> {
+        self.bytes += bytes_transferred;
+        Ok(())
+    }
+
+    fn finalizing(&mut self) -> SpResult<()> {
+        self.finalizing = true;
+        Ok(())
+    }
+
+    fn cancelled(&mut self) -> SpResult<()> {
+        Ok(())
+    }
+}
+
+fn url_request(key: &str, compression: Option<Compression>) -> StreamUploadRequest {
+    StreamUploadRequest {
+        key: key.into(),
+        expected_bytes: None,
+        stall_timeout: None,
+        content_type: None,
+        content_disposition: None,
+        encryption: None,
+        compression,
+        condition: WriteCondition::None,
+    }
+}
+
+#[test]
+fn file_names_come_from_the_disposition_before_the_url() {
+    assert_eq!(
+        disposition_file_name("attachment; filename=\"report q3.pdf\"").as_deref(),
+        Some("report q3.pdf")
+    );
+    assert_eq!(
+        disposition_file_name(
+            "attachment; filename=\"fallback.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9.txt"
+        )
+        .as_deref(),
+        Some("résumé.txt")
+    );
+    assert_eq!(
+        disposition_file_name("attachment; filename*=utf-8'en'notes.md").as_deref(),
+        Some("notes.md")
+    );
+    assert_eq!(
+        disposition_file_name("attachment; filename=\"../../etc/passwd\"").as_deref(),
+        Some("passwd")
+    );
+    assert_eq!(disposition_file_name("inline"), None);
+    assert_eq!(disposition_file_name("attachment; filename=\"..\""), None);
+
+    let url = |value: &str| reqwest::Url::parse(value).expect("test URL should parse");
+    assert_eq!(
+        url_file_name(&url("https://example.com/files/My%20Photo.jpg?size=large")).as_deref(),
+        Some("My Photo.jpg")
+    );
+    assert_eq!(
+        url_file_name(&url("https://example.com/archive/")).as_deref(),
+        Some("archive")
+    );
+    assert_eq!(url_file_name(&url("https://example.com/")), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn declared_length_and_headers_describe_the_object() {
+    let body = patterned_bytes(200 * 1024 + 3, 9);
+    let served = body.clone();
+    let server = LocalHttpServer::start(move |_| {
+        StubResponse::new(200)
+            .header("content-type", "image/png")
+            .header("content-disposition", "attachment; filename=\"chart.png\"")
+            .body(served.clone())
+    });
+
+    let source = open_url(
+        &reqwest::Client::new(),
+        &format!("{}/download?id=7", server.url()),
+    )
+    .await
+    .expect("the URL should open");
+    assert_eq!(source.content_length, Some(body.len() as u64));
+    assert_eq!(source.content_type.as_deref(), Some("image/png"));
+    assert_eq!(source.file_name, "chart.png");
+
+    let operator = memory_operator();
+    let mut observer = ReceivedBytes::default();
+    upload_url(
+        &operator,
+        url_request("imports/chart.png", None),
+        source,
+        controls(),
+        &mut observer,
+    )
+    .await
+    .expect("the body should be stored");
+    assert_eq!(
+        operator.read("imports/chart.png").await.unwrap().to_vec(),
+        body
+    );
+    assert_eq!(observer.bytes, body.len() as u64);
+    assert!(observer.finalizing);
+    assert_eq!(
+        server.requests()[0].header("accept-encoding"),
+        Some("identity")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chunked_bodies_of_unknown_length_record_their_size_when_compressed() {
+    let body = b"2026-10-17 upload finished\n".repeat(4_000);
+    let served = body.clone();
+    let server = LocalHttpServer::start(move |_| {
+        StubResponse::new(200)
+            .header("transfer-encoding", "chunked")
+            .header("content-type", "application/octet-stream")
+            .body(served.clone())
+    });
+
+    let source = open_url(
+        &reqwest::Client::new(),
+        &format!("{}/logs/app.log", server.url()),
+    )
+    .await
+    .expect("the URL should open");
+    assert_eq!(source.content_length, None);
+    assert_eq!(source.content_type, None, "the file name says more");
+    assert_eq!(source.file_name, "app.log");
+
+    let operator = memory_operator();
+    let content = upload_url(
+        &operator,
+        url_request("app.log", Some(Compression::Gzip)),
+        source,
+        controls(),
+        &mut ReceivedBytes::default(),
+    )
+    .await
+    .expect("the body should be stored");
+    assert_eq!(
+        content.encoding.compressed,
+        Some((Compression::Gzip, body.len() as u64))
+    );
+
+    let stored = operator.read("app.log").await.unwrap().to_vec();
+    assert!(stored.len() < body.len() / 10);
+    let mut decoded = Vec::new();
+    std::io::Read::read_to_end(
+        &mut flate2::read::GzDecoder::new(stored.as_slice()),
+        &mut decoded,
+    )
+    .expect("stored content should be gzip");
+    assert_eq!(decoded, body);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unusable_urls_and_error_statuses_fail_before_the_transfer() {
+    let server = LocalHttpServer::start(|request| match request.path() {
+        "/private" => StubResponse::new(401),
+        "/gone" => StubResponse::new(404),
+        _ => StubResponse::new(503),
+    });
+    let client = reqwest::Client::new();
+    let kind = |path: &'static str| {
+        let (client, url) = (client.clone(), format!("{}{path}", server.url()));
+        async move {
+            open_url(&client, &url)
+                .await
+                .err()
+                .expect("the URL must not open")
+                .kind
+        }
+    };
+    assert!(matches!(
+        kind("/private").await,
+        ErrorKind::PermissionDenied
+    ));
+    assert!(matches!(kind("/gone").await, ErrorKind::NotFound));
+    assert!(matches!(kind("/busy").await, ErrorKind::RetryableNet));
+
+    for url in ["ftp://example.com/a.txt", "not a url"] {
+        let error = open_url(&client, url)
+            .await
+            .err()
+            .expect("the URL must be refused");
+        assert!(matches!(error.kind, ErrorKind::NotRetriable), "{error:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_body_that_stalls_fails_the_import() {
+    // The declared length is never sent, so the body goes quiet.
+    let server = LocalHttpServer::start(|_| {
+        StubResponse::new(200)
+            .header("content-length", "100000")
+            .body(patterned_bytes(1024, 4))
+    });
+    let source = open_url(
+        &reqwest::Client::new(),
+        &format!("{}/big.bin", server.url()),
+    )
+    .await
+    .expect("the URL should open");
+
+    let request = StreamUploadRequest {
+        stall_timeout: Some(std::time::Duration::from_millis(300)),
+        ..url_request("big.bin", None)
+    };
+    let error = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        upload_url(
+            &memory_operator(),
+            request,
+            source,
+            controls(),
+            &mut ReceivedBytes::default(),
+        ),
+    )
+    .await
+    .expect("a stalled body must not hang the import")
+    .expect_err("a stalled body must fail");
+    assert!(error.message.contains("sent nothing"), "{error:?}");
+}
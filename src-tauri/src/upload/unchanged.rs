@@ -0,0 +1,117 @@
+//! Detection of uploads whose content is already at the destination key.
+//!
+//! This module owns comparing a local file with the object stored under its
+//! key: the object must hold what a source of the file's size is stored as,
+//! sealed or compressed or byte for byte, and then the file's digest must
+//! equal the source SHA-256 recorded with the object, or, for an object
+//! stored byte for byte, its recorded SHA-256 (see
+//! `crate::objects::recorded_sha256`) or the MD5 ETag of a single-request
+//! write. An object with none of these, such as a multipart upload from
+//! another tool, never counts as unchanged. It must not register transfers
+//! or emit Tauri events.
+
+use super::now_ms;
+use crate::types::{ErrorKind, SpError, SpResult};
+use md5::Md5;
+use opendal::Operator;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Whether the object at `key` holds exactly the bytes of `source_path`.
+/// A missing object is a mismatch, not an error.
+pub(crate) async fn remote_copy_matches(
+    operator: &Operator,
+    key: &str,
+    source_path: &Path,
+) -> SpResult<bool> {
+    let remote = match crate::objects::stat_object(operator, key).await {
+        Ok(remote) => remote,
+        Err(error) if matches!(error.kind, ErrorKind::NotFound) => return Ok(false),
+        Err(error) => return Err(error),
+    };
+    let source_size = tokio::fs::metadata(source_path)
+        .await
+        .map_err(|error| hash_error(source_path, error))?
+        .len();
+    let user_metadata = &remote.headers.user_metadata;
+    let sealed = crate::content_crypto::is_sealed(user_metadata);
+    let original_size = crate::compression::recorded_original_size(user_metadata);
+    let sizes_match = match original_size {
+        _ if sealed => remote.size == crate::content_crypto::sealed_len(source_size),
+        Some(original_size) => original_size == source_size,
+        None => remote.size == source_size,
+    };
+    if !sizes_match {
+        return Ok(false);
+    }
+    if let Some(expected) = crate::objects::recorded_source_sha256(user_metadata) {
+        return Ok(file_sha256(source_path).await? == expected);
+    }
+    // Any other digest covers the stored bytes, which are the file's only
+    // when it was stored byte for byte.
+    if sealed || original_size.is_some() {
+        return Ok(false);
+    }
+    if let Some(expected) = crate::objects::recorded_sha256(user_metadata) {
+        return Ok(file_sha256(source_path).await? == expected);
+    }
+    match remote.etag.as_deref().and_then(single_part_md5) {
+        Some(expected) => Ok(file_digest::<Md5>(source_path).await? == expected),
+        None => Ok(false),
+    }
+}
+
+/// The MD5 an ETag carries, if it is a plain 32-digit hex digest. Multipart
+/// ETags end in `-<parts>` and say nothing about the content as a whole.
+fn single_part_md5(etag: &str) -> Option<String> {
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    (etag.len() == 32 && etag.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        .then(|| etag.to_ascii_lowercase())
+}
+
+/// Lower-case hex SHA-256 of the file.
+pub(super) async fn file_sha256(path: &Path) -> SpResult<String> {
+    file_digest::<Sha256>(path).await
+}
+
+/// Lower-case hex digest of the file, read in chunks off the async runtime.
+async fn file_digest<D: Digest + Send + 'static>(path: &Path) -> SpResult<String>
+where
+    sha2::digest::Output<D>: std::fmt::LowerHex,
+{
+    let path = PathBuf::from(path);
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path).map_err(|error| hash_error(&path, error))?;
+        let mut hasher = D::new();
+        let mut buffer = vec![0; 1024 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .map_err(|error| hash_error(&path, error))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(|error| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("hashing task failed: {error}"),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    })?
+}
+
+fn hash_error(path: &Path, error: std::io::Error) -> SpError {
+    SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("hash {}: {error}", path.display()),
+        retry_after_ms: None,
+        context: None,
+        at: now_ms(),
+    }
+}
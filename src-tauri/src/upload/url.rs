@@ -0,0 +1,254 @@
+//! Tauri-independent import of an HTTP(S) resource.
+//!
+//! This module owns requesting the URL, naming the object and its content
+//! type from the response headers, and pumping the response body into the
+//! push-stream engine in `stream`, which applies pause/cancel, fails a body
+//! that stalls and holds the body back through its bounded channel. It does not own the transfer
+//! registry, construct credentials, read application settings, or emit
+//! Tauri events.
+
+use super::stream::{upload_stream, StreamUploadObserver, StreamUploadRequest};
+use super::{UploadControl, UploadedContent};
+use crate::types::{err_invalid, ErrorKind, SpError, SpResult};
+use opendal::Operator;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+
+/// Body chunks buffered ahead of the engine.
+const BODY_CHUNKS_IN_FLIGHT: usize = 8;
+/// Name given to a download whose response and URL suggest none.
+const FALLBACK_FILE_NAME: &str = "download";
+
+/// A response whose body has not been read yet.
+pub(super) struct UrlSource {
+    response: reqwest::Response,
+    /// Declared by `Content-Length`; `None` for chunked responses.
+    pub(super) content_length: Option<u64>,
+    /// `Content-Type` of the response, unless it is the generic
+    /// `application/octet-stream`, which says less than the body and the
+    /// file name do.
+    pub(super) content_type: Option<String>,
+    /// From `Content-Disposition`, else the last segment of the final URL.
+    pub(super) file_name: String,
+}
+
+/// Requests `url` and reads its headers. A status other than 2xx fails with
+/// the kind that status calls for.
+pub(super) async fn open_url(client: &reqwest::Client, url: &str) -> SpResult<UrlSource> {
+    let parsed = reqwest::Url::parse(url.trim())
+        .map_err(|error| err_invalid(&format!("invalid URL {url:?}: {error}")))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(err_invalid(&format!(
+            "only http and https URLs can be imported, not {}",
+            parsed.scheme()
+        )));
+    }
+    let response = client
+        .get(parsed.clone())
+        // The body is stored as sent; a coding the client undid would make
+        // it disagree with Content-Length.
+        .header(reqwest::header::ACCEPT_ENCODING, "identity")
+        .send()
+        .await
+        .map_err(|error| request_error(&parsed, &error))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(crate::storage::storage_error(
+            status_kind(status.as_u16()),
+            format!("GET {parsed}: HTTP {status}"),
+            "url import",
+            parsed.as_str(),
+            Some(status.as_u16()),
+        ));
+    }
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    let content_type = header(reqwest::header::CONTENT_TYPE)
+        .filter(|value| {
+            !value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .eq_ignore_ascii_case("application/octet-stream")
+        })
+        .map(str::to_string);
+    let file_name = header(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(disposition_file_name)
+        .or_else(|| url_file_name(response.url()))
+        .unwrap_or_else(|| FALLBACK_FILE_NAME.to_string());
+    Ok(UrlSource {
+        content_length: response.content_length(),
+        content_type,
+        file_name,
+        response,
+    })
+}
+
+/// Streams the body of `source` into the object `request.key`. The
+/// request's `expected_bytes` is replaced by the response's length; its
+/// `stall_timeout` bounds how long the body may go quiet, since the HTTP
+/// client has no read timeout unless the network settings set one.
+pub(super) async fn upload_url(
+    operator: &Operator,
+    request: StreamUploadRequest,
+    source: UrlSource,
+    control: UploadControl,
+    observer: &mut impl StreamUploadObserver,
+) -> SpResult<UploadedContent> {
+    let (sender, receiver) = mpsc::channel(BODY_CHUNKS_IN_FLIGHT);
+    let cancelled = control.cancelled.clone();
+    let request = StreamUploadRequest {
+        expected_bytes: source.content_length,
+        ..request
+    };
+    let (uploaded, pumped) = tokio::join!(
+        upload_stream(operator, request, receiver, control, observer),
+        pump_body(source.response, sender, &cancelled),
+    );
+    // A body that broke off explains the unfinished stream the engine saw.
+    pumped?;
+    uploaded
+}
+
+/// Sends the body to the engine and then the explicit finish. Stops quietly
+/// once the engine has hung up, even while the body is stalled, or the
+/// upload is cancelled; the engine reports either.
+async fn pump_body(
+    mut response: reqwest::Response,
+    sender: mpsc::Sender<Option<Vec<u8>>>,
+    cancelled: &AtomicBool,
+) -> SpResult<()> {
+    let url = response.url().clone();
+    loop {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk.map_err(|error| request_error(&url, &error))?,
+            () = cancellation(cancelled) => return Ok(()),
+            () = sender.closed() => return Ok(()),
+        };
+        let message = chunk.map(|bytes| bytes.to_vec());
+        let finished = message.is_none();
+        if sender.send(message).await.is_err() || finished {
+            return Ok(());
+        }
+    }
+}
+
+/// Resolves once `cancelled` is set, so a slow server cannot hold a
+/// cancellation back until its next chunk.
+async fn cancellation(cancelled: &AtomicBool) {
+    while !cancelled.load(Ordering::Relaxed) {
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+/// Like a storage status, except that credentials the remote server wants
+/// are not the profile's to refresh.
+fn status_kind(status: u16) -> ErrorKind {
+    match status {
+        401 => ErrorKind::PermissionDenied,
+        other => crate::storage::kind_for_status(other),
+    }
+}
+
+fn request_error(url: &reqwest::Url, error: &reqwest::Error) -> SpError {
+    let kind = if error.is_builder() {
+        ErrorKind::NotRetriable
+    } else {
+        ErrorKind::RetryableNet
+    };
+    crate::storage::storage_error(
+        kind,
+        format!("GET {url}: {error}"),
+        "url import",
+        url.as_str(),
+        error.status().map(|status| status.as_u16()),
+    )
+}
+
+/// The file name a `Content-Disposition` value gives: `filename*` (RFC 5987,
+/// UTF-8 only) before `filename`.
+pub(super) fn disposition_file_name(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+    for parameter in value.split(';').skip(1) {
+        let Some((name, argument)) = parameter.split_once('=') else {
+            continue;
+        };
+        let argument = argument.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                // charset'language'percent-encoded-name
+                let mut parts = argument.splitn(3, '\'');
+                if let (Some(charset), Some(_), Some(encoded)) =
+                    (parts.next(), parts.next(), parts.next())
+                {
+                    if charset.eq_ignore_ascii_case("utf-8") {
+                        extended = percent_decoded(encoded);
+                    }
+                }
+            }
+            "filename" => {
+                plain = Some(
+                    argument
+                        .strip_prefix('"')
+                        .and_then(|quoted| quoted.strip_suffix('"'))
+                        .map(|quoted| quoted.replace("\\\"", "\"").replace("\\\\", "\\"))
+                        .unwrap_or_else(|| argument.to_string()),
+                );
+            }
+            _ => {}
+        }
+    }
+    extended
+        .and_then(|name| safe_file_name(&name))
+        .or_else(|| plain.and_then(|name| safe_file_name(&name)))
+}
+
+/// The last non-empty path segment of `url`, decoded.
+pub(super) fn url_file_name(url: &reqwest::Url) -> Option<String> {
+    url.path_segments()?
+        .rev()
+        .find(|segment| !segment.is_empty())
+        .and_then(percent_decoded)
+        .and_then(|name| safe_file_name(&name))
+}
+
+/// `name` without directories or control characters; `None` when nothing
+/// usable is left.
+fn safe_file_name(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim();
+    (!matches!(cleaned, "" | "." | "..")).then(|| cleaned.to_string())
+}
+
+/// `value` with `%XX` escapes decoded; `None` when that is not UTF-8.
+fn percent_decoded(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| value.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
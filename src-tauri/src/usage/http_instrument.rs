@@ -28,7 +28,12 @@ fn classify_s3_action(
     let q = uri.query().unwrap_or("");
     match *method {
         http::Method::GET => {
-            if q.contains("list-type=") {
+            if has_query_key(q, "lifecycle") {
+                Some(ClassifiedAction {
+                    name: "GetBucketLifecycleConfiguration",
+                    class: OpClass::B,
+                })
+            } else if q.contains("list-type=") {
                 Some(ClassifiedAction {
                     name: "ListObjectsV2",
                     class: OpClass::A,
@@ -50,7 +55,12 @@ fn classify_s3_action(
             class: OpClass::B,
         }),
         http::Method::PUT => {
-            if q.contains("partNumber=") && q.contains("uploadId=") {
+            if has_query_key(q, "lifecycle") {
+                Some(ClassifiedAction {
+                    name: "PutBucketLifecycleConfiguration",
+                    class: OpClass::A,
+                })
+            } else if q.contains("partNumber=") && q.contains("uploadId=") {
                 Some(ClassifiedAction {
                     name: "UploadPart",
                     class: OpClass::A,
@@ -88,7 +98,12 @@ fn classify_s3_action(
             }
         }
         http::Method::DELETE => {
-            if q.contains("uploadId=") {
+            if has_query_key(q, "lifecycle") {
+                Some(ClassifiedAction {
+                    name: "DeleteBucketLifecycle",
+                    class: OpClass::A,
+                })
+            } else if q.contains("uploadId=") {
                 Some(ClassifiedAction {
                     name: "AbortMultipartUpload",
                     class: OpClass::A,
@@ -104,13 +119,27 @@ fn classify_s3_action(
     }
 }
 
-fn record_usage(
+/// Same rule as OpenDAL's own reqwest client: failures while sending or
+/// streaming a body are transient, builder and redirect errors are not.
+fn is_temporary_error(err: &reqwest::Error) -> bool {
+    err.is_request() || err.is_body() || err.is_decode()
+}
+
+/// Whether the query string names `key` as a parameter, with or without a
+/// value. Subresources such as `?lifecycle` must not match object prefixes.
+fn has_query_key(query: &str, key: &str) -> bool {
+    query
+        .split('&')
+        .any(|pair| pair.split_once('=').map_or(pair, |(name, _)| name) == key)
+}
+
+fn usage_delta(
     action: &ClassifiedAction,
     ingress: u64,
     egress: u64,
     added: u64,
     deleted: u64,
-) -> SpResult<()> {
+) -> UsageDelta {
     let mut a = std::collections::HashMap::new();
     let mut b = std::collections::HashMap::new();
     match action.class {
@@ -121,26 +150,73 @@ fn record_usage(
             b.insert(action.name.into(), 1u64);
         }
     }
-    crate::logger::debug("usage", &format!("record_usage: {:?}", action));
-    UsageSync::record_local_delta(UsageDelta {
+    UsageDelta {
         class_a: a,
         class_b: b,
         ingress_bytes: ingress,
         egress_bytes: egress,
         added_storage_bytes: added,
         deleted_storage_bytes: deleted,
-    })
+        ..UsageDelta::default()
+    }
+}
+
+/// Usage of a request sending `body_len` payload bytes: an object or part
+/// upload counts its payload as ingress and as added storage.
+fn request_delta(action: &ClassifiedAction, body_len: u64) -> UsageDelta {
+    let uploaded = match action.name {
+        "PutObject" | "UploadPart" => body_len,
+        _ => 0,
+    };
+    usage_delta(action, uploaded, 0, uploaded, 0)
+}
+
+fn record_usage(profile: &str, action: &ClassifiedAction, delta: UsageDelta) -> SpResult<()> {
+    crate::logger::debug("usage", &format!("record_usage: {:?}", action));
+    UsageSync::record_local_delta(profile, delta)
+}
+
+/// Usage of a request sent outside OpenDAL, e.g. a signed CopyObject that
+/// replaces metadata or an UploadPart of a resumable upload.
+fn raw_request_delta(
+    method: &http::Method,
+    uri: &http::Uri,
+    headers: &http::HeaderMap,
+    body_len: u64,
+) -> Option<(ClassifiedAction, UsageDelta)> {
+    let action = classify_s3_action(method, uri, headers)?;
+    let delta = request_delta(&action, body_len);
+    Some((action, delta))
+}
+
+/// Counts a request sent outside OpenDAL to the bucket of `profile`, like
+/// operator traffic.
+pub(crate) fn record_raw_request(
+    profile: &str,
+    method: &http::Method,
+    uri: &http::Uri,
+    headers: &http::HeaderMap,
+    body_len: u64,
+) {
+    if let Some((action, delta)) = raw_request_delta(method, uri, headers, body_len) {
+        let _ = record_usage(profile, &action, delta);
+    }
 }
 
 /// A reqwest-based HttpFetch that instruments S3 calls for Class A/B counting.
+/// Usage is recorded against `profile`, the profile whose bucket it talks to.
 #[derive(Clone)]
 pub struct InstrumentedReqwest {
     inner: reqwest::Client,
+    profile: String,
 }
 
 impl InstrumentedReqwest {
-    pub fn new(inner: reqwest::Client) -> Self {
-        Self { inner }
+    pub fn new(inner: reqwest::Client, profile: &str) -> Self {
+        Self {
+            inner,
+            profile: profile.to_string(),
+        }
     }
 }
 
@@ -150,6 +226,7 @@ impl HttpFetch for InstrumentedReqwest {
         req: http::Request<Buffer>,
     ) -> impl future::Future<Output = OdResult<http::Response<HttpBody>>> + MaybeSend {
         let client = self.inner.clone();
+        let profile = self.profile.clone();
         async move {
             // Clone uri & method for context and classification.
             let uri = req.uri().clone();
@@ -213,6 +290,7 @@ impl HttpFetch for InstrumentedReqwest {
                 Error::new(OdErrorKind::Unexpected, "send http request")
                     .with_operation("http_util::Client::send")
                     .with_context("url", uri.to_string())
+                    .with_temporary(is_temporary_error(&err))
                     .set_source(err)
             })?;
 
@@ -238,21 +316,8 @@ impl HttpFetch for InstrumentedReqwest {
             // Instrumentation: classify and record usage based on request (always on)
             let mut is_get_object = false;
             if let Some(action) = classify_s3_action(&method, &uri, &parts.headers) {
-                // Upload (ingress/storage) for PUT/POST with payload
-                let mut ingress_bytes: u64 = 0;
-                let mut added_bytes: u64 = 0;
-                match action.name {
-                    "PutObject" | "UploadPart" => {
-                        let sz = body.len() as u64;
-                        ingress_bytes = ingress_bytes.saturating_add(sz);
-                        added_bytes = added_bytes.saturating_add(sz);
-                    }
-                    "GetObject" => {
-                        is_get_object = true;
-                    }
-                    _ => {}
-                }
-                let _ = record_usage(&action, ingress_bytes, 0, added_bytes, 0);
+                is_get_object = action.name == "GetObject";
+                let _ = record_usage(&profile, &action, request_delta(&action, body.len() as u64));
             }
 
             // Construct streaming body
@@ -261,14 +326,13 @@ impl HttpFetch for InstrumentedReqwest {
                 .try_filter(|v| future::ready(!v.is_empty()))
                 .map_ok(move |bs| {
                     if is_get_object {
-                        let _ = UsageSync::record_local_delta(UsageDelta {
-                            class_a: Default::default(),
-                            class_b: Default::default(),
-                            ingress_bytes: 0,
-                            egress_bytes: bs.len() as u64,
-                            added_storage_bytes: 0,
-                            deleted_storage_bytes: 0,
-                        });
+                        let _ = UsageSync::record_local_delta(
+                            &profile,
+                            UsageDelta {
+                                egress_bytes: bs.len() as u64,
+                                ..UsageDelta::default()
+                            },
+                        );
                     }
                     Buffer::from(bs)
                 })
@@ -276,6 +340,7 @@ impl HttpFetch for InstrumentedReqwest {
                     Error::new(OdErrorKind::Unexpected, "read data from http response")
                         .with_operation("http_util::Client::send")
                         .with_context("url", uri.to_string())
+                        .with_temporary(is_temporary_error(&err))
                         .set_source(err)
                 });
             let body = HttpBody::new(stream, content_length);
@@ -131,6 +131,49 @@ fn delete_requests_distinguish_object_delete_and_multipart_abort() {
     );
 }
 
+#[test]
+fn bucket_lifecycle_requests_are_classified_by_subresource() {
+    assert_action(
+        http::Method::GET,
+        "https://bucket.example/?lifecycle=",
+        http::HeaderMap::new(),
+        "GetBucketLifecycleConfiguration",
+        OpClass::B,
+    );
+    assert_action(
+        http::Method::PUT,
+        "https://bucket.example/?lifecycle",
+        http::HeaderMap::new(),
+        "PutBucketLifecycleConfiguration",
+        OpClass::A,
+    );
+    assert_action(
+        http::Method::DELETE,
+        "https://bucket.example/?lifecycle=",
+        http::HeaderMap::new(),
+        "DeleteBucketLifecycle",
+        OpClass::A,
+    );
+}
+
+#[test]
+fn lifecycle_as_an_object_prefix_is_not_a_bucket_subresource() {
+    assert_action(
+        http::Method::GET,
+        "https://bucket.example/?list-type=2&prefix=lifecycle",
+        http::HeaderMap::new(),
+        "ListObjectsV2",
+        OpClass::A,
+    );
+    assert_action(
+        http::Method::DELETE,
+        "https://bucket.example/lifecycle",
+        http::HeaderMap::new(),
+        "DeleteObject",
+        OpClass::A,
+    );
+}
+
 #[test]
 fn unsupported_http_methods_are_not_counted_as_s3_actions() {
     let result = classify_s3_action(
@@ -143,3 +186,40 @@ fn unsupported_http_methods_are_not_counted_as_s3_actions() {
 
     assert!(result.is_none());
 }
+
+#[test]
+fn raw_part_uploads_count_their_payload_as_ingress_and_storage() {
+    let (action, delta) = raw_request_delta(
+        &http::Method::PUT,
+        &"https://bucket.example/photos/image.arw?partNumber=2&uploadId=upload-1"
+            .parse::<http::Uri>()
+            .expect("test URI should parse"),
+        &http::HeaderMap::new(),
+        8 * 1024 * 1024,
+    )
+    .expect("UploadPart should be counted");
+    assert_eq!(action.name, "UploadPart");
+    assert_eq!(delta.class_a.get("UploadPart"), Some(&1));
+    assert!(delta.class_b.is_empty());
+    assert_eq!(delta.ingress_bytes, 8 * 1024 * 1024);
+    assert_eq!(delta.added_storage_bytes, 8 * 1024 * 1024);
+    assert_eq!(delta.egress_bytes, 0);
+
+    let mut copy_headers = http::HeaderMap::new();
+    copy_headers.insert(
+        "x-amz-copy-source",
+        http::HeaderValue::from_static("/bucket/photos/image.arw"),
+    );
+    let (_, copy) = raw_request_delta(
+        &http::Method::PUT,
+        &"https://bucket.example/photos/image.arw"
+            .parse::<http::Uri>()
+            .expect("test URI should parse"),
+        &copy_headers,
+        0,
+    )
+    .expect("CopyObject should be counted");
+    assert_eq!(copy.class_a.get("CopyObject"), Some(&1));
+    assert_eq!(copy.ingress_bytes, 0);
+    assert_eq!(copy.added_storage_bytes, 0);
+}
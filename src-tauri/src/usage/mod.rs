@@ -1,12 +1,14 @@
 use crate::types::*;
 pub mod http_instrument;
+pub mod pricing;
 use crate::{sp_backend::SpBackend, storage};
 use chrono::NaiveDate;
 // use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::sync::Mutex as AsyncMutex;
 
 pub struct UsageSync;
@@ -14,173 +16,103 @@ pub struct UsageSync;
 impl UsageSync {
     /// Flush all locally accumulated usage deltas to remote ledgers.
     ///
-    /// Scans `usage_deltas/` for `YYYY-MM-DD.json` files and merges each
-    /// into the corresponding daily ledger, clearing the local file after.
-    /// Returns number of days merged.
+    /// Scans `usage_deltas/<profile>/` of every profile in the vault for
+    /// `YYYY-MM-DD.json` files and merges each into that profile's daily
+    /// ledger, clearing the local file after. Returns number of days merged.
     pub async fn sync_all_local_deltas() -> SpResult<usize> {
-        let dir = app_dir()?.join("usage_deltas");
-        if !dir.exists() {
-            return Ok(0);
-        }
-        let mut dates: Vec<String> = Vec::new();
-        let rd = std::fs::read_dir(&dir).map_err(ioe)?;
-        for ent in rd {
-            let ent = ent.map_err(ioe)?;
-            let path = ent.path();
-            if !path.is_file() {
-                continue;
-            }
-            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+        let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
+        let root = app_dir()?.join("usage_deltas");
+        adopt_unkeyed_deltas(&root, &bundle.active_profile)?;
+        let mut merged = 0usize;
+        for profile in bundle.profiles.keys() {
+            let dates = pending_dates(&profile_delta_dir(&root, profile))?;
+            if dates.is_empty() {
                 continue;
             }
-            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                if chrono::NaiveDate::parse_from_str(stem, "%Y-%m-%d").is_ok() {
-                    dates.push(stem.to_string());
-                }
-            }
-        }
-        dates.sort_unstable();
-        if dates.is_empty() {
-            return Ok(0);
-        }
-        crate::logger::info(
-            "usage",
-            &format!("startup sync: pending days = {:?}", dates),
-        );
-        let mut merged = 0usize;
-        for d in dates {
-            match Self::merge_and_write_day(&d).await {
-                Ok(_) => {
-                    merged += 1;
-                }
-                Err(e) => {
-                    crate::logger::error(
-                        "usage",
-                        &format!("startup sync failed for {}: {}", d, e.message),
-                    );
+            crate::logger::info(
+                "usage",
+                &format!("startup sync: profile={profile} pending days = {:?}", dates),
+            );
+            for d in dates {
+                match Self::merge_and_write_day(profile, &d).await {
+                    Ok(_) => {
+                        merged += 1;
+                    }
+                    Err(e) => {
+                        crate::logger::error(
+                            "usage",
+                            &format!("startup sync failed for {profile} {}: {}", d, e.message),
+                        );
+                    }
                 }
             }
         }
         Ok(merged)
     }
-    pub fn record_local_delta(delta: UsageDelta) -> SpResult<()> {
-        let p = local_delta_path_for_today()?;
-        let mut cur: UsageDelta = if p.exists() {
-            serde_json::from_slice(&fs::read(&p).map_err(ioe)?).unwrap_or(UsageDelta {
-                class_a: Default::default(),
-                class_b: Default::default(),
-                ingress_bytes: 0,
-                egress_bytes: 0,
-                added_storage_bytes: 0,
-                deleted_storage_bytes: 0,
-            })
-        } else {
+
+    /// Adds `delta` to today's local delta of `profile`, the profile whose
+    /// bucket the traffic went to.
+    pub fn record_local_delta(profile: &str, delta: UsageDelta) -> SpResult<()> {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        add_to_delta_file(&local_delta_path(profile, &today)?, delta)
+    }
+
+    /// Records the source size of a finished upload, as ingress and as added
+    /// storage. What was stored, after compression or sealing, is counted
+    /// per request by `http_instrument`.
+    pub fn record_logical_upload(profile: &str, source_bytes: u64) -> SpResult<()> {
+        Self::record_local_delta(
+            profile,
             UsageDelta {
-                class_a: Default::default(),
-                class_b: Default::default(),
-                ingress_bytes: 0,
-                egress_bytes: 0,
-                added_storage_bytes: 0,
-                deleted_storage_bytes: 0,
-            }
-        };
-        for (k, v) in delta.class_a {
-            *cur.class_a.entry(k).or_insert(0) += v;
-        }
-        for (k, v) in delta.class_b {
-            *cur.class_b.entry(k).or_insert(0) += v;
-        }
-        cur.ingress_bytes += delta.ingress_bytes;
-        cur.egress_bytes += delta.egress_bytes;
-        cur.added_storage_bytes += delta.added_storage_bytes;
-        cur.deleted_storage_bytes += delta.deleted_storage_bytes;
-        // Ensure parent dir exists
-        if let Some(parent) = p.parent() {
-            fs::create_dir_all(parent).map_err(ioe)?;
-        } else {
-            return Err(SpError {
-                kind: ErrorKind::NotRetriable,
-                message: "invalid usage delta path".into(),
-                retry_after_ms: None,
-                context: None,
-                at: chrono::Utc::now().timestamp_millis(),
-            });
-        }
-        let cur_bytes = serde_json::to_vec(&cur).map_err(|e| SpError {
-            kind: ErrorKind::NotRetriable,
-            message: format!("serialize usage delta failed: {e}"),
-            retry_after_ms: None,
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        })?;
-        fs::write(p, cur_bytes).map_err(ioe)?;
-        Ok(())
+                logical_ingress_bytes: source_bytes,
+                added_logical_storage_bytes: source_bytes,
+                ..UsageDelta::default()
+            },
+        )
     }
 
-    pub async fn merge_and_write_day(date: &str) -> SpResult<DailyLedger> {
+    /// Merges the local delta of `profile` for `date` into the ledger in
+    /// that profile's bucket.
+    pub async fn merge_and_write_day(profile: &str, date: &str) -> SpResult<DailyLedger> {
         // Serialize merges to avoid concurrent duplicate writes
         static MERGE_LOCK: Lazy<AsyncMutex<()>> = Lazy::new(|| AsyncMutex::new(()));
         let _guard = MERGE_LOCK.lock().await;
-        crate::logger::info("usage", &format!("merge_and_write_day date:: {}", date));
+        crate::logger::info(
+            "usage",
+            &format!("merge_and_write_day profile={profile} date={date}"),
+        );
         // Fast-path: if already merged today, skip R2 ops entirely
         let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
         if date == today {
             if let Ok(st) = read_usage_state() {
-                if st.last_merge_date == date {
+                if st.last_merge_dates.get(profile).map(String::as_str) == Some(date) {
                     // No-op result to reduce R2 operations; caller typically ignores return and reloads separately
                     return Ok(DailyLedger {
                         date: date.into(),
-                        class_a: Default::default(),
-                        class_b: Default::default(),
-                        ingress_bytes: 0,
-                        egress_bytes: 0,
-                        storage_bytes: 0,
-                        peak_storage_bytes: 0,
-                        deleted_storage_bytes: 0,
-                        rev: 0,
                         updated_at: chrono::Utc::now().to_rfc3339(),
+                        ..DailyLedger::default()
                     });
                 }
             }
         }
-        let p = local_delta_path(date)?;
+        let p = local_delta_path(profile, date)?;
         let local: UsageDelta = if p.exists() {
-            serde_json::from_slice(&fs::read(&p).map_err(ioe)?).unwrap_or(UsageDelta {
-                class_a: Default::default(),
-                class_b: Default::default(),
-                ingress_bytes: 0,
-                egress_bytes: 0,
-                added_storage_bytes: 0,
-                deleted_storage_bytes: 0,
-            })
+            serde_json::from_slice(&fs::read(&p).map_err(ioe)?).unwrap_or_default()
         } else {
-            UsageDelta {
-                class_a: Default::default(),
-                class_b: Default::default(),
-                ingress_bytes: 0,
-                egress_bytes: 0,
-                added_storage_bytes: 0,
-                deleted_storage_bytes: 0,
-            }
+            UsageDelta::default()
         };
 
         let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-        let operator = storage::build_operator(&bundle.r2).await?;
+        let operator = storage::build_profile_operator(&bundle, profile).await?;
         let key = format!("{}{}.json", ANALYTICS_PREFIX, date);
 
         let (mut day, etag) = match read_usage_object_optional(&operator, &key).await? {
             Some((bytes, etag)) => {
                 let parsed: DailyLedger = serde_json::from_slice(&bytes).unwrap_or(DailyLedger {
                     date: date.into(),
-                    class_a: Default::default(),
-                    class_b: Default::default(),
-                    ingress_bytes: 0,
-                    egress_bytes: 0,
-                    storage_bytes: 0,
-                    peak_storage_bytes: 0,
-                    deleted_storage_bytes: 0,
                     rev: 1,
                     updated_at: chrono::Utc::now().to_rfc3339(),
+                    ..DailyLedger::default()
                 });
                 (parsed, etag)
             }
@@ -204,6 +136,10 @@ impl UsageSync {
             *day.class_b.entry(k).or_insert(0) += v;
         }
         day.ingress_bytes += local.ingress_bytes;
+        day.logical_ingress_bytes += local.logical_ingress_bytes;
+        day.added_logical_storage_bytes = day
+            .added_logical_storage_bytes
+            .saturating_add(local.added_logical_storage_bytes);
         day.egress_bytes += local.egress_bytes;
         // 存储计费：根据本地增量估算存量并更新峰值
         // storage_bytes(t) ≈ storage_bytes(t-1) + added - deleted
@@ -236,7 +172,7 @@ impl UsageSync {
         // Clear local
         let _ = fs::remove_file(p);
         // Persist state that we merged this date
-        let _ = write_usage_state(date);
+        let _ = write_usage_state(profile, date);
         Ok(day)
     }
 
@@ -252,18 +188,15 @@ impl UsageSync {
         let is_current_month = prefix == today_month;
 
         let bundle = SpBackend::get_decrypted_bundle_if_unlocked()?;
-        let operator = storage::build_operator(&bundle.r2).await?;
+        let operator = storage::build_operator(&bundle.active_profile, &bundle.r2).await?;
 
         // List existing objects for this month via OpenDAL
         let list_prefix = format!("{}{}-", ANALYTICS_PREFIX, prefix);
         let mut days_present: Vec<u32> = vec![];
-        let l = operator.list(&list_prefix).await.map_err(|e| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("list (usage month): {e}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        })?;
+        let l = operator
+            .list(&list_prefix)
+            .await
+            .map_err(|e| storage::opendal_error("list (usage month)", &list_prefix, &e))?;
         for e in l.into_iter() {
             let key = e.path().to_string();
             if let Some(day_str) = key.strip_prefix(&list_prefix) {
@@ -313,70 +246,10 @@ impl UsageSync {
     }
 
     pub async fn month_cost(prefix: &str) -> SpResult<serde_json::Value> {
-        // Read month (cached) and compute cost per R2 logic
+        // Read month (cached) and price it with the active provider's table
         let days = Self::list_month(prefix).await?;
-        // Storage: sum daily peak_storage_bytes (GB) / 30 → avg GB-month; ceil to integer; subtract free 10 GB-month (floor at 0)
-        let sum_peak_gb: f64 = days
-            .iter()
-            .map(|d| (d.peak_storage_bytes as f64) / (1024.0 * 1024.0 * 1024.0))
-            .sum();
-        let avg_gb_month = (sum_peak_gb / 30.0).ceil();
-        let free_gb = 10.0_f64;
-        let billable_gb = (avg_gb_month - free_gb).max(0.0);
-        let storage_cost = billable_gb * 0.0; // If Standard cost per GB-month is desired later, set here; currently only usage breakdown
-
-        // Ops: sum class_a/b; apply free tiers 1m/10m, then ceil to next million
-        let total_a: u64 = days
-            .iter()
-            .map(|d| d.class_a.values().copied().sum::<u64>())
-            .sum();
-        let total_b: u64 = days
-            .iter()
-            .map(|d| d.class_b.values().copied().sum::<u64>())
-            .sum();
-        let free_a: u64 = 1_000_000;
-        let free_b: u64 = 10_000_000;
-        let over_a = total_a.saturating_sub(free_a);
-        let over_b = total_b.saturating_sub(free_b);
-        let units_a_m = if over_a == 0 {
-            0
-        } else {
-            (over_a + 1_000_000 - 1) / 1_000_000
-        } as u64;
-        let units_b_m = if over_b == 0 {
-            0
-        } else {
-            (over_b + 1_000_000 - 1) / 1_000_000
-        } as u64;
-        let cost_a = (units_a_m as f64) * 4.50;
-        let cost_b = (units_b_m as f64) * 0.36;
-
-        let report = serde_json::json!({
-            "month": prefix,
-            "storage": {
-                "sum_peak_gb": sum_peak_gb,
-                "avg_gb_month_ceil": avg_gb_month,
-                "free_gb_month": free_gb,
-                "billable_gb_month": billable_gb,
-                "cost_usd": storage_cost,
-            },
-            "class_a": {
-                "total_ops": total_a,
-                "free_ops": free_a,
-                "billable_millions": units_a_m,
-                "unit_price": 4.50,
-                "cost_usd": cost_a,
-            },
-            "class_b": {
-                "total_ops": total_b,
-                "free_ops": free_b,
-                "billable_millions": units_b_m,
-                "unit_price": 0.36,
-                "cost_usd": cost_b,
-            },
-            "total_cost_usd": storage_cost + cost_a + cost_b,
-        });
-        Ok(report)
+        let provider = SpBackend::get_decrypted_bundle_if_unlocked()?.r2.provider;
+        Ok(pricing::month_cost_report(prefix, provider, &days))
     }
 }
 
@@ -385,14 +258,99 @@ fn app_dir() -> SpResult<PathBuf> {
     crate::sp_backend::vault_dir()
 }
 
-fn local_delta_path_for_today() -> SpResult<PathBuf> {
-    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-    local_delta_path(&today)
+fn local_delta_path(profile: &str, date: &str) -> SpResult<PathBuf> {
+    let root = app_dir()?.join("usage_deltas");
+    Ok(profile_delta_dir(&root, profile).join(format!("{}.json", date)))
 }
 
-fn local_delta_path(date: &str) -> SpResult<PathBuf> {
-    let dir = app_dir()?.join("usage_deltas");
-    Ok(dir.join(format!("{}.json", date)))
+/// The directory holding the deltas of `profile` below `root`. Profile names
+/// may hold any printable character, so the directory is named after their
+/// hash.
+fn profile_delta_dir(root: &Path, profile: &str) -> PathBuf {
+    root.join(format!("{:x}", Sha256::digest(profile.as_bytes())))
+}
+
+/// Dates, in order, of the `YYYY-MM-DD.json` deltas directly in `dir`.
+fn pending_dates(dir: &Path) -> SpResult<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut dates: Vec<String> = Vec::new();
+    let rd = std::fs::read_dir(dir).map_err(ioe)?;
+    for ent in rd {
+        let ent = ent.map_err(ioe)?;
+        let path = ent.path();
+        if !path.is_file() {
+            continue;
+        }
+        if path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            if chrono::NaiveDate::parse_from_str(stem, "%Y-%m-%d").is_ok() {
+                dates.push(stem.to_string());
+            }
+        }
+    }
+    dates.sort_unstable();
+    Ok(dates)
+}
+
+/// Moves deltas written before they were kept per profile into the
+/// directory of `profile`. They were always merged into the active
+/// profile's ledger, so they still are.
+fn adopt_unkeyed_deltas(root: &Path, profile: &str) -> SpResult<()> {
+    for date in pending_dates(root)? {
+        let unkeyed = root.join(format!("{date}.json"));
+        let delta = serde_json::from_slice(&fs::read(&unkeyed).map_err(ioe)?).unwrap_or_default();
+        add_to_delta_file(
+            &profile_delta_dir(root, profile).join(format!("{date}.json")),
+            delta,
+        )?;
+        fs::remove_file(unkeyed).map_err(ioe)?;
+    }
+    Ok(())
+}
+
+fn add_to_delta_file(p: &Path, delta: UsageDelta) -> SpResult<()> {
+    let mut cur: UsageDelta = if p.exists() {
+        serde_json::from_slice(&fs::read(p).map_err(ioe)?).unwrap_or_default()
+    } else {
+        UsageDelta::default()
+    };
+    for (k, v) in delta.class_a {
+        *cur.class_a.entry(k).or_insert(0) += v;
+    }
+    for (k, v) in delta.class_b {
+        *cur.class_b.entry(k).or_insert(0) += v;
+    }
+    cur.ingress_bytes += delta.ingress_bytes;
+    cur.egress_bytes += delta.egress_bytes;
+    cur.added_storage_bytes += delta.added_storage_bytes;
+    cur.added_logical_storage_bytes += delta.added_logical_storage_bytes;
+    cur.deleted_storage_bytes += delta.deleted_storage_bytes;
+    cur.logical_ingress_bytes += delta.logical_ingress_bytes;
+    // Ensure parent dir exists
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent).map_err(ioe)?;
+    } else {
+        return Err(SpError {
+            kind: ErrorKind::NotRetriable,
+            message: "invalid usage delta path".into(),
+            retry_after_ms: None,
+            context: None,
+            at: chrono::Utc::now().timestamp_millis(),
+        });
+    }
+    let cur_bytes = serde_json::to_vec(&cur).map_err(|e| SpError {
+        kind: ErrorKind::NotRetriable,
+        message: format!("serialize usage delta failed: {e}"),
+        retry_after_ms: None,
+        context: None,
+        at: chrono::Utc::now().timestamp_millis(),
+    })?;
+    fs::write(p, cur_bytes).map_err(ioe)?;
+    Ok(())
 }
 
 fn ioe(e: std::io::Error) -> SpError {
@@ -407,7 +365,9 @@ fn ioe(e: std::io::Error) -> SpError {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 struct UsageState {
-    last_merge_date: String,
+    /// The date each profile's ledger was last merged.
+    #[serde(default)]
+    last_merge_dates: std::collections::BTreeMap<String, String>,
 }
 
 fn usage_state_path() -> SpResult<PathBuf> {
@@ -424,14 +384,13 @@ fn read_usage_state() -> SpResult<UsageState> {
     Ok(st)
 }
 
-fn write_usage_state(date: &str) -> SpResult<()> {
+fn write_usage_state(profile: &str, date: &str) -> SpResult<()> {
     let p = usage_state_path()?;
     if let Some(parent) = p.parent() {
         fs::create_dir_all(parent).map_err(ioe)?;
     }
-    let st = UsageState {
-        last_merge_date: date.into(),
-    };
+    let mut st = read_usage_state().unwrap_or_default();
+    st.last_merge_dates.insert(profile.into(), date.into());
     let bytes = serde_json::to_vec(&st).map_err(|e| SpError {
         kind: ErrorKind::NotRetriable,
         message: format!("serialize usage state: {e}"),
@@ -463,15 +422,11 @@ async fn initial_ledger_with_baseline(
 
     Ok(DailyLedger {
         date: date.into(),
-        class_a: Default::default(),
-        class_b: Default::default(),
-        ingress_bytes: 0,
-        egress_bytes: 0,
         storage_bytes: baseline_storage,
         peak_storage_bytes: baseline_storage,
-        deleted_storage_bytes: 0,
         rev: 1,
         updated_at: chrono::Utc::now().to_rfc3339(),
+        ..DailyLedger::default()
     })
 }
 
@@ -497,13 +452,10 @@ async fn latest_ledger_before(
 
 async fn compute_bucket_total_storage(operator: &opendal::Operator) -> SpResult<u64> {
     let mut total: u64 = 0;
-    let l = operator.list("").await.map_err(|e| SpError {
-        kind: ErrorKind::RetryableNet,
-        message: format!("list (total storage): {e}"),
-        retry_after_ms: Some(500),
-        context: None,
-        at: chrono::Utc::now().timestamp_millis(),
-    })?;
+    let l = operator
+        .list("")
+        .await
+        .map_err(|e| storage::opendal_error("list (total storage)", "", &e))?;
     for e in l.into_iter() {
         let path = e.path();
         if path.ends_with('/') {
@@ -523,12 +475,14 @@ async fn read_usage_object(
 ) -> SpResult<(Vec<u8>, Option<String>)> {
     read_usage_object_optional(operator, key)
         .await?
-        .ok_or_else(|| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("GetObject: not found: {key}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
+        .ok_or_else(|| {
+            storage::storage_error(
+                ErrorKind::NotFound,
+                format!("GetObject: not found: {key}"),
+                "GetObject",
+                key,
+                Some(404),
+            )
         })
 }
 
@@ -546,13 +500,7 @@ async fn read_usage_object_optional(
             Ok(Some((data.to_vec(), etag)))
         }
         Err(error) if matches!(error.kind(), opendal::ErrorKind::NotFound) => Ok(None),
-        Err(error) => Err(SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("GetObject: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        }),
+        Err(error) => Err(storage::opendal_error("GetObject", key, &error)),
     }
 }
 
@@ -570,13 +518,7 @@ async fn write_usage_object(
         .write(key, bytes)
         .await
         .map(|_| ())
-        .map_err(|error| SpError {
-            kind: ErrorKind::RetryableNet,
-            message: format!("PutObject: {error}"),
-            retry_after_ms: Some(500),
-            context: None,
-            at: chrono::Utc::now().timestamp_millis(),
-        })
+        .map_err(|error| storage::opendal_error("PutObject", key, &error))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -616,3 +558,6 @@ fn write_month_cache(prefix: &str, cache: &MonthCache) -> SpResult<()> {
     fs::write(p, bytes).map_err(ioe)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests;
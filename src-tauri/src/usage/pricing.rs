@@ -0,0 +1,143 @@
+use crate::types::{DailyLedger, StorageProvider};
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Published list prices used for monthly estimates. Operation classes follow
+/// the ledger's R2-style Class A (mutating/list) and Class B (read) split.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProviderPricing {
+    pub storage_usd_per_gb_month: f64,
+    pub free_storage_gb_month: f64,
+    pub class_a_usd_per_million: f64,
+    pub free_class_a_ops: u64,
+    pub class_b_usd_per_million: f64,
+    pub free_class_b_ops: u64,
+    pub egress_usd_per_gb: f64,
+}
+
+pub fn pricing_for(provider: StorageProvider) -> ProviderPricing {
+    match provider {
+        StorageProvider::R2 | StorageProvider::R2Eu | StorageProvider::R2Fedramp => {
+            ProviderPricing {
+                storage_usd_per_gb_month: 0.015,
+                free_storage_gb_month: 10.0,
+                class_a_usd_per_million: 4.50,
+                free_class_a_ops: 1_000_000,
+                class_b_usd_per_million: 0.36,
+                free_class_b_ops: 10_000_000,
+                egress_usd_per_gb: 0.0,
+            }
+        }
+        StorageProvider::AwsS3 => ProviderPricing {
+            storage_usd_per_gb_month: 0.023,
+            free_storage_gb_month: 0.0,
+            class_a_usd_per_million: 5.00,
+            free_class_a_ops: 0,
+            class_b_usd_per_million: 0.40,
+            free_class_b_ops: 0,
+            egress_usd_per_gb: 0.09,
+        },
+        StorageProvider::BackblazeB2 => ProviderPricing {
+            storage_usd_per_gb_month: 0.006,
+            free_storage_gb_month: 10.0,
+            class_a_usd_per_million: 0.0,
+            free_class_a_ops: 0,
+            class_b_usd_per_million: 0.40,
+            free_class_b_ops: 0,
+            egress_usd_per_gb: 0.0,
+        },
+        StorageProvider::Wasabi => ProviderPricing {
+            storage_usd_per_gb_month: 0.0068,
+            free_storage_gb_month: 0.0,
+            class_a_usd_per_million: 0.0,
+            free_class_a_ops: 0,
+            class_b_usd_per_million: 0.0,
+            free_class_b_ops: 0,
+            egress_usd_per_gb: 0.0,
+        },
+        // Self-hosted: no provider bill to estimate.
+        StorageProvider::Minio => ProviderPricing {
+            storage_usd_per_gb_month: 0.0,
+            free_storage_gb_month: 0.0,
+            class_a_usd_per_million: 0.0,
+            free_class_a_ops: 0,
+            class_b_usd_per_million: 0.0,
+            free_class_b_ops: 0,
+            egress_usd_per_gb: 0.0,
+        },
+    }
+}
+
+pub(super) fn month_cost_report(
+    month: &str,
+    provider: StorageProvider,
+    days: &[DailyLedger],
+) -> serde_json::Value {
+    let pricing = pricing_for(provider);
+    // Storage: sum daily peak_storage_bytes (GB) / 30 → avg GB-month; ceil to integer; subtract free tier (floor at 0)
+    let sum_peak_gb: f64 = days
+        .iter()
+        .map(|d| (d.peak_storage_bytes as f64) / GIB)
+        .sum();
+    let avg_gb_month = (sum_peak_gb / 30.0).ceil();
+    let free_gb = pricing.free_storage_gb_month;
+    let billable_gb = (avg_gb_month - free_gb).max(0.0);
+    let storage_cost = billable_gb * pricing.storage_usd_per_gb_month;
+
+    // Ops: sum class_a/b; apply free tiers, then ceil to next million
+    let total_a: u64 = days
+        .iter()
+        .map(|d| d.class_a.values().copied().sum::<u64>())
+        .sum();
+    let total_b: u64 = days
+        .iter()
+        .map(|d| d.class_b.values().copied().sum::<u64>())
+        .sum();
+    let units_a_m = billable_millions(total_a, pricing.free_class_a_ops);
+    let units_b_m = billable_millions(total_b, pricing.free_class_b_ops);
+    let cost_a = (units_a_m as f64) * pricing.class_a_usd_per_million;
+    let cost_b = (units_b_m as f64) * pricing.class_b_usd_per_million;
+
+    let egress_gb: f64 = days.iter().map(|d| d.egress_bytes as f64 / GIB).sum();
+    let egress_cost = egress_gb * pricing.egress_usd_per_gb;
+
+    serde_json::json!({
+        "month": month,
+        "provider": provider.as_str(),
+        "storage": {
+            "sum_peak_gb": sum_peak_gb,
+            "avg_gb_month_ceil": avg_gb_month,
+            "free_gb_month": free_gb,
+            "billable_gb_month": billable_gb,
+            "unit_price": pricing.storage_usd_per_gb_month,
+            "cost_usd": storage_cost,
+        },
+        "class_a": {
+            "total_ops": total_a,
+            "free_ops": pricing.free_class_a_ops,
+            "billable_millions": units_a_m,
+            "unit_price": pricing.class_a_usd_per_million,
+            "cost_usd": cost_a,
+        },
+        "class_b": {
+            "total_ops": total_b,
+            "free_ops": pricing.free_class_b_ops,
+            "billable_millions": units_b_m,
+            "unit_price": pricing.class_b_usd_per_million,
+            "cost_usd": cost_b,
+        },
+        "egress": {
+            "total_gb": egress_gb,
+            "unit_price": pricing.egress_usd_per_gb,
+            "cost_usd": egress_cost,
+        },
+        "total_cost_usd": storage_cost + cost_a + cost_b + egress_cost,
+    })
+}
+
+fn billable_millions(total_ops: u64, free_ops: u64) -> u64 {
+    total_ops.saturating_sub(free_ops).div_ceil(1_000_000)
+}
+
+#[cfg(test)]
+mod tests;
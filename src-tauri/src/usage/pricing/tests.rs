@@ -0,0 +1,50 @@
+use super::*;
+use std::collections::HashMap;
+
+fn ledger(class_a: u64, class_b: u64, peak_storage_gb: u64, egress_gb: u64) -> DailyLedger {
+    DailyLedger {
+        date: "2025-01-01".into(),
+        class_a: HashMap::from([("PutObject".to_string(), class_a)]),
+        class_b: HashMap::from([("GetObject".to_string(), class_b)]),
+        egress_bytes: egress_gb * 1024 * 1024 * 1024,
+        peak_storage_bytes: peak_storage_gb * 1024 * 1024 * 1024,
+        rev: 1,
+        updated_at: "2025-01-01T00:00:00Z".into(),
+        ..DailyLedger::default()
+    }
+}
+
+#[test]
+fn r2_report_applies_free_tiers_and_rounds_operations_up_to_millions() {
+    let days = vec![ledger(1_000_001, 10_000_000, 0, 5)];
+
+    let report = month_cost_report("2025-01", StorageProvider::R2, &days);
+
+    assert_eq!(report["provider"], "r2");
+    assert_eq!(report["class_a"]["billable_millions"], 1);
+    assert_eq!(report["class_a"]["cost_usd"], 4.5);
+    assert_eq!(report["class_b"]["billable_millions"], 0);
+    assert_eq!(report["egress"]["cost_usd"], 0.0);
+    assert_eq!(report["total_cost_usd"], 4.5);
+}
+
+#[test]
+fn r2_jurisdictions_share_r2_pricing() {
+    let r2 = pricing_for(StorageProvider::R2);
+
+    assert_eq!(pricing_for(StorageProvider::R2Eu), r2);
+    assert_eq!(pricing_for(StorageProvider::R2Fedramp), r2);
+}
+
+#[test]
+fn provider_tables_price_storage_and_egress_differently() {
+    // 30 days at 40 GiB peak averages to 40 GB-month.
+    let days = vec![ledger(0, 0, 40, 10); 30];
+
+    let aws = month_cost_report("2025-01", StorageProvider::AwsS3, &days);
+    let minio = month_cost_report("2025-01", StorageProvider::Minio, &days);
+
+    assert_eq!(aws["storage"]["billable_gb_month"], 40.0);
+    assert!(aws["egress"]["cost_usd"].as_f64().expect("egress cost") > 0.0);
+    assert_eq!(minio["total_cost_usd"], 0.0);
+}
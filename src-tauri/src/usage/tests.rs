@@ -0,0 +1,56 @@
+use super::*;
+
+fn egress(bytes: u64) -> UsageDelta {
+    UsageDelta {
+        egress_bytes: bytes,
+        ..UsageDelta::default()
+    }
+}
+
+fn read_delta(path: &Path) -> UsageDelta {
+    serde_json::from_slice(&fs::read(path).expect("delta file should exist"))
+        .expect("delta file should parse")
+}
+
+#[test]
+fn each_profile_keeps_its_own_deltas_for_a_day() {
+    let directory = tempfile::tempdir().expect("temporary directory should exist");
+    let root = directory.path();
+    let work = profile_delta_dir(root, "work/../eu");
+    let home = profile_delta_dir(root, "home");
+    assert_ne!(work, home);
+    assert_eq!(work.parent(), Some(root));
+
+    add_to_delta_file(&work.join("2026-10-17.json"), egress(5)).expect("record work");
+    add_to_delta_file(&work.join("2026-10-17.json"), egress(7)).expect("record work");
+    add_to_delta_file(&home.join("2026-10-17.json"), egress(11)).expect("record home");
+
+    assert_eq!(read_delta(&work.join("2026-10-17.json")).egress_bytes, 12);
+    assert_eq!(read_delta(&home.join("2026-10-17.json")).egress_bytes, 11);
+    assert_eq!(
+        pending_dates(&work).expect("list work"),
+        vec!["2026-10-17".to_string()]
+    );
+    assert!(pending_dates(root).expect("list root").is_empty());
+}
+
+#[test]
+fn unkeyed_deltas_are_adopted_by_the_given_profile() {
+    let directory = tempfile::tempdir().expect("temporary directory should exist");
+    let root = directory.path();
+    let home = profile_delta_dir(root, "home");
+    fs::write(
+        root.join("2026-10-16.json"),
+        serde_json::to_vec(&egress(3)).expect("serialize"),
+    )
+    .expect("write unkeyed delta");
+    add_to_delta_file(&home.join("2026-10-16.json"), egress(4)).expect("record home");
+
+    adopt_unkeyed_deltas(root, "home").expect("adopt");
+
+    assert!(!root.join("2026-10-16.json").exists());
+    assert_eq!(read_delta(&home.join("2026-10-16.json")).egress_bytes, 7);
+    assert!(pending_dates(&profile_delta_dir(root, "work"))
+        .expect("list work")
+        .is_empty());
+}
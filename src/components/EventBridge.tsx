@@ -433,6 +433,18 @@ function handleUploadEvent(ev: UploadEvent) {
       setTimeout(() => void refreshUploadStatus(id), 0);
       break;
     }
+    case "SourceChanged": {
+      setTimeout(() => void refreshUploadStatus(id), 0);
+      {
+        const k = keyOrName();
+        toast.info(
+          k
+            ? `Source changed; upload restarted: ${k}`
+            : "Source changed; upload restarted",
+        );
+      }
+      break;
+    }
     case "Completed": {
       setTimeout(() => void refreshUploadStatus(id), 0);
       {
@@ -452,6 +464,20 @@ function handleUploadEvent(ev: UploadEvent) {
       console.log("query invalidated");
       break;
     }
+    case "Skipped": {
+      setTimeout(() => void refreshUploadStatus(id), 0);
+      {
+        const k = keyOrName();
+        toast.info(k ? `Upload skipped: ${k}` : "Upload skipped");
+      }
+      triggerAggregateUpdate(true);
+      break;
+    }
+    case "Renamed": {
+      setTimeout(() => void refreshUploadStatus(id), 0);
+      toast.info(`Name taken; uploading as ${ev.key}`);
+      break;
+    }
     case "Failed": {
       setTimeout(() => void refreshUploadStatus(id), 0);
       {
@@ -723,7 +749,8 @@ async function ensureBridgeOnce() {
       (item) =>
         item.state !== "completed" &&
         item.state !== "failed" &&
-        item.state !== "cancelled",
+        item.state !== "cancelled" &&
+        item.state !== "skipped",
     );
     if (active.length === 0) return;
     // Limit per tick to avoid bursts
@@ -56,7 +56,8 @@ export function useDesktopFileTransfer() {
           t.type === "upload" &&
           t.key === key &&
           t.state !== "completed" &&
-          t.state !== "failed",
+          t.state !== "failed" &&
+          t.state !== "skipped",
       );
       if (dup) {
         toast.info(`Already uploading: ${key}`);
@@ -1,3 +1,4 @@
+import type { SpError } from "@/lib/api/bridge";
 import type { FileItem as File } from "@/lib/api/schemas";
 import { api } from "@/lib/api/tauriBridge";
 import { useAppStore } from "@/store/app-store";
@@ -6,6 +7,52 @@ import { useTransferStore } from "@/store/transfer-store";
 import { useCallback } from "react";
 import { toast } from "sonner";
 
+const STREAM_CHUNK = 1024 * 1024 * 4;
+// The backend gives up on a sender that stays quiet for a minute.
+const STREAM_RECONNECT_WINDOW_MS = 60_000;
+const STREAM_RECONNECT_DELAY_MS = 2000;
+const STREAM_BACKPRESSURE_DELAY_MS = 100;
+
+const sleep = (ms: number) => new Promise((r) => setTimeout(r, ms));
+
+/**
+ * Sends `file` into a push stream by offset. A full backend queue is
+ * backpressure, and the chunk is sent again once it drains. After any other
+ * failed write the sender reconnects and continues from what the backend
+ * acknowledged, for as long as reconnecting succeeds within the minute the
+ * backend waits; chunks it sends twice are dropped there.
+ */
+async function sendFileStream(id: string, file: Blob) {
+  let offset = 0;
+  let failingSince: number | null = null;
+  while (true) {
+    try {
+      if (offset >= file.size) {
+        await api.upload_stream_finish(id, file.size);
+        return;
+      }
+      const end = Math.min(file.size, offset + STREAM_CHUNK);
+      const buf = new Uint8Array(await file.slice(offset, end).arrayBuffer());
+      await api.upload_stream_write(id, offset, buf);
+      offset = end;
+      failingSince = null;
+    } catch (err) {
+      const error = err as Partial<SpError> | undefined;
+      // A full queue is the only write failure the backend calls retryable.
+      if (error?.kind === "RetryableNet") {
+        await sleep(error.retry_after_ms ?? STREAM_BACKPRESSURE_DELAY_MS);
+        continue;
+      }
+      if (failingSince === null) failingSince = Date.now();
+      if (Date.now() - failingSince > STREAM_RECONNECT_WINDOW_MS) throw err;
+      console.warn("upload stream interrupted; reconnecting", err);
+      await sleep(STREAM_RECONNECT_DELAY_MS);
+      // Rejects once the upload has given up on this sender.
+      offset = (await api.upload_stream_reconnect(id)).committed_offset;
+    }
+  }
+}
+
 export function useMobileFileTransfer(filesOverride?: File[]) {
   const setTransfersOpen = useTransferStore((s) => s.ui.setOpen);
   const androidTreeUri = useAppStore((s) => s.androidTreeUri);
@@ -40,7 +87,8 @@ export function useMobileFileTransfer(filesOverride?: File[]) {
               t.type === "upload" &&
               t.key === key &&
               t.state !== "completed" &&
-              t.state !== "failed",
+              t.state !== "failed" &&
+              t.state !== "skipped",
           );
           if (dup) {
             toast.info(`Already uploading: ${key}`);
@@ -97,7 +145,8 @@ export function useMobileFileTransfer(filesOverride?: File[]) {
             t.type === "upload" &&
             t.key === key &&
             t.state !== "completed" &&
-            t.state !== "failed",
+            t.state !== "failed" &&
+            t.state !== "skipped",
         );
         if (dup) {
           toast.info(`Already uploading: ${key}`);
@@ -112,19 +161,12 @@ export function useMobileFileTransfer(filesOverride?: File[]) {
           });
           pendingObjectKeys.add(key);
           setTransfersOpen(true);
-          const CHUNK = 1024 * 1024 * 4;
-          for (let offset = 0; offset < f.size; offset += CHUNK) {
-            const slice = f.slice(offset, Math.min(f.size, offset + CHUNK));
-            const buf = new Uint8Array(await slice.arrayBuffer());
-            try {
-              await api.upload_stream_write(id, buf);
-            } catch (err) {
-              console.error("upload_stream_write failed", err);
-              toast.error(`Upload chunk failed for ${key}`);
-              break;
-            }
+          try {
+            await sendFileStream(id, f);
+          } catch (err) {
+            console.error("upload stream failed", err);
+            toast.error(`Upload chunk failed for ${key}`);
           }
-          await api.upload_stream_finish(id);
         } catch (e) {
           console.error(e);
           toast.error(`Upload failed to start: ${key}`);
@@ -9,19 +9,34 @@ export type SpError = {
     | "SourceChanged"
     | "DiskFull"
     | "NotImplemented"
-    | "TaskExists";
+    | "TaskExists"
+    | "NotFound"
+    | "PermissionDenied"
+    | "Conflict"
+    | "RateLimited"
+    | "Integrity";
   message: string;
   retry_after_ms?: number;
   context?: Record<string, unknown>;
   at: number;
 };
 
+export type StorageProvider =
+  | "r2"
+  | "r2_eu"
+  | "r2_fedramp"
+  | "aws_s3"
+  | "minio"
+  | "backblaze_b2"
+  | "wasabi";
+
 export type R2Config = {
   endpoint: string;
   access_key_id: string;
   secret_access_key: string;
   bucket: string;
   region?: string;
+  provider?: StorageProvider;
 };
 export type BackendState = {
   is_unlocked: boolean;
@@ -32,14 +47,62 @@ export type BackendState = {
 };
 export type CredentialBundle = {
   r2: R2Config;
+  active_profile?: string;
+  profiles?: Record<string, R2Config>;
 };
 
+export type Compression = "gzip" | "zstd";
+export type ConflictPolicy =
+  | { type: "overwrite" }
+  | { type: "skip" }
+  | { type: "fail" }
+  | { type: "rename" }
+  | { type: "if_match"; etag: string };
 export type NewUploadParams = {
   key: string;
   source_path: string;
   part_size: number;
   content_type?: string;
   content_disposition?: string;
+  skip_unchanged?: boolean;
+  compression?: Compression;
+  conflict?: ConflictPolicy;
+};
+export type DirectoryUploadParams = {
+  source_dir: string;
+  target_prefix: string;
+  include?: string[];
+  exclude?: string[];
+  include_hidden?: boolean;
+  follow_symlinks?: boolean;
+  skip_unchanged?: boolean;
+  compression?: Compression;
+  conflict?: ConflictPolicy;
+  part_size: number;
+};
+export type NewUrlUploadParams = {
+  url: string;
+  key?: string;
+  target_prefix?: string;
+  part_size?: number;
+  content_type?: string;
+  compression?: Compression;
+  conflict?: ConflictPolicy;
+};
+export type DirectoryUpload = {
+  group_id: string;
+  transfer_ids: string[];
+  bytes_total: number;
+};
+export type UploadGroupStatus = {
+  group_id: string;
+  files_total: number;
+  files_completed: number;
+  files_failed: number;
+  files_cancelled: number;
+  files_skipped: number;
+  bytes_total: number;
+  bytes_done: number;
 };
 export type UploadStatus = {
   transfer_id: string;
@@ -53,6 +116,10 @@ export type UploadStatus = {
   eta_ms?: number;
   last_error?: SpError;
 };
+export type StreamAck = {
+  committed_offset: number;
+  accepted_offset: number;
+};
 export type UploadEvent =
   | { type: "Started"; transfer_id: string }
   | {
@@ -64,9 +131,12 @@ export type UploadEvent =
   | { type: "Paused"; transfer_id: string }
   | { type: "Resumed"; transfer_id: string }
   | { type: "Cancelling"; transfer_id: string }
+  | { type: "SourceChanged"; transfer_id: string }
   | { type: "Completed"; transfer_id: string }
   | { type: "Failed"; transfer_id: string; error: SpError }
-  | { type: "Cancelled"; transfer_id: string };
+  | { type: "Cancelled"; transfer_id: string }
+  | { type: "Skipped"; transfer_id: string }
+  | { type: "Renamed"; transfer_id: string; key: string };
 
 export type NewDownloadParams = {
   key: string;
@@ -76,6 +146,7 @@ export type NewDownloadParams = {
   android_tree_uri?: string;
   android_relative_path?: string;
   mime?: string;
+  decompress?: boolean;
 };
 export type TransferLifecycle =
   | "queued"
@@ -84,7 +155,8 @@ export type TransferLifecycle =
   | "cancelling"
   | "completed"
   | "failed"
-  | "cancelled";
+  | "cancelled"
+  | "skipped";
 export type TransferPhase =
   | "preparing_source"
   | "uploading_remote"
@@ -114,7 +186,12 @@ export type ErrorKind =
   | "source_changed"
   | "disk_full"
   | "task_exists"
-  | "not_implemented";
+  | "not_implemented"
+  | "not_found"
+  | "permission_denied"
+  | "conflict"
+  | "rate_limited"
+  | "integrity";
 export type TransferSnapshot = {
   transfer_id: string;
   kind: "upload" | "download";
@@ -151,6 +228,146 @@ export type DownloadEvent =
   | { type: "Cancelled"; transfer_id: string }
   | { type: "SourceChanged"; transfer_id: string };
 
+export type ObjectHeaders = {
+  content_type?: string;
+  content_disposition?: string;
+  cache_control?: string;
+  content_encoding?: string;
+  // Keys without the `x-amz-meta-` prefix.
+  user_metadata: Record<string, string>;
+};
+export type ObjectMetadata = ObjectHeaders & {
+  key: string;
+  size: number;
+  last_modified_ms?: number;
+  etag?: string;
+  content_md5?: string;
+  version?: string;
+};
+export type ObjectVerification = {
+  key: string;
+  size: number;
+  // Hex SHA-256 from `x-amz-meta-sha256`, if the object has one.
+  recorded_sha256?: string;
+  computed_sha256: string;
+  // Absent when there is no recorded hash to compare with.
+  matches?: boolean;
+};
+// Replaces every editable header; omitted headers are removed.
+export type ObjectMetadataUpdate = ObjectHeaders & {
+  expected_etag?: string;
+};
+
+// Emitted as `sp://prefix_op_event` by folder-wide operations.
+export type PrefixOpProgress = {
+  op_id: string;
+  op: string;
+  prefix: string;
+  processed: number;
+  total: number;
+  current_key?: string;
+  cancelled: boolean;
+};
+
+export type TrashEntry = {
+  trash_key: string;
+  original_key: string;
+  deleted_at_ms: number;
+  size: number;
+};
+
+export type ObjectIndexSort = "key" | "name" | "size" | "modified";
+
+export type ObjectIndexQuery = {
+  /** Case-insensitive substring, or a glob when it contains `*`, `?` or `[`. */
+  name?: string;
+  extensions?: string[];
+  prefix?: string;
+  min_size?: number;
+  max_size?: number;
+  modified_after_ms?: number;
+  modified_before_ms?: number;
+  include_folders?: boolean;
+  sort?: ObjectIndexSort;
+  descending?: boolean;
+  offset?: number;
+  limit?: number;
+};
+
+export type ObjectIndexPage = {
+  items: Array<{
+    key: string;
+    size?: number;
+    last_modified_ms?: number;
+    etag?: string;
+    thumbnail_key?: string;
+    is_prefix: boolean;
+    protected: boolean;
+  }>;
+  total: number;
+  next_offset?: number;
+};
+
+export type ObjectIndexSync = {
+  prefix: string;
+  indexed: number;
+  removed: number;
+  finished_at_ms: number;
+};
+
+export type ObjectIndexStatus = {
+  profile: string;
+  objects: number;
+  last_full_sync_ms?: number;
+};
+
+/** Proxy, timeouts and extra trust roots for storage traffic. 0 disables a timeout. */
+export type NetworkSettings = {
+  proxyUrl?: string | null;
+  proxyUsername?: string | null;
+  proxyPassword?: string | null;
+  noProxy: string[];
+  connectTimeoutSecs: number;
+  readTimeoutSecs: number;
+  extraRootPems: string[];
+  retryMaxAttempts: number;
+};
+
+export type LifecycleRule = {
+  id: string;
+  enabled: boolean;
+  prefix: string;
+  expiration_days?: number | null;
+  abort_incomplete_multipart_days?: number | null;
+  unsupported?: string[];
+};
+
+export type DiagnosticCheck =
+  | "dns"
+  | "tls_handshake"
+  | "clock_skew"
+  | "signature"
+  | "list"
+  | "write"
+  | "read"
+  | "delete";
+
+export type DiagnosticStep = {
+  check: DiagnosticCheck;
+  status: "pass" | "fail" | "skipped";
+  detail: string;
+  hint?: string | null;
+  elapsed_ms: number;
+};
+
+export type DiagnosticsReport = {
+  url: string;
+  bucket: string;
+  ok: boolean;
+  clock_skew_ms?: number | null;
+  steps: DiagnosticStep[];
+};
+
 export type ShareParams = {
   key: string;
   ttl_secs: number;
@@ -168,7 +385,9 @@ export type UsageDelta = {
   ingress_bytes: number;
   egress_bytes: number;
   added_storage_bytes: number;
+  added_logical_storage_bytes: number;
   deleted_storage_bytes: number;
+  logical_ingress_bytes: number;
 };
 export type DailyLedger = {
   date: string;
@@ -179,6 +398,8 @@ export type DailyLedger = {
   storage_bytes: number;
   peak_storage_bytes: number;
   deleted_storage_bytes: number;
+  logical_ingress_bytes: number;
+  added_logical_storage_bytes: number;
   rev: number;
   updated_at: string;
 };
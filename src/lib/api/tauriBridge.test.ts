@@ -65,6 +65,7 @@ describe("Tauri bridge contract", () => {
 
     await api.upload_stream_write(
       "upload-1",
+      4096,
       new Uint8Array([0, 1, 127, 128, 255]),
     );
 
@@ -73,6 +74,7 @@ describe("Tauri bridge contract", () => {
         cmd: "upload_stream_write",
         args: {
           transferId: "upload-1",
+          offset: 4096,
           chunk: [0, 1, 127, 128, 255],
         },
       },
@@ -7,10 +7,30 @@ import {
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import type {
+  Compression,
+  ConflictPolicy,
   CredentialExportPayload,
   DailyLedger,
+  DiagnosticsReport,
+  DirectoryUpload,
+  DirectoryUploadParams,
+  LifecycleRule,
+  NewUrlUploadParams,
+  NetworkSettings,
+  ObjectIndexPage,
+  ObjectIndexQuery,
+  ObjectIndexStatus,
+  ObjectIndexSync,
+  ObjectMetadata,
+  ObjectMetadataUpdate,
+  ObjectVerification,
+  PrefixOpProgress,
+  R2Config,
   ShareLink,
+  StreamAck,
+  TrashEntry,
   TransferSnapshot,
+  UploadGroupStatus,
   UploadStatus,
 } from "./bridge";
 
@@ -84,14 +104,22 @@ export const api = {
       maxConcurrency: number;
       defaultDownloadDir?: string | null;
       uploadThumbnail: boolean;
+      perTaskParts: number;
       androidTreeUri?: string | null;
+      recycleBin: boolean;
+      trashRetentionDays: number;
+      network: NetworkSettings;
     }>("settings_get"),
   settings_set: (settings: {
     logLevel: string;
     maxConcurrency: number;
     defaultDownloadDir?: string | null;
     uploadThumbnail: boolean;
+    perTaskParts?: number;
     androidTreeUri?: string | null;
+    recycleBin?: boolean;
+    trashRetentionDays?: number;
+    network?: NetworkSettings;
   }) =>
     invokeBridge<void>("settings_set", {
       settings,
@@ -112,6 +140,44 @@ export const api = {
     invokeBridge<void>("backend_patch_credentials", {
       patch,
     }),
+  backend_list_profiles: () =>
+    invokeBridge<
+      {
+        name: string;
+        is_active: boolean;
+        endpoint: string;
+        bucket: string;
+        region?: string | null;
+        is_encrypted: boolean;
+      }[]
+    >("backend_list_profiles"),
+  backend_add_profile: (
+    name: string,
+    r2: {
+      endpoint: string;
+      access_key_id: string;
+      secret_access_key: string;
+      bucket: string;
+      region?: string;
+    },
+  ) =>
+    invokeBridge<void>("backend_add_profile", {
+      name,
+      r2,
+    }),
+  backend_switch_profile: (name: string) =>
+    invokeBridge<void>("backend_switch_profile", {
+      name,
+    }),
+  backend_delete_profile: (name: string) =>
+    invokeBridge<void>("backend_delete_profile", {
+      name,
+    }),
+  backend_set_profile_encryption: (name: string, passphrase: string | null) =>
+    invokeBridge<void>("backend_set_profile_encryption", {
+      name,
+      passphrase,
+    }),
   backend_status: () => invokeBridge("backend_status"),
   backend_credentials_redacted: () =>
     invokeBridge<{
@@ -133,6 +199,8 @@ export const api = {
     }),
   vault_status: () => invokeBridge("vault_status"),
   r2_sanity_check: () => invokeBridge("r2_sanity_check"),
+  r2_diagnostics: (r2?: R2Config) =>
+    invokeBridge<DiagnosticsReport>("r2_diagnostics", { r2: r2 ?? null }),
   // deprecated
   list_objects: (prefix = "", token?: string, max = 1000) =>
     invokeBridge<ListPage>("list_objects", {
@@ -154,6 +222,44 @@ export const api = {
     >("list_all_objects", { maxTotal }),
   delete_object: (key: string) =>
     invokeBridge<string>("delete_object", { key }),
+  stat_object: (key: string) =>
+    invokeBridge<ObjectMetadata>("stat_object", { key }),
+  update_object_metadata: (key: string, update: ObjectMetadataUpdate) =>
+    invokeBridge<ObjectMetadata>("update_object_metadata", { key, update }),
+  verify_object: (key: string) =>
+    invokeBridge<ObjectVerification>("verify_object", { key }),
+  create_folder: (prefix: string) =>
+    invokeBridge<string>("create_folder", { prefix }),
+  copy_object: (fromKey: string, toKey: string) =>
+    invokeBridge<string>("copy_object", { fromKey, toKey }),
+  move_object: (fromKey: string, toKey: string) =>
+    invokeBridge<string>("move_object", { fromKey, toKey }),
+  move_prefix: (opId: string, fromPrefix: string, toPrefix: string) =>
+    invokeBridge<PrefixOpProgress>("move_prefix", {
+      opId,
+      fromPrefix,
+      toPrefix,
+    }),
+  delete_prefix: (opId: string, prefix: string) =>
+    invokeBridge<PrefixOpProgress>("delete_prefix", { opId, prefix }),
+  cancel_prefix_op: (opId: string) =>
+    invokeBridge<void>("cancel_prefix_op", { opId }),
+  trash_list: () => invokeBridge<TrashEntry[]>("trash_list"),
+  trash_restore: (trashKey: string) =>
+    invokeBridge<string>("trash_restore", { trashKey }),
+  trash_purge: (olderThanDays?: number) =>
+    invokeBridge<TrashEntry[]>("trash_purge", { olderThanDays }),
+  object_index_search: (query: ObjectIndexQuery = {}) =>
+    invokeBridge<ObjectIndexPage>("object_index_search", { query }),
+  object_index_refresh: (prefix?: string) =>
+    invokeBridge<ObjectIndexSync>("object_index_refresh", { prefix }),
+  object_index_status: () =>
+    invokeBridge<ObjectIndexStatus>("object_index_status"),
+  lifecycle_get: () => invokeBridge<LifecycleRule[]>("lifecycle_get"),
+  lifecycle_validate: (rules: LifecycleRule[]) =>
+    invokeBridge<void>("lifecycle_validate", { rules }),
+  lifecycle_set: (rules: LifecycleRule[]) =>
+    invokeBridge<void>("lifecycle_set", { rules }),
   generate_thumbnail_and_upload: (key: string, sourcePath: string) =>
     invokeBridge<string | null>("generate_thumbnail_and_upload", {
       key,
@@ -187,25 +293,43 @@ export const api = {
     part_size: number;
     content_type?: string;
     content_disposition?: string;
+    skip_unchanged?: boolean;
+    compression?: Compression;
+    conflict?: ConflictPolicy;
   }) => invokeBridge<string>("upload_new", { params }),
+  upload_directory: (params: DirectoryUploadParams) =>
+    invokeBridge<DirectoryUpload>("upload_directory", { params }),
   upload_new_stream: (params: {
     key: string;
     bytes_total: number;
     part_size: number;
     content_type?: string;
     content_disposition?: string;
+    compression?: Compression;
+    conflict?: ConflictPolicy;
   }) => invokeBridge<string>("upload_new_stream", { params }),
-  upload_stream_write: (transferId: string, chunk: Uint8Array | number[]) =>
-    invokeBridge<void>("upload_stream_write", {
+  upload_from_url: (params: NewUrlUploadParams) =>
+    invokeBridge<string>("upload_from_url", { params }),
+  upload_stream_write: (
+    transferId: string,
+    offset: number,
+    chunk: Uint8Array | number[],
+  ) =>
+    invokeBridge<StreamAck>("upload_stream_write", {
       transferId,
+      offset,
       chunk: Array.from(chunk as any),
     }),
-  upload_stream_finish: (transferId: string) =>
-    invokeBridge<void>("upload_stream_finish", { transferId }),
+  upload_stream_finish: (transferId: string, offset: number) =>
+    invokeBridge<StreamAck>("upload_stream_finish", { transferId, offset }),
+  upload_stream_reconnect: (transferId: string) =>
+    invokeBridge<StreamAck>("upload_stream_reconnect", { transferId }),
   upload_ctrl: (transferId: string, action: "pause" | "resume" | "cancel") =>
     invokeBridge<void>("upload_ctrl", { transferId, action }),
   upload_status: (transferId: string) =>
     invokeBridge<UploadStatus>("upload_status", { transferId }),
+  upload_group_status: (groupId: string) =>
+    invokeBridge<UploadGroupStatus>("upload_group_status", { groupId }),
   download_new: (params: {
     key: string;
     dest_path?: string;
@@ -228,7 +352,8 @@ export const api = {
         | "cancelling"
         | "completed"
         | "failed"
-        | "cancelled";
+        | "cancelled"
+        | "skipped";
       phase?:
         | "preparing_source"
         | "uploading_remote"
@@ -214,6 +214,7 @@ export default function SettingsPage() {
     setAndroidDirClearing(true);
     try {
       await api.settings_set({
+        ...(await api.settings_get()),
         logLevel,
         maxConcurrency,
         defaultDownloadDir: defaultDownloadDir || undefined,
@@ -468,6 +469,7 @@ export default function SettingsPage() {
                 onClick={async () => {
                   try {
                     await api.settings_set({
+                      ...(await api.settings_get()),
                       logLevel,
                       maxConcurrency,
                       defaultDownloadDir: defaultDownloadDir || undefined,
@@ -66,7 +66,8 @@ export default function TransfersPage() {
       if (
         t.state === "completed" ||
         t.state === "failed" ||
-        t.state === "cancelled"
+        t.state === "cancelled" ||
+        t.state === "skipped"
       ) {
         // eslint-disable-next-line no-await-in-loop
         await removeTransfer(t.id, t.type);
@@ -104,7 +105,8 @@ export default function TransfersPage() {
                 const canRemove =
                   t.state === "completed" ||
                   t.state === "failed" ||
-                  t.state === "cancelled";
+                  t.state === "cancelled" ||
+                  t.state === "skipped";
                 return (
                   <li
                     key={t.id}
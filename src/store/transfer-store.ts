@@ -9,6 +9,7 @@ export type TransferState =
   | "cancelling"
   | "completed"
   | "cancelled"
+  | "skipped"
   | "failed";
 
 export interface TransferItem {